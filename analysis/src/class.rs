@@ -4,6 +4,8 @@ use super::*;
 pub enum ClassItem {
     Value {
         name: SrcNode<Ident>,
+        // Generic types that the field is polymorphic over
+        gen_scope: GenScopeId,
         ty: SrcNode<TyId>,
     },
     Type {
@@ -16,6 +18,8 @@ pub struct Class {
     pub obligations: Option<Vec<SrcNode<Obligation>>>,
     pub attr: Vec<SrcNode<ast::Attr>>,
     pub gen_scope: GenScopeId,
+    // The kind of types that may be members of the class
+    pub self_kind: Kind,
     pub assoc: Option<Vec<ClassItem>>,
     pub fields: Option<Vec<ClassItem>>,
 }
@@ -32,13 +36,13 @@ impl Class {
             })
     }
 
-    pub fn field(&self, field: Ident) -> Option<&SrcNode<TyId>> {
+    pub fn field(&self, field: Ident) -> Option<(GenScopeId, &SrcNode<TyId>)> {
         self.fields
             .as_ref()
             .expect("Class fields must be known here")
             .iter()
             .find_map(|item| match item {
-                ClassItem::Value { name, ty } if **name == field => Some(ty),
+                ClassItem::Value { name, gen_scope, ty } if **name == field => Some((*gen_scope, ty)),
                 _ => None,
             })
     }
@@ -147,11 +151,14 @@ impl Classes {
         self.members[id.0].fields = Some(fields);
     }
 
-    pub fn lookup_member(&self, hir: &Context, ctx: &ConContext, ty: ConTyId, class: ClassId) -> Option<&Member> {
+    /// Find the member of a class that covers a type. For classes with parameters, the member must also cover the given
+    /// class parameters (if any are given).
+    pub fn lookup_member(&self, hir: &Context, ctx: &ConContext, ty: ConTyId, class: ClassId, params: &[ConTyId]) -> Option<&Member> {
         // Returns true if member covers ty
         fn covers(hir: &Context, ctx: &ConContext, member: TyId, ty: ConTyId) -> bool {
             match (hir.tys.get(member), ctx.get_ty(ty)) {
                 (Ty::Gen(_, _), _) => true, // Blanket impls match everything
+                (Ty::Hole(x), ConTy::Hole(y)) => x == *y,
                 (Ty::Prim(a), ConTy::Prim(b)) if a == *b => true,
                 (Ty::List(x), ConTy::List(y)) => covers(hir, ctx, x, *y),
                 (Ty::Tuple(xs), ConTy::Tuple(ys)) if xs.len() == ys.len() => xs
@@ -173,18 +180,81 @@ impl Classes {
             }
         }
 
-        self.member_lut
-            .get(&class)
-            .and_then(|xs| {
-                let candidates = xs
+        fn candidates<'a>(classes: &'a Classes, hir: &Context, ctx: &ConContext, ty: ConTyId, class: ClassId, params: &[ConTyId]) -> Vec<&'a Member> {
+            classes.member_lut
+                .get(&class)
+                .map(|xs| xs.as_slice())
+                .unwrap_or(&[])
+                .iter()
+                .map(|m| classes.get_member(*m))
+                .filter(|member| covers(hir, ctx, member.member, ty))
+                .filter(|member| member.args
                     .iter()
-                    .map(|m| self.get_member(*m))
-                    .filter(|member| covers(hir, ctx, member.member, ty))
-                    .collect::<Vec<_>>();
+                    .zip(params)
+                    .all(|(arg, param)| covers(hir, ctx, *arg, *param)))
+                .filter(|member| classes.fulfils_bounds(
+                    &hir.tys,
+                    member,
+                    &mut |_| None,
+                    &mut |bound_member| covers(hir, ctx, bound_member.member, ty),
+                ))
+                .collect()
+        }
 
-                assert!(candidates.len() <= 1, "Multiple member candidates detected during lowering <{:?} as {:?}>, incoherence has occurred", ctx.get_ty(ty), class);
+        // Blanket members may not overlap with other members (see `Context::check_blanket_members`), so there is no need
+        // to choose between them
+        let candidates = candidates(self, hir, ctx, ty, class, params);
+
+        assert!(candidates.len() <= 1, "Multiple member candidates detected during lowering <{:?} as {:?}>, incoherence has occurred", ctx.get_ty(ty), class);
+
+        candidates.first().copied()
+    }
 
-                candidates.first().copied()
+    /// Returns true if a type fulfils the bounds of a member. Only blanket members have bounds, each of which requires
+    /// the type to be covered by some member of the bounding class whose own bounds are, in turn, fulfilled.
+    ///
+    /// `covers` determines whether a member covers the type, while `implied` may instead decide up front whether the
+    /// type is a member of a class (as it is for a generic type that carries the class as a bound). A class is only
+    /// visited once along a chain of bounds, so blanket members bounded on each other's classes (such as
+    /// `for S < A member S of B` and `for S < B member S of A`) cover nothing rather than recursing forever.
+    pub fn fulfils_bounds(
+        &self,
+        tys: &Types,
+        member: &Member,
+        implied: &mut impl FnMut(ClassId) -> Option<bool>,
+        covers: &mut impl FnMut(&Member) -> bool,
+    ) -> bool {
+        self.fulfils_bounds_inner(tys, member, implied, covers, &mut Vec::new())
+    }
+
+    fn fulfils_bounds_inner(
+        &self,
+        tys: &Types,
+        member: &Member,
+        implied: &mut impl FnMut(ClassId) -> Option<bool>,
+        covers: &mut impl FnMut(&Member) -> bool,
+        visited: &mut Vec<ClassId>,
+    ) -> bool {
+        let Ty::Gen(gen_idx, gen_scope) = tys.get(member.member) else { return true };
+        tys
+            .get_gen_scope(gen_scope)
+            .get(gen_idx)
+            .obligations()
+            .iter()
+            .all(|obl| match &**obl {
+                Obligation::MemberOf(bound, _, _) => match implied(*bound) {
+                    Some(is_member) => is_member,
+                    None if visited.contains(bound) => false,
+                    None => {
+                        visited.push(*bound);
+                        let covered = self
+                            .members_of(*bound)
+                            .any(|(_, bound_member)| covers(bound_member)
+                                && self.fulfils_bounds_inner(tys, bound_member, implied, covers, visited));
+                        visited.pop();
+                        covered
+                    },
+                },
             })
     }
 
@@ -213,11 +283,19 @@ pub struct Member {
     pub gen_scope: GenScopeId,
     pub attr: Vec<SrcNode<ast::Attr>>,
     pub member: TyId,
+    // The parameters of the class that this is a member of (i.e: `Int` in `member Nat of Convert Int`)
+    pub args: Vec<TyId>,
     pub assoc: Option<HashMap<Ident, TyId>>,
     pub fields: Option<HashMap<Ident, TyExpr>>,
 }
 
 impl Member {
+    /// Returns true if this is a blanket member (i.e: `for S < Stream member S of IntoStream`) that covers any type
+    /// fulfilling the bounds of its generic type.
+    pub fn is_blanket(&self, tys: &Types) -> bool {
+        matches!(tys.get(self.member), Ty::Gen(_, _))
+    }
+
     pub fn assoc_ty(&self, assoc: Ident) -> Option<TyId> {
         self.assoc
             .as_ref()
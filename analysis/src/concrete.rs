@@ -12,6 +12,8 @@ pub enum ConTy {
     Record(BTreeMap<Ident, ConTyId>),
     Func(ConTyId, ConTyId),
     Data(ConDataId),
+    // A placeholder in a type constructor, to be filled by the nth argument when the constructor is applied
+    Hole(usize),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -108,35 +110,125 @@ impl ConContext {
             })
     }
 
-    fn derive_links(&self, hir: &Context, member: TyId, ty: ConTyId, link_gen: &mut impl FnMut(usize, ConTyId)) {
-        match (hir.tys.get(member), self.get_ty(ty)) {
-            (Ty::Prim(x), ConTy::Prim(y)) => assert_eq!(x, *y),
+    fn derive_links(&mut self, hir: &Context, member: TyId, ty: ConTyId, link_gen: &mut impl FnMut(usize, ConTyId)) {
+        match (hir.tys.get(member), self.get_ty(ty).clone()) {
+            (Ty::Prim(x), ConTy::Prim(y)) => assert_eq!(x, y),
             (Ty::Gen(gen_idx, _), _) => link_gen(gen_idx, ty),
-            (Ty::List(x), ConTy::List(y)) => self.derive_links(hir, x, *y, link_gen),
+            (Ty::SelfType, _) => {},
+            (Ty::Hole(x), ConTy::Hole(y)) => assert_eq!(x, y),
+            (Ty::List(x), ConTy::List(y)) => self.derive_links(hir, x, y, link_gen),
             (Ty::Tuple(xs), ConTy::Tuple(ys)) => xs
                 .into_iter()
                 .zip(ys.into_iter())
-                .for_each(|(x, y)| self.derive_links(hir, x, *y, link_gen)),
+                .for_each(|(x, y)| self.derive_links(hir, x, y, link_gen)),
             (Ty::Union(xs), ConTy::Union(ys)) => xs
                 .into_iter()
                 .zip(ys.into_iter())
-                .for_each(|(x, y)| self.derive_links(hir, x, *y, link_gen)),
+                .for_each(|(x, y)| self.derive_links(hir, x, y, link_gen)),
             (Ty::Record(xs), ConTy::Record(ys)) => xs
                 .into_iter()
                 .zip(ys.into_iter())
-                .for_each(|((_, x), (_, y))| self.derive_links(hir, x, *y, link_gen)),
+                .for_each(|((_, x), (_, y))| self.derive_links(hir, x, y, link_gen)),
             (Ty::Func(x_i, x_o), ConTy::Func(y_i, y_o)) => {
-                self.derive_links(hir, x_i, *y_i, link_gen);
-                self.derive_links(hir, x_o, *y_o, link_gen);
+                self.derive_links(hir, x_i, y_i, link_gen);
+                self.derive_links(hir, x_o, y_o, link_gen);
             },
             (Ty::Data(_, xs), ConTy::Data(y)) => xs
                 .into_iter()
                 .zip(y.1.iter())
                 .for_each(|(x, y)| self.derive_links(hir, x, *y, link_gen)),
+            // Applied type constructors take the trailing parameters of the data type
+            (Ty::Apply(f, xs), ConTy::Data(y)) if xs.len() <= y.1.len() => {
+                let split = y.1.len() - xs.len();
+                let ctor_params = y.1[..split]
+                    .iter()
+                    .copied()
+                    .chain((0..xs.len()).map(|idx| self.insert_ty(ConTy::Hole(idx))))
+                    .collect();
+                let ctor = self.insert_ty(ConTy::Data(Intern::new((y.0, ctor_params))));
+                self.derive_links(hir, f, ctor, link_gen);
+                xs
+                    .into_iter()
+                    .zip(y.1[split..].iter())
+                    .for_each(|(x, y)| self.derive_links(hir, x, *y, link_gen));
+            },
+            (Ty::Apply(f, xs), ConTy::List(y)) if xs.len() == 1 => {
+                let hole = self.insert_ty(ConTy::Hole(0));
+                let ctor = self.insert_ty(ConTy::List(hole));
+                self.derive_links(hir, f, ctor, link_gen);
+                self.derive_links(hir, xs[0], y, link_gen);
+            },
             (x, y) => todo!("{:?}", (x, y)),
         }
     }
 
+    fn lower_data(&mut self, hir: &Context, data: DataId, args: Vec<ConTyId>) -> ConTy {
+        let id = Intern::new((data, args.clone()));
+        // Type constructors have no values, so there is nothing to define for them
+        if !self.datas.contains_key(&id) && !args.iter().any(|arg| self.has_holes(*arg)) {
+            self.datas.insert(id, None); // Prevent overflow with phoney value
+            let data = ConData {
+                cons: hir.datas
+                    .get_data(data)
+                    .cons
+                    .iter()
+                    .map(|(name, ty)| (**name, self.lower_ty(hir, *ty, &TyInsts {
+                        self_ty: None,
+                        gen: &args,
+                    })))
+                    .collect(),
+            };
+            self.datas.insert(id, Some(data));
+        }
+        ConTy::Data(id)
+    }
+
+    fn has_holes(&self, ty: ConTyId) -> bool {
+        match self.get_ty(ty) {
+            ConTy::Prim(_) => false,
+            ConTy::Hole(_) => true,
+            ConTy::List(item) => self.has_holes(*item),
+            ConTy::Tuple(xs) | ConTy::Union(xs) => xs.iter().any(|x| self.has_holes(*x)),
+            ConTy::Record(fields) => fields.values().any(|field| self.has_holes(*field)),
+            ConTy::Func(i, o) => self.has_holes(*i) || self.has_holes(*o),
+            ConTy::Data(data) => data.1.iter().any(|arg| self.has_holes(*arg)),
+        }
+    }
+
+    // Apply a type constructor to arguments by filling its holes
+    fn con_apply(&mut self, hir: &Context, ctor: ConTyId, args: &[ConTyId]) -> ConTyId {
+        let cty = match self.get_ty(ctor).clone() {
+            ConTy::Hole(idx) => return args[idx],
+            ConTy::Prim(_) => return ctor,
+            ConTy::List(item) => ConTy::List(self.con_apply(hir, item, args)),
+            ConTy::Tuple(fields) => ConTy::Tuple(fields
+                .into_iter()
+                .map(|field| self.con_apply(hir, field, args))
+                .collect()),
+            ConTy::Union(variants) => ConTy::Union(variants
+                .into_iter()
+                .map(|variant| self.con_apply(hir, variant, args))
+                .collect()),
+            ConTy::Record(fields) => ConTy::Record(fields
+                .into_iter()
+                .map(|(name, field)| (name, self.con_apply(hir, field, args)))
+                .collect()),
+            ConTy::Func(i, o) => ConTy::Func(
+                self.con_apply(hir, i, args),
+                self.con_apply(hir, o, args),
+            ),
+            ConTy::Data(data) => {
+                let params = data.1
+                    .iter()
+                    .map(|param| self.con_apply(hir, *param, args))
+                    .collect();
+                self.lower_data(hir, data.0, params)
+            },
+        };
+
+        self.insert_ty(cty)
+    }
+
     pub fn lower_ty(&mut self, hir: &Context, ty: TyId, ty_insts: &TyInsts) -> ConTyId {
         let cty = match hir.tys.get(ty) {
             Ty::Error(_) => panic!("Concretizable type cannot be an error"),
@@ -163,52 +255,84 @@ impl ConContext {
                     .into_iter()
                     .map(|arg| self.lower_ty(hir, arg, ty_insts))
                     .collect::<Vec<_>>();
-                let id = Intern::new((data, args.clone()));
-                if !self.datas.contains_key(&id) {
-                    self.datas.insert(id, None); // Prevent overflow with phoney value
-                    let data = ConData {
-                        cons: hir.datas
-                            .get_data(data)
-                            .cons
-                            .iter()
-                            .map(|(name, ty)| (**name, self.lower_ty(hir, *ty, &TyInsts {
-                                self_ty: None,
-                                gen: &args,
-                            })))
-                            .collect(),
-                    };
-                    self.datas.insert(id, Some(data));
-                }
-                ConTy::Data(id)
+                self.lower_data(hir, data, args)
+            },
+            Ty::Hole(idx) => ConTy::Hole(idx),
+            Ty::Apply(ctor, args) => {
+                let ctor = self.lower_ty(hir, ctor, ty_insts);
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.lower_ty(hir, arg, ty_insts))
+                    .collect::<Vec<_>>();
+                return self.con_apply(hir, ctor, &args);
             },
             Ty::Gen(idx, _) => return ty_insts.gen[idx],
             Ty::SelfType => return ty_insts.self_ty.expect("Self type required during concretization but none was provided"),
             Ty::Assoc(ty, class, assoc) => {
                 let self_ty = self.lower_ty(hir, ty, ty_insts);
-                let member = hir.classes
-                    .lookup_member(hir, self, self_ty, class)
-                    .expect("Could not select member candidate");
-                let member_gen_scope = hir.tys.get_gen_scope(member.gen_scope);
-
-                let mut links = HashMap::new();
-                self.derive_links(hir, member.member, self_ty, &mut |gen_idx, ty| { links.insert(gen_idx, ty); });
-                let gen = (0..member_gen_scope.len())
-                    .map(|idx| *links.get(&idx).expect("Generic type not mentioned in member"))
-                    .collect::<Vec<_>>();
-
-                let assoc = member
-                    .assoc_ty(*assoc)
-                    .unwrap();
-                return self.lower_ty(hir, assoc, &TyInsts {
-                    self_ty: Some(self_ty),
-                    gen: &gen,
-                });
+                return self.lower_assoc(hir, self_ty, class, &[], *assoc);
             },
         };
 
         self.insert_ty(cty)
     }
 
+    // Find the associated type of the member of a class that covers the given type
+    fn lower_assoc(&mut self, hir: &Context, self_ty: ConTyId, class: ClassId, params: &[ConTyId], assoc: Ident) -> ConTyId {
+        let member = hir.classes
+            .lookup_member(hir, self, self_ty, class, params)
+            .expect("Could not select member candidate");
+        let member_gen_scope = hir.tys.get_gen_scope(member.gen_scope);
+
+        let mut links = HashMap::new();
+        self.derive_links(hir, member.member, self_ty, &mut |gen_idx, ty| { links.insert(gen_idx, ty); });
+        let gen = (0..member_gen_scope.len())
+            .map(|idx| *links.get(&idx).expect("Generic type not mentioned in member"))
+            .collect::<Vec<_>>();
+
+        let assoc = member
+            .assoc_ty(assoc)
+            .unwrap();
+        self.lower_ty(hir, assoc, &TyInsts {
+            self_ty: Some(self_ty),
+            gen: &gen,
+        })
+    }
+
+    // Inference guarantees that associated type equalities in generic bounds hold, so any mismatch found here means
+    // that an invalid instantiation slipped through
+    fn check_gen_obligations(&mut self, hir: &Context, gen_scope: GenScopeId, gen: &[ConTyId]) {
+        let gen_scope = hir.tys.get_gen_scope(gen_scope);
+        for (idx, gen_ty) in gen.iter().enumerate().take(gen_scope.len()) {
+            for obl in gen_scope.get(idx).obligations() {
+                match &**obl {
+                    Obligation::MemberOf(class, params, assoc) => {
+                        if assoc.is_empty() {
+                            continue;
+                        }
+                        let ty_insts = TyInsts { self_ty: None, gen };
+                        let params = params
+                            .iter()
+                            .map(|param| self.lower_ty(hir, *param, &ty_insts))
+                            .collect::<Vec<_>>();
+                        for (name, assoc_ty) in assoc {
+                            let expected = self.lower_ty(hir, *assoc_ty, &ty_insts);
+                            let found = self.lower_assoc(hir, *gen_ty, *class, &params, **name);
+                            assert!(
+                                expected == found,
+                                "Associated type {} of {} is {} but the bound requires {}",
+                                **name,
+                                self.display(hir, *gen_ty),
+                                self.display(hir, found),
+                                self.display(hir, expected),
+                            );
+                        }
+                    },
+                }
+            }
+        }
+    }
+
     // Returns (record_ty, field_ty, number_of_indirections)
     pub fn follow_field_access(&self, hir: &Context, mut ty: ConTyId, field: Ident) -> Option<(ConTyId, ConTyId, usize)> {
         let mut already_seen = Vec::new();
@@ -243,6 +367,8 @@ impl ConContext {
         if !self.defs.contains_key(&def) {
             self.defs.insert(def, None);
 
+            self.check_gen_obligations(hir, hir.defs.get(def.0).gen_scope, &def.1);
+
             let body = self.lower_expr(
                 hir,
                 hir.defs
@@ -264,9 +390,11 @@ impl ConContext {
             hir::Pat::Error => panic!("Error pattern should not exist during concretization"),
             hir::Pat::Wildcard => hir::Pat::Wildcard,
             hir::Pat::Literal(litr) => hir::Pat::Literal(*litr),
+            hir::Pat::Range(lo, hi) => hir::Pat::Range(*lo, *hi),
             hir::Pat::Single(inner) => hir::Pat::Single(self.lower_binding(hir, inner, ty_insts)),
             hir::Pat::Union(inner) => hir::Pat::Union(self.lower_binding(hir, inner, ty_insts)),
             hir::Pat::Add(lhs, rhs) => hir::Pat::Add(self.lower_binding(hir, lhs, ty_insts), rhs.clone()),
+            hir::Pat::Mul(lhs, rhs) => hir::Pat::Mul(self.lower_binding(hir, lhs, ty_insts), rhs.clone()),
             hir::Pat::Tuple(fields) => hir::Pat::Tuple(fields
                 .iter()
                 .map(|field| self.lower_binding(hir, field, ty_insts))
@@ -284,6 +412,10 @@ impl ConContext {
                 .map(|item| self.lower_binding(hir, item, ty_insts))
                 .collect(), tail.as_ref().map(|tail| self.lower_binding(hir, tail, ty_insts))),
             hir::Pat::Decons(data, variant, inner) => hir::Pat::Decons(data.clone(), *variant, self.lower_binding(hir, inner, ty_insts)),
+            hir::Pat::Or(alts) => hir::Pat::Or(alts
+                .iter()
+                .map(|alt| self.lower_binding(hir, alt, ty_insts))
+                .collect()),
         };
 
         ConNode::new(
@@ -324,6 +456,10 @@ impl ConContext {
                 .iter()
                 .map(|(name, field)| (name.clone(), self.lower_expr(hir, field, ty_insts)))
                 .collect()),
+            hir::Expr::Update(record, fields) => hir::Expr::Update(self.lower_expr(hir, record, ty_insts), fields
+                .iter()
+                .map(|(name, field)| (name.clone(), self.lower_expr(hir, field, ty_insts)))
+                .collect()),
             hir::Expr::Access(record, field) => hir::Expr::Access(self.lower_expr(hir, record, ty_insts), field.clone()),
             hir::Expr::Binary(op, a, b) => hir::Expr::Binary(
                 op.clone(),
@@ -335,8 +471,9 @@ impl ConContext {
                 self.lower_expr(hir, pred, ty_insts),
                 arms
                     .iter()
-                    .map(|(binding, arm)| (
+                    .map(|(binding, guard, arm)| (
                         self.lower_binding(hir, binding, ty_insts),
+                        guard.as_ref().map(|guard| self.lower_expr(hir, guard, ty_insts)),
                         self.lower_expr(hir, arm, ty_insts),
                     ))
                     .collect(),
@@ -352,15 +489,40 @@ impl ConContext {
             hir::Expr::Cons(data, variant, inner) => hir::Expr::Cons(data.clone(), *variant, self.lower_expr(hir, inner, ty_insts)),
             hir::Expr::ClassAccess(ty, class, field) => {
                 let self_ty = self.lower_ty(hir, ty.1, ty_insts);
+                let class_id = class.expect("Uninferred class during concretization");
+
+                // The parameters of the class and the generic types of polymorphic fields are determined by the type of
+                // the access
+                let class_param_count = hir.tys.get_gen_scope(hir.classes.get(class_id).gen_scope).len();
+                let (field_gen_scope, field_ty) = hir.classes
+                    .get(class_id)
+                    .field(**field)
+                    .expect("Class field must exist");
+                let field_gen_count = hir.tys.get_gen_scope(field_gen_scope).len();
+                let field_gens = if field_gen_count > 0 {
+                    let access_ty = self.lower_ty(hir, ty_expr.meta().1, ty_insts);
+                    let mut field_links = HashMap::new();
+                    self.derive_links(hir, **field_ty, access_ty, &mut |gen_idx, ty| { field_links.insert(gen_idx, ty); });
+                    (0..field_gen_count)
+                        .map(|idx| *field_links.get(&idx).expect("Generic type not mentioned in class field"))
+                        .collect::<Vec<_>>()
+                } else {
+                    Vec::new()
+                };
+
                 let member = hir.classes
-                    .lookup_member(hir, self, self_ty, class.expect("Uninferred class during concretization"))
+                    .lookup_member(hir, self, self_ty, class_id, &field_gens[..class_param_count])
                     .expect("Could not select member candidate");
                 let member_gen_scope = hir.tys.get_gen_scope(member.gen_scope);
 
                 let mut links = HashMap::new();
                 self.derive_links(hir, member.member, self_ty, &mut |gen_idx, ty| { links.insert(gen_idx, ty); });
+                for (arg, param) in member.args.iter().zip(&field_gens) {
+                    self.derive_links(hir, *arg, *param, &mut |gen_idx, ty| { links.insert(gen_idx, ty); });
+                }
                 let gen = (0..member_gen_scope.len())
                     .map(|idx| *links.get(&idx).expect("Generic type not mentioned in member"))
+                    .chain(field_gens.iter().copied())
                     .collect::<Vec<_>>();
 
                 let field = member
@@ -435,6 +597,7 @@ impl<'a> fmt::Display for ConTyDisplay<'a> {
                 .iter()
                 .map(|param| format!(" {}", self.with_ty(*param, true)))
                 .collect::<String>()),
+            ConTy::Hole(_) => write!(f, "_"),
         }
     }
 }
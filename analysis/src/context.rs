@@ -30,17 +30,39 @@ impl Context {
         // Declare items before declaration
         for (attr, class) in module.classes() {
             let (gen_scope, mut errs) = GenScope::from_ast(&class.generics);
-            if gen_scope.len() != 0 {
-                errors.push(Error::Unsupported(class.generics.span(), "type parameters on classes"));
-                continue;
-            }
             errors.append(&mut errs);
+
+            // Fields may be polymorphic, so each has its own generic scope. The parameters of the class come first.
+            let field_scopes = class.items
+                .iter()
+                .filter_map(|item| match item {
+                    ast::ClassItem::Value { generics, .. } => {
+                        let (field_gen_scope, mut errs) = GenScope::from_ast(generics);
+                        errors.append(&mut errs);
+                        Some(this.tys.insert_gen_scope(gen_scope.concat(&field_gen_scope)))
+                    },
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
             let gen_scope = this.tys.insert_gen_scope(gen_scope);
+
+            // Classes over type constructors apply `Self` to arguments
+            let mut self_kind = None;
+            for item in &class.items {
+                if let ast::ClassItem::Value { ty, .. } = item {
+                    ty::visit_kinds(ty, &|_, _| None, &mut |name, kind, is_applied| if is_applied && **name == Ident::new("Self") {
+                        self_kind.get_or_insert(kind);
+                    });
+                }
+            }
+
             match this.classes.declare(class.name.clone(), Class {
                 name: class.name.clone(),
                 obligations: None,
                 attr: attr.to_vec(),
                 gen_scope,
+                self_kind: self_kind.unwrap_or(Kind::Ty),
                 fields: None,
                 assoc: None,
             }) {
@@ -49,7 +71,7 @@ impl Context {
                     continue;
                 },
                 // Only mark for further processing if no errors occurred during declaration
-                Ok(class_id) => classes.push((attr, class, class_id, gen_scope)),
+                Ok(class_id) => classes.push((attr, class, class_id, gen_scope, field_scopes)),
             }
         }
         for (attr, alias) in module.aliases() {
@@ -96,6 +118,50 @@ impl Context {
             defs_init.push((attr, def, gen_scope));
         }
 
+        // Infer the kinds of generic types. Data types go first (twice, so that they can pass type constructors to one
+        // another) because other items may pass their generic types to them.
+        for _ in 0..2 {
+            for (_, data) in &datas {
+                let gen_scope = this.datas.name_gen_scope(*data.name);
+                this.infer_gen_kinds(gen_scope, data.variants.iter().map(|(_, ty)| ty));
+            }
+        }
+        for (_, data) in &datas {
+            let gen_scope = this.datas.name_gen_scope(*data.name);
+            errors.append(&mut this.infer_gen_kinds(gen_scope, data.variants.iter().map(|(_, ty)| ty)));
+        }
+        for (_, alias) in &aliases {
+            let gen_scope = this.datas.name_gen_scope(*alias.name);
+            errors.append(&mut this.infer_gen_kinds(gen_scope, std::iter::once(&alias.ty)));
+        }
+        for (_, class, _, gen_scope, field_scopes) in &classes {
+            let field_tys = class.items
+                .iter()
+                .filter_map(|item| match item {
+                    ast::ClassItem::Value { ty, .. } => Some(ty),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            errors.append(&mut this.infer_gen_kinds(*gen_scope, field_tys.iter().copied()));
+            for (field_ty, field_scope) in field_tys.into_iter().zip(field_scopes) {
+                errors.append(&mut this.infer_gen_kinds(*field_scope, std::iter::once(field_ty)));
+            }
+        }
+        for (_, member, _, gen_scope) in &members_init {
+            let uses = std::iter::once(&member.member)
+                .chain(member.class.params.iter())
+                .chain(member.items
+                    .iter()
+                    .filter_map(|item| match item {
+                        ast::MemberItem::Type { ty, .. } => Some(ty),
+                        _ => None,
+                    }));
+            errors.append(&mut this.infer_gen_kinds(*gen_scope, uses));
+        }
+        for (_, def, gen_scope) in &defs_init {
+            errors.append(&mut this.infer_gen_kinds(*gen_scope, std::iter::once(&def.ty_hint)));
+        }
+
         // Check for lang items
         this.errors.append(&mut this.classes.check_lang_items());
 
@@ -130,30 +196,8 @@ impl Context {
             );
         }
 
-        // Derive class obligations
-        for (attr, class, class_id, gen_scope) in &classes {
-            this.classes.define_obligations(
-                *class_id,
-                class
-                    .obligation
-                    .iter()
-                    .filter_map(|obl| {
-                        if !obl.params.is_empty() {
-                            errors.push(Error::Unsupported(obl.span(), "type parameters on classes"));
-                        }
-                        match this.classes.lookup(*obl.name) {
-                            Some(class) => Some(SrcNode::new(Obligation::MemberOf(class), obl.span())),
-                            None => {
-                                errors.push(Error::NoSuchClass(obl.name.clone()));
-                                None
-                            },
-                        }
-                    })
-                    .collect(),
-            );
-        }
-        // Class associated types
-        for (attr, class, class_id, gen_scope) in &classes {
+        // Class associated types (declared before obligations, which may constrain them)
+        for (attr, class, class_id, gen_scope, _) in &classes {
             let mut existing_tys = HashMap::new();
             let assoc = class.items
                 .iter()
@@ -176,14 +220,55 @@ impl Context {
                 .collect::<Vec<_>>();
             this.classes.define_assoc(*class_id, assoc);
         }
+        // Derive class obligations
+        for (attr, class, class_id, gen_scope, _) in &classes {
+            let mut infer = Infer::new(&mut this, Some(*gen_scope));
+            let obligations = class
+                .obligation
+                .iter()
+                .filter_map(|obl| match infer.ctx().classes.lookup(*obl.name) {
+                    Some(obl_class) => {
+                        let params = lower_obligation_params(&mut infer, obl, obl_class);
+                        let assoc = lower_obligation_assoc(&mut infer, obl, obl_class);
+                        Some((obl_class, params, assoc, obl.span()))
+                    },
+                    None => {
+                        errors.push(Error::NoSuchClass(obl.name.clone()));
+                        None
+                    },
+                })
+                .collect::<Vec<_>>();
+            let (mut checked, mut errs) = infer.into_checked();
+            errors.append(&mut errs);
+            let obligations = obligations
+                .into_iter()
+                .map(|(obl_class, params, assoc, span)| SrcNode::new(Obligation::MemberOf(
+                    obl_class,
+                    params
+                        .into_iter()
+                        .map(|param| checked.reify(param))
+                        .collect(),
+                    assoc
+                        .into_iter()
+                        .map(|(name, ty)| (name, checked.reify(ty)))
+                        .collect(),
+                ), span))
+                .collect();
+            this.classes.define_obligations(*class_id, obligations);
+        }
+        // Generic obligations may have parameters that refer to aliases or to other generic types, so they can only be
+        // lowered now
+        errors.append(&mut this.check_obligation_params());
         // Class fields
-        for (attr, class, class_id, gen_scope) in &classes {
+        for (attr, class, class_id, gen_scope, field_scopes) in &classes {
             let mut existing_fields = HashMap::new();
             let fields = class.items
                 .iter()
-                .filter_map(|item| match item {
-                    ast::ClassItem::Value { name, ty } => {
-                        let mut infer = Infer::new(&mut this, Some(*gen_scope))
+                .filter(|item| matches!(item, ast::ClassItem::Value { .. }))
+                .zip(field_scopes)
+                .filter_map(|(item, field_scope)| match item {
+                    ast::ClassItem::Value { name, ty, .. } => {
+                        let mut infer = Infer::new(&mut this, Some(*field_scope))
                             .with_unknown_self(class.name.span(), vec![*class_id]);
 
                         let ty = ty.to_hir(&mut infer, &Scope::Empty);
@@ -199,6 +284,7 @@ impl Context {
                             existing_fields.insert(**name, name.span());
                             Some(ClassItem::Value {
                                 name: name.clone(),
+                                gen_scope: *field_scope,
                                 ty: SrcNode::new(checked.reify(ty.meta().1), ty.meta().0),
                             })
                         }
@@ -213,7 +299,13 @@ impl Context {
         for (attr, member, class_id, gen_scope) in members_init {
             let mut infer = Infer::new(&mut this, Some(gen_scope));
 
-            let member_ty = member.member.to_hir(&mut infer, &Scope::Empty);
+            let self_kind = infer.ctx().classes.get(class_id).self_kind;
+            let member_ty = lower_ty_of_kind(&member.member, &mut infer, &Scope::Empty, Some(self_kind));
+            let args = lower_obligation_params(&mut infer, &member.class, class_id);
+            // Members give their associated types with `=> Item = ...` instead
+            if let Some((name, _)) = member.class.assoc.first() {
+                errors.push(Error::Unsupported(name.span(), "associated type equalities on members"));
+            }
             // for obl in infer.ctx().classes.get(class_id).obligations.clone().expect("Obligations must be known") {
             //     match obl.inner() {
             //         Obligation::MemberOf(class) => infer.make_impl(member_ty.meta().1, *class, obl.span(), Vec::new()),
@@ -224,29 +316,58 @@ impl Context {
             errors.append(&mut errs);
 
             let member_ty = checked.reify(member_ty.meta().1);
+            let args = args
+                .into_iter()
+                .map(|arg| checked.reify(arg))
+                .collect();
 
             let member_id = this.classes.declare_member(class_id, Member {
                 gen_scope,
                 attr: attr.to_vec(),
                 member: member_ty,
+                args,
                 fields: None,
                 assoc: None,
             });
             members.push((member, class_id, member_id, gen_scope));
         }
+        // Blanket members must not overlap with other members of the same class
+        let member_spans = members
+            .iter()
+            .map(|(member, class_id, member_id, _)| (member.member.span(), *class_id, *member_id))
+            .collect::<Vec<_>>();
+        errors.append(&mut this.check_blanket_members(&member_spans));
         // Member obligations
         for (member, class_id, member_id, gen_scope) in &members {
             let mut infer = Infer::new(&mut this, Some(*gen_scope));
 
-            let member_ty = member.member.to_hir(&mut infer, &Scope::Empty);
-            for obl in infer.ctx().classes.get(*class_id).obligations.clone().expect("Obligations must be known") {
-                match obl.inner() {
-                    Obligation::MemberOf(class) => infer.make_impl(member_ty.meta().1, *class, obl.span(), Vec::new()),
-                }
+            let self_kind = infer.ctx().classes.get(*class_id).self_kind;
+            let member_ty = lower_ty_of_kind(&member.member, &mut infer, &Scope::Empty, Some(self_kind));
+            // Obligations of the class may refer to its parameters, which the member provides
+            let args = infer.ctx().classes
+                .get_member(*member_id).args
+                .clone()
+                .into_iter()
+                .map(|arg| infer.instantiate_local(arg, member.class.span()))
+                .collect::<Vec<_>>();
+            let obligations = infer.ctx().classes.get(*class_id).obligations.clone().expect("Obligations must be known");
+            for obl in &obligations {
+                infer.make_obligation(member_ty.meta().1, obl, &|idx, _, _| args[idx], Some(member_ty.meta().1));
             }
 
-            let (mut checked, mut errs) = infer.into_checked();
-            errors.append(&mut errs);
+            let (_, errs) = infer.into_checked();
+            // Failing to fulfil one of the class's own obligations means that a superclass member is missing
+            errors.extend(errs
+                .into_iter()
+                .map(|err| match err {
+                    Error::TypeDoesNotFulfil(superclass, ty, span, _) if obligations
+                        .iter()
+                        .any(|obl| obl.span() == span && matches!(&**obl, Obligation::MemberOf(class, _, _) if *class == superclass)) =>
+                    {
+                        Error::MissingSuperclassMember(*class_id, superclass, ty, member.member.span(), span)
+                    },
+                    err => err,
+                }));
         }
         // Member associated types
         for (member, class_id, member_id, gen_scope) in &members {
@@ -375,7 +496,26 @@ impl Context {
                 .iter()
                 .filter_map(|item| {
                     let member_ty = this.classes.get_member(*member_id).member;
-                    let mut infer = Infer::new(&mut this, Some(*gen_scope))
+
+                    // Polymorphic fields may make use of the generic types of both the member and the field
+                    let field_gen_scope = match item {
+                        ast::MemberItem::Value { name, .. } => this.classes
+                            .get(*class_id)
+                            .field(**name)
+                            .map(|(field_gen_scope, _)| field_gen_scope)
+                            .filter(|field_gen_scope| this.tys.get_gen_scope(*field_gen_scope).len() > 0),
+                        _ => None,
+                    };
+                    let item_gen_scope = if let Some(field_gen_scope) = field_gen_scope {
+                        let item_gen_scope = this.tys
+                            .get_gen_scope(*gen_scope)
+                            .concat(this.tys.get_gen_scope(field_gen_scope));
+                        this.tys.insert_gen_scope(item_gen_scope)
+                    } else {
+                        *gen_scope
+                    };
+
+                    let mut infer = Infer::new(&mut this, Some(item_gen_scope))
                         .with_self_type(member_ty, member.member.span());
 
                     let class = infer.ctx().classes.get(*class_id);
@@ -387,12 +527,31 @@ impl Context {
                         } else {
                             let val = val.to_hir(&mut infer, &Scope::Empty);
                             let class = infer.ctx().classes.get(*class_id);
-                            if let Some(field_ty) = class.field(**name).cloned() {
-                                let self_ty = member.member.to_hir(&mut infer, &Scope::Empty).meta().1;
+                            let (self_kind, class_gen_scope) = (class.self_kind, class.gen_scope);
+                            if let Some(field_ty) = class.field(**name).map(|(_, field_ty)| field_ty.clone()) {
+                                let self_ty = lower_ty_of_kind(&member.member, &mut infer, &Scope::Empty, Some(self_kind)).meta().1;
+                                // The parameters of the class are provided by the member, the generic types of the
+                                // field follow those of the member
+                                let member_gen_count = infer.ctx().tys.get_gen_scope(*gen_scope).len();
+                                let class_param_count = infer.ctx().tys.get_gen_scope(class_gen_scope).len();
+                                let args = infer.ctx().classes
+                                    .get_member(*member_id).args
+                                    .clone()
+                                    .into_iter()
+                                    .map(|arg| infer.instantiate_local(arg, member.class.span()))
+                                    .collect::<Vec<_>>();
+                                let field_gens = args
+                                    .into_iter()
+                                    .chain((member_gen_count + class_param_count..infer.ctx().tys.get_gen_scope(item_gen_scope).len())
+                                        .map(|idx| {
+                                            let span = infer.ctx().tys.get_gen_scope(item_gen_scope).get(idx).name.span();
+                                            infer.insert(span, TyInfo::Gen(idx, item_gen_scope, span))
+                                        }))
+                                    .collect::<Vec<_>>();
                                 let val_ty = infer.instantiate(
                                     *field_ty,
                                     field_ty.span(),
-                                    &|_, _, _| panic!("Generics not yet supported on classes"),
+                                    &|idx, _, _| field_gens[idx],
                                     Some(self_ty),
                                 );
                                 infer.check_flow(val.meta().1, val_ty, EqInfo::new(name.span(), format!("Type of member item must match class")));
@@ -467,9 +626,157 @@ impl Context {
 
         errors.append(&mut this.errors);
 
+        // The kinds of generic types are checked both when they are inferred and when each of their uses is lowered,
+        // so the same mismatch may have been reported twice
+        let mut kind_mismatches = Vec::new();
+        errors.retain(|err| match err {
+            Error::KindMismatch(span, found, expected) if kind_mismatches.contains(&(*span, *found, *expected)) => false,
+            Error::KindMismatch(span, found, expected) => {
+                kind_mismatches.push((*span, *found, *expected));
+                true
+            },
+            _ => true,
+        });
+
         (this, errors)
     }
 
+    // Lower the parameters of generic obligations (i.e: `Nat` in `A < Convert Nat`)
+    fn check_obligation_params(&mut self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for gen_scope in self.tys.gen_scope_ids().collect::<Vec<_>>() {
+            for idx in 0..self.tys.get_gen_scope(gen_scope).len() {
+                let ast_obligations = self.tys.get_gen_scope(gen_scope).get(idx).ast_obligations.clone();
+                if ast_obligations.is_empty() {
+                    continue;
+                }
+
+                let mut infer = Infer::new(self, Some(gen_scope));
+                let obligations = ast_obligations
+                    .iter()
+                    // Obligations on classes that do not exist have already been reported
+                    .filter_map(|obl| infer.ctx().classes.lookup(*obl.name).map(|class| (obl, class)))
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .map(|(obl, class)| (
+                        class,
+                        lower_obligation_params(&mut infer, obl, class),
+                        lower_obligation_assoc(&mut infer, obl, class),
+                        obl.name.span(),
+                    ))
+                    .collect::<Vec<_>>();
+                let (mut checked, mut errs) = infer.into_checked();
+                errors.append(&mut errs);
+                let obligations = obligations
+                    .into_iter()
+                    .map(|(class, params, assoc, span)| SrcNode::new(Obligation::MemberOf(
+                        class,
+                        params
+                            .into_iter()
+                            .map(|param| checked.reify(param))
+                            .collect(),
+                        assoc
+                            .into_iter()
+                            .map(|(name, ty)| (name, checked.reify(ty)))
+                            .collect(),
+                    ), span))
+                    .collect();
+                self.tys.define_gen_obligations(gen_scope, idx, obligations);
+            }
+        }
+        errors
+    }
+
+    // Check that blanket members (i.e: `for S < Stream member S of IntoStream`) do not overlap with other members of
+    // the same class. A more specific member overlaps with a blanket member only if it could fulfil the bounds of the
+    // blanket member. Nothing is known about the bounds of two blanket members, so they always overlap.
+    fn check_blanket_members(&self, members: &[(Span, ClassId, MemberId)]) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for (i, (blanket_span, class, blanket_id)) in members.iter().enumerate() {
+            let blanket = self.classes.get_member(*blanket_id);
+            let Some(bounds) = self.blanket_bounds(blanket) else { continue };
+            for (j, (other_span, other_class, other_id)) in members.iter().enumerate() {
+                let other = self.classes.get_member(*other_id);
+                // Overlapping blanket members only get reported once
+                if i == j || class != other_class || (other.is_blanket(&self.tys) && j < i) {
+                    continue;
+                }
+
+                let overlaps = blanket.args
+                    .iter()
+                    .zip(&other.args)
+                    .all(|(x, y)| self.tys_overlap(*x, *y))
+                    && (other.is_blanket(&self.tys) || bounds
+                        .iter()
+                        .all(|bound| self.classes
+                            .members_of(*bound)
+                            .any(|(_, bound_member)| self.tys_overlap(bound_member.member, other.member))));
+                if overlaps {
+                    errors.push(Error::OverlappingMembers(*class, *blanket_span, *other_span));
+                }
+            }
+        }
+        errors
+    }
+
+    fn blanket_bounds(&self, member: &Member) -> Option<Vec<ClassId>> {
+        match self.tys.get(member.member) {
+            Ty::Gen(gen_idx, gen_scope) => Some(self.tys
+                .get_gen_scope(gen_scope)
+                .get(gen_idx)
+                .obligations()
+                .iter()
+                .map(|obl| match &**obl {
+                    Obligation::MemberOf(class, _, _) => *class,
+                })
+                .collect()),
+            _ => None,
+        }
+    }
+
+    // Returns true if some type could be an instance of both types (generic types may be instantiated with anything)
+    fn tys_overlap(&self, x: TyId, y: TyId) -> bool {
+        match (self.tys.get(x), self.tys.get(y)) {
+            (Ty::Error(_), _) | (_, Ty::Error(_)) => false,
+            (Ty::Gen(_, _), _) | (_, Ty::Gen(_, _)) => true,
+            // Projections and applications are not known until instantiation, so they could be anything
+            (Ty::Assoc(_, _, _) | Ty::Apply(_, _) | Ty::SelfType, _)
+            | (_, Ty::Assoc(_, _, _) | Ty::Apply(_, _) | Ty::SelfType) => true,
+            (Ty::Prim(x), Ty::Prim(y)) => x == y,
+            (Ty::Hole(x), Ty::Hole(y)) => x == y,
+            (Ty::List(x), Ty::List(y)) => self.tys_overlap(x, y),
+            (Ty::Tuple(xs), Ty::Tuple(ys)) => xs.len() == ys.len() && xs
+                .into_iter()
+                .zip(ys)
+                .all(|(x, y)| self.tys_overlap(x, y)),
+            (Ty::Union(xs), Ty::Union(ys)) => xs.len() == ys.len(),
+            (Ty::Record(xs), Ty::Record(ys)) => xs.len() == ys.len() && xs
+                .into_iter()
+                .zip(ys)
+                .all(|((x_name, x), (y_name, y))| x_name == y_name && self.tys_overlap(x, y)),
+            (Ty::Func(x_i, x_o), Ty::Func(y_i, y_o)) => self.tys_overlap(x_i, y_i) && self.tys_overlap(x_o, y_o),
+            (Ty::Data(x, xs), Ty::Data(y, ys)) => x == y && xs
+                .into_iter()
+                .zip(ys)
+                .all(|(x, y)| self.tys_overlap(x, y)),
+            _ => false,
+        }
+    }
+
+    // Infer the kinds of the types in a generic scope from the types that make use of them
+    fn infer_gen_kinds<'a>(&mut self, gen_scope: GenScopeId, uses: impl IntoIterator<Item = &'a SrcNode<ast::Type>>) -> Vec<Error> {
+        let (kinds, errors) = self.tys.get_gen_scope(gen_scope).infer_kinds(
+            uses,
+            &|name, idx| self.datas.lookup_data(name).and_then(|_| {
+                let data_gen_scope = self.tys.get_gen_scope(self.datas.name_gen_scope(name));
+                (idx < data_gen_scope.len()).then(|| data_gen_scope.get(idx).kind)
+            }),
+            &|name| self.classes.lookup(name).map(|class| self.classes.get(class).self_kind),
+        );
+        self.tys.define_gen_kinds(gen_scope, kinds);
+        errors
+    }
+
     pub fn concretize(&self) -> (ConContext, Vec<Error>) {
         ConContext::from_ctx(self)
     }
@@ -7,6 +7,8 @@ pub enum Error {
     CannotInfer(TyId, Option<Span>),
     Recursive(TyId, Span, Span),
     NoSuchItem(TyId, Span, SrcNode<Ident>),
+    // Record patterns with `..` and record updates only work on records
+    NotARecord(TyId, Span),
     NoSuchLocal(SrcNode<Ident>),
     WrongNumberOfParams(Span, usize, Span, usize),
     NoBranches(Span),
@@ -19,7 +21,13 @@ pub enum Error {
     NoSuchClass(SrcNode<Ident>),
     NoSuchClassItem(SrcNode<Ident>, SrcNode<Ident>),
     AmbiguousClassItem(SrcNode<Ident>, Vec<ClassId>),
+    // (class, type, class_params, obligation_origin)
+    AmbiguousClassParams(ClassId, TyId, Vec<TyId>, Span),
     MissingClassItem(Span, SrcNode<Ident>, SrcNode<Ident>),
+    // (class, blanket_member, other_member)
+    OverlappingMembers(ClassId, Span, Span),
+    // (class, superclass, type, member, superclass_origin)
+    MissingSuperclassMember(ClassId, ClassId, TyId, Span, Span),
     RecursiveAlias(AliasId, TyId, Span),
     DuplicateTypeName(Ident, Span, Span),
     DuplicateDefName(Ident, Span, Span),
@@ -28,10 +36,18 @@ pub enum Error {
     DuplicateClassName(Ident, Span, Span),
     DuplicateClassItem(Ident, Span, Span),
     DuplicateMemberItem(Ident, Span, Span),
+    DuplicateFieldName(Ident, Span, Span),
     PatternNotSupported(TyId, SrcNode<ast::BinaryOp>, TyId, Span),
+    // (lower_bound, upper_bound, span)
+    RangeNotSupported(TyId, TyId, Span),
+    EmptyRange(Span),
+    // (name, alternative_missing_name)
+    OrPatternMismatch(SrcNode<Ident>, Span),
     // Span, uncovered example, hidden_outer
     NotExhaustive(Span, ExamplePat, bool),
     WrongNumberOfGenerics(Span, usize, Span, usize),
+    // (span, found, expected)
+    KindMismatch(Span, Kind, Kind),
     DefTypeNotSpecified(Span, Span, Ident),
     SelfNotValidHere(Span),
     NoEntryPoint(Span),
@@ -41,6 +57,8 @@ pub enum Error {
     Unsupported(Span, &'static str),
     NonNumeric(TyId, Span, NumLitr),
     MissingLangItem(&'static str),
+    // (span, expected, locals_in_scope, fitting_defs)
+    TypedHole(Span, TyId, Vec<(Ident, TyId)>, Vec<DefId>),
 }
 
 impl Error {
@@ -98,6 +116,15 @@ impl Error {
                 ],
                 vec![],
             ),
+            Error::NotARecord(a, span) => (
+                format!("Type {} is not a record", display(a).fg(Color::Red)),
+                vec![(span, format!("Has type {}", display(a).fg(Color::Yellow)), Color::Red)],
+                vec![format!(
+                    "Only records support {} patterns and {} updates, data types must be deconstructed first",
+                    format!("{{ .. }}").fg(Color::Blue),
+                    format!("{{ r with ... }}").fg(Color::Blue),
+                )],
+            ),
             Error::NoSuchLocal(local) => (
                 format!("No such local {}", (*local).fg(Color::Red)),
                 vec![(local.span(), format!("Scope does not contain this"), Color::Red)],
@@ -197,6 +224,34 @@ impl Error {
                 ],
                 vec![format!("Consider adding the item like {}", format!("=> {} = ...", *item).fg(Color::Blue))],
             ),
+            Error::MissingSuperclassMember(class, superclass, ty, member, span) => (
+                format!(
+                    "Type {} is a member of {} but not of its superclass {}",
+                    display(ty).fg(Color::Red),
+                    (*ctx.classes.get(class).name).fg(Color::Yellow),
+                    (*ctx.classes.get(superclass).name).fg(Color::Red),
+                ),
+                vec![
+                    (member, format!("Member of {} declared here", (*ctx.classes.get(class).name).fg(Color::Yellow)), Color::Red),
+                    (span, format!(
+                        "Members of {} must also be members of {}",
+                        (*ctx.classes.get(class).name).fg(Color::Yellow),
+                        (*ctx.classes.get(superclass).name).fg(Color::Red),
+                    ), Color::Yellow),
+                ],
+                vec![format!(
+                    "Consider adding a member like {}",
+                    format!("member {} of {}", display(ty), *ctx.classes.get(superclass).name).fg(Color::Blue),
+                )],
+            ),
+            Error::OverlappingMembers(class, blanket, other) => (
+                format!("Overlapping members of class {}", (*ctx.classes.get(class).name).fg(Color::Red)),
+                vec![
+                    (blanket, format!("This blanket member covers every type that fulfils its bounds"), Color::Red),
+                    (other, format!("This member covers some of the same types"), Color::Yellow),
+                ],
+                vec![format!("A type may only be a member of a class once")],
+            ),
             Error::RecursiveAlias(alias, ty, span) => (
                 format!("Recursive type alias"),
                 vec![
@@ -267,14 +322,47 @@ impl Error {
                 ],
                 vec![],
             ),
+            Error::DuplicateFieldName(name, old, new) => (
+                format!("Field {} specified multiple times in record", name.fg(Color::Red)),
+                vec![
+                    (old, format!("Previous field"), Color::Yellow),
+                    (new, format!("Conflicting field"), Color::Red),
+                ],
+                vec![],
+            ),
             Error::PatternNotSupported(lhs, op, rhs, span) => (
                 format!("Arithmetic pattern {} {} {} is not supported", display(lhs).fg(Color::Red), (*op).fg(Color::Red), display(rhs).fg(Color::Red)),
                 vec![(span, format!("Pattern {} used here", (*op).fg(Color::Red)), Color::Red)],
                 vec![format!(
-                    "Only specific arithmetic patterns, such as {}, are supported",
-                    format!("Nat + Nat").fg(Color::Blue),
+                    "Only specific arithmetic patterns, such as {} or {} (where {} is a natural or integer literal), are supported",
+                    format!("x + N").fg(Color::Blue),
+                    format!("x * N").fg(Color::Blue),
+                    format!("N").fg(Color::Blue),
                 )],
             ),
+            Error::RangeNotSupported(lo, hi, span) => (
+                format!("Range pattern {}..{} is not supported", display(lo).fg(Color::Red), display(hi).fg(Color::Red)),
+                vec![(span, format!("Range used here"), Color::Red)],
+                vec![format!(
+                    "Only ranges between two {}, {} or {} bounds are supported",
+                    format!("Nat").fg(Color::Blue),
+                    format!("Int").fg(Color::Blue),
+                    format!("Char").fg(Color::Blue),
+                )],
+            ),
+            Error::EmptyRange(span) => (
+                format!("Range pattern is empty"),
+                vec![(span, format!("This range matches no values"), Color::Red)],
+                vec![format!("The lower bound of a range pattern must not be greater than its upper bound")],
+            ),
+            Error::OrPatternMismatch(name, alt) => (
+                format!("Variable {} is not bound in all alternatives of pattern", (*name).fg(Color::Red)),
+                vec![
+                    (name.span(), format!("Bound here"), Color::Yellow),
+                    (alt, format!("Alternative does not bind {}", (*name).fg(Color::Red)), Color::Red),
+                ],
+                vec![format!("Every alternative of an or-pattern must bind the same variables")],
+            ),
             Error::NotExhaustive(span, example, is_match) => (
                 format!("{} is not exhaustive", if is_match { "Pattern match"} else { "Let" }),
                 vec![(span, format!("Pattern {} not covered", example.display(ctx, is_match).fg(Color::Red)), Color::Red)],
@@ -295,6 +383,13 @@ impl Error {
                 ],
                 vec![],
             ),
+            Error::KindMismatch(span, found, expected) => (
+                format!("Type of kind {} cannot be used where kind {} is expected", found.fg(Color::Red), expected.fg(Color::Yellow)),
+                vec![
+                    (span, format!("Has kind {}", found.fg(Color::Red)), Color::Red),
+                ],
+                vec![format!("Types of kind {} are type constructors: they must be applied to other types to produce a type of kind {}", "* -> *".fg(Color::Blue), "*".fg(Color::Blue))],
+            ),
             Error::DefTypeNotSpecified(def, usage, name) => (
                 format!("Type of {} must be fully specified", name.fg(Color::Red)),
                 vec![
@@ -345,6 +440,24 @@ impl Error {
                     .collect::<Vec<_>>()
                     .join(", "))],
             ),
+            Error::AmbiguousClassParams(class, ty, params, span) => (
+                format!(
+                    "Cannot determine which member of {} to use for type {}",
+                    (*ctx.classes.get(class).name).fg(Color::Red),
+                    display(ty).fg(Color::Red),
+                ),
+                vec![
+                    (span, format!(
+                        "Requires {} to be a member of {}",
+                        display(ty).fg(Color::Yellow),
+                        format!("{}{}", *ctx.classes.get(class).name, params
+                            .iter()
+                            .map(|param| format!(" {}", display(*param)))
+                            .collect::<String>()).fg(Color::Yellow),
+                    ), Color::Red),
+                ],
+                vec![format!("The parameters of the class could not be inferred, consider adding a type annotation")],
+            ),
             Error::InvalidIntrinsic(intrinsic) => (
                 format!("Intrinsic {} is not valid", (*intrinsic).fg(Color::Red)),
                 vec![
@@ -372,6 +485,37 @@ impl Error {
                 Vec::new(),
                 vec![format!("All lang items must be defined")],
             ),
+            Error::TypedHole(span, ty, locals, defs) => (
+                format!("Found hole of type {}", display(ty).fg(Color::Yellow)),
+                vec![(span, format!("Expected a value of type {} here", display(ty).fg(Color::Yellow)), Color::Yellow)],
+                {
+                    let mut notes = Vec::new();
+                    if !locals.is_empty() {
+                        notes.push(format!(
+                            "Locals in scope: {}",
+                            locals
+                                .into_iter()
+                                .map(|(local, ty)| format!("{} : {}", local, display(ty)).fg(Color::Blue).to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        ));
+                    }
+                    if !defs.is_empty() {
+                        // Very general holes may be filled by much of the program, which isn't a useful suggestion
+                        const MAX_SHOWN: usize = 8;
+                        let mut shown = defs
+                            .iter()
+                            .take(MAX_SHOWN)
+                            .map(|def| (*ctx.defs.get(*def).name).fg(Color::Blue).to_string())
+                            .collect::<Vec<_>>();
+                        if defs.len() > MAX_SHOWN {
+                            shown.push(format!("and {} more", defs.len() - MAX_SHOWN));
+                        }
+                        notes.push(format!("Definitions that fit: {}", shown.join(", ")));
+                    }
+                    notes
+                },
+            ),
         };
 
         let mut report = Report::build(
@@ -6,14 +6,148 @@ use std::{
 };
 use ranges::Ranges;
 
+// Bounds of at least this magnitude are treated as infinite, so that offsetting an unbounded set leaves it unbounded
+const INF: i128 = 1 << 100;
+// The largest period that we track precisely: beyond this, sets are under-approximated
+const MAX_PERIOD: i128 = 1 << 12;
+
+const NAT_DOMAIN: (i128, i128) = (0, INF);
+const INT_DOMAIN: (i128, i128) = (-INF, INF);
+const CHAR_DOMAIN: (i128, i128) = (0, char::MAX as i128);
+
+fn is_inf(x: i128) -> bool { x <= -INF || x >= INF }
+
+fn gcd(a: i128, b: i128) -> i128 { if b == 0 { a } else { gcd(b, a % b) } }
+
+/// A set of naturals, integers, or characters (by codepoint), used to reason about arithmetic and range patterns.
+///
+/// Values are split into residue classes modulo `period`: a value `v` is in the set if `v.div_euclid(period)` falls
+/// within one of the inclusive ranges in `residues[v.rem_euclid(period)]`. This is enough to represent patterns like
+/// `x * 2 + 1` exactly.
+///
+/// Where a set cannot be represented precisely it is under-approximated. This is sound: patterns are treated as
+/// covering fewer values than they really do, which can only cause matches to be considered inexhaustive.
+#[derive(Clone, Debug)]
+pub struct IntSet {
+    period: i128,
+    residues: Vec<Vec<(i128, i128)>>,
+}
+
+impl IntSet {
+    fn range(lo: i128, hi: i128) -> Self {
+        Self { period: 1, residues: vec![if lo <= hi { vec![(lo, hi)] } else { Vec::new() }] }
+    }
+
+    fn empty() -> Self { Self::range(1, 0) }
+
+    fn single(x: i128) -> Self { Self::range(x, x) }
+
+    fn surrogates() -> Self { Self::range(0xD800, 0xDFFF) }
+
+    // Add `n` to every value in the set
+    fn shift(&self, n: i128) -> Self {
+        let shift_bound = |x: i128, d: i128| if is_inf(x) { x } else { (x + d).clamp(-INF, INF) };
+        let mut residues = vec![Vec::new(); self.period as usize];
+        for (r, ranges) in self.residues.iter().enumerate() {
+            let r = r as i128 + n;
+            let (d, r) = (r.div_euclid(self.period), r.rem_euclid(self.period));
+            residues[r as usize].extend(ranges.iter().map(|(lo, hi)| (shift_bound(*lo, d), shift_bound(*hi, d))));
+        }
+        Self { period: self.period, residues }
+    }
+
+    // Multiply every value in the set by `n`
+    fn scale(&self, n: i128) -> Self {
+        let period = self.period * n;
+        if period > MAX_PERIOD {
+            return Self::empty();
+        }
+        let mut residues = vec![Vec::new(); period as usize];
+        for (r, ranges) in self.residues.iter().enumerate() {
+            residues[r * n as usize] = ranges.clone();
+        }
+        Self { period, residues }
+    }
+
+    // Represent the same set with a period that is a multiple of the current one
+    fn with_period(&self, period: i128) -> Self {
+        let factor = period / self.period;
+        // Residue `r` of the new period is residue `r % self.period` of the old one, where a value with old quotient
+        // `q` has new quotient `(q - r / self.period) / factor` (if that divides exactly)
+        let div_bound = |x: i128, j: i128, ceil: bool| if is_inf(x) {
+            x
+        } else if ceil {
+            -(j - x).div_euclid(factor)
+        } else {
+            (x - j).div_euclid(factor)
+        };
+        let residues = (0..period)
+            .map(|r| self.residues[(r % self.period) as usize]
+                .iter()
+                .map(|(lo, hi)| (div_bound(*lo, r / self.period, true), div_bound(*hi, r / self.period, false)))
+                .filter(|(lo, hi)| lo <= hi)
+                .collect::<Vec<_>>())
+            .collect();
+        Self { period, residues }
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        let period = self.period / gcd(self.period, other.period) * other.period;
+        if period > MAX_PERIOD {
+            return self.clone();
+        }
+        let (this, other) = (self.with_period(period), other.with_period(period));
+        Self {
+            period,
+            residues: this.residues
+                .into_iter()
+                .zip(other.residues)
+                .map(|(mut a, b)| { a.extend(b); a })
+                .collect(),
+        }
+    }
+
+    // Find the value closest to zero within the inclusive domain that is not in the set, if there is one
+    fn uncovered(&self, (lo, hi): (i128, i128)) -> Option<i128> {
+        // Prefer finite examples
+        let pick = |lo: i128, hi: i128| if !is_inf(lo) { lo } else if !is_inf(hi) { hi } else { 0 };
+        (0..self.period)
+            .filter_map(|r| {
+                // The quotients of the values within the domain
+                let q_lo = if is_inf(lo) { lo } else { -(r - lo).div_euclid(self.period) };
+                let q_hi = if is_inf(hi) { hi } else { (hi - r).div_euclid(self.period) };
+
+                let mut ranges = self.residues[r as usize].clone();
+                ranges.sort();
+                // The smallest quotient not yet known to be covered
+                let mut next = q_lo;
+                for (a, b) in ranges {
+                    if next > q_hi {
+                        break;
+                    } else if a > next {
+                        return Some(pick(next, (a - 1).min(q_hi)) * self.period + r);
+                    } else {
+                        next = next.max(b + 1);
+                    }
+                }
+                if next <= q_hi {
+                    Some(pick(next, q_hi) * self.period + r)
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|x| x.abs())
+    }
+}
+
 #[derive(Debug)]
 pub enum AbstractPat {
     Wildcard,
     Bool([bool; 2]),
-    Nat(Ranges<u64>),
-    Int(Ranges<i64>),
+    Nat(IntSet),
+    Int(IntSet),
     Real(f64),
-    Char(char),
+    Char(IntSet),
     Tuple(Vec<Self>),
     Record(Vec<(Ident, Self)>),
     Variant(DataId, Ident, Box<Self>),
@@ -21,6 +155,7 @@ pub enum AbstractPat {
     ListFront(Vec<Self>, Box<Self>), // At least N in size
     Gen(Ident),
     Union(TyId, Box<Self>),
+    Or(Vec<Self>), // Matches if any alternative matches
 }
 
 impl AbstractPat {
@@ -32,19 +167,22 @@ impl AbstractPat {
                 !x,
                 *x,
             ]),
-            hir::Pat::Literal(hir::Literal::Nat(x)) => Self::Nat({
-                let mut range = Ranges::new();
-                range.insert(*x..*x + 1);
-                range
-            }),
-            hir::Pat::Literal(hir::Literal::Str(x)) => Self::ListExact(x.chars().map(Self::Char).collect()),
+            hir::Pat::Literal(hir::Literal::Nat(x)) => Self::Nat(IntSet::single(*x as i128)),
+            hir::Pat::Literal(hir::Literal::Int(x)) => Self::Int(IntSet::single(*x as i128)),
+            hir::Pat::Literal(hir::Literal::Char(c)) => Self::Char(IntSet::single(*c as i128)),
+            hir::Pat::Literal(hir::Literal::Str(x)) => Self::ListExact(x
+                .chars()
+                .map(|c| Self::Char(IntSet::single(c as i128)))
+                .collect()),
             hir::Pat::Single(inner) => Self::from_binding(ctx, inner),
             hir::Pat::Union(inner) => Self::Union(inner.meta().1, Box::new(Self::from_binding(ctx, inner))),
-            hir::Pat::Add(lhs, rhs) if matches!(&*lhs.pat, hir::Pat::Wildcard) => Self::Nat({
-                let mut range = Ranges::new();
-                range.insert(**rhs..);
-                range
-            }),
+            hir::Pat::Add(_, _) | hir::Pat::Mul(_, _) | hir::Pat::Range(_, _) => match ctx.tys.get(binding.meta().1) {
+                Ty::Prim(Prim::Nat) => Self::Nat(Self::int_set(binding, NAT_DOMAIN)),
+                Ty::Prim(Prim::Int) => Self::Int(Self::int_set(binding, INT_DOMAIN)),
+                Ty::Prim(Prim::Char) => Self::Char(Self::int_set(binding, CHAR_DOMAIN)),
+                // Type mismatch, don't yield an error because one was already generated
+                _ => Self::Wildcard,
+            },
             hir::Pat::Tuple(fields) => AbstractPat::Tuple(fields
                 .iter()
                 .map(|field| AbstractPat::from_binding(ctx, field))
@@ -58,21 +196,53 @@ impl AbstractPat {
                 .iter()
                 .map(|item| AbstractPat::from_binding(ctx, item))
                 .collect(), Box::new(tail.as_ref().map(|tail| AbstractPat::from_binding(ctx, tail)).unwrap_or(AbstractPat::Wildcard))),
-            hir::Pat::Record(fields) => AbstractPat::Record(fields
+            hir::Pat::Record(fields) => match ctx.tys.get(binding.meta().1) {
+                // Fields not mentioned by a `..` pattern are covered by wildcards
+                Ty::Record(tys) => AbstractPat::Record(tys
+                    .keys()
+                    .map(|name| (*name, fields
+                        .get(name)
+                        .map(|field| AbstractPat::from_binding(ctx, field))
+                        .unwrap_or(AbstractPat::Wildcard)))
+                    .collect()),
+                // Type mismatch, don't yield an error because one was already generated
+                _ => AbstractPat::Wildcard,
+            },
+            hir::Pat::Or(alts) => AbstractPat::Or(alts
                 .iter()
-                .map(|(name, field)| (*name, AbstractPat::from_binding(ctx, field)))
+                .map(|alt| AbstractPat::from_binding(ctx, alt))
                 .collect()),
             pat => todo!("{:?}", pat),
         }
     }
 
+    // The values matched by a pattern over naturals, integers, or characters with the given domain
+    fn int_set(binding: &TyBinding, domain: (i128, i128)) -> IntSet {
+        match &*binding.pat {
+            hir::Pat::Literal(hir::Literal::Nat(x)) => IntSet::single(*x as i128),
+            hir::Pat::Literal(hir::Literal::Int(x)) => IntSet::single(*x as i128),
+            hir::Pat::Literal(hir::Literal::Char(c)) => IntSet::single(*c as i128),
+            hir::Pat::Range(hir::Literal::Nat(lo), hir::Literal::Nat(hi)) => IntSet::range(*lo as i128, *hi as i128),
+            hir::Pat::Range(hir::Literal::Int(lo), hir::Literal::Int(hi)) => IntSet::range(*lo as i128, *hi as i128),
+            hir::Pat::Range(hir::Literal::Char(lo), hir::Literal::Char(hi)) => IntSet::range(*lo as i128, *hi as i128),
+            hir::Pat::Single(inner) => Self::int_set(inner, domain),
+            hir::Pat::Add(lhs, rhs) => Self::int_set(lhs, domain).shift(**rhs as i128),
+            hir::Pat::Mul(lhs, rhs) => Self::int_set(lhs, domain).scale(**rhs as i128),
+            hir::Pat::Or(alts) => alts
+                .iter()
+                .fold(IntSet::empty(), |set, alt| set.union(&Self::int_set(alt, domain))),
+            // Wildcards and errors (an error has already been generated for type mismatches)
+            _ => IntSet::range(domain.0, domain.1),
+        }
+    }
+
     fn is_refutable(&self, ctx: &Context) -> bool {
         match self {
             AbstractPat::Wildcard => false,
             AbstractPat::Bool([t, f]) => !(*t && *f),
-            AbstractPat::Nat(set) => !set.clone().invert().is_empty(),
-            AbstractPat::Int(set) => !set.clone().invert().is_empty(),
-            AbstractPat::Char(_) => true,
+            AbstractPat::Nat(set) => set.uncovered(NAT_DOMAIN).is_some(),
+            AbstractPat::Int(set) => set.uncovered(INT_DOMAIN).is_some(),
+            AbstractPat::Char(set) => set.union(&IntSet::surrogates()).uncovered(CHAR_DOMAIN).is_some(),
             AbstractPat::Tuple(fields) => !fields
                 .iter()
                 .all(|field| !field.is_refutable(ctx)),
@@ -82,16 +252,33 @@ impl AbstractPat {
             AbstractPat::Record(fields) => !fields
                 .iter()
                 .all(|(_, field)| !field.is_refutable(ctx)),
+            // Alternatives might cover every value together without any one doing so alone, so this may over-report
+            AbstractPat::Or(alts) => alts
+                .iter()
+                .all(|alt| alt.is_refutable(ctx)),
             pat => todo!("{:?}", pat),
         }
     }
 
+    // Collect the alternatives of a pattern, flattening or-patterns
+    fn alternatives<'a>(&'a self, alts: &mut Vec<&'a Self>) {
+        match self {
+            AbstractPat::Or(pats) => pats.iter().for_each(|pat| pat.alternatives(alts)),
+            pat => alts.push(pat),
+        }
+    }
+
     fn inexhaustive_pat<'a>(
         ctx: &Context,
         ty: TyId,
-        mut filter: &mut dyn Iterator<Item = &'a AbstractPat>,
+        filter: &mut dyn Iterator<Item = &'a AbstractPat>,
         get_gen_ty: Option<&dyn Fn(usize) -> Option<TyId>>,
     ) -> Option<ExamplePat> {
+        // An or-pattern covers exactly the values that its alternatives cover when taken as separate arms
+        let mut alts = Vec::new();
+        filter.for_each(|pat| pat.alternatives(&mut alts));
+        let mut filter = alts.into_iter();
+
         let ty = match ctx.tys.get(ty) {
             Ty::Gen(idx, _) => {
                 get_gen_ty.and_then(|get_gen_ty| get_gen_ty(idx)).unwrap_or(ty)
@@ -102,26 +289,26 @@ impl AbstractPat {
         match ctx.tys.get(ty) {
             Ty::Error(_) => None,
             Ty::Prim(Prim::Nat) => {
-                let mut covered = Ranges::new();
+                let mut covered = IntSet::empty();
                 for pat in filter {
                     match pat {
                         AbstractPat::Wildcard => return None,
-                        AbstractPat::Nat(x) => covered = covered.clone().union(x.clone()),
+                        AbstractPat::Nat(x) => covered = covered.union(x),
                         _ => return None, // Type mismatch, don't yield an error because one was already generated
                     }
                 }
-                covered.clone().invert().into_iter().next().map(ExamplePrim::Nat).map(ExamplePat::Prim)
+                covered.uncovered(NAT_DOMAIN).map(|x| ExamplePat::Prim(ExamplePrim::Nat(x as u64)))
             },
             Ty::Prim(Prim::Int) => {
-                let mut covered = Ranges::new();
+                let mut covered = IntSet::empty();
                 for pat in filter {
                     match pat {
                         AbstractPat::Wildcard => return None,
-                        AbstractPat::Int(x) => covered = covered.clone().union(x.clone()),
+                        AbstractPat::Int(x) => covered = covered.union(x),
                         _ => return None, // Type mismatch, don't yield an error because one was already generated
                     }
                 }
-                covered.clone().invert().into_iter().next().map(ExamplePrim::Int).map(ExamplePat::Prim)
+                covered.uncovered(INT_DOMAIN).map(|x| ExamplePat::Prim(ExamplePrim::Int(x as i64)))
             },
             Ty::Prim(Prim::Bool) => {
                 let (mut caught_f, mut caught_t) = (false, false);
@@ -144,14 +331,17 @@ impl AbstractPat {
                 }
             },
             Ty::Prim(Prim::Char) => {
+                // Surrogates are not valid characters, so they never need to be covered
+                let mut covered = IntSet::surrogates();
                 for pat in filter {
                     match pat {
                         AbstractPat::Wildcard => return None,
-                        AbstractPat::Char(_) => {},
+                        AbstractPat::Char(x) => covered = covered.union(x),
                         _ => return None, // Type mismatch, don't yield an error because one was already generated
                     }
                 }
-                Some(ExamplePat::Wildcard)
+                covered.uncovered(CHAR_DOMAIN).map(|x| char::from_u32(x as u32)
+                    .map_or(ExamplePat::Wildcard, |c| ExamplePat::Prim(ExamplePrim::Char(c))))
             },
             Ty::Prim(Prim::Real) => {
                 for pat in filter {
@@ -377,6 +567,7 @@ pub enum ExamplePrim {
     Bool(bool),
     Nat(u64),
     Int(i64),
+    Char(char),
 }
 
 impl fmt::Display for ExamplePrim {
@@ -384,7 +575,8 @@ impl fmt::Display for ExamplePrim {
         match self {
             Self::Bool(x) => write!(f, "{}", if *x { "True" } else { "False" }),
             Self::Nat(x) => write!(f, "{}", x),
-            Self::Int(x) => write!(f, "{}", x),
+            Self::Int(x) => write!(f, "{}i", x),
+            Self::Char(c) => write!(f, "{:?}", c),
         }
     }
 }
@@ -30,11 +30,14 @@ pub enum Pat<M: Meta> {
     Single(Node<Binding<M>, M>),
     Union(Node<Binding<M>, M>),
     Add(Node<Binding<M>, M>, SrcNode<u64>),
+    Mul(Node<Binding<M>, M>, SrcNode<u64>),
+    Range(Literal, Literal),
     Tuple(Vec<Node<Binding<M>, M>>),
     Record(BTreeMap<Ident, Node<Binding<M>, M>>),
     ListExact(Vec<Node<Binding<M>, M>>),
     ListFront(Vec<Node<Binding<M>, M>>, Option<Node<Binding<M>, M>>),
     Decons(SrcNode<DataId>, Ident, Node<Binding<M>, M>),
+    Or(Vec<Node<Binding<M>, M>>), // Every alternative binds the same names
 }
 
 #[derive(Debug)]
@@ -73,9 +76,11 @@ impl<M: Meta> Binding<M> {
             Pat::Error => {},
             Pat::Wildcard => {},
             Pat::Literal(_) => {},
+            Pat::Range(_, _) => {},
             Pat::Single(inner) => inner.visit_bindings_inner(visit),
             Pat::Union(inner) => inner.visit_bindings_inner(visit),
             Pat::Add(lhs, _) => lhs.visit_bindings_inner(visit),
+            Pat::Mul(lhs, _) => lhs.visit_bindings_inner(visit),
             Pat::Tuple(items) => items
                 .iter()
                 .for_each(|item| item.visit_bindings_inner(visit)),
@@ -92,6 +97,8 @@ impl<M: Meta> Binding<M> {
                 if let Some(tail) = tail { tail.visit_bindings_inner(visit); }
             },
             Pat::Decons(_, _, inner) => inner.visit_bindings_inner(visit),
+            // Alternatives all bind the same names, so the first is representative
+            Pat::Or(alts) => alts[0].visit_bindings_inner(visit),
         }
     }
 }
@@ -116,10 +123,12 @@ pub enum Expr<M: Meta> {
     List(Vec<Node<Self, M>>),
     ListFront(Vec<Node<Self, M>>, Node<Self, M>),
     Record(Vec<(SrcNode<Ident>, Node<Self, M>)>),
+    // Functional record update, fields that are not listed are taken from the record
+    Update(Node<Self, M>, Vec<(SrcNode<Ident>, Node<Self, M>)>),
     Access(Node<Self, M>, SrcNode<Ident>),
     Binary(SrcNode<ast::BinaryOp>, Node<Self, M>, Node<Self, M>),
-    // hidden_outer
-    Match(bool, Node<Self, M>, Vec<(Node<Binding<M>, M>, Node<Self, M>)>),
+    // hidden_outer, (binding, guard, body)
+    Match(bool, Node<Self, M>, Vec<(Node<Binding<M>, M>, Option<Node<Self, M>>, Node<Self, M>)>),
     Func(Node<Ident, M>, Node<Self, M>),
     Apply(Node<Self, M>, Node<Self, M>),
     Cons(SrcNode<DataId>, Ident, Node<Self, M>),
@@ -150,9 +159,12 @@ impl Expr<ConMeta> {
                 .for_each(|arg| arg.required_locals_inner(stack, required)),
             Expr::Match(_, pred, arms) => {
                 pred.required_locals_inner(stack, required);
-                for (arm, body) in arms {
+                for (arm, guard, body) in arms {
                     let old_stack = stack.len();
                     arm.visit_bindings_inner(&mut |name, _| stack.push(**name));
+                    if let Some(guard) = guard {
+                        guard.required_locals_inner(stack, required);
+                    }
                     body.required_locals_inner(stack, required);
                     stack.truncate(old_stack);
                 }
@@ -176,6 +188,12 @@ impl Expr<ConMeta> {
             Expr::Record(fields) => fields
                 .iter()
                 .for_each(|(_, field)| field.required_locals_inner(stack, required)),
+            Expr::Update(record, fields) => {
+                record.required_locals_inner(stack, required);
+                fields
+                    .iter()
+                    .for_each(|(_, field)| field.required_locals_inner(stack, required));
+            },
             Expr::List(items) => items
                 .iter()
                 .for_each(|item| item.required_locals_inner(stack, required)),
@@ -52,6 +52,10 @@ pub enum TyInfo {
     SelfType,
     // An opaque associated type that *cannot* be determined due to lack of information
     Assoc(TyVar, ClassId, SrcNode<Ident>),
+    // Application of a type constructor that is not yet known
+    Apply(TyVar, Vec<TyVar>),
+    // A placeholder within a type constructor
+    Hole(usize),
 }
 
 #[derive(Clone, Default, Debug)]
@@ -79,13 +83,18 @@ pub enum InferError {
     // Type, recursive element
     Recursive(TyVar, TyVar),
     NoSuchItem(TyVar, Span, SrcNode<Ident>),
+    NotARecord(TyVar, Span),
     InvalidUnaryOp(SrcNode<ast::UnaryOp>, TyVar),
     InvalidBinaryOp(SrcNode<ast::BinaryOp>, TyVar, TyVar),
     TypeDoesNotFulfil(ClassId, TyVar, Span, Option<Span>),
     RecursiveAlias(AliasId, TyVar, Span),
     PatternNotSupported(TyVar, SrcNode<ast::BinaryOp>, TyVar, Span),
+    RangeNotSupported(TyVar, TyVar, Span),
     AmbiguousClassItem(SrcNode<Ident>, Vec<ClassId>),
+    AmbiguousClassParams(ClassId, TyVar, Vec<TyVar>, Span),
     NonNumeric(TyVar, Span, NumLitr),
+    // (span, expected, locals_in_scope, fitting_defs)
+    TypedHole(Span, TyVar, Vec<(Ident, TyVar)>, Vec<DefId>),
 }
 
 #[derive(Clone, Debug)]
@@ -93,8 +102,11 @@ enum Constraint {
     CheckFlow(TyVar, TyVar, EqInfo),
     // (record, field_name, field)
     Access(TyVar, SrcNode<Ident>, TyVar),
+    // (record, fields, span), unlike `Access` the type must be a record
+    RecordFields(TyVar, Vec<(SrcNode<Ident>, TyVar)>, Span),
     Binary(SrcNode<ast::BinaryOp>, TyVar, TyVar, TyVar),
-    Impl(TyVar, ClassId, Span, Vec<(SrcNode<Ident>, TyVar)>),
+    // (ty, class, class_params, span, unchecked_assoc)
+    Impl(TyVar, ClassId, Vec<TyVar>, Span, Vec<(SrcNode<Ident>, TyVar)>),
     ClassField(TyVar, ClassVar, SrcNode<Ident>, TyVar, Span),
     ClassAssoc(TyVar, ClassVar, SrcNode<Ident>, TyVar, Span),
 }
@@ -120,7 +132,13 @@ pub struct Infer<'a> {
     lazy_literals: VecDeque<LazyLiteral>,
     errors: Vec<InferError>,
     self_type: Option<TyVar>,
+    self_kind: Kind,
     self_obligations: Vec<ClassId>,
+    // Applications of type constructors that could not yet be reduced
+    applies: Vec<TyVar>,
+    // Typed holes (`?` or `_` in expression position) with the locals that are in scope at each and the definition
+    // that each appears in
+    typed_holes: Vec<(Span, TyVar, Vec<(Ident, TyVar)>, Option<DefId>)>,
 }
 
 impl<'a> Infer<'a> {
@@ -134,23 +152,33 @@ impl<'a> Infer<'a> {
             lazy_literals: VecDeque::new(),
             errors: Vec::new(),
             self_type: None,
+            self_kind: Kind::Ty,
             self_obligations: Vec::new(),
+            applies: Vec::new(),
+            typed_holes: Vec::new(),
         }
     }
 
     pub fn with_self_type(mut self, ty: TyId, span: Span) -> Self {
         self.self_type = Some(self.instantiate_local(ty, span));
+        self.self_kind = self.ctx.tys.kind_of(ty);
         self
     }
 
     pub fn with_unknown_self(mut self, span: Span, self_obligations: Vec<ClassId>) -> Self {
         self.self_type = Some(self.insert(span, TyInfo::SelfType));
+        self.self_kind = self_obligations
+            .first()
+            .map(|class| self.ctx.classes.get(*class).self_kind)
+            .unwrap_or(Kind::Ty);
         self.self_obligations = self_obligations;
         self
     }
 
     pub fn self_type(&self) -> Option<TyVar> { self.self_type }
 
+    pub fn self_kind(&self) -> Kind { self.self_kind }
+
     pub fn ctx(&self) -> &Context { self.ctx }
     pub fn ctx_mut(&mut self) -> &mut Context { self.ctx }
 
@@ -196,7 +224,8 @@ impl<'a> Infer<'a> {
                 | TyInfo::Unknown(_)
                 | TyInfo::Prim(_)
                 | TyInfo::Gen(..)
-                | TyInfo::SelfType => {},
+                | TyInfo::SelfType
+                | TyInfo::Hole(_) => {},
                 TyInfo::List(item) => self.set_error(item),
                 TyInfo::Tuple(fields) => fields
                     .into_iter()
@@ -217,6 +246,9 @@ impl<'a> Infer<'a> {
                 // Type is projected, so error does not propagate backwards
                 // TODO: Should it?
                 TyInfo::Assoc(_, _, _) => {},
+                TyInfo::Apply(_, args) => args
+                    .into_iter()
+                    .for_each(|arg| self.set_error(arg)),
             }
         }
     }
@@ -290,13 +322,134 @@ impl<'a> Infer<'a> {
             Ty::Assoc(inner, class_id, assoc) => {
                 let inner = self.instantiate(inner, span, f, self_ty);
                 let assoc_ty = self.unknown(span);
-                self.make_impl(inner, class_id, span, vec![(assoc, assoc_ty)]);
+                let params = self.unknown_class_params(class_id, span);
+                self.make_impl(inner, class_id, params, span, vec![(assoc, assoc_ty)]);
                 TyInfo::Ref(assoc_ty)
             },
+            Ty::Apply(ctor, args) => {
+                let ctor = self.instantiate(ctor, span, f, self_ty);
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.instantiate(arg, span, f, self_ty))
+                    .collect();
+                return self.apply(self.ctx.tys.get_span(ty), ctor, args);
+            },
+            Ty::Hole(idx) => TyInfo::Hole(idx),
         };
         self.insert(self.ctx.tys.get_span(ty) /*span*/, info)
     }
 
+    /// Apply a type constructor to some arguments. If the constructor is already known, the application is reduced
+    /// immediately.
+    pub fn apply(&mut self, span: Span, ctor: TyVar, args: Vec<TyVar>) -> TyVar {
+        let ty = self.insert(span, TyInfo::Apply(ctor, args));
+        if !self.reduce_apply(ty) {
+            self.applies.push(ty);
+        }
+        ty
+    }
+
+    // If the type is an application of a known type constructor, replace it with the constructor, substituting the
+    // arguments for its holes. Returns `false` if the type is an application that cannot be reduced yet.
+    fn reduce_apply(&mut self, ty: TyVar) -> bool {
+        match self.info(ty) {
+            TyInfo::Apply(ctor, args) => match self.follow_info(ctor) {
+                TyInfo::Unknown(_)
+                | TyInfo::Gen(..)
+                | TyInfo::SelfType
+                | TyInfo::Assoc(..)
+                | TyInfo::Apply(..) => false,
+                _ => {
+                    let reduced = self.substitute_holes(ctor, &args);
+                    self.set_info(ty, TyInfo::Ref(reduced));
+                    true
+                },
+            },
+            _ => true,
+        }
+    }
+
+    fn reduce_applies(&mut self) {
+        for ty in std::mem::take(&mut self.applies) {
+            if !self.reduce_apply(ty) {
+                self.applies.push(ty);
+            }
+        }
+    }
+
+    fn substitute_holes(&mut self, ty: TyVar, args: &[TyVar]) -> TyVar {
+        let info = match self.info(ty) {
+            TyInfo::Ref(x) => return self.substitute_holes(x, args),
+            TyInfo::Hole(idx) => return args.get(idx).copied().unwrap_or(ty),
+            TyInfo::Error(_)
+            | TyInfo::Unknown(_)
+            | TyInfo::Prim(_)
+            | TyInfo::Gen(..)
+            | TyInfo::SelfType => return ty,
+            TyInfo::List(item) => TyInfo::List(self.substitute_holes(item, args)),
+            TyInfo::Tuple(fields) => TyInfo::Tuple(fields
+                .into_iter()
+                .map(|field| self.substitute_holes(field, args))
+                .collect()),
+            TyInfo::Union(variants) => TyInfo::Union(variants
+                .into_iter()
+                .map(|variant| self.substitute_holes(variant, args))
+                .collect()),
+            TyInfo::Record(fields) => TyInfo::Record(fields
+                .into_iter()
+                .map(|(name, field)| (name, self.substitute_holes(field, args)))
+                .collect()),
+            TyInfo::Func(i, o) => TyInfo::Func(self.substitute_holes(i, args), self.substitute_holes(o, args)),
+            TyInfo::Data(data, params) => TyInfo::Data(data, params
+                .into_iter()
+                .map(|param| self.substitute_holes(param, args))
+                .collect()),
+            TyInfo::Assoc(inner, class_id, assoc) => TyInfo::Assoc(self.substitute_holes(inner, args), class_id, assoc),
+            TyInfo::Apply(ctor, params) => {
+                let params = params
+                    .into_iter()
+                    .map(|param| self.substitute_holes(param, args))
+                    .collect();
+                return self.apply(self.span(ty), ctor, params);
+            },
+        };
+        self.insert(self.span(ty), info)
+    }
+
+    // Unify an application of an unknown type constructor with a known type by splitting the type into a constructor
+    // and the arguments it was applied to. Like partial application of data types, the arguments are always the
+    // trailing parameters of the type.
+    fn decompose_apply(&mut self, ctor: TyVar, args: &[TyVar], ty: TyVar, apply_flows_in: bool) -> Result<(), ()> {
+        if !matches!(self.follow_info(ctor), TyInfo::Unknown(_)) {
+            return Err(());
+        }
+
+        let span = self.span(ty);
+        let (ctor_info, ty_args) = match self.follow_info(ty) {
+            TyInfo::Data(data, params) if params.len() >= args.len() => {
+                let split = params.len() - args.len();
+                let holes = (0..args.len())
+                    .map(|idx| self.insert(span, TyInfo::Hole(idx)))
+                    .collect::<Vec<_>>();
+                (TyInfo::Data(data, params[..split].iter().copied().chain(holes).collect()), params[split..].to_vec())
+            },
+            TyInfo::List(item) if args.len() == 1 => (TyInfo::List(self.insert(span, TyInfo::Hole(0))), vec![item]),
+            _ => return Err(()),
+        };
+
+        let ctor_ty = self.insert(span, ctor_info);
+        self.set_info(ctor, TyInfo::Ref(ctor_ty));
+        for (arg, ty_arg) in args.iter().zip(ty_args) {
+            if apply_flows_in {
+                self.make_flow_inner(*arg, ty_arg)
+            } else {
+                self.make_flow_inner(ty_arg, *arg)
+            }
+                .map_err(|_| ())?;
+        }
+        Ok(())
+    }
+
     pub fn unknown(&mut self, span: Span) -> TyVar {
         self.insert(span, TyInfo::Unknown(None))
     }
@@ -309,13 +462,48 @@ impl<'a> Infer<'a> {
         self.constraints.push_back(Constraint::Access(record, field_name, field));
     }
 
+    /// Require that a type is a record with (at least) the given fields.
+    pub fn make_record_fields(&mut self, record: TyVar, fields: Vec<(SrcNode<Ident>, TyVar)>, span: Span) {
+        self.constraints.push_back(Constraint::RecordFields(record, fields, span));
+    }
+
     pub fn make_binary(&mut self, op: SrcNode<ast::BinaryOp>, a: TyVar, b: TyVar, output: TyVar) {
         self.constraints.push_back(Constraint::Binary(op, a, b, output));
     }
 
     // `unchecked_assoc` allows unification of type variables with an instance's associated type
-    pub fn make_impl(&mut self, ty: TyVar, class: ClassId, span: Span, unchecked_assoc: Vec<(SrcNode<Ident>, TyVar)>) {
-        self.constraints.push_back(Constraint::Impl(ty, class, span, unchecked_assoc));
+    pub fn make_impl(&mut self, ty: TyVar, class: ClassId, params: Vec<TyVar>, span: Span, unchecked_assoc: Vec<(SrcNode<Ident>, TyVar)>) {
+        self.constraints.push_back(Constraint::Impl(ty, class, params, span, unchecked_assoc));
+    }
+
+    /// Require that a type fulfils an obligation, instantiating the class parameters of the obligation in terms of the
+    /// given generic types.
+    pub fn make_obligation(&mut self, ty: TyVar, obl: &SrcNode<Obligation>, f: &impl Fn(usize, GenScopeId, &Context) -> TyVar, self_ty: Option<TyVar>) {
+        match &**obl {
+            Obligation::MemberOf(class, params, assoc) => {
+                let params = params
+                    .iter()
+                    .map(|param| self.instantiate(*param, obl.span(), f, self_ty))
+                    .collect();
+                // Associated type equalities are checked by unifying them with the member's associated types
+                let assoc = assoc
+                    .iter()
+                    .map(|(name, assoc_ty)| (name.clone(), self.instantiate(*assoc_ty, obl.span(), f, self_ty)))
+                    .collect();
+                self.make_impl(ty, *class, params, obl.span(), assoc);
+            },
+        }
+    }
+
+    // Create fresh type variables for the parameters of a class
+    fn unknown_class_params(&mut self, class: ClassId, span: Span) -> Vec<TyVar> {
+        let class_gen_scope = self.ctx.tys.get_gen_scope(self.ctx.classes.get(class).gen_scope);
+        (0..class_gen_scope.len())
+            .map(|idx| class_gen_scope.get(idx).name.span())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|origin| self.insert(span, TyInfo::Unknown(Some(origin))))
+            .collect()
     }
 
     pub fn make_class_field_known(&mut self, ty: TyVar, field_name: SrcNode<Ident>, class_id: Option<ClassId>, field_ty: TyVar, span: Span) -> ClassVar {
@@ -339,6 +527,10 @@ impl<'a> Infer<'a> {
         class
     }
 
+    pub fn make_hole(&mut self, span: Span, ty: TyVar, locals: Vec<(Ident, TyVar)>, def: Option<DefId>) {
+        self.typed_holes.push((span, ty, locals, def));
+    }
+
     pub fn make_num_litr(&mut self, ty: TyVar, span: Span, num: NumLitr) {
         self.lazy_literals.push_back(LazyLiteral { ty, span, num });
     }
@@ -358,7 +550,8 @@ impl<'a> Infer<'a> {
                 | TyInfo::Error(_)
                 | TyInfo::Prim(_)
                 | TyInfo::SelfType
-                | TyInfo::Gen(_, _, _) => false,
+                | TyInfo::Gen(_, _, _)
+                | TyInfo::Hole(_) => false,
                 TyInfo::Ref(y) => x == y || self.occurs_in_inner(x, y, seen),
                 TyInfo::List(item) => x == item || self.occurs_in_inner(x, item, seen),
                 TyInfo::Func(i, o) => x == i || x == o || self.occurs_in_inner(x, i, seen) || self.occurs_in_inner(x, o, seen),
@@ -375,6 +568,9 @@ impl<'a> Infer<'a> {
                     .into_iter()
                     .any(|y| x == y || self.occurs_in_inner(x, y, seen)),
                 TyInfo::Assoc(inner, _, _) => x == inner || self.occurs_in_inner(x, inner, seen),
+                TyInfo::Apply(ctor, args) => x == ctor || self.occurs_in_inner(x, ctor, seen) || args
+                    .into_iter()
+                    .any(|arg| x == arg || self.occurs_in_inner(x, arg, seen)),
             };
 
             seen.pop();
@@ -446,6 +642,9 @@ impl<'a> Infer<'a> {
         }
 
         if x == y { return Ok(()) } // If the vars are equal, we have no need to check flow
+        // Applications of type constructors that have since become known must be reduced before comparison
+        self.reduce_apply(x);
+        self.reduce_apply(y);
         match (self.info(x), self.info(y)) {
             // Follow references
             (TyInfo::Ref(x), _) => self.make_flow_inner(x, y),
@@ -479,6 +678,21 @@ impl<'a> Infer<'a> {
                 Ok(self.set_info(y, TyInfo::Ref(x)))
             },
 
+            // Unify type constructor applications
+            (TyInfo::Apply(x_ctor, xs), TyInfo::Apply(y_ctor, ys)) if xs.len() == ys.len() => {
+                self.make_flow_inner(x_ctor, y_ctor)?;
+                make_flow_many(self, xs, ys)?;
+                Ok(self.set_info(x, TyInfo::Ref(y)))
+            },
+            (TyInfo::Apply(ctor, args), _) => {
+                self.decompose_apply(ctor, &args, y, true).map_err(|()| (x, y))?;
+                Ok(self.set_info(x, TyInfo::Ref(y)))
+            },
+            (_, TyInfo::Apply(ctor, args)) => {
+                self.decompose_apply(ctor, &args, x, false).map_err(|()| (x, y))?;
+                Ok(self.set_info(y, TyInfo::Ref(x)))
+            },
+
             (TyInfo::Prim(x), TyInfo::Prim(y)) if x == y => Ok(()),
             (TyInfo::List(x), TyInfo::List(y)) => self.make_flow_inner(x, y),
             (TyInfo::Tuple(xs), TyInfo::Tuple(ys)) if xs.len() == ys.len() => make_flow_many(self, xs, ys),
@@ -512,6 +726,7 @@ impl<'a> Infer<'a> {
             (TyInfo::SelfType, TyInfo::SelfType) => Ok(()),
             (TyInfo::Assoc(x, class_x, assoc_x), TyInfo::Assoc(y, class_y, assoc_y))
                 if class_x == class_y && assoc_x == assoc_y => self.make_flow_inner(x, y),
+            (TyInfo::Hole(a), TyInfo::Hole(b)) if a == b => Ok(()),
             (_, _) => Err((x, y)),
         }
     }
@@ -523,7 +738,7 @@ impl<'a> Infer<'a> {
         match self.info(ty) {
             TyInfo::Ref(x) => self.try_reinstantiate(span, x),
             TyInfo::Error(reason) => self.insert(self.span(ty), TyInfo::Error(reason)),
-            TyInfo::Unknown(_) | TyInfo::Prim(_) => ty,
+            TyInfo::Unknown(_) | TyInfo::Prim(_) | TyInfo::Hole(_) => ty,
             TyInfo::List(item) => {
                 let item = self.try_reinstantiate(span, item);
                 self.insert(self.span(ty), TyInfo::List(item))
@@ -569,6 +784,14 @@ impl<'a> Infer<'a> {
                 let inner = self.try_reinstantiate(span, inner);
                 self.insert(self.span(ty), TyInfo::Assoc(inner, class_id, assoc))
             },
+            TyInfo::Apply(ctor, args) => {
+                let ctor = self.try_reinstantiate(span, ctor);
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.try_reinstantiate(span, arg))
+                    .collect();
+                self.apply(self.span(ty), ctor, args)
+            },
         }
     }
 
@@ -614,6 +837,8 @@ impl<'a> Infer<'a> {
         }
 
         if x == y { return Some(Ok(())) } // If the vars are equal, we have no need to check equivalence
+        self.reduce_apply(x);
+        self.reduce_apply(y);
         match (self.info(x), self.info(y)) {
             // Follow references
             (TyInfo::Ref(x), _) => self.check_flow_inner(x, y),
@@ -639,6 +864,9 @@ impl<'a> Infer<'a> {
             (_, TyInfo::Error(_)) => Some(Ok(())),
             (TyInfo::Error(_), _) => Some(Ok(())),
 
+            // Type constructor applications are invariant, so unify them
+            (TyInfo::Apply(_, _), _) | (_, TyInfo::Apply(_, _)) => Some(self.make_flow_inner(x, y)),
+
             (TyInfo::Prim(x), TyInfo::Prim(y)) if x == y => Some(Ok(())),
             (TyInfo::List(x), TyInfo::List(y)) => self.check_flow_inner(x, y),
             (TyInfo::Tuple(xs), TyInfo::Tuple(ys)) if xs.len() == ys.len() => check_flow_many(self, xs, ys),
@@ -679,12 +907,14 @@ impl<'a> Infer<'a> {
             (TyInfo::SelfType, TyInfo::SelfType) => Some(Ok(())),
             (TyInfo::Assoc(x, class_x, assoc_x), TyInfo::Assoc(y, class_y, assoc_y))
                 if class_x == class_y && assoc_x == assoc_y => self.check_flow_inner(x, y),
+            (TyInfo::Hole(a), TyInfo::Hole(b)) if a == b => Some(Ok(())),
             (_, _) => Some(Err((x, y))),
         }
     }
 
     fn resolve(&mut self, c: Constraint) -> Option<Result<(), InferError>> {
         use ast::BinaryOp::*;
+        self.reduce_applies();
         match c {
             Constraint::CheckFlow(x, y, info) => match self.check_flow_impl(x, y, info) {
                 Some(Ok(())) => Some(Ok(())),
@@ -735,6 +965,29 @@ impl<'a> Infer<'a> {
                     self.set_error(field);
                     Err(InferError::NoSuchItem(record, self.span(record), field_name.clone()))
                 }),
+            Constraint::RecordFields(record, fields, span) => match self.follow_info(record) {
+                _ if self.is_error(record) => {
+                    fields.iter().for_each(|(_, field)| self.set_error(*field));
+                    Some(Ok(()))
+                },
+                TyInfo::Unknown(_) => None,
+                TyInfo::Record(tys) => Some(fields
+                    .iter()
+                    .fold(Ok(()), |res, (field_name, field)| match tys.get(field_name) {
+                        Some(field_ty) => {
+                            self.make_flow(*field_ty, *field, field_name.span());
+                            res
+                        },
+                        None => {
+                            self.set_error(*field);
+                            res.and(Err(InferError::NoSuchItem(record, span, field_name.clone())))
+                        },
+                    })),
+                _ => {
+                    fields.iter().for_each(|(_, field)| self.set_error(*field));
+                    Some(Err(InferError::NotARecord(record, span)))
+                },
+            },
             Constraint::Binary(op, a, b, output) => match (&*op, self.follow_info(a), self.follow_info(b)) {
                 (_, _, TyInfo::Error(reason)) => Some(Ok(TyInfo::Error(reason))),
                 (_, TyInfo::Error(reason), _) => Some(Ok(TyInfo::Error(reason))),
@@ -809,7 +1062,7 @@ impl<'a> Infer<'a> {
                     let result_ty = self.insert(self.span(output), info);
                     self.make_flow(result_ty, output, self.span(output));
                 })),
-            Constraint::Impl(ty, obligation, span, unchecked_assoc) => self.resolve_obligation(ty, obligation, span).map(|res| match res {
+            Constraint::Impl(ty, obligation, params, span, unchecked_assoc) => self.resolve_obligation(ty, obligation, &params, span).map(|res| match res {
                     Ok(member) => {
                         for (assoc, assoc_ty) in unchecked_assoc {
                             match member {
@@ -818,6 +1071,9 @@ impl<'a> Infer<'a> {
 
                                     let mut links = HashMap::new();
                                     self.derive_links(member.member, ty, &mut |gen_idx, var| { links.insert(gen_idx, var); });
+                                    for (member_arg, param) in member.args.iter().zip(&params) {
+                                        self.derive_links(*member_arg, *param, &mut |gen_idx, var| { links.insert(gen_idx, var); });
+                                    }
 
                                     if let Some(member_assoc_ty) = member.assoc_ty(*assoc) {
                                         let assoc_ty_inst = self.instantiate(member_assoc_ty, span, &|idx, gen_scope, ctx| links[&idx], Some(ty));
@@ -830,7 +1086,17 @@ impl<'a> Infer<'a> {
                                     self.set_error(assoc_ty);
                                 },
                                 Err(false) => {
-                                    let assoc_info = self.insert(span, TyInfo::Assoc(ty, obligation, assoc.clone()));
+                                    let assoc_info = match self.follow_info(ty) {
+                                        // Bounds like `S < Stream with Item = Nat` fix the associated type of a generic type
+                                        TyInfo::Gen(gen_idx, gen_scope, _) => self
+                                            .implied_gen_bounds(ty, gen_scope, gen_idx, obligation, span)
+                                            .into_iter()
+                                            .flat_map(|(_, bound_assoc)| bound_assoc)
+                                            .find(|(name, _)| **name == *assoc)
+                                            .map(|(_, bound_ty)| bound_ty),
+                                        _ => None,
+                                    }
+                                        .unwrap_or_else(|| self.insert(span, TyInfo::Assoc(ty, obligation, assoc.clone())));
                                     // TODO: Check ordering for soundness
                                     self.make_flow(assoc_info, assoc_ty, span);
                                 },
@@ -887,7 +1153,8 @@ impl<'a> Infer<'a> {
                 self.class_vars[class_var.0].1 = Some(class_id); // Can't fail
 
                 // Require an implementation to exist
-                self.make_impl(ty, class_id, span, vec![
+                let params = self.unknown_class_params(class_id, span);
+                self.make_impl(ty, class_id, params, span, vec![
                     (assoc, assoc_ty),
                 ]);
 
@@ -917,12 +1184,31 @@ impl<'a> Infer<'a> {
             1 => {
                 let class_id = possible_classes.into_iter().next().unwrap();
                 self.class_vars[class_var.0].1 = Some(class_id); // Can't fail
-                self.make_impl(ty, class_id, span, Vec::new());
-                let field_ty_id = **self.ctx.classes
+                let (field_gen_scope, field_ty_id) = self.ctx.classes
                     .get(class_id)
                     .field(*field)
+                    .map(|(gen_scope, ty)| (gen_scope, **ty))
                     .unwrap();
-                let inst_field_ty = self.instantiate(field_ty_id, field.span(), &|_, _, _| panic!("Tried to substitute generic type for non-generic class"), Some(ty));
+
+                // The parameters of the class and the generic types of polymorphic fields get fresh type variables. The
+                // parameters of the class come first in the field's generic scope.
+                let class_param_count = self.ctx.tys.get_gen_scope(self.ctx.classes.get(class_id).gen_scope).len();
+                let field_gen_scope = self.ctx.tys.get_gen_scope(field_gen_scope);
+                let (gen_spans, gen_obligations): (Vec<_>, Vec<_>) = (0..field_gen_scope.len())
+                    .map(|idx| (field_gen_scope.get(idx).name.span(), field_gen_scope.get(idx).obligations().to_vec()))
+                    .unzip();
+                let field_gens = gen_spans
+                    .into_iter()
+                    .map(|gen_span| self.insert(field.span(), TyInfo::Unknown(Some(gen_span))))
+                    .collect::<Vec<_>>();
+                for (gen_ty, obligations) in field_gens.iter().zip(gen_obligations) {
+                    for obl in obligations {
+                        self.make_obligation(*gen_ty, &obl, &|idx, _, _| field_gens[idx], None);
+                    }
+                }
+                self.make_impl(ty, class_id, field_gens[..class_param_count].to_vec(), span, Vec::new());
+
+                let inst_field_ty = self.instantiate(field_ty_id, field.span(), &|idx, _, _| field_gens[idx], Some(ty));
                 // TODO: Check soundness of flow relationship
                 self.make_flow(inst_field_ty, field_ty, field.span());
                 Some(Ok(()))
@@ -942,7 +1228,7 @@ impl<'a> Infer<'a> {
             .obligations()
         {
             match &**obl {
-                Obligation::MemberOf(class) => self.walk_implied_obligations(&mut implied, *class),
+                Obligation::MemberOf(class, _, _) => self.walk_implied_obligations(&mut implied, *class),
             }
         }
         implied
@@ -956,18 +1242,17 @@ impl<'a> Infer<'a> {
                 return None
             }
             TyInfo::Unknown(_) => return None, // We don't know what the type is yet, so how can we possibly determine what classes it is a member of?
-            TyInfo::Gen(gen_idx, gen_scope, _) => {
-                return Some(self
-                    .implied_obligations_for_gen(gen_scope, gen_idx)
-                    .into_iter()
-                    // Filter by class obligations that contain the given field
-                    .filter(|class_id| if assoc_ty {
-                        self.ctx.classes.get(*class_id).assoc_ty(*item).is_some()
-                    } else {
-                        self.ctx.classes.get(*class_id).field(*item).is_some()
-                    })
-                    .collect())
-            },
+            // Blanket members may also cover generic types, so these are found with the external candidates below
+            TyInfo::Gen(gen_idx, gen_scope, _) => self
+                .implied_obligations_for_gen(gen_scope, gen_idx)
+                .into_iter()
+                // Filter by class obligations that contain the given field
+                .filter(|class_id| if assoc_ty {
+                    self.ctx.classes.get(*class_id).assoc_ty(*item).is_some()
+                } else {
+                    self.ctx.classes.get(*class_id).field(*item).is_some()
+                })
+                .collect(),
             TyInfo::SelfType => {
                 let mut implied = HashSet::default();
                 for obl in &self.self_obligations {
@@ -996,7 +1281,7 @@ impl<'a> Infer<'a> {
             // Filter further by classes that have members that cover our type
             .filter(|(class_id, _)| self.ctx.classes
                 .members_of(*class_id)
-                .find(|(_, member)| Self::covers_var(self, ty, member.member))
+                .find(|(_, member)| Self::covers_var(self, ty, member.member) && self.blanket_may_cover(ty, member))
                 .is_some())
             .map(|(class_id, _)| class_id);
 
@@ -1009,6 +1294,7 @@ impl<'a> Infer<'a> {
     fn covers_var(&self, var: TyVar, ty: TyId) -> bool {
         match (self.follow_info(var), self.ctx.tys.get(ty)) {
             (_, Ty::Gen(_, _)) => true, // Blanket impls match everything
+            (TyInfo::Hole(x), Ty::Hole(y)) => x == y,
             (TyInfo::Prim(x), Ty::Prim(y)) if x == y => true,
             (TyInfo::List(x), Ty::List(y)) => self.covers_var(x, y),
             (TyInfo::Tuple(xs), Ty::Tuple(ys)) if xs.len() == ys.len() => xs
@@ -1036,6 +1322,60 @@ impl<'a> Infer<'a> {
         }
     }
 
+    // Blanket members (i.e: `for S < Stream member S of IntoStream`) cover only those types that could fulfil the bounds
+    // of their generic type
+    fn blanket_may_cover(&self, var: TyVar, member: &Member) -> bool {
+        // The bounds of generic types (and of `Self`) imply which classes they are members of
+        let implied = match self.follow_info(var) {
+            TyInfo::Gen(var_idx, var_scope, _) => Some(self.implied_obligations_for_gen(var_scope, var_idx)),
+            TyInfo::SelfType => {
+                let mut implied = HashSet::default();
+                for obl in &self.self_obligations {
+                    self.walk_implied_obligations(&mut implied, *obl);
+                }
+                Some(implied)
+            },
+            _ => None,
+        };
+        self.ctx.classes.fulfils_bounds(
+            &self.ctx.tys,
+            member,
+            &mut |class| implied.as_ref().map(|implied| implied.contains(&class)),
+            &mut |bound_member| self.covers_var(var, bound_member.member),
+        )
+    }
+
+    // Returns true if the given definition could fill a typed hole. The definition's generic types are instantiated
+    // with fresh type variables and unified with the type of the hole, after which inference is rolled back so that
+    // trying a definition leaves no trace.
+    fn fits_hole(&mut self, var: TyVar, def_id: DefId, ty: TyId) -> bool {
+        if matches!(self.ctx.tys.get(ty), Ty::Error(_)) {
+            return false;
+        }
+
+        let vars = self.vars.clone();
+        let (class_vars, constraints, applies, errors) = (
+            self.class_vars.len(),
+            self.constraints.len(),
+            self.applies.len(),
+            self.errors.len(),
+        );
+
+        let span = self.span(var);
+        let gens = (0..self.ctx.tys.get_gen_scope(self.ctx.defs.get(def_id).gen_scope).len())
+            .map(|_| self.unknown(span))
+            .collect::<Vec<_>>();
+        let def_ty = self.instantiate(ty, span, &|idx, _, _| gens[idx], None);
+        let fits = self.make_flow_inner(def_ty, var).is_ok();
+
+        self.vars = vars;
+        self.class_vars.truncate(class_vars);
+        self.constraints.truncate(constraints);
+        self.applies.truncate(applies);
+        self.errors.truncate(errors);
+        fits
+    }
+
     // Link the generic types of a class member with type variables in the current scope so obligations
     // can be generated
     fn derive_links(&self, member: TyId, ty: TyVar, link_gen: &mut impl FnMut(usize, TyVar)) {
@@ -1072,7 +1412,7 @@ impl<'a> Infer<'a> {
         if classes.insert(class) {
             for obl in self.ctx.classes.get(class).obligations.as_ref().expect("Obligations must be known here") {
                 match obl.inner() {
-                    Obligation::MemberOf(class) => self.walk_implied_obligations(classes, *class),
+                    Obligation::MemberOf(class, _, _) => self.walk_implied_obligations(classes, *class),
                 }
             }
         }
@@ -1081,7 +1421,7 @@ impl<'a> Infer<'a> {
     /// Resolve a class obligation for a type, returning the ID of the type's membership. If no member can be provided
     /// (because, for example, the membership is implied by a generic bound) then `Err(false)` is returned instead. If
     /// resolution failed due to an existing error, `Err(true)` is returned.
-    fn resolve_obligation(&mut self, ty: TyVar, obligation: ClassId, span: Span) -> Option<Result<Result<MemberId, bool>, InferError>> {
+    fn resolve_obligation(&mut self, ty: TyVar, obligation: ClassId, params: &[TyVar], span: Span) -> Option<Result<Result<MemberId, bool>, InferError>> {
         // TODO: Resolve possible infinite loop when resolving by having an obligation cache
         match self.follow_info(ty) {
             TyInfo::Error(_) => {
@@ -1094,7 +1434,7 @@ impl<'a> Infer<'a> {
                 .into_iter()
                 // .any(|c| self.ctx.classes.implies(c, obligation))
                 .any(|c| c == obligation) => {
-                    Some(Ok(Err(false)))
+                    self.resolve_gen_params(ty, gen_scope, gen_idx, obligation, params, span)
             },
             TyInfo::SelfType if {
                 let mut implied = HashSet::default();
@@ -1106,24 +1446,56 @@ impl<'a> Infer<'a> {
                 Some(Ok(Err(false)))
             },
             _ => {
-                // Find a class member declaration that covers our type
-                let covering_member = self.ctx.classes
+                // Find class member declarations that cover our type
+                let covering_members = self.ctx.classes
                     .members_of(obligation)
-                    .find(|(_, member)| Self::covers_var(self, ty, member.member));
+                    .filter(|(_, member)| Self::covers_var(self, ty, member.member))
+                    .filter(|(_, member)| member.args
+                        .iter()
+                        .zip(params)
+                        .all(|(arg, param)| matches!(self.follow_info(*param), TyInfo::Unknown(_)) || self.covers_var(*param, *arg)))
+                    .filter(|(_, member)| self.blanket_may_cover(ty, member))
+                    .map(|(member_id, _)| member_id)
+                    .collect::<Vec<_>>();
+
+                // If several members cover the type, we must wait until the parameters of the class are known
+                if covering_members.len() > 1 && params.iter().any(|param| matches!(self.follow_info(*param), TyInfo::Unknown(_))) {
+                    return None;
+                }
 
-                if let Some((covering_member_id, covering_member)) = covering_member {
+                if let Some(covering_member_id) = covering_members.first().copied() {
+                    let covering_member = self.ctx.classes.get_member(covering_member_id);
                     let scope = self.ctx.tys.get_gen_scope(covering_member.gen_scope);
 
-                    let mut links = HashSet::new();
-                    self.derive_links(covering_member.member, ty, &mut |gen_idx, var| { links.insert((gen_idx, var)); });
+                    let mut links = HashMap::new();
+                    self.derive_links(covering_member.member, ty, &mut |gen_idx, var| { links.insert(gen_idx, var); });
+                    for (arg, param) in covering_member.args.iter().zip(params) {
+                        self.derive_links(*arg, *param, &mut |gen_idx, var| { links.insert(gen_idx, var); });
+                    }
 
-                    for (gen_idx, ty) in links {
-                        for obl in scope.get(gen_idx).obligations.as_ref().unwrap() {
-                            match &**obl {
-                                Obligation::MemberOf(class) => {
-                                    self.constraints.push_back(Constraint::Impl(ty, *class, span, Vec::new()));
-                                },
-                            }
+                    // Generic types of the member that could not be linked are given fresh type variables
+                    let gen_spans = (0..scope.len())
+                        .map(|idx| scope.get(idx).name.span())
+                        .collect::<Vec<_>>();
+                    let obligations = (0..scope.len())
+                        .map(|idx| scope.get(idx).obligations.clone().unwrap())
+                        .collect::<Vec<_>>();
+                    let member_args = covering_member.args.clone();
+                    for (idx, gen_span) in gen_spans.into_iter().enumerate() {
+                        if !links.contains_key(&idx) {
+                            links.insert(idx, self.insert(span, TyInfo::Unknown(Some(gen_span))));
+                        }
+                    }
+
+                    // The only covering member determines the parameters of the class
+                    for (arg, param) in member_args.into_iter().zip(params) {
+                        let arg = self.instantiate(arg, span, &|idx, _, _| links[&idx], Some(ty));
+                        self.make_flow(arg, *param, span);
+                    }
+
+                    for (gen_idx, obligations) in obligations.into_iter().enumerate() {
+                        for obl in obligations {
+                            self.make_obligation(links[&gen_idx], &obl, &|idx, _, _| links[&idx], None);
                         }
                     }
 
@@ -1140,6 +1512,110 @@ impl<'a> Infer<'a> {
         }
     }
 
+    // Generic types with bounds on multi-parameter classes determine the parameters of the class
+    fn resolve_gen_params(&mut self, ty: TyVar, gen_scope: GenScopeId, gen_idx: usize, obligation: ClassId, params: &[TyVar], span: Span) -> Option<Result<Result<MemberId, bool>, InferError>> {
+        if params.is_empty() {
+            return Some(Ok(Err(false)));
+        }
+
+        let bounds = self.ctx.tys
+            .get_gen_scope(gen_scope)
+            .get(gen_idx)
+            .obligations()
+            .iter()
+            .filter_map(|obl| match &**obl {
+                Obligation::MemberOf(class, bound_params, _) if *class == obligation => Some(bound_params.clone()),
+                _ => None,
+            })
+            .filter(|bound_params| bound_params
+                .iter()
+                .zip(params)
+                .all(|(bound_param, param)| matches!(self.follow_info(*param), TyInfo::Unknown(_)) || self.covers_var(*param, *bound_param)))
+            .collect::<Vec<_>>();
+
+        match bounds.as_slice() {
+            // The obligation is only implied through a superclass, so take the parameters from the superclass chain
+            [] if !self.ctx.tys.get_gen_scope(gen_scope).get(gen_idx).obligations().iter().any(|obl| matches!(&**obl, Obligation::MemberOf(class, _, _) if *class == obligation)) => {
+                let implied_params = self
+                    .implied_gen_bounds(ty, gen_scope, gen_idx, obligation, span)
+                    .into_iter()
+                    .map(|(params, _)| params)
+                    .collect::<Vec<_>>();
+                match implied_params.as_slice() {
+                    [implied_params] => {
+                        for (implied_param, param) in implied_params.clone().into_iter().zip(params) {
+                            self.make_flow(implied_param, *param, span);
+                        }
+                        Some(Ok(Err(false)))
+                    },
+                    _ if params.iter().any(|param| matches!(self.follow_info(*param), TyInfo::Unknown(_))) => None,
+                    _ => Some(Ok(Err(false))),
+                }
+            },
+            [] => Some(Err(InferError::TypeDoesNotFulfil(obligation, ty, span, None))),
+            [bound_params] => {
+                for (bound_param, param) in bound_params.clone().into_iter().zip(params) {
+                    let bound_param = self.instantiate_local(bound_param, span);
+                    self.make_flow(bound_param, *param, span);
+                }
+                Some(Ok(Err(false)))
+            },
+            // Several bounds could apply, so wait until the parameters are known
+            _ if params.iter().any(|param| matches!(self.follow_info(*param), TyInfo::Unknown(_))) => None,
+            _ => Some(Ok(Err(false))),
+        }
+    }
+
+    // Walk the superclass chain of a generic type's bounds, instantiating the parameters and associated type equalities
+    // of each superclass along the way, to find the bounds through which the generic type is a member of the class
+    fn implied_gen_bounds(&mut self, ty: TyVar, gen_scope: GenScopeId, gen_idx: usize, obligation: ClassId, span: Span) -> Vec<(Vec<TyVar>, Vec<(SrcNode<Ident>, TyVar)>)> {
+        let mut pending = self.ctx.tys
+            .get_gen_scope(gen_scope)
+            .get(gen_idx)
+            .obligations()
+            .to_vec()
+            .into_iter()
+            .map(|obl| match obl.into_inner() {
+                Obligation::MemberOf(class, params, assoc) => (
+                    class,
+                    params
+                        .into_iter()
+                        .map(|param| self.instantiate_local(param, span))
+                        .collect::<Vec<_>>(),
+                    assoc
+                        .into_iter()
+                        .map(|(name, assoc_ty)| (name, self.instantiate_local(assoc_ty, span)))
+                        .collect::<Vec<_>>(),
+                ),
+            })
+            .collect::<Vec<_>>();
+
+        let mut visited = HashSet::<_>::default();
+        let mut implied = Vec::new();
+        while let Some((class, params, assoc)) = pending.pop() {
+            if class == obligation {
+                implied.push((params, assoc));
+            } else if visited.insert(class) {
+                for obl in self.ctx.classes.get(class).obligations.clone().expect("Obligations must be known here") {
+                    match obl.into_inner() {
+                        Obligation::MemberOf(superclass, super_params, super_assoc) => {
+                            let super_params = super_params
+                                .into_iter()
+                                .map(|param| self.instantiate(param, span, &|idx, _, _| params[idx], Some(ty)))
+                                .collect();
+                            let super_assoc = super_assoc
+                                .into_iter()
+                                .map(|(name, assoc_ty)| (name, self.instantiate(assoc_ty, span, &|idx, _, _| params[idx], Some(ty))))
+                                .collect();
+                            pending.push((superclass, super_params, super_assoc));
+                        },
+                    }
+                }
+            }
+        }
+        implied
+    }
+
     fn resolve_constraints(&mut self) {
         loop {
             let mut tries = self.constraints.len();
@@ -1180,10 +1656,18 @@ impl<'a> Infer<'a> {
                 Constraint::Access(record, field_name, _field) => {
                     InferError::NoSuchItem(record, self.span(record), field_name.clone())
                 },
+                Constraint::RecordFields(record, _fields, span) => {
+                    InferError::NotARecord(record, span)
+                },
                 Constraint::Binary(op, a, b, _output) => {
                     InferError::InvalidBinaryOp(op.clone(), a, b)
                 },
-                Constraint::Impl(ty, obligation, span, _) => {
+                Constraint::Impl(ty, obligation, params, span, _) => if params
+                    .iter()
+                    .any(|param| matches!(self.follow_info(*param), TyInfo::Unknown(_)))
+                {
+                    InferError::AmbiguousClassParams(obligation, ty, params, span)
+                } else {
                     InferError::TypeDoesNotFulfil(obligation, ty, span, None)
                 },
                 Constraint::ClassField(_ty, _class, field, _field_ty, _span) => {
@@ -1198,15 +1682,39 @@ impl<'a> Infer<'a> {
 
     pub fn into_checked(mut self) -> (Checked<'a>, Vec<Error>) {
         self.resolve_constraints();
+        self.reduce_applies();
 
         let mut errors = std::mem::take(&mut self.errors);
 
-        // Report errors for types that cannot be inferred
+        // Report the expected type of typed holes, along with the definitions that could fill them
+        for (span, ty, locals, enclosing) in std::mem::take(&mut self.typed_holes) {
+            let defs = if matches!(self.follow_info(ty), TyInfo::Unknown(_)) {
+                Vec::new() // Every definition would fit, so there's no use listing them
+            } else {
+                self.ctx.defs
+                    .iter()
+                    // A definition filling its own hole would only refer to itself
+                    .filter(|(def_id, _)| Some(*def_id) != enclosing)
+                    .filter_map(|(def_id, def)| Some((def_id, def.body.as_ref().map(|body| body.meta().1).or(def.ty_hint)?)))
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .filter(|(def_id, def_ty)| self.fits_hole(ty, *def_id, *def_ty))
+                    .map(|(def_id, _)| def_id)
+                    .collect()
+            };
+            errors.push(InferError::TypedHole(span, ty, locals, defs));
+        }
+
+        // Report errors for types that cannot be inferred. Holes leave types unknown by design, so these errors would
+        // just be noise.
+        let has_holes = errors.iter().any(|err| matches!(err, InferError::TypedHole(_, _, _, _)));
         let tys = self.iter().collect::<Vec<_>>();
         for (ty, info) in tys {
             if let TyInfo::Unknown(origin) = info {
                 if !self.is_error(ty) {
-                    errors.push(InferError::CannotInfer(ty, origin));
+                    if !has_holes {
+                        errors.push(InferError::CannotInfer(ty, origin));
+                    }
                     self.set_error(ty);
                 }
             }
@@ -1224,13 +1732,33 @@ impl<'a> Infer<'a> {
                 InferError::CannotInfer(a, origin) => Error::CannotInfer(checked.reify(a), origin),
                 InferError::Recursive(a, part) => Error::Recursive(checked.reify(a), checked.infer.span(a), checked.infer.span(part)),
                 InferError::NoSuchItem(a, record_span, field) => Error::NoSuchItem(checked.reify(a), record_span, field),
+                InferError::NotARecord(a, span) => Error::NotARecord(checked.reify(a), span),
                 InferError::InvalidUnaryOp(op, a) => Error::InvalidUnaryOp(op, checked.reify(a), checked.infer.span(a)),
                 InferError::InvalidBinaryOp(op, a, b) => Error::InvalidBinaryOp(op, checked.reify(a), checked.infer.span(a), checked.reify(b), checked.infer.span(b)),
                 InferError::TypeDoesNotFulfil(class, ty, span, gen_span) => Error::TypeDoesNotFulfil(class, checked.reify(ty), span, gen_span),
                 InferError::RecursiveAlias(alias, a, span) => Error::RecursiveAlias(alias, checked.reify(a), span),
                 InferError::PatternNotSupported(lhs, op, rhs, span) => Error::PatternNotSupported(checked.reify(lhs), op, checked.reify(rhs), span),
+                InferError::RangeNotSupported(lo, hi, span) => Error::RangeNotSupported(checked.reify(lo), checked.reify(hi), span),
                 InferError::AmbiguousClassItem(field, candidate_classes) => Error::AmbiguousClassItem(field, candidate_classes),
+                InferError::AmbiguousClassParams(class, ty, params, span) => Error::AmbiguousClassParams(
+                    class,
+                    checked.reify(ty),
+                    params
+                        .into_iter()
+                        .map(|param| checked.reify(param))
+                        .collect(),
+                    span,
+                ),
                 InferError::NonNumeric(ty, span, num_litr) => Error::NonNumeric(checked.reify(ty), span, num_litr),
+                InferError::TypedHole(span, ty, locals, defs) => Error::TypedHole(
+                    span,
+                    checked.reify(ty),
+                    locals
+                        .into_iter()
+                        .map(|(local, ty)| (local, checked.reify(ty)))
+                        .collect(),
+                    defs,
+                ),
             })
             .collect();
 
@@ -1292,6 +1820,15 @@ impl<'a> Checked<'a> {
                 TyInfo::Gen(name, scope, _) => Ty::Gen(name, scope),
                 TyInfo::SelfType => Ty::SelfType,
                 TyInfo::Assoc(inner, class_id, assoc) => Ty::Assoc(self.reify_inner(inner), class_id, assoc),
+                TyInfo::Apply(ctor, args) => if self.infer.reduce_apply(var) {
+                    return self.reify_inner(var);
+                } else {
+                    Ty::Apply(self.reify_inner(ctor), args
+                        .into_iter()
+                        .map(|arg| self.reify_inner(arg))
+                        .collect())
+                },
+                TyInfo::Hole(idx) => Ty::Hole(idx),
             };
             self.infer.ctx.tys.insert(self.infer.span(var), ty)
         }
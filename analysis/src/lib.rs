@@ -23,9 +23,9 @@ pub use crate::{
     exhaustivity::{exhaustivity, ExamplePat},
     hir::{InferExpr, InferBinding, TyExpr, TyBinding, ConBinding, ConExpr, Intrinsic},
     infer::{Infer, Checked, TyVar, TyInfo, InferNode, InferMeta, InferError, EqInfo, ClassVar, NumLitr},
-    lower::{Scope, ToHir},
+    lower::{Scope, ToHir, lower_ty_of_kind, lower_obligation_params, lower_obligation_assoc},
     reify::Reify,
-    ty::{Types, TyId, GenScope, GenScopeId, Prim, Ty, TyNode, TyMeta, ErrorReason, Obligation},
+    ty::{Types, TyId, GenScope, GenScopeId, Prim, Ty, TyNode, TyMeta, ErrorReason, Obligation, Kind},
 };
 pub use tao_syntax::ast::Ident;
 
@@ -13,6 +13,15 @@ fn litr_ty_info(litr: &ast::Literal, infer: &mut Infer, span: Span) -> (TyInfo,
     }
 }
 
+// Each field of a record may only be given once
+fn check_field_names<T>(fields: &[(SrcNode<Ident>, T)], infer: &mut Infer) {
+    for (i, (name, _)) in fields.iter().enumerate() {
+        if let Some((old, _)) = fields[..i].iter().find(|(old, _)| **old == **name) {
+            infer.ctx_mut().emit(Error::DuplicateFieldName(**name, old.span(), name.span()));
+        }
+    }
+}
+
 pub enum Scope<'a> {
     Empty,
     Recursive(SrcNode<Ident>, TyVar, DefId, Vec<(Span, TyVar)>),
@@ -31,6 +40,44 @@ impl<'a> Scope<'a> {
         Scope::Many(self, many)
     }
 
+    // The locals that are visible from this scope, innermost first
+    fn locals(&self) -> Vec<(Ident, TyVar)> {
+        let mut locals = Vec::new();
+        let mut scope = self;
+        loop {
+            let parent = match scope {
+                Self::Empty | Self::Recursive(_, _, _, _) => break,
+                Self::Binding(parent, local, ty) => {
+                    locals.push((**local, *ty));
+                    parent
+                },
+                Self::Many(parent, many) => {
+                    locals.extend(many.iter().map(|(local, ty)| (**local, *ty)));
+                    parent
+                },
+            };
+            scope = *parent;
+        }
+
+        // Shadowed locals are not visible
+        let mut visible: Vec<(Ident, TyVar)> = Vec::new();
+        for (local, ty) in locals {
+            if !visible.iter().any(|(name, _)| *name == local) {
+                visible.push((local, ty));
+            }
+        }
+        visible
+    }
+
+    // The definition whose body this scope belongs to, if any
+    fn def(&self) -> Option<DefId> {
+        match self {
+            Self::Empty => None,
+            Self::Recursive(_, _, def_id, _) => Some(*def_id),
+            Self::Binding(parent, _, _) | Self::Many(parent, _) => parent.def(),
+        }
+    }
+
     // bool = is_local
     fn find(&self, infer: &mut Infer, span: Span, name: &Ident) -> Option<(TyVar, Option<(DefId, Vec<(Span, TyVar)>)>)> {
         match self {
@@ -61,119 +108,244 @@ impl ToHir for ast::Type {
     type Output = ();
 
     fn to_hir(self: &SrcNode<Self>, infer: &mut Infer, scope: &Scope) -> InferNode<()> {
-        let info = match &**self {
-            ast::Type::Error => TyInfo::Error(ErrorReason::Unknown),
-            ast::Type::Unknown => TyInfo::Unknown(None),
-            ast::Type::List(item) => TyInfo::List(item.to_hir(infer, scope).meta().1),
-            ast::Type::Tuple(items) => TyInfo::Tuple(items
-                .iter()
-                .map(|item| item.to_hir(infer, scope).meta().1)
-                .collect()),
-            ast::Type::Union(items) => TyInfo::Union(items
-                .iter()
-                .map(|item| item.to_hir(infer, scope).meta().1)
-                .collect()),
-            ast::Type::Record(fields) => TyInfo::Record(fields
-                .iter()
-                .map(|(name, field)| (**name, field.to_hir(infer, scope).meta().1))
-                .collect()),
-            ast::Type::Func(i, o) => TyInfo::Func(i.to_hir(infer, scope).meta().1, o.to_hir(infer, scope).meta().1),
-            ast::Type::Data(name, params) => match (name.as_str(), params.len()) {
-                ("Self", 0) => if let Some(var) = infer.self_type() {
+        lower_ty_of_kind(self, infer, scope, Some(Kind::Ty))
+    }
+}
+
+/// Lower a type, checking that it has the expected kind. If no kind is expected, type constructors are permitted.
+pub fn lower_ty_of_kind(ty: &SrcNode<ast::Type>, infer: &mut Infer, scope: &Scope, expected: Option<Kind>) -> InferNode<()> {
+    let mut holes = 0;
+    let (var, own_kind) = lower_ty(ty, infer, scope, &mut holes);
+    // Holes (including omitted trailing parameters of data types) turn the type into a type constructor
+    let kind = if holes == 0 { own_kind } else { Kind::from_arity(holes) };
+    let var = match expected {
+        Some(expected) if expected != kind => {
+            infer.ctx_mut().emit(Error::KindMismatch(ty.span(), kind, expected));
+            infer.insert(ty.span(), TyInfo::Error(ErrorReason::Invalid))
+        },
+        _ => var,
+    };
+    InferNode::new((), (ty.span(), var))
+}
+
+// Lower a type that must have kind `*`, although it may contain holes that belong to an outer type constructor
+fn lower_inner_ty(ty: &SrcNode<ast::Type>, infer: &mut Infer, scope: &Scope, holes: &mut usize) -> TyVar {
+    let outer_holes = *holes;
+    match lower_ty(ty, infer, scope, holes) {
+        (var, Kind::Ty) => var,
+        (_, kind) => {
+            // Don't let an erroneous type constructor affect the kind of the outer type
+            *holes = outer_holes;
+            infer.ctx_mut().emit(Error::KindMismatch(ty.span(), kind, Kind::Ty));
+            infer.insert(ty.span(), TyInfo::Error(ErrorReason::Invalid))
+        },
+    }
+}
+
+// Returns the kind of the type, not including any holes within it
+fn lower_ty(ty: &SrcNode<ast::Type>, infer: &mut Infer, scope: &Scope, holes: &mut usize) -> (TyVar, Kind) {
+    let mut kind = Kind::Ty;
+    let info = match &**ty {
+        ast::Type::Error => TyInfo::Error(ErrorReason::Unknown),
+        ast::Type::Unknown => TyInfo::Unknown(None),
+        ast::Type::Hole => {
+            *holes += 1;
+            TyInfo::Hole(*holes - 1)
+        },
+        ast::Type::List(item) => TyInfo::List(lower_inner_ty(item, infer, scope, holes)),
+        ast::Type::Tuple(items) => TyInfo::Tuple(items
+            .iter()
+            .map(|item| lower_inner_ty(item, infer, scope, holes))
+            .collect()),
+        ast::Type::Union(items) => TyInfo::Union(items
+            .iter()
+            .map(|item| lower_inner_ty(item, infer, scope, holes))
+            .collect()),
+        ast::Type::Record(fields) => TyInfo::Record(fields
+            .iter()
+            .map(|(name, field)| (**name, lower_inner_ty(field, infer, scope, holes)))
+            .collect()),
+        ast::Type::Func(i, o) => TyInfo::Func(lower_inner_ty(i, infer, scope, holes), lower_inner_ty(o, infer, scope, holes)),
+        ast::Type::Data(name, params) => match (name.as_str(), params.len()) {
+            ("Self", _) => if let Some(var) = infer.self_type() {
+                let self_kind = infer.self_kind();
+                if params.is_empty() {
+                    kind = self_kind;
                     TyInfo::Ref(var)
+                } else if params.len() == self_kind.arity() {
+                    let params = params
+                        .iter()
+                        .map(|param| lower_inner_ty(param, infer, scope, holes))
+                        .collect();
+                    TyInfo::Ref(infer.apply(ty.span(), var, params))
                 } else {
-                    infer.ctx_mut().emit(Error::SelfNotValidHere(name.span()));
+                    infer.ctx_mut().emit(Error::KindMismatch(name.span(), self_kind, Kind::from_arity(params.len())));
                     TyInfo::Error(ErrorReason::Invalid)
-                },
-                ("Nat", 0) => TyInfo::Prim(Prim::Nat),
-                ("Int", 0) => TyInfo::Prim(Prim::Int),
-                ("Real", 0) => TyInfo::Prim(Prim::Real),
-                ("Bool", 0) => TyInfo::Prim(Prim::Bool),
-                ("Char", 0) => TyInfo::Prim(Prim::Char),
-                _ => {
+                }
+            } else {
+                infer.ctx_mut().emit(Error::SelfNotValidHere(name.span()));
+                TyInfo::Error(ErrorReason::Invalid)
+            },
+            ("Nat", 0) => TyInfo::Prim(Prim::Nat),
+            ("Int", 0) => TyInfo::Prim(Prim::Int),
+            ("Real", 0) => TyInfo::Prim(Prim::Real),
+            ("Bool", 0) => TyInfo::Prim(Prim::Bool),
+            ("Char", 0) => TyInfo::Prim(Prim::Char),
+            _ => if let Some((scope_id, (gen_idx, gen_ty))) = infer
+                .gen_scope()
+                .and_then(|scope| Some((scope, infer.ctx().tys.get_gen_scope(scope).find(**name)?)))
+            {
+                let (gen_span, gen_kind) = (gen_ty.name.span(), gen_ty.kind);
+                if params.is_empty() {
+                    kind = gen_kind;
+                    TyInfo::Gen(gen_idx, scope_id, gen_span)
+                } else if params.len() == gen_kind.arity() {
+                    let ctor = infer.insert(name.span(), TyInfo::Gen(gen_idx, scope_id, gen_span));
                     let params = params
                         .iter()
-                        .map(|param| param.to_hir(infer, scope).meta().1)
-                        .collect::<Vec<_>>();
+                        .map(|param| lower_inner_ty(param, infer, scope, holes))
+                        .collect();
+                    TyInfo::Ref(infer.apply(ty.span(), ctor, params))
+                } else {
+                    infer.ctx_mut().emit(Error::KindMismatch(name.span(), gen_kind, Kind::from_arity(params.len())));
+                    TyInfo::Error(ErrorReason::Invalid)
+                }
+            } else if let Some(alias_id) = infer.ctx().datas.lookup_alias(**name) {
+                let params = params
+                    .iter()
+                    .map(|param| lower_inner_ty(param, infer, scope, holes))
+                    .collect::<Vec<_>>();
 
-                    if let Some((scope, (gen_idx, gen_ty))) = infer
-                        .gen_scope()
-                        .and_then(|scope| Some((scope, infer.ctx().tys.get_gen_scope(scope).find(**name)?)))
-                    {
-                        TyInfo::Gen(gen_idx, scope, gen_ty.name.span())
-                    } else if let Some(alias_id) = infer.ctx().datas.lookup_alias(**name) {
-                        if let Some(alias) = infer.ctx().datas.get_alias(alias_id) {
-                            let alias_gen_scope = infer.ctx().tys.get_gen_scope(alias.gen_scope);
-                            if alias_gen_scope.len() != params.len() {
-                                let err = Error::WrongNumberOfGenerics(
-                                    self.span(),
-                                    params.len(),
-                                    alias_gen_scope.span,
-                                    alias_gen_scope.len(),
-                                );
-                                infer.ctx_mut().emit(err);
-                                TyInfo::Error(ErrorReason::Unknown)
-                            } else {
-                                let (alias_ty, alias_gen_scope) = (alias.ty, alias.gen_scope);
-                                let get_gen = |index, scope, ctx: &Context| {
-                                    params[index]
-                                };
-                                TyInfo::Ref(infer.instantiate(alias_ty, self.span(), &get_gen, None))
-                            }
-                        } else {
-                            let err_ty = infer.insert(self.span(), TyInfo::Error(ErrorReason::Unknown));
-                            infer.emit(InferError::RecursiveAlias(alias_id, err_ty, name.span()));
-                            TyInfo::Ref(err_ty)
-                        }
-                    } else if let Some(data) = infer.ctx().datas.lookup_data(**name) {
-                        let data_gen_scope = infer.ctx().tys.get_gen_scope(infer.ctx().datas.name_gen_scope(**name));
-
-                        if data_gen_scope.len() != params.len() {
-                            let err = Error::WrongNumberOfGenerics(
-                                self.span(),
-                                params.len(),
-                                data_gen_scope.span,
-                                data_gen_scope.len(),
-                            );
-                            infer.ctx_mut().emit(err);
-                            TyInfo::Error(ErrorReason::Unknown)
-                        } else {
-                            // Enforce obligations from data type
-                            let mut obls = Vec::new();
-                            for idx in 0..data_gen_scope.len() {
-                                for obl in data_gen_scope
-                                    .get(idx)
-                                    .obligations()
-                                {
-                                    match &**obl {
-                                        Obligation::MemberOf(class) => obls.push((idx, *class, obl.span())),
-                                    }
-                                }
-                            }
-                            for (idx, class, span) in obls {
-                                infer.make_impl(params[idx], class, span, Vec::new());
-                            }
-
-                            TyInfo::Data(data, params)
-                        }
+                if let Some(alias) = infer.ctx().datas.get_alias(alias_id) {
+                    let alias_gen_scope = infer.ctx().tys.get_gen_scope(alias.gen_scope);
+                    if alias_gen_scope.len() != params.len() {
+                        let err = Error::WrongNumberOfGenerics(
+                            ty.span(),
+                            params.len(),
+                            alias_gen_scope.span,
+                            alias_gen_scope.len(),
+                        );
+                        infer.ctx_mut().emit(err);
+                        TyInfo::Error(ErrorReason::Unknown)
                     } else {
-                        infer.ctx_mut().emit(Error::NoSuchData(name.clone()));
-                        TyInfo::Error(ErrorReason::Invalid)
+                        let (alias_ty, alias_gen_scope) = (alias.ty, alias.gen_scope);
+                        let get_gen = |index, scope, ctx: &Context| {
+                            params[index]
+                        };
+                        TyInfo::Ref(infer.instantiate(alias_ty, ty.span(), &get_gen, None))
                     }
-                },
-            },
-            ast::Type::Assoc(inner, assoc) => {
-                let inner = inner.to_hir(infer, scope);
-                let assoc_ty = infer.unknown(self.span());
-                infer.make_class_assoc(inner.meta().1, assoc.clone(), assoc_ty, inner.meta().0);
-                TyInfo::Ref(assoc_ty)
+                } else {
+                    let err_ty = infer.insert(ty.span(), TyInfo::Error(ErrorReason::Unknown));
+                    infer.emit(InferError::RecursiveAlias(alias_id, err_ty, name.span()));
+                    TyInfo::Ref(err_ty)
+                }
+            } else if let Some(data) = infer.ctx().datas.lookup_data(**name) {
+                let data_gen_scope = infer.ctx().tys.get_gen_scope(infer.ctx().datas.name_gen_scope(**name));
+
+                if data_gen_scope.len() < params.len() {
+                    let err = Error::WrongNumberOfGenerics(
+                        ty.span(),
+                        params.len(),
+                        data_gen_scope.span,
+                        data_gen_scope.len(),
+                    );
+                    infer.ctx_mut().emit(err);
+                    TyInfo::Error(ErrorReason::Unknown)
+                } else {
+                    // Enforce obligations from data type
+                    let mut obls = Vec::new();
+                    for idx in 0..params.len() {
+                        for obl in data_gen_scope
+                            .get(idx)
+                            .obligations()
+                        {
+                            obls.push((idx, obl.clone()));
+                        }
+                    }
+                    let param_kinds = (0..data_gen_scope.len())
+                        .map(|idx| data_gen_scope.get(idx).kind)
+                        .collect::<Vec<_>>();
+
+                    // Parameters that are type constructors start a type constructor of their own
+                    let mut params = params
+                        .iter()
+                        .zip(&param_kinds)
+                        .map(|(param, param_kind)| match param_kind {
+                            Kind::Ty => lower_inner_ty(param, infer, scope, holes),
+                            _ => lower_ty_of_kind(param, infer, scope, Some(*param_kind)).meta().1,
+                        })
+                        .collect::<Vec<_>>();
+
+                    // Omitted trailing parameters make the type a type constructor
+                    kind = Kind::from_arity(param_kinds.len() - params.len());
+                    for _ in params.len()..param_kinds.len() {
+                        params.push(infer.insert(ty.span(), TyInfo::Hole(*holes)));
+                        *holes += 1;
+                    }
+
+                    for (idx, obl) in obls {
+                        infer.make_obligation(params[idx], &obl, &|idx, _, _| params[idx], None);
+                    }
+
+                    TyInfo::Data(data, params)
+                }
+            } else {
+                infer.ctx_mut().emit(Error::NoSuchData(name.clone()));
+                TyInfo::Error(ErrorReason::Invalid)
             },
-        };
+        },
+        ast::Type::Assoc(inner, assoc) => {
+            let inner = lower_inner_ty(inner, infer, scope, holes);
+            let assoc_ty = infer.unknown(ty.span());
+            infer.make_class_assoc(inner, assoc.clone(), assoc_ty, ty.span());
+            TyInfo::Ref(assoc_ty)
+        },
+    };
+
+    (infer.insert(ty.span(), info), kind)
+}
+
+/// Lower the parameters of a class obligation (i.e: `Nat` in `A < Convert Nat`), checking them against the parameters of
+/// the class.
+pub fn lower_obligation_params(infer: &mut Infer, obl: &SrcNode<ast::ClassInst>, class: ClassId) -> Vec<TyVar> {
+    let class_gen_scope = infer.ctx().tys.get_gen_scope(infer.ctx().classes.get(class).gen_scope);
+    let (class_gen_span, param_kinds) = (class_gen_scope.span, (0..class_gen_scope.len())
+        .map(|idx| class_gen_scope.get(idx).kind)
+        .collect::<Vec<_>>());
 
-        InferNode::new((), (self.span(), infer.insert(self.span(), info)))
+    if obl.params.len() != param_kinds.len() {
+        infer.ctx_mut().emit(Error::WrongNumberOfGenerics(obl.span(), obl.params.len(), class_gen_span, param_kinds.len()));
+        param_kinds
+            .iter()
+            .map(|_| infer.insert(obl.span(), TyInfo::Error(ErrorReason::Invalid)))
+            .collect()
+    } else {
+        obl.params
+            .iter()
+            .zip(param_kinds)
+            .map(|(param, kind)| lower_ty_of_kind(param, infer, &Scope::Empty, Some(kind)).meta().1)
+            .collect()
     }
 }
 
+/// Lower the associated type equalities of a class obligation (i.e: `Item = Nat` in `S < Stream with Item = Nat`),
+/// checking that the class has the associated types.
+pub fn lower_obligation_assoc(infer: &mut Infer, obl: &SrcNode<ast::ClassInst>, class: ClassId) -> Vec<(SrcNode<Ident>, TyVar)> {
+    obl.assoc
+        .iter()
+        .filter_map(|(name, ty)| {
+            let class = infer.ctx().classes.get(class);
+            if class.assoc_ty(**name).is_none() {
+                let err = Error::NoSuchClassItem(name.clone(), class.name.clone());
+                infer.ctx_mut().emit(err);
+                None
+            } else {
+                Some((name.clone(), lower_ty_of_kind(ty, infer, &Scope::Empty, Some(Kind::Ty)).meta().1))
+            }
+        })
+        .collect()
+}
+
 impl ToHir for ast::Binding {
     type Output = hir::Binding<InferMeta>;
 
@@ -200,11 +372,21 @@ impl ToHir for ast::Binding {
             },
             ast::Pat::Binary(op, lhs, rhs) => {
                 let lhs = lhs.to_hir(infer, scope);
-                match (&**rhs, &**op) {
-                    (ast::Literal::Nat(rhs_nat), ast::BinaryOp::Add) => {
-                        let nat = infer.insert(rhs.span(), TyInfo::Prim(Prim::Nat));
-                        infer.make_flow(nat, lhs.meta().1, EqInfo::new(self.span(), format!("Only natural numbers support arithmetic patterns")));
-                        (TyInfo::Ref(nat), hir::Pat::Add(lhs, SrcNode::new(*rhs_nat, rhs.span())))
+                let operand = match &**rhs {
+                    ast::Literal::Nat(x) => Some((Prim::Nat, *x)),
+                    ast::Literal::Int(x) if *x >= 0 => Some((Prim::Int, *x as u64)),
+                    _ => None,
+                };
+                match (operand, &**op) {
+                    // Multiplying by zero would leave the inner pattern with no single value to bind
+                    (Some((prim, x)), op @ (ast::BinaryOp::Add | ast::BinaryOp::Mul)) if x > 0 || *op == ast::BinaryOp::Add => {
+                        let ty = infer.insert(rhs.span(), TyInfo::Prim(prim));
+                        infer.make_flow(ty, lhs.meta().1, EqInfo::new(self.span(), format!("Only natural numbers and integers support arithmetic patterns")));
+                        let rhs = SrcNode::new(x, rhs.span());
+                        (TyInfo::Ref(ty), match op {
+                            ast::BinaryOp::Add => hir::Pat::Add(lhs, rhs),
+                            _ => hir::Pat::Mul(lhs, rhs),
+                        })
                     },
                     (_, _) => {
                         let (info, _) = litr_ty_info(rhs, infer, self.pat.span());
@@ -214,6 +396,29 @@ impl ToHir for ast::Binding {
                     },
                 }
             },
+            ast::Pat::Range(lo, hi) => {
+                let is_empty = match (&**lo, &**hi) {
+                    (ast::Literal::Nat(lo), ast::Literal::Nat(hi)) => Some(lo > hi),
+                    (ast::Literal::Int(lo), ast::Literal::Int(hi)) => Some(lo > hi),
+                    (ast::Literal::Char(lo), ast::Literal::Char(hi)) => Some(lo > hi),
+                    _ => None,
+                };
+                let (lo_info, _) = litr_ty_info(lo, infer, lo.span());
+                let lo_ty = infer.insert(lo.span(), lo_info);
+                match is_empty {
+                    Some(false) => (TyInfo::Ref(lo_ty), hir::Pat::Range(**lo, **hi)),
+                    Some(true) => {
+                        infer.ctx_mut().emit(Error::EmptyRange(self.span()));
+                        (TyInfo::Error(ErrorReason::Unknown), hir::Pat::Error)
+                    },
+                    None => {
+                        let (hi_info, _) = litr_ty_info(hi, infer, hi.span());
+                        let hi_ty = infer.insert(hi.span(), hi_info);
+                        infer.emit(InferError::RangeNotSupported(lo_ty, hi_ty, self.span()));
+                        (TyInfo::Error(ErrorReason::Unknown), hir::Pat::Error)
+                    },
+                }
+            },
             ast::Pat::Tuple(items) => {
                 let items = items
                     .iter()
@@ -224,15 +429,30 @@ impl ToHir for ast::Binding {
                     .map(|item| item.meta().1)
                     .collect()), hir::Pat::Tuple(items))
             },
-            ast::Pat::Record(fields) => {
+            ast::Pat::Record(fields, is_open) => {
                 let fields = fields
                     .iter()
-                    .map(|(name, field)| (**name, field.to_hir(infer, scope)))
-                    .collect::<BTreeMap<_, _>>();
-                (TyInfo::Record(fields
-                    .iter()
-                    .map(|(name, field)| (*name, field.meta().1))
-                    .collect()), hir::Pat::Record(fields))
+                    .map(|(name, field)| (name.clone(), field.to_hir(infer, scope)))
+                    .collect::<Vec<_>>();
+                check_field_names(&fields, infer);
+                let info = if *is_open {
+                    // The record may have other fields, so its type can only be constrained
+                    let record_ty = infer.unknown(self.pat.span());
+                    infer.make_record_fields(record_ty, fields
+                        .iter()
+                        .map(|(name, field)| (name.clone(), field.meta().1))
+                        .collect(), self.pat.span());
+                    TyInfo::Ref(record_ty)
+                } else {
+                    TyInfo::Record(fields
+                        .iter()
+                        .map(|(name, field)| (**name, field.meta().1))
+                        .collect())
+                };
+                (info, hir::Pat::Record(fields
+                    .into_iter()
+                    .map(|(name, field)| (name.into_inner(), field))
+                    .collect()))
             },
             ast::Pat::ListExact(items) => {
                 let item_ty = infer.unknown(self.pat.span());
@@ -301,6 +521,29 @@ impl ToHir for ast::Binding {
                 // TODO: Don't use a hard, preserve inner expression
                 (TyInfo::Error(ErrorReason::Unknown), hir::Pat::Error)
             },
+            ast::Pat::Or(alts) => {
+                let alts = alts
+                    .iter()
+                    .map(|alt| alt.to_hir(infer, scope))
+                    .collect::<Vec<_>>();
+                let names = alts[0].get_binding_tys();
+                for alt in &alts[1..] {
+                    infer.make_flow(alt.meta().1, alts[0].meta().1, alt.meta().0);
+                    let alt_names = alt.get_binding_tys();
+                    for (name, ty) in &names {
+                        match alt_names.iter().find(|(alt_name, _)| **alt_name == **name) {
+                            Some((alt_name, alt_ty)) => infer.make_flow(*alt_ty, *ty, EqInfo::new(alt_name.span(), format!("Alternatives must bind variables of the same type"))),
+                            None => infer.ctx_mut().emit(Error::OrPatternMismatch(name.clone(), alt.meta().0)),
+                        }
+                    }
+                    for (alt_name, _) in &alt_names {
+                        if !names.iter().any(|(name, _)| **name == **alt_name) {
+                            infer.ctx_mut().emit(Error::OrPatternMismatch(alt_name.clone(), alts[0].meta().0));
+                        }
+                    }
+                }
+                (TyInfo::Ref(alts[0].meta().1), hir::Pat::Or(alts))
+            },
         };
 
         let ty = infer.insert(self.span(), info);
@@ -324,6 +567,11 @@ impl ToHir for ast::Expr {
         let mut span = self.span();
         let (info, expr) = match &**self {
             ast::Expr::Error => (TyInfo::Error(ErrorReason::Unknown), hir::Expr::Error),
+            ast::Expr::Hole => {
+                let ty = infer.unknown(span);
+                infer.make_hole(span, ty, scope.locals(), scope.def());
+                (TyInfo::Ref(ty), hir::Expr::Error)
+            },
             ast::Expr::Literal(litr) => {
                 let (ty_info, num_litr) = litr_ty_info(litr, infer, span);
                 let ty = infer.insert(span, ty_info);
@@ -356,9 +604,7 @@ impl ToHir for ast::Expr {
                         .obligations()
                         .to_vec()
                     {
-                        match &*obl {
-                            Obligation::MemberOf(class) => infer.make_impl(*ty, *class, obl.span(), Vec::new()),
-                        }
+                        infer.make_obligation(*ty, &obl, &|idx, _, _| generic_tys[idx].1, None);
                     }
                 }
 
@@ -432,12 +678,31 @@ impl ToHir for ast::Expr {
                     .iter()
                     .map(|(name, field)| (name.clone(), field.to_hir(infer, scope)))
                     .collect::<Vec<_>>();
+                check_field_names(&fields, infer);
                 let tys = fields
                     .iter()
                     .map(|(name, field)| (**name, field.meta().1))
                     .collect();
                 (TyInfo::Record(tys), hir::Expr::Record(fields))
             },
+            ast::Expr::Update(record, fields) => {
+                let record = record.to_hir(infer, scope);
+                let fields = fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), field.to_hir(infer, scope)))
+                    .collect::<Vec<_>>();
+                check_field_names(&fields, infer);
+                let field_tys = fields
+                    .iter()
+                    .map(|(name, field)| {
+                        let field_ty = infer.unknown(field.meta().0);
+                        infer.make_flow(field.meta().1, field_ty, EqInfo::new(name.span(), format!("Updated fields must keep their type")));
+                        (name.clone(), field_ty)
+                    })
+                    .collect();
+                infer.make_record_fields(record.meta().1, field_tys, record.meta().0);
+                (TyInfo::Ref(record.meta().1), hir::Expr::Update(record, fields))
+            },
             ast::Expr::Access(record, field) => {
                 let record = record.to_hir(infer, scope);
                 let field_ty = infer.unknown(self.span());
@@ -486,7 +751,7 @@ impl ToHir for ast::Expr {
                             InferNode::new(hir::Expr::Match(
                                 false,
                                 val,
-                                vec![(binding, then)],
+                                vec![(binding, None, then)],
                             ), (span, ty))
                         },
                         None => then.to_hir(infer, scope),
@@ -515,12 +780,14 @@ impl ToHir for ast::Expr {
 
                     let arms = arms
                         .iter()
-                        .map(|(bindings, body)| {
+                        .map(|(bindings, guard, body)| {
                             let binding = tupleify_binding(bindings, infer, scope);
                             infer.make_flow(pred.meta().1, binding.meta().1, binding.meta().0);
-                            let body = body.to_hir(infer, &scope.with_many(binding.get_binding_tys()));
+                            let scope = scope.with_many(binding.get_binding_tys());
+                            let guard = guard.as_ref().map(|guard| guard_to_hir(guard, infer, &scope));
+                            let body = body.to_hir(infer, &scope);
                             infer.make_flow(body.meta().1, output_ty, EqInfo::new(self.span(), format!("Branches must produce compatible values")));
-                            (binding, body)
+                            (binding, guard, body)
                         })
                         .collect();
 
@@ -537,8 +804,8 @@ impl ToHir for ast::Expr {
                 infer.make_flow(a.meta().1, output_ty, EqInfo::new(self.span(), format!("Branches must produce compatible values")));
                 infer.make_flow(b.meta().1, output_ty, EqInfo::new(self.span(), format!("Branches must produce compatible values")));
                 let arms = vec![
-                    (InferNode::new(hir::Binding::from_pat(SrcNode::new(hir::Pat::Literal(ast::Literal::Bool(true)), pred.meta().0)), *pred.meta()), a),
-                    (InferNode::new(hir::Binding::from_pat(SrcNode::new(hir::Pat::Literal(ast::Literal::Bool(false)), pred.meta().0)), *pred.meta()), b),
+                    (InferNode::new(hir::Binding::from_pat(SrcNode::new(hir::Pat::Literal(ast::Literal::Bool(true)), pred.meta().0)), *pred.meta()), None, a),
+                    (InferNode::new(hir::Binding::from_pat(SrcNode::new(hir::Pat::Literal(ast::Literal::Bool(false)), pred.meta().0)), *pred.meta()), None, b),
                 ];
                 (TyInfo::Ref(output_ty), hir::Expr::Match(false, pred, arms))
             },
@@ -573,12 +840,14 @@ impl ToHir for ast::Expr {
 
                         let arms = arms
                             .iter()
-                            .map(|(bindings, body)| {
+                            .map(|(bindings, guard, body)| {
                                 let binding = tupleify_binding(bindings, infer, scope);
                                 infer.make_flow(pred.meta().1, binding.meta().1, binding.meta().0);
-                                let body = body.to_hir(infer, &scope.with_many(binding.get_binding_tys()));
+                                let scope = scope.with_many(binding.get_binding_tys());
+                                let guard = guard.as_ref().map(|guard| guard_to_hir(guard, infer, &scope));
+                                let body = body.to_hir(infer, &scope);
                                 infer.make_flow(body.meta().1, output_ty, EqInfo::new(self.span(), format!("Branches must produce compatible values")));
-                                (binding, body)
+                                (binding, guard, body)
                             })
                             .collect();
 
@@ -631,13 +900,11 @@ impl ToHir for ast::Expr {
                         .get(idx)
                         .obligations()
                     {
-                        match &**obl {
-                            Obligation::MemberOf(class) => obls.push((idx, *class, obl.span())),
-                        }
+                        obls.push((idx, obl.clone()));
                     }
                 }
-                for (idx, class, span) in obls {
-                    infer.make_impl(generic_tys[idx], class, span, Vec::new());
+                for (idx, obl) in obls {
+                    infer.make_obligation(generic_tys[idx], &obl, &|idx, _, _| generic_tys[idx], None);
                 }
 
                 let inner_ty = infer
@@ -668,7 +935,8 @@ impl ToHir for ast::Expr {
                 (TyInfo::Error(ErrorReason::Unknown), hir::Expr::Error)
             },
             ast::Expr::ClassAccess(ty, field) => {
-                let ty = ty.to_hir(infer, scope);
+                // Classes may be over type constructors, so the type need not be of kind `*`
+                let ty = lower_ty_of_kind(ty, infer, scope, None);
                 let field_ty = infer.unknown(field.span());
                 let class = infer.make_class_field(ty.meta().1, field.clone(), field_ty, ty.meta().0);
                 (TyInfo::Ref(field_ty), hir::Expr::ClassAccess(*ty.meta(), class, field.clone()))
@@ -753,3 +1021,11 @@ fn tupleify_binding(items: &SrcNode<Vec<SrcNode<ast::Binding>>>, infer: &mut Inf
     };
     InferNode::new(binding, (items.span(), tuple_ty))
 }
+
+// Lower the guard of an arm, which must be a boolean
+fn guard_to_hir(guard: &SrcNode<ast::Expr>, infer: &mut Infer, scope: &Scope) -> InferExpr {
+    let guard = guard.to_hir(infer, scope);
+    let bool_ty = infer.insert(guard.meta().0, TyInfo::Prim(Prim::Bool));
+    infer.make_flow(guard.meta().1, bool_ty, EqInfo::new(guard.meta().0, format!("Guards must be booleans")));
+    guard
+}
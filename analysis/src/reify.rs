@@ -18,9 +18,11 @@ impl Reify for hir::Binding<InferMeta> {
                 hir::Pat::Error => hir::Pat::Error,
                 hir::Pat::Wildcard => hir::Pat::Wildcard,
                 hir::Pat::Literal(litr) => hir::Pat::Literal(litr),
+                hir::Pat::Range(lo, hi) => hir::Pat::Range(lo, hi),
                 hir::Pat::Single(inner) => hir::Pat::Single(inner.reify(infer)),
                 hir::Pat::Union(inner) => hir::Pat::Union(inner.reify(infer)),
                 hir::Pat::Add(lhs, rhs) => hir::Pat::Add(lhs.reify(infer), rhs),
+                hir::Pat::Mul(lhs, rhs) => hir::Pat::Mul(lhs.reify(infer), rhs),
                 hir::Pat::Tuple(items) => hir::Pat::Tuple(items
                     .into_iter()
                     .map(|item| item.reify(infer))
@@ -38,6 +40,10 @@ impl Reify for hir::Binding<InferMeta> {
                     .map(|item| item.reify(infer))
                     .collect(), tail.map(|tail| tail.reify(infer))),
                 hir::Pat::Decons(data, variant, inner) => hir::Pat::Decons(data, variant, inner.reify(infer)),
+                hir::Pat::Or(alts) => hir::Pat::Or(alts
+                    .into_iter()
+                    .map(|alt| alt.reify(infer))
+                    .collect()),
             }),
             name: this.name,
         }, (span, infer.reify(ty)))
@@ -74,16 +80,25 @@ impl Reify for hir::Expr<InferMeta> {
                 .into_iter()
                 .map(|(name, field)| (name, field.reify(infer)))
                 .collect()),
+            hir::Expr::Update(record, fields) => hir::Expr::Update(record.reify(infer), fields
+                .into_iter()
+                .map(|(name, field)| (name, field.reify(infer)))
+                .collect()),
             hir::Expr::Access(record, field_name) => hir::Expr::Access(record.reify(infer), field_name),
             hir::Expr::Binary(op, a, b) => hir::Expr::Binary(op, a.reify(infer), b.reify(infer)),
             hir::Expr::Match(hidden_outer, pred, arms) => {
                 let pred = pred.reify(infer);
                 let arms = arms
                     .into_iter()
-                    .map(|(binding, arm)| (binding.reify(infer), arm.reify(infer)))
+                    .map(|(binding, guard, arm)| (binding.reify(infer), guard.map(|guard| guard.reify(infer)), arm.reify(infer)))
                     .collect::<Vec<_>>();
 
-                if let Err(example) = exhaustivity(infer.ctx(), pred.meta().1, arms.iter().map(|(b, _)| b)) {
+                // A guarded arm might not be taken, so it cannot contribute to the exhaustivity of the match
+                let unguarded = arms
+                    .iter()
+                    .filter(|(_, guard, _)| guard.is_none())
+                    .map(|(b, _, _)| b);
+                if let Err(example) = exhaustivity(infer.ctx(), pred.meta().1, unguarded) {
                     infer.ctx_mut().emit(Error::NotExhaustive(span, example, hidden_outer));
                 }
 
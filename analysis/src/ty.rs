@@ -45,6 +45,40 @@ pub enum Ty {
     Gen(usize, GenScopeId),
     SelfType,
     Assoc(TyId, ClassId, SrcNode<Ident>),
+    // Application of a type constructor that is not yet known (a generic type or `Self`) to some arguments
+    Apply(TyId, Vec<TyId>),
+    // A placeholder in a type constructor, substituted with the nth argument when the constructor is applied
+    Hole(usize),
+}
+
+/// The kind of a type: either a type that values can have, or a type constructor that produces one when applied to
+/// some number of other types. Constructor arguments are always of kind `*`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Ty,
+    Ctor(usize),
+}
+
+impl Kind {
+    pub fn from_arity(arity: usize) -> Self {
+        if arity == 0 { Kind::Ty } else { Kind::Ctor(arity) }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Kind::Ty => 0,
+            Kind::Ctor(arity) => *arity,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for _ in 0..self.arity() {
+            write!(f, "* -> ")?;
+        }
+        write!(f, "*")
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -67,6 +101,54 @@ impl Types {
         id
     }
 
+    pub fn gen_scope_ids(&self) -> impl Iterator<Item = GenScopeId> {
+        (0..self.scopes.len()).map(GenScopeId)
+    }
+
+    pub fn define_gen_obligations(&mut self, scope: GenScopeId, idx: usize, obligations: Vec<SrcNode<Obligation>>) {
+        self.scopes[scope.0].types[idx].obligations = Some(obligations);
+    }
+
+    pub fn define_gen_kinds(&mut self, scope: GenScopeId, kinds: Vec<Kind>) {
+        for (ty, kind) in self.scopes[scope.0].types.iter_mut().zip(kinds) {
+            ty.kind = kind;
+        }
+    }
+
+    /// Determine the kind of a type. Type constructors are written with holes, generic types have whatever kind was
+    /// inferred for them.
+    pub fn kind_of(&self, ty: TyId) -> Kind {
+        fn holes(tys: &Types, ty: TyId) -> usize {
+            match tys.get(ty) {
+                Ty::Hole(idx) => idx + 1,
+                Ty::Error(_) | Ty::Prim(_) | Ty::Gen(_, _) | Ty::SelfType => 0,
+                Ty::List(item) => holes(tys, item),
+                Ty::Tuple(xs) | Ty::Union(xs) | Ty::Data(_, xs) => xs
+                    .into_iter()
+                    .map(|x| holes(tys, x))
+                    .max()
+                    .unwrap_or(0),
+                Ty::Record(fields) => fields
+                    .into_iter()
+                    .map(|(_, x)| holes(tys, x))
+                    .max()
+                    .unwrap_or(0),
+                Ty::Func(i, o) => holes(tys, i).max(holes(tys, o)),
+                Ty::Assoc(inner, _, _) => holes(tys, inner),
+                Ty::Apply(_, args) => args
+                    .into_iter()
+                    .map(|arg| holes(tys, arg))
+                    .max()
+                    .unwrap_or(0),
+            }
+        }
+
+        match self.get(ty) {
+            Ty::Gen(idx, scope) => self.get_gen_scope(scope).get(idx).kind,
+            _ => Kind::from_arity(holes(self, ty)),
+        }
+    }
+
     pub fn check_gen_scopes(&mut self, classes: &Classes) -> Vec<Error> {
         let mut errors = Vec::new();
         for scope in &mut self.scopes {
@@ -116,6 +198,11 @@ impl Types {
             (Ty::Assoc(x_ty, x_class, x_name), Ty::Assoc(y_ty, y_class, y_name)) => self.is_eq(x_ty, y_ty)
                 && x_class == y_class
                 && *x_name == *y_name,
+            (Ty::Apply(x, xs), Ty::Apply(y, ys)) => self.is_eq(x, y) && xs.len() == ys.len() && xs
+                .into_iter()
+                .zip(ys)
+                .all(|(x, y)| self.is_eq(x, y)),
+            (Ty::Hole(x), Ty::Hole(y)) => x == y,
             _ => false,
         }
     }
@@ -195,20 +282,32 @@ impl<'a> fmt::Display for TyDisplay<'a> {
             // TODO: Include class_id?
             Ty::Assoc(inner, _class_id, assoc) => write!(f, "{}.{}", self.with_ty(inner, true), *assoc),
             Ty::SelfType => write!(f, "Self"),
+            Ty::Apply(ctor, args) if self.lhs_exposed => write!(f, "({}{})", self.with_ty(ctor, true), args
+                .iter()
+                .map(|arg| format!(" {}", self.with_ty(*arg, true)))
+                .collect::<String>()),
+            Ty::Apply(ctor, args) => write!(f, "{}{}", self.with_ty(ctor, true), args
+                .iter()
+                .map(|arg| format!(" {}", self.with_ty(*arg, true)))
+                .collect::<String>()),
+            Ty::Hole(_) => write!(f, "_"),
         }
     }
 }
 
 #[derive(Clone)]
 pub enum Obligation {
-    MemberOf(ClassId),
+    // (class, class_params, assoc_equalities)
+    MemberOf(ClassId, Vec<TyId>, Vec<(SrcNode<Ident>, TyId)>),
 }
 
+#[derive(Clone)]
 pub struct GenTy {
     pub name: SrcNode<Ident>,
     // TODO: Don't store this here, it's silly
     pub ast_obligations: Vec<SrcNode<ast::ClassInst>>,
     pub obligations: Option<Vec<SrcNode<Obligation>>>,
+    pub kind: Kind,
 }
 
 impl GenTy {
@@ -219,6 +318,42 @@ impl GenTy {
     }
 }
 
+/// Find the places in which named types are used as type constructors, either by being applied to arguments or by
+/// being passed, unapplied, to a data type in place of a type constructor parameter.
+pub(crate) fn visit_kinds(
+    ty: &SrcNode<ast::Type>,
+    data_kind: &impl Fn(Ident, usize) -> Option<Kind>,
+    found: &mut impl FnMut(&SrcNode<Ident>, Kind, bool),
+) {
+    match &**ty {
+        ast::Type::Error | ast::Type::Unknown | ast::Type::Hole => {},
+        ast::Type::List(item) => visit_kinds(item, data_kind, found),
+        ast::Type::Tuple(items) | ast::Type::Union(items) => items
+            .iter()
+            .for_each(|item| visit_kinds(item, data_kind, found)),
+        ast::Type::Record(fields) => fields
+            .iter()
+            .for_each(|(_, field)| visit_kinds(field, data_kind, found)),
+        ast::Type::Func(i, o) => {
+            visit_kinds(i, data_kind, found);
+            visit_kinds(o, data_kind, found);
+        },
+        ast::Type::Data(name, params) => {
+            if !params.is_empty() {
+                found(name, Kind::from_arity(params.len()), true);
+            }
+            for (idx, param) in params.iter().enumerate() {
+                match (&**param, data_kind(**name, idx)) {
+                    (ast::Type::Data(param_name, args), Some(kind @ Kind::Ctor(_))) if args.is_empty() => found(param_name, kind, false),
+                    _ => {},
+                }
+                visit_kinds(param, data_kind, found);
+            }
+        },
+        ast::Type::Assoc(inner, _) => visit_kinds(inner, data_kind, found),
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct GenScopeId(usize);
 
@@ -246,11 +381,78 @@ impl GenScope {
                     name: gen_ty.name.clone(),
                     ast_obligations: gen_ty.obligations.clone(),
                     obligations: None,
+                    kind: Kind::Ty,
                 })
                 .collect(),
         }, errors)
     }
 
+    /// Create a scope containing the types of this scope followed by those of another, for items that can see the
+    /// generic types of their parent.
+    pub fn concat(&self, other: &Self) -> Self {
+        Self {
+            span: other.span,
+            types: self.types
+                .iter()
+                .chain(other.types.iter())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Infer the kind of each generic type from the way the given types use it. A generic that is applied to arguments,
+    /// that is passed to a data type in place of a type constructor, or that must be a member of a class of type
+    /// constructors is itself a type constructor. Everything else has kind `*`. Uses that disagree with the inferred
+    /// kind are reported.
+    pub fn infer_kinds<'a>(
+        &self,
+        uses: impl IntoIterator<Item = &'a SrcNode<ast::Type>>,
+        data_kind: &impl Fn(Ident, usize) -> Option<Kind>,
+        class_kind: &impl Fn(Ident) -> Option<Kind>,
+    ) -> (Vec<Kind>, Vec<Error>) {
+        // The kind that each use of each generic type requires, and whether it was applied to arguments
+        let mut uses_of = vec![Vec::new(); self.len()];
+        for ty in uses {
+            visit_kinds(ty, data_kind, &mut |name, kind, is_applied| if let Some((idx, _)) = self.find(**name) {
+                uses_of[idx].push((name.span(), kind, is_applied));
+            });
+        }
+
+        let mut errors = Vec::new();
+        let kinds = self.types
+            .iter()
+            .zip(uses_of)
+            .map(|(ty, uses)| {
+                let obligation_kind = ty.ast_obligations
+                    .iter()
+                    .filter_map(|obl| class_kind(*obl.name))
+                    .find(|kind| *kind != Kind::Ty);
+                let first_use = |applied| uses
+                    .iter()
+                    .find(|(_, _, is_applied)| *is_applied == applied)
+                    .map(|(_, kind, _)| *kind);
+                let kind = first_use(true)
+                    .or(obligation_kind)
+                    .or(first_use(false))
+                    .unwrap_or(Kind::Ty);
+                for (span, use_kind, _) in &uses {
+                    if *use_kind != kind {
+                        errors.push(Error::KindMismatch(*span, kind, *use_kind));
+                    }
+                }
+                for obl in &ty.ast_obligations {
+                    match class_kind(*obl.name) {
+                        Some(class_kind) if class_kind != kind => errors.push(Error::KindMismatch(ty.name.span(), kind, class_kind)),
+                        _ => {},
+                    }
+                }
+                kind
+            })
+            .collect();
+
+        (kinds, errors)
+    }
+
     pub fn len(&self) -> usize { self.types.len() }
 
     pub fn get(&self, index: usize) -> &GenTy {
@@ -267,7 +469,8 @@ impl GenScope {
                 .ast_obligations
                 .iter()
                 .filter_map(|obl| if let Some(class) = classes.lookup(*obl.name) {
-                    Some(SrcNode::new(Obligation::MemberOf(class), obl.name.span()))
+                    // Parameters are lowered later, once all obligations are known
+                    Some(SrcNode::new(Obligation::MemberOf(class, Vec::new(), Vec::new()), obl.name.span()))
                 } else {
                     errors.push(Error::NoSuchClass(obl.name.clone()));
                     None
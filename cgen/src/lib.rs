@@ -0,0 +1,16 @@
+pub mod lower;
+
+pub use crate::lower::Module;
+
+use tao_middle::{
+    mir,
+    repr,
+    Context as MirContext,
+    MirNode,
+    ProcId,
+};
+use hashbrown::HashMap;
+use std::io::{self, Write};
+
+/// The C runtime that every generated module is prefixed with.
+pub const RUNTIME: &str = include_str!("runtime.c");
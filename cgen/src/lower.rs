@@ -0,0 +1,473 @@
+use super::*;
+
+fn local_name(local: mir::Local) -> String {
+    format!("l{}", local.0)
+}
+
+fn int_litr(x: i64) -> String {
+    if x == i64::MIN {
+        // `-9223372036854775808` is not a valid C literal, since the negation is applied after the fact
+        format!("INT64_MIN")
+    } else {
+        format!("INT64_C({})", x)
+    }
+}
+
+fn make_list(items: Vec<String>) -> String {
+    if items.is_empty() {
+        format!("tao_make_list(0, NULL)")
+    } else {
+        format!("tao_make_list({}, (tao_val[]){{ {} }})", items.len(), items.join(", "))
+    }
+}
+
+fn compile_litr(literal: &mir::Literal) -> String {
+    match literal {
+        mir::Literal::Unknown(x) => *x,
+        mir::Literal::Nat(x) => format!("tao_int({})", int_litr(*x as i64)),
+        mir::Literal::Int(x) => format!("tao_int({})", int_litr(*x)),
+        mir::Literal::Real(x) => format!("tao_real_bits(UINT64_C(0x{:016X}))", x.to_bits()),
+        mir::Literal::Char(c) => format!("tao_char({})", *c as u32),
+        mir::Literal::Bool(x) => format!("tao_bool({})", x),
+        mir::Literal::Tuple(fields) => make_list(fields
+            .iter()
+            .map(compile_litr)
+            .collect()),
+        mir::Literal::List(items) => make_list(items
+            .iter()
+            .map(compile_litr)
+            .collect()),
+        mir::Literal::Sum(variant, inner) => format!("tao_make_sum({}, {})", variant, compile_litr(inner)),
+        mir::Literal::Union(id, inner) => format!("tao_make_sum({}, {})", id, compile_litr(inner)),
+    }
+}
+
+fn all(conds: Vec<String>) -> String {
+    if conds.is_empty() {
+        format!("true")
+    } else {
+        format!("({})", conds.join(" && "))
+    }
+}
+
+fn any(conds: Vec<String>) -> String {
+    if conds.is_empty() {
+        format!("false")
+    } else {
+        format!("({})", conds.join(" || "))
+    }
+}
+
+// Produces a C boolean expression that is true if the value matches the literal
+fn compile_litr_matcher(literal: &mir::Literal, val: &str) -> String {
+    match literal {
+        mir::Literal::Unknown(x) => *x,
+        mir::Literal::Nat(x) => format!("({}.i == {})", val, int_litr(*x as i64)),
+        mir::Literal::Int(x) => format!("({}.i == {})", val, int_litr(*x)),
+        mir::Literal::Real(x) => format!("({}.r == tao_real_bits(UINT64_C(0x{:016X})).r)", val, x.to_bits()),
+        mir::Literal::Char(c) => format!("({}.c == {})", val, *c as u32),
+        mir::Literal::Bool(true) => format!("{}.b", val),
+        mir::Literal::Bool(false) => format!("!{}.b", val),
+        mir::Literal::Tuple(fields) => all(fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| compile_litr_matcher(field, &format!("tao_index({}, {})", val, i)))
+            .collect()),
+        mir::Literal::List(items) => all(std::iter::once(format!("tao_len({}) == {}", val, items.len()))
+            .chain(items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| compile_litr_matcher(item, &format!("tao_index({}, {})", val, i))))
+            .collect()),
+        mir::Literal::Sum(variant, inner) => all(vec![
+            format!("tao_variant({}) == {}", val, variant),
+            compile_litr_matcher(inner, &format!("tao_inner({})", val)),
+        ]),
+        mir::Literal::Union(id, inner) => all(vec![
+            format!("tao_variant({}) == {}", val, id),
+            compile_litr_matcher(inner, &format!("tao_inner({})", val)),
+        ]),
+    }
+}
+
+// Produces a C boolean expression that is true if the value matches the binding
+fn compile_matcher(binding: &MirNode<mir::Binding>, val: &str) -> String {
+    match &binding.pat {
+        mir::Pat::Wildcard => format!("true"),
+        mir::Pat::Literal(literal) => compile_litr_matcher(literal, val),
+        mir::Pat::Range(lo, hi) => match (lo, hi) {
+            (mir::Literal::Nat(lo), mir::Literal::Nat(hi)) => format!("({}.i >= {} && {}.i <= {})", val, int_litr(*lo as i64), val, int_litr(*hi as i64)),
+            (mir::Literal::Int(lo), mir::Literal::Int(hi)) => format!("({}.i >= {} && {}.i <= {})", val, int_litr(*lo), val, int_litr(*hi)),
+            (mir::Literal::Char(lo), mir::Literal::Char(hi)) => format!("({}.c >= {} && {}.c <= {})", val, *lo as u32, val, *hi as u32),
+            range => unreachable!("{:?}", range),
+        },
+        mir::Pat::Single(inner) => compile_matcher(inner, val),
+        // Only naturals have a lower bound to check (see `mir::Pat::Add`)
+        mir::Pat::Add(lhs, rhs) if *lhs.meta() == repr::Repr::Prim(repr::Prim::Int) => {
+            compile_matcher(lhs, &format!("tao_sub_int({}, tao_int({}))", val, int_litr(*rhs as i64)))
+        },
+        mir::Pat::Add(lhs, rhs) => all(vec![
+            format!("{}.i >= {}", val, int_litr(*rhs as i64)),
+            compile_matcher(lhs, &format!("tao_sub_int({}, tao_int({}))", val, int_litr(*rhs as i64))),
+        ]),
+        mir::Pat::Mul(lhs, rhs) => all(vec![
+            format!("tao_rem_int({}, tao_int({})).i == 0", val, int_litr(*rhs as i64)),
+            compile_matcher(lhs, &format!("tao_div_int({}, tao_int({}))", val, int_litr(*rhs as i64))),
+        ]),
+        mir::Pat::Tuple(fields) => all(fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| compile_matcher(field, &format!("tao_index({}, {})", val, i)))
+            .collect()),
+        mir::Pat::ListExact(items) => all(std::iter::once(format!("tao_len({}) == {}", val, items.len()))
+            .chain(items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| compile_matcher(item, &format!("tao_index({}, {})", val, i))))
+            .collect()),
+        mir::Pat::ListFront(items, tail) => all(std::iter::once(format!("tao_len({}) >= {}", val, items.len()))
+            .chain(items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| compile_matcher(item, &format!("tao_index({}, {})", val, i))))
+            .chain(tail
+                .as_ref()
+                .map(|tail| compile_matcher(tail, &format!("tao_skip({}, {})", val, items.len()))))
+            .collect()),
+        mir::Pat::Variant(variant, inner) => all(vec![
+            format!("tao_variant({}) == {}", val, variant),
+            compile_matcher(inner, &format!("tao_inner({})", val)),
+        ]),
+        mir::Pat::UnionVariant(id, inner) => all(vec![
+            format!("tao_variant({}) == {}", val, id),
+            compile_matcher(inner, &format!("tao_inner({})", val)),
+        ]),
+        mir::Pat::Or(alts) => any(alts
+            .iter()
+            .map(|alt| compile_matcher(alt, val))
+            .collect()),
+    }
+}
+
+/// A C function body under construction.
+#[derive(Default)]
+struct Body {
+    code: String,
+    depth: usize,
+}
+
+impl Body {
+    fn line(&mut self, line: impl AsRef<str>) {
+        self.code += &"    ".repeat(self.depth + 1);
+        self.code += line.as_ref();
+        self.code += "\n";
+    }
+}
+
+// Collects the C expressions that produce the value of each local bound by the binding
+fn binding_vals(binding: &MirNode<mir::Binding>, val: &str, vals: &mut Vec<(mir::Local, String)>) {
+    if let Some(name) = binding.name {
+        vals.push((name, val.to_string()));
+    }
+
+    match &binding.pat {
+        mir::Pat::Wildcard => {},
+        mir::Pat::Literal(_) => {},
+        mir::Pat::Range(_, _) => {},
+        mir::Pat::Single(inner) => binding_vals(inner, val, vals),
+        mir::Pat::Add(lhs, rhs) => binding_vals(lhs, &format!("tao_sub_int({}, tao_int({}))", val, int_litr(*rhs as i64)), vals),
+        mir::Pat::Mul(lhs, rhs) => binding_vals(lhs, &format!("tao_div_int({}, tao_int({}))", val, int_litr(*rhs as i64)), vals),
+        mir::Pat::Tuple(items) | mir::Pat::ListExact(items) => for (i, item) in items.iter().enumerate() {
+            if item.binds() {
+                binding_vals(item, &format!("tao_index({}, {})", val, i), vals);
+            }
+        },
+        mir::Pat::ListFront(items, tail) => {
+            for (i, item) in items.iter().enumerate() {
+                if item.binds() {
+                    binding_vals(item, &format!("tao_index({}, {})", val, i), vals);
+                }
+            }
+            if let Some(tail) = tail.as_ref() {
+                binding_vals(tail, &format!("tao_skip({}, {})", val, items.len()), vals);
+            }
+        },
+        mir::Pat::Variant(_, inner) | mir::Pat::UnionVariant(_, inner) => binding_vals(inner, &format!("tao_inner({})", val), vals),
+        // Each local takes its value from the first alternative that matches
+        mir::Pat::Or(alts) => {
+            let alt_vals = alts
+                .iter()
+                .map(|alt| {
+                    let mut alt_vals = Vec::new();
+                    binding_vals(alt, val, &mut alt_vals);
+                    alt_vals
+                })
+                .collect::<Vec<_>>();
+            for name in alts[0].binding_names() {
+                let val_of = |alt_vals: &[(mir::Local, String)]| alt_vals
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .expect("Alternatives must bind the same locals")
+                    .1
+                    .clone();
+                let (last, init) = alt_vals.split_last().unwrap();
+                let alt_val = alts
+                    .iter()
+                    .zip(init)
+                    .rev()
+                    .fold(val_of(last), |rest, (alt, alt_vals)| format!("({} ? {} : {})", compile_matcher(alt, val), val_of(alt_vals), rest));
+                vals.push((name, alt_val));
+            }
+        },
+    }
+}
+
+// Declares the locals bound by the binding, extracting them from the value
+fn compile_extractor(binding: &MirNode<mir::Binding>, val: &str, body: &mut Body) {
+    let mut vals = Vec::new();
+    binding_vals(binding, val, &mut vals);
+    for (name, val) in vals {
+        body.line(format!("tao_val {} = {};", local_name(name), val));
+    }
+}
+
+/// A C translation unit produced from MIR.
+#[derive(Default)]
+pub struct Module {
+    procs: HashMap<ProcId, usize>,
+    proc_defs: Vec<String>,
+    lambdas: Vec<String>,
+    entry: Option<usize>,
+    tmps: usize,
+}
+
+impl Module {
+    fn tmp(&mut self) -> String {
+        self.tmps += 1;
+        format!("t{}", self.tmps)
+    }
+
+    // Compile an expression, emitting any statements it needs into `body` and returning a C expression for its value
+    fn compile_expr(&mut self, expr: &MirNode<mir::Expr>, body: &mut Body) -> String {
+        match &**expr {
+            mir::Expr::Literal(literal) => compile_litr(literal),
+            mir::Expr::Local(local) => local_name(*local),
+            mir::Expr::Global(global, _) => format!("proc_{}()", self.procs[global]),
+            mir::Expr::Intrinsic(intrinsic, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.compile_expr(arg, body))
+                    .collect::<Vec<_>>();
+                use mir::Intrinsic::*;
+                match intrinsic {
+                    MakeList(_) => make_list(args),
+                    NotBool => format!("tao_bool(!{}.b)", args[0]),
+                    NegNat | NegInt => format!("tao_neg_int({})", args[0]),
+                    NegReal => format!("tao_real(-{}.r)", args[0]),
+                    AddNat | AddInt => format!("tao_add_int({}, {})", args[0], args[1]),
+                    SubNat | SubInt => format!("tao_sub_int({}, {})", args[0], args[1]),
+                    MulNat | MulInt => format!("tao_mul_int({}, {})", args[0], args[1]),
+                    EqNat | EqInt => format!("tao_bool({}.i == {}.i)", args[0], args[1]),
+                    EqChar => format!("tao_bool({}.c == {}.c)", args[0], args[1]),
+                    NotEqNat | NotEqInt => format!("tao_bool({}.i != {}.i)", args[0], args[1]),
+                    NotEqChar => format!("tao_bool({}.c != {}.c)", args[0], args[1]),
+                    LessNat | LessInt => format!("tao_bool({}.i < {}.i)", args[0], args[1]),
+                    MoreNat | MoreInt => format!("tao_bool({}.i > {}.i)", args[0], args[1]),
+                    LessEqNat | LessEqInt => format!("tao_bool({}.i <= {}.i)", args[0], args[1]),
+                    MoreEqNat | MoreEqInt => format!("tao_bool({}.i >= {}.i)", args[0], args[1]),
+                    Join(_) => format!("tao_join({}, {})", args[0], args[1]),
+                    Union(id) => format!("tao_make_sum({}, {})", id, args[0]),
+                }
+            },
+            mir::Expr::Match(pred, arms) => {
+                let pred = self.compile_expr(pred, body);
+                let scrutinee = self.tmp();
+                let output = self.tmp();
+                body.line(format!("tao_val {} = {};", scrutinee, pred));
+                body.line(format!("tao_val {};", output));
+
+                let mut else_blocks = 0;
+                for (i, (binding, guard, arm)) in arms.iter().enumerate() {
+                    let is_last = i + 1 == arms.len();
+                    // The last arm is assumed to match (see `mir::Expr::Match`)
+                    let is_tested = binding.is_refutable() && !is_last;
+                    // The last arm never has a guard
+                    let guard = guard.as_ref().filter(|_| !is_last);
+
+                    // Whether a guarded arm was taken, since the arms that follow it must be tried if it was not
+                    let taken = guard.map(|_| self.tmp());
+                    if let Some(taken) = &taken {
+                        body.line(format!("bool {} = false;", taken));
+                    }
+
+                    if is_tested {
+                        body.line(format!("if ({}) {{", compile_matcher(binding, &scrutinee)));
+                    } else {
+                        body.line("{");
+                    }
+                    body.depth += 1;
+                    compile_extractor(binding, &scrutinee, body);
+                    if let (Some(guard), Some(taken)) = (guard, &taken) {
+                        let guard = self.compile_expr(guard, body);
+                        body.line(format!("if ({}.b) {{", guard));
+                        body.depth += 1;
+                        let arm = self.compile_expr(arm, body);
+                        body.line(format!("{} = {};", output, arm));
+                        body.line(format!("{} = true;", taken));
+                        body.depth -= 1;
+                        body.line("}");
+                    } else {
+                        let arm = self.compile_expr(arm, body);
+                        body.line(format!("{} = {};", output, arm));
+                    }
+                    body.depth -= 1;
+
+                    if let Some(taken) = &taken {
+                        body.line("}");
+                        body.line(format!("if (!{}) {{", taken));
+                        body.depth += 1;
+                        else_blocks += 1;
+                    } else if is_tested {
+                        body.line("} else {");
+                        body.depth += 1;
+                        else_blocks += 1;
+                    } else {
+                        body.line("}");
+                        // Later arms can never be reached
+                        break;
+                    }
+                }
+
+                if arms.is_empty() {
+                    body.line("tao_panic(\"match has no arms\");");
+                }
+
+                for _ in 0..else_blocks {
+                    body.depth -= 1;
+                    body.line("}");
+                }
+
+                output
+            },
+            mir::Expr::Func(arg, func_body) => {
+                let mut captures = Vec::new();
+                for capture in func_body.required_locals(Some(*arg)) {
+                    if !captures.contains(&capture) {
+                        captures.push(capture);
+                    }
+                }
+
+                let idx = self.lambdas.len();
+                self.lambdas.push(String::new()); // Reserve the index, nested lambdas are pushed after
+
+                let mut inner = Body::default();
+                inner.line(format!("tao_val {} = arg;", local_name(*arg)));
+                for (i, capture) in captures.iter().enumerate() {
+                    inner.line(format!("tao_val {} = env[{}];", local_name(*capture), i));
+                }
+                let output = self.compile_expr(func_body, &mut inner);
+                inner.line(format!("return {};", output));
+
+                self.lambdas[idx] = format!(
+                    "static tao_val lambda_{}(tao_val *env, tao_val arg) {{\n    (void)env;\n{}}}\n",
+                    idx,
+                    inner.code,
+                );
+
+                if captures.is_empty() {
+                    format!("tao_make_func(lambda_{}, 0, NULL)", idx)
+                } else {
+                    format!("tao_make_func(lambda_{}, {}, (tao_val[]){{ {} }})", idx, captures.len(), captures
+                        .iter()
+                        .map(|capture| local_name(*capture))
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                }
+            },
+            mir::Expr::Apply(f, arg) => {
+                let f = self.compile_expr(f, body);
+                let arg = self.compile_expr(arg, body);
+                format!("tao_apply({}, {})", f, arg)
+            },
+            mir::Expr::Tuple(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| self.compile_expr(field, body))
+                    .collect();
+                make_list(fields)
+            },
+            mir::Expr::List(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.compile_expr(item, body))
+                    .collect();
+                make_list(items)
+            },
+            mir::Expr::Access(record, field) => format!("tao_index({}, {})", self.compile_expr(record, body), field),
+            mir::Expr::Variant(variant, inner) => format!("tao_make_sum({}, {})", variant, self.compile_expr(inner, body)),
+            mir::Expr::AccessVariant(inner, _) => format!("tao_inner({})", self.compile_expr(inner, body)),
+            mir::Expr::Debug(inner) => format!("tao_debug({})", self.compile_expr(inner, body)),
+        }
+    }
+
+    pub fn compile_proc(&mut self, mir: &MirContext, proc: ProcId) {
+        let mut body = Body::default();
+        let output = self.compile_expr(&mir.procs.get(proc).unwrap().body, &mut body);
+        body.line(format!("return {};", output));
+
+        self.proc_defs.push(format!(
+            "// Proc {:?}\nstatic tao_val proc_{}(void) {{\n{}}}\n",
+            proc,
+            self.procs[&proc],
+            body.code,
+        ));
+    }
+
+    pub fn from_mir(mir: &MirContext) -> Self {
+        let mut this = Self::default();
+
+        let reachable = mir.reachable_procs();
+
+        // Assign names up-front so that procs can refer to one-another regardless of order
+        for proc_id in reachable.iter() {
+            let idx = this.procs.len();
+            this.procs.insert(*proc_id, idx);
+        }
+
+        for proc_id in reachable {
+            this.compile_proc(mir, proc_id);
+        }
+
+        this.entry = Some(this.procs[&mir.entry.expect("No entry point")]);
+
+        this
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", RUNTIME)?;
+
+        writeln!(writer, "// Declarations\n")?;
+        for idx in 0..self.procs.len() {
+            writeln!(writer, "static tao_val proc_{}(void);", idx)?;
+        }
+        for idx in 0..self.lambdas.len() {
+            writeln!(writer, "static tao_val lambda_{}(tao_val *env, tao_val arg);", idx)?;
+        }
+
+        writeln!(writer, "\n// Lambdas\n")?;
+        for lambda in &self.lambdas {
+            writeln!(writer, "{}", lambda)?;
+        }
+
+        writeln!(writer, "// Procedures\n")?;
+        for proc in &self.proc_defs {
+            writeln!(writer, "{}", proc)?;
+        }
+
+        writeln!(writer, "int main(void) {{")?;
+        writeln!(writer, "    tao_print(stdout, proc_{}());", self.entry.expect("No entry point"))?;
+        writeln!(writer, "    putchar('\\n');")?;
+        writeln!(writer, "    return 0;")?;
+        writeln!(writer, "}}")
+    }
+}
@@ -0,0 +1,228 @@
+// Tao C runtime
+//
+// Values are represented uniformly so that generated code does not need to know about the shape of `Repr`s beyond
+// what the MIR tells it. Memory is never reclaimed: programs are expected to be short-lived.
+
+#include <stdint.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <math.h>
+#include <stdlib.h>
+#include <string.h>
+#include <stdio.h>
+
+enum tao_kind {
+    TAO_INT,
+    TAO_REAL,
+    TAO_CHAR,
+    TAO_BOOL,
+    TAO_LIST,
+    TAO_FUNC,
+    TAO_SUM,
+};
+
+typedef struct tao_val {
+    uint8_t kind;
+    union {
+        int64_t i;
+        double r;
+        uint32_t c;
+        bool b;
+        struct tao_list *list;
+        struct tao_func *func;
+        struct tao_sum *sum;
+    };
+} tao_val;
+
+// Tuples and lists share a representation. Skipping items shares the underlying storage.
+typedef struct tao_list {
+    size_t len;
+    tao_val *items;
+} tao_list;
+
+// A closure: the code pointer and its captured environment
+typedef struct tao_func {
+    tao_val (*code)(tao_val *env, tao_val arg);
+    size_t captures;
+    tao_val env[];
+} tao_func;
+
+typedef struct tao_sum {
+    size_t variant;
+    tao_val inner;
+} tao_sum;
+
+static inline void *tao_alloc(size_t size) {
+    void *ptr = malloc(size ? size : 1);
+    if (!ptr) {
+        fputs("Tao runtime error: out of memory\n", stderr);
+        exit(1);
+    }
+    return ptr;
+}
+
+static inline void tao_panic(const char *msg) {
+    fprintf(stderr, "Tao runtime error: %s\n", msg);
+    exit(1);
+}
+
+static inline tao_val tao_int(int64_t x) { tao_val v; v.kind = TAO_INT; v.i = x; return v; }
+static inline tao_val tao_real(double x) { tao_val v; v.kind = TAO_REAL; v.r = x; return v; }
+static inline tao_val tao_char(uint32_t x) { tao_val v; v.kind = TAO_CHAR; v.c = x; return v; }
+static inline tao_val tao_bool(bool x) { tao_val v; v.kind = TAO_BOOL; v.b = x; return v; }
+
+// Reals are emitted by bit pattern so that infinities, NaNs and rounding survive the trip through C source
+static inline tao_val tao_real_bits(uint64_t bits) { double x; memcpy(&x, &bits, sizeof(x)); return tao_real(x); }
+
+// Integer arithmetic wraps, like the VM, rather than invoking undefined behaviour
+static inline tao_val tao_neg_int(tao_val x) { return tao_int((int64_t)(0 - (uint64_t)x.i)); }
+static inline tao_val tao_add_int(tao_val x, tao_val y) { return tao_int((int64_t)((uint64_t)x.i + (uint64_t)y.i)); }
+static inline tao_val tao_sub_int(tao_val x, tao_val y) { return tao_int((int64_t)((uint64_t)x.i - (uint64_t)y.i)); }
+static inline tao_val tao_mul_int(tao_val x, tao_val y) { return tao_int((int64_t)((uint64_t)x.i * (uint64_t)y.i)); }
+
+// Euclidean division, like the VM, so that the remainder is never negative. The divisor is always positive.
+static inline tao_val tao_div_int(tao_val x, tao_val y) { int64_t q = x.i / y.i; return tao_int(x.i % y.i < 0 ? q - 1 : q); }
+static inline tao_val tao_rem_int(tao_val x, tao_val y) { int64_t r = x.i % y.i; return tao_int(r < 0 ? r + y.i : r); }
+
+static inline tao_val tao_make_list(size_t len, const tao_val *items) {
+    tao_list *list = tao_alloc(sizeof(tao_list));
+    list->len = len;
+    list->items = tao_alloc(sizeof(tao_val) * len);
+    if (len) { memcpy(list->items, items, sizeof(tao_val) * len); }
+    tao_val v; v.kind = TAO_LIST; v.list = list; return v;
+}
+
+static inline size_t tao_len(tao_val x) { return x.list->len; }
+
+static inline tao_val tao_index(tao_val x, size_t i) { return x.list->items[i]; }
+
+static inline tao_val tao_skip(tao_val x, size_t n) {
+    tao_list *list = tao_alloc(sizeof(tao_list));
+    list->len = x.list->len - n;
+    list->items = x.list->items + n;
+    tao_val v; v.kind = TAO_LIST; v.list = list; return v;
+}
+
+static inline tao_val tao_join(tao_val x, tao_val y) {
+    tao_list *list = tao_alloc(sizeof(tao_list));
+    list->len = x.list->len + y.list->len;
+    list->items = tao_alloc(sizeof(tao_val) * list->len);
+    if (x.list->len) { memcpy(list->items, x.list->items, sizeof(tao_val) * x.list->len); }
+    if (y.list->len) { memcpy(list->items + x.list->len, y.list->items, sizeof(tao_val) * y.list->len); }
+    tao_val v; v.kind = TAO_LIST; v.list = list; return v;
+}
+
+static inline tao_val tao_make_sum(size_t variant, tao_val inner) {
+    tao_sum *sum = tao_alloc(sizeof(tao_sum));
+    sum->variant = variant;
+    sum->inner = inner;
+    tao_val v; v.kind = TAO_SUM; v.sum = sum; return v;
+}
+
+static inline size_t tao_variant(tao_val x) { return x.sum->variant; }
+
+static inline tao_val tao_inner(tao_val x) { return x.sum->inner; }
+
+static inline tao_val tao_make_func(tao_val (*code)(tao_val *, tao_val), size_t captures, const tao_val *env) {
+    tao_func *func = tao_alloc(sizeof(tao_func) + sizeof(tao_val) * captures);
+    func->code = code;
+    func->captures = captures;
+    if (captures) { memcpy(func->env, env, sizeof(tao_val) * captures); }
+    tao_val v; v.kind = TAO_FUNC; v.func = func; return v;
+}
+
+static inline tao_val tao_apply(tao_val f, tao_val arg) {
+    return f.func->code(f.func->env, arg);
+}
+
+static inline void tao_print_char(FILE *f, uint32_t c) {
+    if (c < 0x80) {
+        fputc((int)c, f);
+    } else if (c < 0x800) {
+        fputc((int)(0xC0 | (c >> 6)), f);
+        fputc((int)(0x80 | (c & 0x3F)), f);
+    } else if (c < 0x10000) {
+        fputc((int)(0xE0 | (c >> 12)), f);
+        fputc((int)(0x80 | ((c >> 6) & 0x3F)), f);
+        fputc((int)(0x80 | (c & 0x3F)), f);
+    } else {
+        fputc((int)(0xF0 | (c >> 18)), f);
+        fputc((int)(0x80 | ((c >> 12) & 0x3F)), f);
+        fputc((int)(0x80 | ((c >> 6) & 0x3F)), f);
+        fputc((int)(0x80 | (c & 0x3F)), f);
+    }
+}
+
+// Prints the shortest decimal that round-trips, without an exponent, as Rust's `Display` for `f64` does
+static void tao_print_real(FILE *f, double x) {
+    if (isnan(x)) { fputs("NaN", f); return; }
+    if (isinf(x)) { fputs(x < 0 ? "-inf" : "inf", f); return; }
+
+    char buf[32];
+    for (int prec = 0; prec < 17; prec ++) {
+        snprintf(buf, sizeof(buf), "%.*e", prec, x);
+        if (strtod(buf, NULL) == x) { break; }
+    }
+
+    // Split `buf` (of the form `-d.ddde+XX`) into its digits and decimal exponent
+    const char *c = buf;
+    if (*c == '-') { fputc('-', f); c ++; }
+    char digits[20];
+    int len = 0;
+    for (; *c != 'e'; c ++) {
+        if (*c != '.') { digits[len ++] = *c; }
+    }
+    while (len > 1 && digits[len - 1] == '0') { len --; }
+    int point = atoi(c + 1) + 1;
+
+    if (point <= 0) {
+        fputs("0.", f);
+        for (int i = 0; i < -point; i ++) { fputc('0', f); }
+        fwrite(digits, 1, (size_t)len, f);
+    } else if (point >= len) {
+        fwrite(digits, 1, (size_t)len, f);
+        for (int i = len; i < point; i ++) { fputc('0', f); }
+    } else {
+        fwrite(digits, 1, (size_t)point, f);
+        fputc('.', f);
+        fwrite(digits + point, 1, (size_t)(len - point), f);
+    }
+}
+
+// Mirrors the `Display` implementation of `tao_vm::Value` so that outputs can be compared directly
+static void tao_print(FILE *f, tao_val x) {
+    switch (x.kind) {
+        case TAO_INT: fprintf(f, "%lldi", (long long)x.i); break;
+        case TAO_REAL: tao_print_real(f, x.r); fputc('f', f); break;
+        case TAO_CHAR: tao_print_char(f, x.c); break;
+        case TAO_BOOL: fputs(x.b ? "True" : "False", f); break;
+        case TAO_LIST:
+            if (x.list->len > 0 && x.list->items[0].kind == TAO_CHAR) {
+                for (size_t i = 0; i < x.list->len; i ++) { tao_print(f, x.list->items[i]); }
+            } else {
+                fputc('[', f);
+                for (size_t i = 0; i < x.list->len; i ++) {
+                    if (i > 0) { fputs(", ", f); }
+                    tao_print(f, x.list->items[i]);
+                }
+                fputc(']', f);
+            }
+            break;
+        // There is no bytecode address to show, so the code pointer stands in for it
+        case TAO_FUNC:
+            fprintf(f, "Function(addr = 0x%03llX, captures = %zu)", (unsigned long long)(uintptr_t)x.func->code, x.func->captures);
+            break;
+        case TAO_SUM:
+            fprintf(f, "#%zu ", x.sum->variant);
+            tao_print(f, x.sum->inner);
+            break;
+        default: tao_panic("invalid value");
+    }
+}
+
+static inline tao_val tao_debug(tao_val x) {
+    fputs("Debug: ", stderr);
+    tao_print(stderr, x);
+    fputc('\n', stderr);
+    return x;
+}
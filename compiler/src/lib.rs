@@ -1,33 +1,261 @@
 mod error;
 
 pub use tao_syntax::SrcId;
+pub use tao_middle::OptMode;
 
 use tao_syntax::{parse_module, ast, SrcNode, Error as SyntaxError};
 use tao_analysis::Context as HirContext;
-use tao_middle::{Context, OptMode};
+use tao_middle::{Context, Elimination, ProcId, opt};
 use tao_vm::{Program, exec};
 use ariadne::sources;
 use structopt::StructOpt;
 use internment::Intern;
 use std::{
     str::FromStr,
-    io::Write,
+    io::{self, Write},
     collections::HashMap,
+    path::Path,
+    process::Command,
+    fs,
     fmt,
 };
 use error::Error;
 
 #[derive(Clone, Debug, StructOpt)]
 pub struct Options {
-    /// Add a debugging layer to stdout (tokens, ast, hir, mir, bytecode)
+    /// Add a debugging layer to stdout (tokens, ast, hir, mir, reachability, bytecode, c, wat)
     #[structopt(long)]
     pub debug: Vec<String>,
     /// Specify an optimisation mode (none, fast, size)
     #[structopt(short, long, default_value = "none")]
     pub opt: OptMode,
+    /// Specify the optimisation passes to run, in order, instead of those chosen by the optimisation mode (e.g:
+    /// const_fold,remove_unused_bindings)
+    #[structopt(long, use_delimiter = true)]
+    pub passes: Option<Vec<String>>,
+    /// Write the MIR to `<pass>-<n>.mir` in the current directory after the nth run of the given pass
+    #[structopt(long)]
+    pub dump_mir_after: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Target {
+    C,
+    Wat,
+}
+
+impl Target {
+    /// The extension given to build outputs when no output file is specified.
+    pub fn default_extension(&self) -> &'static str {
+        match self {
+            Target::C => "",
+            Target::Wat => "wat",
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, &'static str> {
+        match s {
+            "c" => Ok(Target::C),
+            "wat" => Ok(Target::Wat),
+            _ => Err("Target does not exist"),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::C => write!(f, "c"),
+            Target::Wat => write!(f, "wat"),
+        }
+    }
+}
+
+pub fn run<F: FnMut(SrcId) -> Option<String>>(src: String, src_id: SrcId, options: Options, mut writer: impl Write, get_file: F) {
+    if let Some(ctx) = compile(src, src_id, &options, &mut writer, get_file) {
+        let prog = Program::from_mir(&ctx);
+
+        if options.debug.contains(&"bytecode".to_string()) {
+            prog.write(&mut writer);
+        }
+
+        if let Some(result) = exec(&prog) {
+            writeln!(writer, "{}", result).unwrap();
+        }
+    }
+}
+
+/// Compile a program for the given target at `output`, reporting any problems to `writer`. Returns `true` on success.
+pub fn build<F: FnMut(SrcId) -> Option<String>>(
+    src: String,
+    src_id: SrcId,
+    options: Options,
+    target: Target,
+    output: &Path,
+    mut writer: impl Write,
+    get_file: F,
+) -> bool {
+    let ctx = match compile(src, src_id, &options, &mut writer, get_file) {
+        Some(ctx) => ctx,
+        None => return false,
+    };
+
+    match target {
+        Target::C => {
+            let module = tao_cgen::Module::from_mir(&ctx);
+
+            if options.debug.contains(&"c".to_string()) {
+                module.write(&mut writer).unwrap();
+            }
+
+            let c_path = output.with_extension("c");
+            if let Err(err) = fs::File::create(&c_path).and_then(|file| module.write(file)) {
+                writeln!(writer, "Failed to write {}: {}", c_path.display(), err).unwrap();
+                return false;
+            }
+
+            // Respect the conventional `CC` variable so that cross-compilers can be used
+            let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+            match Command::new(&cc)
+                .arg("-std=c11")
+                .arg("-O2")
+                .arg("-o")
+                .arg(output)
+                .arg(&c_path)
+                .status()
+            {
+                Ok(status) if status.success() => true,
+                Ok(status) => {
+                    writeln!(writer, "C compiler `{}` failed ({}) while compiling {}", cc, status, c_path.display()).unwrap();
+                    false
+                },
+                Err(err) => {
+                    writeln!(writer, "Failed to invoke C compiler `{}`: {}", cc, err).unwrap();
+                    false
+                },
+            }
+        },
+        Target::Wat => {
+            let module = tao_wasm::Module::from_mir(&ctx);
+
+            if options.debug.contains(&"wat".to_string()) {
+                module.write(&mut writer).unwrap();
+            }
+
+            match fs::File::create(output).and_then(|file| module.write(file)) {
+                Ok(()) => true,
+                Err(err) => {
+                    writeln!(writer, "Failed to write {}: {}", output.display(), err).unwrap();
+                    false
+                },
+            }
+        },
+    }
+}
+
+// Functions are displayed differently by each stage (the VM includes their address, and closure conversion changes
+// what they capture), so only the fact that a value is a function is compared
+fn without_functions(display: String) -> String {
+    let mut out = String::new();
+    let mut rest = display.as_str();
+    while let Some(start) = rest.find("Function(") {
+        out += &rest[..start + "Function".len()];
+        rest = &rest[start..];
+        rest = &rest[rest.find(')').map_or(rest.len(), |end| end + 1)..];
+    }
+    out + rest
+}
+
+/// Run a program through the MIR interpreter after lowering and after every optimisation pass, then through the VM,
+/// returning a description of the first stage at which the result diverged from that of the unoptimised MIR.
+///
+/// Functions have no meaningful textual representation, so function values are only compared by the fact that they are
+/// functions, not by what they capture or where their code lives.
+pub fn differential<F: FnMut(SrcId) -> Option<String>>(src: String, src_id: SrcId, options: Options, mut writer: impl Write, get_file: F) -> Result<(), String> {
+    let mut results = Vec::new();
+    let ctx = compile_with(src, src_id, &options, &mut writer, get_file, |stage, ctx| {
+        results.push((stage.to_string(), tao_middle::eval::eval(ctx).map(|val| without_functions(val.to_string()))));
+    })
+        .ok_or_else(|| "Compilation failed".to_string())?;
+
+    let prog = Program::from_mir(&ctx);
+    results.push(("vm".to_string(), exec(&prog).map(|val| without_functions(val.to_string()))));
+
+    let (_, expected) = &results[0];
+    for (stage, result) in &results[1..] {
+        if result != expected {
+            return Err(format!(
+                "Result diverged after {}\n\nexpected {}\n\nfound {}",
+                stage,
+                expected.as_deref().unwrap_or("nothing"),
+                result.as_deref().unwrap_or("nothing"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn write_mir(ctx: &Context, mut writer: impl Write) -> io::Result<()> {
+    for (id, proc) in ctx.procs.iter() {
+        writeln!(writer, "PROCEDURE {:?}\n\n{}\n", id, proc.body.print())?;
+    }
+    Ok(())
+}
+
+fn write_reachability(hir: &HirContext, ctx: &Context, mut writer: impl Write) -> io::Result<()> {
+    let proc_name = |proc: ProcId| match proc {
+        ProcId::Def(def) => hir.defs.get(def.0).name.to_string(),
+        ProcId::Lifted(def, n) => format!("{} (lifted #{})", *hir.defs.get(def.0).name, n),
+        ProcId::Named(name) => name.to_string(),
+    };
+
+    writeln!(
+        writer,
+        "{} procedure(s) remain, {} eliminated",
+        ctx.procs.iter().count(),
+        ctx.eliminated.len(),
+    )?;
+    for (proc, reason) in &ctx.eliminated {
+        match reason {
+            Elimination::Unreferenced => writeln!(
+                writer,
+                "Eliminated `{}`: no references remain after inlining and folding",
+                proc_name(*proc),
+            )?,
+            Elimination::ReferencedBy(referrers) => writeln!(
+                writer,
+                "Eliminated `{}`: only referenced by eliminated procedures {}",
+                proc_name(*proc),
+                referrers
+                    .iter()
+                    .map(|referrer| format!("`{}`", proc_name(*referrer)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Compile a program to optimised MIR, reporting any errors to `writer`.
+pub fn compile<F: FnMut(SrcId) -> Option<String>>(src: String, src_id: SrcId, options: &Options, writer: impl Write, get_file: F) -> Option<Context> {
+    compile_with(src, src_id, options, writer, get_file, |_, _| {})
 }
 
-pub fn run<F: FnMut(SrcId) -> Option<String>>(src: String, src_id: SrcId, options: Options, mut writer: impl Write, mut get_file: F) {
+/// Like [`compile`], but `on_mir` is given the MIR after lowering (as stage `lower`) and after each optimisation pass.
+pub fn compile_with<F: FnMut(SrcId) -> Option<String>>(
+    src: String,
+    src_id: SrcId,
+    options: &Options,
+    mut writer: impl Write,
+    mut get_file: F,
+    mut on_mir: impl FnMut(&str, &Context),
+) -> Option<Context> {
     let (mut ast, mut syntax_errors) = parse_module(&src, src_id);
 
     // TODO: Write a proper module system you lazy git
@@ -74,7 +302,7 @@ pub fn run<F: FnMut(SrcId) -> Option<String>>(src: String, src_id: SrcId, option
         for e in import_errors {
             e.write(&mut srcs, &mut writer);
         }
-        return;
+        return None;
     }
 
     let mut syntax_error = false;
@@ -108,26 +336,54 @@ pub fn run<F: FnMut(SrcId) -> Option<String>>(src: String, src_id: SrcId, option
                     e.write(&ctx, &mut srcs, src_id, &mut writer);
                 }
             } else {
-                let mut ctx = Context::from_concrete(&ctx, &concrete);
-
-                ctx.optimize(options.opt);
+                let hir = &ctx;
+                let mut ctx = Context::from_concrete(hir, &concrete);
 
-                if options.debug.contains(&"mir".to_string()) {
-                    for (id, proc) in ctx.procs.iter() {
-                        writeln!(writer, "PROCEDURE {:?}\n\n{}\n", id, proc.body.print()).unwrap();
+                let pass_names = match &options.passes {
+                    Some(names) => names.iter().map(|name| name.as_str()).collect(),
+                    None => options.opt.default_passes().to_vec(),
+                };
+                let mut passes = Vec::new();
+                for name in pass_names {
+                    match opt::pass_from_name(name, options.opt) {
+                        Some(pass) => passes.push(pass),
+                        None => {
+                            writeln!(writer, "No such optimisation pass `{}` (expected one of {})", name, opt::PASS_NAMES.join(", ")).unwrap();
+                            return None;
+                        },
                     }
                 }
 
-                let prog = Program::from_mir(&ctx);
+                on_mir("lower", &ctx);
+                let mut dump_counts = HashMap::new();
+                let mut after_pass = |pass: &str, ctx: &Context| {
+                    if options.dump_mir_after.iter().any(|name| name == pass) {
+                        let n = dump_counts.entry(pass.to_string()).or_insert(0);
+                        let path = format!("{}-{}.mir", pass, n);
+                        *n += 1;
+                        if let Err(err) = fs::File::create(&path).and_then(|file| write_mir(ctx, file)) {
+                            writeln!(writer, "Failed to write {}: {}", path, err).unwrap();
+                        }
+                    }
+                    on_mir(pass, ctx);
+                };
+                // Closures are converted even when no passes are run so that every backend sees the same representation
+                ctx.convert_closures(&mut after_pass);
+                ctx.run_passes(passes, &mut after_pass);
+                ctx.convert_closures(&mut after_pass);
 
-                if options.debug.contains(&"bytecode".to_string()) {
-                    prog.write(&mut writer);
+                if options.debug.contains(&"mir".to_string()) {
+                    write_mir(&ctx, &mut writer).unwrap();
                 }
 
-                if let Some(result) = exec(&prog) {
-                    writeln!(writer, "{}", result).unwrap();
+                if options.debug.contains(&"reachability".to_string()) {
+                    write_reachability(hir, &ctx, &mut writer).unwrap();
                 }
+
+                return Some(ctx);
             }
         }
     }
+
+    None
 }
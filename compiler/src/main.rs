@@ -1,5 +1,5 @@
-use tao::{Options, SrcId, run};
-use structopt::StructOpt;
+use tao::{Options, Target, SrcId, run, build};
+use structopt::{StructOpt, clap::{Error as ClapError, ErrorKind}};
 use std::{fs, path::PathBuf};
 
 #[derive(Clone, Debug, StructOpt)]
@@ -8,13 +8,51 @@ pub struct Args {
     pub options: Options,
     /// Specify the file to run
     #[structopt(name = "FILE", parse(from_os_str))]
-    pub file: PathBuf,
+    pub file: Option<PathBuf>,
+    #[structopt(subcommand)]
+    pub cmd: Option<Cmd>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum Cmd {
+    /// Compile a program ahead of time
+    Build {
+        #[structopt(flatten)]
+        options: Options,
+        /// Specify the compilation target (c, wat)
+        #[structopt(long, default_value = "c")]
+        target: Target,
+        /// Specify the output file (defaults to the name of the input file)
+        #[structopt(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// Specify the file to build
+        #[structopt(name = "FILE", parse(from_os_str))]
+        file: PathBuf,
+    },
 }
 
 fn main() {
     let args = Args::from_args();
-    let src = fs::read_to_string(&args.file)
-        .expect("Failed to read file");
-    let src_id = SrcId::from_path(args.file);
-    run(src, src_id, args.options, std::io::stdout(), |src| fs::read_to_string(src.to_path()).ok());
+    match args.cmd {
+        None => {
+            // FILE is only optional so that subcommands can be given instead
+            let file = args.file.unwrap_or_else(|| ClapError::with_description(
+                "The following required arguments were not provided:\n    <FILE>\n\nFor more information try --help",
+                ErrorKind::MissingRequiredArgument,
+            ).exit());
+            let src = fs::read_to_string(&file)
+                .expect("Failed to read file");
+            let src_id = SrcId::from_path(file);
+            run(src, src_id, args.options, std::io::stdout(), |src| fs::read_to_string(src.to_path()).ok());
+        },
+        Some(Cmd::Build { options, target, out, file }) => {
+            let src = fs::read_to_string(&file)
+                .expect("Failed to read file");
+            let out = out.unwrap_or_else(|| file.with_extension(target.default_extension()));
+            let src_id = SrcId::from_path(&file);
+            if !build(src, src_id, options, target, &out, std::io::stdout(), |src| fs::read_to_string(src.to_path()).ok()) {
+                std::process::exit(1);
+            }
+        },
+    }
 }
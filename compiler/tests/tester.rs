@@ -10,66 +10,386 @@ macro_rules! test {
 test!(math);
 test!(lists);
 test!(records);
+test!(patterns);
+test!(fusion);
+test!(classes);
 
-use tao::{Options, Opt, SrcId, run};
-use std::fs;
+use tao::{Options, OptMode, Target, SrcId, run, build, compile, differential};
+use tao_middle::{opt::VisitOrder, Repr, Prim};
+use std::{fs, path::Path, process::Command};
+
+// Test cases run without the standard library, but every program needs the lang items
+const PRELUDE: &str = "import \"tests/prelude.tao\"\n";
+
+fn get_file(src: SrcId) -> Option<String> {
+    fs::read_to_string(src.to_path()).ok()
+}
+
+// Returns (input, expected_output) pairs
+fn test_cases(name: &str) -> Vec<(String, String)> {
+    let path = format!("tests/{}.tao", name);
+    let src = fs::read_to_string(&path).unwrap();
+
+    let mut cases = Vec::new();
+
+    let mut input = String::new();
+    let mut expected = String::new();
+
+    #[derive(Debug)]
+    enum State {
+        Start,
+        Input,
+        Output,
+    }
+
+    let mut state = State::Start;
+    for line in src.lines().chain(std::iter::once("# >>>> END")) {
+        match &state {
+            State::Start | State::Output => if line.trim() == "# >>>> INPUT" || line.trim() == "# >>>> END" {
+                if let State::Output = &state {
+                    cases.push((format!("{}{}", PRELUDE, std::mem::take(&mut input)), std::mem::take(&mut expected)));
+                }
+                state = State::Input;
+            } else {
+                expected += line;
+                expected += "\n";
+            },
+            State::Input => if line.trim() == "# >>>> OUTPUT" {
+                state = State::Output;
+            } else {
+                input += line;
+                input += "\n";
+            },
+        }
+    }
+
+    cases
+}
 
 fn test_configs(name: &str) {
-    fn test_config(name: &str, options: Options) {
-        let path = format!("tests/{}.tao", name);
-        let src = fs::read_to_string(&path).unwrap();
-        let src_id = SrcId::from_path(&path);
-
-        let mut input = String::new();
-        let mut expected = String::new();
-
-        #[derive(Debug)]
-        enum State {
-            Start,
-            Input,
-            Output,
+    let path = format!("tests/{}.tao", name);
+    let src_id = SrcId::from_path(&path);
+
+    fn test_config(name: &str, src_id: SrcId, options: Options) {
+        for (input, expected) in test_cases(name) {
+            let mut output = Vec::new();
+            run(input, src_id, options.clone(), &mut output, get_file);
+            let output = String::from_utf8(output).unwrap();
+            if output.trim() != expected.trim() {
+                panic!("\n\n \
+                    ========[ EXPECTED OUTPUT ]========\n\n \
+                    {}\n \
+                    ========[ FOUND OUTPUT ]========\n\n \
+                    {}\n", expected, output);
+            }
         }
+    }
 
-        let mut state = State::Start;
-        for line in src.lines().chain(std::iter::once("# >>>> END")) {
-            match &state {
-                State::Start | State::Output => if line.trim() == "# >>>> INPUT" || line.trim() == "# >>>> END" {
-                    if let State::Output = &state {
-                        let mut output = Vec::new();
-                        run(input.clone(), src_id, options.clone(), &mut output, |_| unimplemented!());
-                        let output = String::from_utf8(output).unwrap();
-                        if output.trim() != expected.trim() {
-                            panic!("\n\n \
-                                ========[ EXPECTED OUTPUT ]========\n\n \
-                                {}\n \
-                                ========[ FOUND OUTPUT ]========\n\n \
-                                {}\n", expected, output);
-                        }
-
-                        input.clear();
-                        expected.clear();
-                    }
-                    state = State::Input;
-                } else {
-                    expected += line;
-                    expected += "\n";
-                },
-                State::Input => if line.trim() == "# >>>> OUTPUT" {
-                    state = State::Output;
-                } else {
-                    input += line;
-                    input += "\n";
-                },
+    // The MIR must mean the same thing before and after every optimisation pass, and the VM must agree with it
+    fn test_differential(name: &str, src_id: SrcId, options: Options) {
+        for (input, _) in test_cases(name) {
+            let mut output = Vec::new();
+            if let Err(err) = differential(input, src_id, options.clone(), &mut output, get_file) {
+                panic!("\n\n{}\n\n{}\n", err, String::from_utf8_lossy(&output));
             }
         }
     }
 
+    // Every backend must produce the expected output too. `run` executes the built artifact and returns its output.
+    fn check_backend(name: &str, src_id: SrcId, options: Options, target: Target, run: impl Fn(&Path) -> String) {
+        let dir = std::env::temp_dir().join(format!("tao-test-{:?}-{}-{}", target, name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for (i, (input, expected)) in test_cases(name).into_iter().enumerate() {
+            let artifact = dir.join(format!("case{}-{}", i, options.opt)).with_extension(target.default_extension());
+            let mut build_output = Vec::new();
+            assert!(
+                build(input, src_id, options.clone(), target, &artifact, &mut build_output, get_file),
+                "{:?} build failed:\n{}",
+                target,
+                String::from_utf8_lossy(&build_output),
+            );
+            let output = run(&artifact);
+
+            if output.trim() != expected.trim() {
+                panic!("\n\n \
+                    ========[ EXPECTED OUTPUT ]========\n\n \
+                    {}\n \
+                    ========[ {:?} OUTPUT ]========\n\n \
+                    {}\n", expected, target, output);
+            }
+        }
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    fn test_backends(name: &str, src_id: SrcId, options: Options) {
+        if Command::new("cc").arg("--version").output().is_ok() {
+            check_backend(name, src_id, options.clone(), Target::C, |exe| {
+                String::from_utf8(Command::new(exe).output().unwrap().stdout).unwrap()
+            });
+        } else {
+            eprintln!("No C compiler found, skipping C backend tests for {}", name);
+        }
+        // The wasm backend runs with an embedded interpreter, so this needs no toolchain
+        check_backend(name, src_id, options, Target::Wat, |wat| wasm::exec(&fs::read_to_string(wat).unwrap()));
+    }
+
     let mut options = Options {
         debug: Vec::new(),
-        opt: Opt::None,
+        opt: OptMode::None,
+        passes: None,
+        dump_mir_after: Vec::new(),
+    };
+    options.opt = OptMode::None;
+    test_config(name, src_id, options.clone());
+    test_differential(name, src_id, options.clone());
+    test_backends(name, src_id, options.clone());
+    options.opt = OptMode::Fast;
+    test_config(name, src_id, options.clone());
+    test_differential(name, src_id, options.clone());
+    test_backends(name, src_id, options.clone());
+}
+
+// Fused chains of list combinators must not build intermediate lists
+#[test]
+fn fusion_bytecode() {
+    let src_id = SrcId::from_path("tests/fusion.tao");
+    let (input, _) = test_cases("fusion").remove(0);
+
+    let bytecode = |opt| {
+        let options = Options {
+            debug: vec!["bytecode".to_string()],
+            opt,
+            passes: None,
+            dump_mir_after: Vec::new(),
+        };
+        let mut output = Vec::new();
+        run(input.clone(), src_id, options, &mut output, get_file);
+        String::from_utf8(output).unwrap()
+    };
+    let unfused = bytecode(OptMode::None);
+    let fused = bytecode(OptMode::Fast);
+
+    assert!(unfused.contains("list.join"));
+    assert!(!fused.contains("list.join"), "Fused program still joins lists:\n\n{}", fused);
+    assert!(
+        fused.matches("list.make").count() < unfused.matches("list.make").count(),
+        "Fused program makes as many lists as the unfused program:\n\n{}",
+        fused,
+    );
+}
+
+// Compile a program that is expected to fail, returning its diagnostics without colours
+fn diagnostics(src: &str) -> String {
+    let options = Options {
+        debug: Vec::new(),
+        opt: OptMode::None,
+        passes: None,
+        dump_mir_after: Vec::new(),
     };
-    options.opt = Opt::None;
-    test_config(name, options.clone());
-    options.opt = Opt::Fast;
-    test_config(name, options.clone());
+    let mut output = Vec::new();
+    run(format!("{}{}", PRELUDE, src), SrcId::from_path("tests/diagnostics.tao"), options, &mut output, get_file);
+    let output = String::from_utf8(output).unwrap();
+    let mut plain = String::new();
+    let mut chars = output.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+// Typed holes suggest only the other definitions whose type, once instantiated, unifies with that of the hole
+#[test]
+fn typed_hole_candidates() {
+    let output = diagnostics("
+        def id A : A -> A = fn x => x
+        def first A, B : (A, B) -> A = fn (a, _) => a
+        def len A : [A] -> Nat =
+            | [] => 0
+            \\ [_ .. xs] => 1 + xs:len
+        $[main]
+        def main : [Nat] -> Nat = ?
+    ");
+    assert!(output.contains("Definitions that fit: len\n"), "{}", output);
+
+    let output = diagnostics("
+        def a : Nat = 1
+        def b : Nat = 2
+        def c : Nat = 3
+        def d : Nat = 4
+        def e : Nat = 5
+        def f : Nat = 6
+        def g : Nat = 7
+        def h : Nat = 8
+        def i : Nat = 9
+        $[main]
+        def main : Nat = ?
+    ");
+    assert!(output.contains("Definitions that fit: a, b, c, d, e, f, g, h, and 1 more"), "{}", output);
+}
+
+// Every use of a generic type must agree about its kind, and each disagreement is reported once
+#[test]
+fn generic_kind_mismatches() {
+    let output = diagnostics("
+        data Wrap F = F Nat
+        data Bad F = (Wrap F, F Nat Nat)
+        $[main]
+        def main = 0
+    ");
+    assert_eq!(
+        output.matches("Type of kind * -> * -> * cannot be used where kind * -> * is expected").count(),
+        1,
+        "{}",
+        output,
+    );
+}
+
+// A member of a class may not overlap with a blanket member of the same class, since neither would take precedence
+#[test]
+fn overlapping_blanket_members() {
+    let output = diagnostics("
+        class Stream =
+            => next : Self -> Self
+        class IntoStream =
+            => into : Self -> Nat
+        data Items A = [A]
+        for A member Items A of Stream =
+            => next = fn xs => xs
+        for S < Stream member S of IntoStream =
+            => into = fn _ => 0
+        for A member Items A of IntoStream =
+            => into = fn _ => 1
+        $[main]
+        def main = 0
+    ");
+    assert!(output.contains("Overlapping members of class IntoStream"), "{}", output);
+}
+
+// Blanket members bounded on each other's classes cover nothing, rather than sending the search for a member in circles
+#[test]
+fn mutually_bounded_blanket_members() {
+    let output = diagnostics("
+        class A =
+            => a : Self -> Nat
+        class B =
+            => b : Self -> Nat
+        for S < A member S of B =
+            => b = fn _ => 0
+        for S < B member S of A =
+            => a = fn _ => 1
+        def f X < B : X -> Nat = fn x => X.a(x)
+        $[main]
+        def main = f(5)
+    ");
+    assert!(output.contains("Type Nat does not fulfil B obligation"), "{}", output);
+}
+
+// Giving a field twice in a record update is an error rather than letting the last one win
+#[test]
+fn duplicate_update_fields() {
+    let output = diagnostics("
+        def origin = { x: 0, y: 0 }
+        $[main]
+        def main = { origin with x: 1, x: 2 }.x
+    ");
+    assert_eq!(output.matches("Field x specified multiple times in record").count(), 1, "{}", output);
+}
+
+// Visiting a program visits the representation of each data type once, so rewrites of it are not applied twice
+#[test]
+fn data_reprs_visited_once() {
+    let options = Options {
+        debug: Vec::new(),
+        opt: OptMode::None,
+        passes: Some(Vec::new()),
+        dump_mir_after: Vec::new(),
+    };
+    let src = "
+        data Pair = (Nat, Nat)
+        def sum : Pair -> Nat =
+            \\ Pair (a, b) => a + b
+        $[main]
+        def main = sum(Pair (1, 2))
+    ";
+    let mut output = Vec::new();
+    let mut ctx = compile(format!("{}{}", PRELUDE, src), SrcId::from_path("tests/reprs.tao"), &options, &mut output, get_file)
+        .unwrap_or_else(|| panic!("{}", String::from_utf8(output).unwrap()));
+
+    // Wrapping is not idempotent, so a data type visited twice would have its fields wrapped twice
+    ctx.visit(
+        VisitOrder::Last,
+        |repr| if *repr == Repr::Prim(Prim::Nat) {
+            *repr = Repr::Tuple(vec![Repr::Prim(Prim::Nat)]);
+        },
+        |_| {},
+        |_| {},
+    );
+    let datas = ctx.reprs
+        .iter_mut()
+        .map(|repr| repr.to_string())
+        .collect::<Vec<_>>();
+    assert!(datas.iter().any(|repr| repr.contains("((Nat,), (Nat,),)")), "{:?}", datas);
+    assert!(datas.iter().all(|repr| !repr.contains("((Nat,),)")), "{:?}", datas);
+}
+
+// A minimal host for modules produced by the wasm backend
+mod wasm {
+    use wasmi::{
+        nan_preserving_float::F64,
+        Externals, FuncInstance, FuncRef, ImportsBuilder, ModuleImportResolver, ModuleInstance, RuntimeArgs,
+        RuntimeValue, Signature, Trap, ValueType,
+    };
+
+    const WRITE_CHAR: usize = 0;
+    const WRITE_INT: usize = 1;
+    const WRITE_REAL: usize = 2;
+
+    struct Resolver;
+
+    impl ModuleImportResolver for Resolver {
+        fn resolve_func(&self, name: &str, _: &Signature) -> Result<FuncRef, wasmi::Error> {
+            let (idx, param) = match name {
+                "write_char" => (WRITE_CHAR, ValueType::I32),
+                "write_int" => (WRITE_INT, ValueType::I64),
+                "write_real" => (WRITE_REAL, ValueType::F64),
+                _ => return Err(wasmi::Error::Instantiation(format!("Unknown import `{}`", name))),
+            };
+            Ok(FuncInstance::alloc_host(Signature::new(vec![param], None), idx))
+        }
+    }
+
+    #[derive(Default)]
+    struct Host {
+        output: String,
+    }
+
+    impl Externals for Host {
+        fn invoke_index(&mut self, idx: usize, args: RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+            match idx {
+                WRITE_CHAR => self.output.push(char::from_u32(args.nth_checked(0)?).unwrap()),
+                WRITE_INT => self.output += &args.nth_checked::<i64>(0)?.to_string(),
+                WRITE_REAL => self.output += &args.nth_checked::<F64>(0)?.to_float().to_string(),
+                _ => unreachable!(),
+            }
+            Ok(None)
+        }
+    }
+
+    pub fn exec(src: &str) -> String {
+        let wasm = wat::parse_str(src).expect("Generated module is not valid wat");
+        let module = wasmi::Module::from_buffer(&wasm).expect("Generated module is not valid wasm");
+        let instance = ModuleInstance::new(&module, &ImportsBuilder::new().with_resolver("env", &Resolver))
+            .expect("Failed to instantiate module")
+            .assert_no_start();
+
+        let mut host = Host::default();
+        instance.invoke_export("main", &[], &mut host).expect("Module trapped");
+        host.output
+    }
 }
@@ -2,10 +2,21 @@ use super::*;
 
 use std::{fmt, str::FromStr};
 
+/// The reason that a procedure was removed from the program during optimisation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Elimination {
+    /// Nothing refers to the procedure any more, usually because every reference to it was inlined or folded away.
+    Unreferenced,
+    /// The procedure is only referred to by the given procedures, which were eliminated alongside it.
+    ReferencedBy(Vec<ProcId>),
+}
+
 pub struct Context {
     pub reprs: Reprs,
     pub procs: Procs,
     pub entry: Option<ProcId>,
+    /// Procedures that have been removed by optimisation, in the order they were removed.
+    pub eliminated: Vec<(ProcId, Elimination)>,
 }
 
 impl Context {
@@ -14,6 +25,7 @@ impl Context {
             reprs: Reprs::default(),
             procs: Procs::default(),
             entry: None,
+            eliminated: Vec::new(),
         };
 
         this.entry = Some(this.lower_def(hir, con, con.entry_def()));
@@ -22,28 +34,84 @@ impl Context {
     }
 
     pub fn optimize(&mut self, opt_mode: OptMode) {
-        if matches!(opt_mode, OptMode::None) {
+        self.optimize_with(opt_mode, |_, _| {});
+    }
+
+    /// Like [`Context::optimize`], but `after_pass` is given the name of each pass and the MIR it produced. This is
+    /// useful for checking that passes preserve the meaning of the program.
+    pub fn optimize_with(&mut self, opt_mode: OptMode, mut after_pass: impl FnMut(&str, &Self)) {
+        let passes = opt_mode
+            .default_passes()
+            .iter()
+            .map(|name| opt::pass_from_name(name, opt_mode).unwrap())
+            .collect();
+        self.convert_closures(&mut after_pass);
+        self.run_passes(passes, &mut after_pass);
+        self.convert_closures(&mut after_pass);
+    }
+
+    /// Lift closures into procedures of their own (see [`opt::ClosureConversion`]). This happens before optimisation,
+    /// so that passes see closures as the backends will, and again afterwards to lift any functions that optimisation
+    /// introduced (such as a procedure inlined as an argument).
+    pub fn convert_closures(&mut self, after_pass: impl FnMut(&str, &Self)) {
+        self.run_passes(vec![Box::new(opt::ClosureConversion::default())], after_pass);
+    }
+
+    /// Run a pipeline of passes in order, repeating it until it no longer changes the MIR or a limit on the number of
+    /// repetitions is reached.
+    pub fn run_passes(&mut self, mut passes: Vec<Box<dyn Pass>>, mut after_pass: impl FnMut(&str, &Self)) {
+        // Passes should converge quickly, but there's no guarantee that a combination of them won't oscillate. Every
+        // pass leaves the MIR valid, so if they have not settled by then we simply stop.
+        const MAX_ITERATIONS: usize = 16;
+
+        if passes.is_empty() {
             return;
         }
 
         opt::prepare(self);
 
-        let debug = false;
+        for _ in 0..MAX_ITERATIONS {
+            let mut changed = false;
+            for pass in passes.iter_mut() {
+                changed |= pass.run(self);
+                after_pass(&pass.name(), self);
+            }
 
-        if debug {
-            println!("\nMIR before optimisation:\n\n");
-            for (id, proc) in self.procs.iter() {
-                println!("PROCEDURE {:?}\n\n{}\n", id, proc.body.print());
+            if !changed {
+                return;
             }
         }
+    }
 
-        for _ in 0..2 {
-            opt::FlattenSingleField::default().run(self, debug);
-            opt::ConstFold {
-                inline: !matches!(opt_mode, OptMode::Size),
-            }.run(self, debug);
-            opt::RemoveUnusedBindings::default().run(self, debug);
-        }
+    /// Render the program as textual MIR (see [`crate::parse`]), beginning with the entry point.
+    pub fn print(&self) -> String {
+        // Procedures are ordered by name rather than by ID so that the output does not depend on interning
+        let mut procs = self.procs
+            .iter()
+            .map(|(id, proc)| {
+                let name = match id {
+                    ProcId::Named(name) => name.to_string(),
+                    id => format!("{:?}", id),
+                };
+                (Some(id) != self.entry, name, proc)
+            })
+            .collect::<Vec<_>>();
+        procs.sort_by(|(a_entry, a_name, _), (b_entry, b_name, _)| (a_entry, a_name).cmp(&(b_entry, b_name)));
+        procs
+            .into_iter()
+            .map(|(_, name, proc)| {
+                let repr = proc.body.meta();
+                format!("proc {} : {} =\n{}\n\n", name, repr, proc.body.print_with_repr(repr, 1))
+            })
+            .collect()
+    }
+
+    /// Replace the body of a procedure, returning whether it differs from the old body.
+    pub fn set_body(&mut self, proc: ProcId, body: MirNode<Expr>) -> bool {
+        let old = &mut self.procs.get_mut(proc).unwrap().body;
+        let changed = *old != body;
+        *old = body;
+        changed
     }
 
     fn reachable_procs_from(&self, proc: ProcId, globals: &mut HashSet<ProcId>) {
@@ -75,6 +143,46 @@ pub enum OptMode {
     Fast,
 }
 
+impl OptMode {
+    /// The passes that this mode runs by default, in order.
+    pub fn default_passes(&self) -> &'static [&'static str] {
+        match self {
+            OptMode::None => &[],
+            // Fusing lists, pushing matches into the arms of other matches and building decision trees all duplicate
+            // code in exchange for speed, so they are left out when optimising for size
+            OptMode::Size => &[
+                "flatten_single_field",
+                "flatten_single_variant",
+                "niche_sums",
+                "tag_unit_sums",
+                "inline",
+                "const_fold",
+                "known_constructors",
+                "common_subexprs",
+                "float_lets",
+                "remove_unused_bindings",
+                "remove_dead_procs",
+            ],
+            OptMode::Fast => &[
+                "flatten_single_field",
+                "flatten_single_variant",
+                "niche_sums",
+                "tag_unit_sums",
+                "inline",
+                "const_fold",
+                "fuse_lists",
+                "case_of_case",
+                "known_constructors",
+                "common_subexprs",
+                "float_lets",
+                "decision_trees",
+                "remove_unused_bindings",
+                "remove_dead_procs",
+            ],
+        }
+    }
+}
+
 impl FromStr for OptMode {
     type Err = &'static str;
 
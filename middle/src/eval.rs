@@ -0,0 +1,281 @@
+//! A tree-walking reference interpreter for MIR.
+//!
+//! This is deliberately naïve: it exists to define what MIR *means* so that backends and optimisation passes can be
+//! checked against it, not to be fast.
+
+use super::*;
+use std::{fmt, rc::Rc};
+
+#[derive(Clone, Debug)]
+pub enum Value<'a> {
+    Int(i64),
+    Real(f64),
+    Char(char),
+    Bool(bool),
+    List(Vec<Self>),
+    Func(Local, &'a MirNode<Expr>, Rc<Vec<(Local, Self)>>),
+    Sum(usize, Box<Self>),
+}
+
+impl<'a> Value<'a> {
+    pub fn int(self) -> i64 { if let Value::Int(x) = self { x } else { panic!("{}", self) } }
+    pub fn real(self) -> f64 { if let Value::Real(x) = self { x } else { panic!("{}", self) } }
+    pub fn char(self) -> char { if let Value::Char(c) = self { c } else { panic!("{}", self) } }
+    pub fn bool(self) -> bool { if let Value::Bool(x) = self { x } else { panic!("{}", self) } }
+    pub fn list(self) -> Vec<Self> { if let Value::List(xs) = self { xs } else { panic!("{}", self) } }
+    pub fn sum(self) -> (usize, Box<Self>) { if let Value::Sum(variant, inner) = self { (variant, inner) } else { panic!("{}", self) } }
+}
+
+// Mirrors the `Display` implementation of `tao_vm::Value` so that outputs can be compared directly. Functions are the
+// exception, since they have no address here.
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(x) => write!(f, "{}i", x),
+            Value::Real(x) => write!(f, "{}f", x),
+            Value::Char(c) => write!(f, "{}", c),
+            Value::Bool(true) => write!(f, "True"),
+            Value::Bool(false) => write!(f, "False"),
+            Value::List(items) => match items.iter().next() {
+                Some(Value::Char(_)) => items
+                    .iter()
+                    .try_for_each(|c| write!(f, "{}", c)),
+                _ => write!(f, "[{}]", items
+                    .iter()
+                    .map(|x| format!("{}", x))
+                    .collect::<Vec<_>>()
+                    .join(", ")),
+            },
+            Value::Func(_, _, captures) => write!(f, "Function(captures = {})", captures.len()),
+            Value::Sum(variant, inner) => write!(f, "#{} {}", variant, inner),
+        }
+    }
+}
+
+fn litr_to_value<'a>(literal: &Literal) -> Value<'a> {
+    match literal {
+        Literal::Unknown(x) => *x,
+        Literal::Nat(x) => Value::Int(*x as i64),
+        Literal::Int(x) => Value::Int(*x),
+        Literal::Real(x) => Value::Real(*x),
+        Literal::Char(c) => Value::Char(*c),
+        Literal::Bool(x) => Value::Bool(*x),
+        Literal::Tuple(fields) => Value::List(fields
+            .iter()
+            .map(litr_to_value)
+            .collect()),
+        Literal::List(items) => Value::List(items
+            .iter()
+            .map(litr_to_value)
+            .collect()),
+        Literal::Sum(variant, inner) => Value::Sum(*variant, Box::new(litr_to_value(inner))),
+        Literal::Union(id, inner) => Value::Sum(*id as usize, Box::new(litr_to_value(inner))),
+    }
+}
+
+fn litr_matches(literal: &Literal, val: &Value) -> bool {
+    match (literal, val) {
+        (Literal::Unknown(x), _) => *x,
+        (Literal::Nat(x), Value::Int(y)) => *x as i64 == *y,
+        (Literal::Int(x), Value::Int(y)) => x == y,
+        (Literal::Real(x), Value::Real(y)) => x == y,
+        (Literal::Char(x), Value::Char(y)) => x == y,
+        (Literal::Bool(x), Value::Bool(y)) => x == y,
+        (Literal::Tuple(xs), Value::List(ys)) => xs
+            .iter()
+            .zip(ys)
+            .all(|(x, y)| litr_matches(x, y)),
+        (Literal::List(xs), Value::List(ys)) => xs.len() == ys.len() && xs
+            .iter()
+            .zip(ys)
+            .all(|(x, y)| litr_matches(x, y)),
+        (Literal::Sum(x, x_inner), Value::Sum(y, y_inner)) => x == y && litr_matches(x_inner, y_inner),
+        (Literal::Union(x, x_inner), Value::Sum(y, y_inner)) => *x as usize == *y && litr_matches(x_inner, y_inner),
+        (literal, val) => panic!("Literal {:?} cannot be compared with value {}", literal, val),
+    }
+}
+
+fn lookup<'a>(locals: &[(Local, Value<'a>)], local: Local) -> Value<'a> {
+    locals
+        .iter()
+        .rev()
+        .find(|(name, _)| *name == local)
+        .unwrap_or_else(|| panic!("Local ${} is not in scope", local.0))
+        .1
+        .clone()
+}
+
+/// The state of an evaluation.
+pub struct Interpreter<'a> {
+    ctx: &'a Context,
+    // Procs are constants, so there's no sense in evaluating them more than once
+    globals: HashMap<ProcId, Value<'a>>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(ctx: &'a Context) -> Self {
+        Self {
+            ctx,
+            globals: HashMap::default(),
+        }
+    }
+
+    // Returns `true` (having pushed any bound locals) if the value matches the binding
+    fn bind(&self, binding: &MirNode<Binding>, val: &Value<'a>, locals: &mut Vec<(Local, Value<'a>)>) -> bool {
+        if let Some(name) = binding.name {
+            locals.push((name, val.clone()));
+        }
+
+        match (&binding.pat, val) {
+            (Pat::Wildcard, _) => true,
+            (Pat::Literal(literal), val) => litr_matches(literal, val),
+            (Pat::Single(inner), val) => self.bind(inner, val, locals),
+            (Pat::Add(lhs, rhs), Value::Int(x)) => (*x >= *rhs as i64 || *lhs.meta() == Repr::Prim(Prim::Int))
+                && self.bind(lhs, &Value::Int(x.wrapping_sub(*rhs as i64)), locals),
+            (Pat::Mul(lhs, rhs), Value::Int(x)) => x.rem_euclid(*rhs as i64) == 0
+                && self.bind(lhs, &Value::Int(x.div_euclid(*rhs as i64)), locals),
+            (Pat::Range(lo, hi), val) => match (lo, hi, val) {
+                (Literal::Nat(lo), Literal::Nat(hi), Value::Int(x)) => (*lo as i64..=*hi as i64).contains(x),
+                (Literal::Int(lo), Literal::Int(hi), Value::Int(x)) => (*lo..=*hi).contains(x),
+                (Literal::Char(lo), Literal::Char(hi), Value::Char(c)) => (*lo..=*hi).contains(c),
+                (lo, hi, val) => panic!("Range {:?}..{:?} cannot be compared with value {}", lo, hi, val),
+            },
+            (Pat::Tuple(fields), Value::List(xs)) => fields
+                .iter()
+                .zip(xs)
+                .all(|(field, x)| self.bind(field, x, locals)),
+            (Pat::ListExact(items), Value::List(xs)) => items.len() == xs.len() && items
+                .iter()
+                .zip(xs)
+                .all(|(item, x)| self.bind(item, x, locals)),
+            (Pat::ListFront(items, tail), Value::List(xs)) => xs.len() >= items.len()
+                && items
+                    .iter()
+                    .zip(xs)
+                    .all(|(item, x)| self.bind(item, x, locals))
+                && tail
+                    .as_ref()
+                    .map_or(true, |tail| self.bind(tail, &Value::List(xs[items.len()..].to_vec()), locals)),
+            (Pat::Variant(variant, inner), Value::Sum(tag, x)) => variant == tag && self.bind(inner, x, locals),
+            (Pat::UnionVariant(id, inner), Value::Sum(tag, x)) => *id as usize == *tag && self.bind(inner, x, locals),
+            // Locals bound by an alternative that failed to match must not be left behind
+            (Pat::Or(alts), val) => alts.iter().any(|alt| {
+                let old_locals = locals.len();
+                self.bind(alt, val, locals) || {
+                    locals.truncate(old_locals);
+                    false
+                }
+            }),
+            (pat, val) => panic!("Pattern {:?} cannot be matched against value {}", pat, val),
+        }
+    }
+
+    pub fn eval_global(&mut self, proc: ProcId) -> Value<'a> {
+        if let Some(val) = self.globals.get(&proc) {
+            val.clone()
+        } else {
+            let ctx = self.ctx;
+            let val = self.eval_expr(&ctx.procs.get(proc).unwrap().body, &mut Vec::new());
+            self.globals.insert(proc, val.clone());
+            val
+        }
+    }
+
+    pub fn eval_expr(&mut self, expr: &'a MirNode<Expr>, locals: &mut Vec<(Local, Value<'a>)>) -> Value<'a> {
+        match &**expr {
+            Expr::Literal(literal) => litr_to_value(literal),
+            Expr::Local(local) => lookup(locals, *local),
+            Expr::Global(proc, _) => self.eval_global(*proc),
+            Expr::Intrinsic(intrinsic, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval_expr(arg, locals))
+                    .collect::<Vec<_>>();
+                let arg = |i: usize| args[i].clone();
+                use Intrinsic::*;
+                // Integer arithmetic wraps, like the VM
+                match intrinsic {
+                    MakeList(_) => Value::List(args.clone()),
+                    NotBool => Value::Bool(!arg(0).bool()),
+                    NegNat | NegInt => Value::Int(arg(0).int().wrapping_neg()),
+                    NegReal => Value::Real(-arg(0).real()),
+                    AddNat | AddInt => Value::Int(arg(0).int().wrapping_add(arg(1).int())),
+                    SubNat | SubInt => Value::Int(arg(0).int().wrapping_sub(arg(1).int())),
+                    MulNat | MulInt => Value::Int(arg(0).int().wrapping_mul(arg(1).int())),
+                    EqNat | EqInt => Value::Bool(arg(0).int() == arg(1).int()),
+                    EqChar => Value::Bool(arg(0).char() == arg(1).char()),
+                    NotEqNat | NotEqInt => Value::Bool(arg(0).int() != arg(1).int()),
+                    NotEqChar => Value::Bool(arg(0).char() != arg(1).char()),
+                    LessNat | LessInt => Value::Bool(arg(0).int() < arg(1).int()),
+                    MoreNat | MoreInt => Value::Bool(arg(0).int() > arg(1).int()),
+                    LessEqNat | LessEqInt => Value::Bool(arg(0).int() <= arg(1).int()),
+                    MoreEqNat | MoreEqInt => Value::Bool(arg(0).int() >= arg(1).int()),
+                    Join(_) => Value::List(arg(0).list().into_iter().chain(arg(1).list()).collect()),
+                    Union(id) => Value::Sum(*id as usize, Box::new(arg(0))),
+                }
+            },
+            Expr::Match(pred, arms) => {
+                let pred = self.eval_expr(pred, locals);
+                for (binding, guard, arm) in arms {
+                    let old_locals = locals.len();
+                    let is_taken = self.bind(binding, &pred, locals)
+                        && guard.as_ref().map_or(true, |guard| self.eval_expr(guard, locals).bool());
+                    if is_taken {
+                        let val = self.eval_expr(arm, locals);
+                        locals.truncate(old_locals);
+                        return val;
+                    } else {
+                        locals.truncate(old_locals);
+                    }
+                }
+                panic!("No arms matched value {}", pred)
+            },
+            Expr::Func(arg, body) => {
+                let mut captures = Vec::new();
+                for capture in body.required_locals(Some(*arg)) {
+                    if !captures.iter().any(|(name, _)| *name == capture) {
+                        captures.push((capture, lookup(locals, capture)));
+                    }
+                }
+                Value::Func(*arg, body, Rc::new(captures))
+            },
+            Expr::Apply(f, arg) => {
+                let f = self.eval_expr(f, locals);
+                let arg = self.eval_expr(arg, locals);
+                self.apply(f, arg)
+            },
+            Expr::Tuple(fields) => Value::List(fields
+                .iter()
+                .map(|field| self.eval_expr(field, locals))
+                .collect()),
+            Expr::List(items) => Value::List(items
+                .iter()
+                .map(|item| self.eval_expr(item, locals))
+                .collect()),
+            Expr::Access(record, field) => self.eval_expr(record, locals).list().swap_remove(*field),
+            Expr::Variant(variant, inner) => Value::Sum(*variant, Box::new(self.eval_expr(inner, locals))),
+            Expr::AccessVariant(inner, variant) => {
+                let (tag, inner) = self.eval_expr(inner, locals).sum();
+                assert_eq!(tag, *variant, "Accessed wrong variant of sum");
+                *inner
+            },
+            Expr::Debug(inner) => self.eval_expr(inner, locals),
+        }
+    }
+
+    pub fn apply(&mut self, f: Value<'a>, arg: Value<'a>) -> Value<'a> {
+        match f {
+            Value::Func(param, body, captures) => {
+                let mut locals = captures.to_vec();
+                locals.push((param, arg));
+                self.eval_expr(body, &mut locals)
+            },
+            f => panic!("Cannot apply non-function {}", f),
+        }
+    }
+}
+
+/// Evaluate the entry point of the program, if it has one.
+pub fn eval(ctx: &Context) -> Option<Value> {
+    Some(Interpreter::new(ctx).eval_global(ctx.entry?))
+}
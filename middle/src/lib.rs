@@ -7,14 +7,17 @@ pub mod opt;
 pub mod repr;
 pub mod lower;
 pub mod context;
+pub mod eval;
+pub mod parse;
 
 pub use crate::{
     error::Error,
     opt::Pass,
-    proc::{ProcId, Proc, Procs},
+    proc::{ProcId, Proc, Procs, InlineHint, Fusion},
     mir::{MirNode, Pat, Binding, Expr, Literal, Partial, Intrinsic, Local},
     repr::{Repr, Reprs, Prim},
-    context::{Context, OptMode},
+    context::{Context, OptMode, Elimination},
+    parse::ParseError,
 };
 pub use tao_analysis::Ident;
 
@@ -23,8 +23,28 @@ impl Context {
         // Instantiate proc if not already done
         if !self.procs.is_declared(id) {
             self.procs.declare(id);
+            let attr = &hir.defs.get(def.0).attr;
+            let inline = if attr.iter().any(|a| &**a.name == "no_inline") {
+                InlineHint::Never
+            } else if attr.iter().any(|a| &**a.name == "inline") {
+                InlineHint::Always
+            } else {
+                InlineHint::Auto
+            };
+            let fusion = attr
+                .iter()
+                .find(|a| &**a.name == "fuse")
+                .and_then(|a| a.args.as_ref())
+                .and_then(|args| args.iter().find_map(|a| match a.name.as_str() {
+                    "map" => Some(Fusion::Map),
+                    "filter" => Some(Fusion::Filter),
+                    "fold" => Some(Fusion::Fold),
+                    _ => None,
+                }));
             let proc = Proc {
                 body: self.lower_expr(hir, con, con.get_def(def), &mut Vec::new()),
+                inline,
+                fusion,
             };
             self.procs.define(id, proc);
         }
@@ -94,6 +114,7 @@ impl Context {
                 fields.sort_by_key(|(name, _)| name.as_ref());
                 Repr::Tuple(fields.into_iter().map(|(_, ty)| ty).collect())
             },
+            ConTy::Hole(_) => unreachable!("Type constructors have no runtime representation"),
         }
     }
 
@@ -102,8 +123,10 @@ impl Context {
             hir::Pat::Error => unreachable!(),
             hir::Pat::Wildcard => mir::Pat::Wildcard,
             hir::Pat::Literal(litr) => mir::Pat::Literal(self.lower_litr(hir, con, litr)),
+            hir::Pat::Range(lo, hi) => mir::Pat::Range(self.lower_litr(hir, con, lo), self.lower_litr(hir, con, hi)),
             hir::Pat::Single(inner) => mir::Pat::Single(self.lower_binding(hir, con, inner, bindings)),
             hir::Pat::Add(lhs, rhs) => mir::Pat::Add(self.lower_binding(hir, con, lhs, bindings), **rhs),
+            hir::Pat::Mul(lhs, rhs) => mir::Pat::Mul(self.lower_binding(hir, con, lhs, bindings), **rhs),
             hir::Pat::Tuple(fields) => mir::Pat::Tuple(fields
                 .iter()
                 .map(|field| self.lower_binding(hir, con, field, bindings))
@@ -131,9 +154,18 @@ impl Context {
                 mir::Pat::Variant(variant, self.lower_binding(hir, con, inner, bindings))
             },
             hir::Pat::Record(fields) => {
-                let mut fields = fields
-                    .iter()
-                    .map(|(name, field)| (*name, self.lower_binding(hir, con, field, bindings)))
+                let tys = if let ConTy::Record(tys) = con.get_ty(*con_binding.meta()) {
+                    tys.clone()
+                } else {
+                    unreachable!();
+                };
+                // Fields not mentioned by a `..` pattern are matched by wildcards
+                let mut fields = tys
+                    .into_iter()
+                    .map(|(name, ty)| (name, match fields.get(&name) {
+                        Some(field) => self.lower_binding(hir, con, field, bindings),
+                        None => MirNode::new(mir::Binding { pat: mir::Pat::Wildcard, name: None }, self.lower_ty(hir, con, ty)),
+                    }))
                     .collect::<Vec<_>>();
                 fields.sort_by_key(|(name, _)| name.as_ref());
                 mir::Pat::Tuple(fields.into_iter().map(|(_, field)| field).collect())
@@ -142,6 +174,25 @@ impl Context {
                 let id = inner.meta().id();
                 mir::Pat::UnionVariant(id, self.lower_binding(hir, con, inner, bindings))
             },
+            hir::Pat::Or(alts) => {
+                // Every alternative binds the same names, so they all reuse the locals of the first alternative
+                let mut names = Vec::new();
+                let first = self.lower_binding(hir, con, &alts[0], &mut names);
+                let alts = std::iter::once(first)
+                    .chain(alts[1..].iter().map(|alt| {
+                        let mut alt_names = Vec::new();
+                        let mut alt = self.lower_binding(hir, con, alt, &mut alt_names);
+                        let mut renames = alt_names
+                            .into_iter()
+                            .map(|(name, local)| (local, names.iter().find(|(n, _)| *n == name).unwrap().1))
+                            .collect::<Vec<_>>();
+                        alt.refresh_locals_inner(&mut renames);
+                        alt
+                    }))
+                    .collect();
+                bindings.extend(names);
+                mir::Pat::Or(alts)
+            },
             pat => todo!("{:?}", pat),
         };
 
@@ -205,12 +256,19 @@ impl Context {
             hir::Expr::Match(_, pred, arms) => {
                 let arms = arms
                     .iter()
-                    .map(|(binding, arm)| {
+                    .enumerate()
+                    .map(|(i, (binding, guard, arm))| {
                         let old_stack = stack.len();
                         let binding = self.lower_binding(hir, con, binding, stack);
+                        // Guarded arms do not count towards exhaustivity, so a guarded last arm is unreachable and its
+                        // guard may be dropped. This ensures that the last arm of a match never has a guard.
+                        let guard = guard
+                            .as_ref()
+                            .filter(|_| i + 1 < arms.len())
+                            .map(|guard| self.lower_expr(hir, con, guard, stack));
                         let arm = self.lower_expr(hir, con, arm, stack);
                         stack.truncate(old_stack);
-                        (binding, arm)
+                        (binding, guard, arm)
                     })
                     .collect();
                 mir::Expr::Match(self.lower_expr(hir, con, pred, stack), arms)
@@ -293,6 +351,39 @@ impl Context {
                 fields.sort_by_key(|(name, _)| name.as_ref());
                 mir::Expr::Tuple(fields.into_iter().map(|(_, field)| field).collect())
             },
+            hir::Expr::Update(record, fields) => {
+                let tys = if let ConTy::Record(tys) = con.get_ty(*record.meta()) {
+                    tys.clone()
+                } else {
+                    unreachable!();
+                };
+                let record_repr = self.lower_ty(hir, con, *record.meta());
+                let record = self.lower_expr(hir, con, record, stack);
+                let mut fields = fields
+                    .iter()
+                    .map(|(name, field)| (**name, self.lower_expr(hir, con, field, stack)))
+                    .collect::<Vec<_>>();
+                let mut tys = tys.into_iter().collect::<Vec<_>>();
+                tys.sort_by_key(|(name, _)| name.as_ref());
+                // The record is bound to a local so that the fields that are not updated may be taken from it
+                let record_local = Local::new();
+                let fields = tys
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, (name, ty))| match fields.iter().position(|(field, _)| *field == name) {
+                        Some(pos) => fields.remove(pos).1,
+                        None => MirNode::new(
+                            mir::Expr::Access(MirNode::new(mir::Expr::Local(record_local), record_repr.clone()), idx),
+                            self.lower_ty(hir, con, ty),
+                        ),
+                    })
+                    .collect();
+                mir::Expr::Match(record, vec![(
+                    MirNode::new(mir::Binding::wildcard(record_local), record_repr.clone()),
+                    None,
+                    MirNode::new(mir::Expr::Tuple(fields), record_repr),
+                )])
+            },
             hir::Expr::ClassAccess(ty, class, field) => panic!("Class access should not still exist during MIR lowering"),
             hir::Expr::Debug(inner) => mir::Expr::Debug(self.lower_expr(hir, con, inner, stack)),
             hir::Expr::Intrinsic(name, args) => {
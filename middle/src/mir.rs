@@ -81,7 +81,7 @@ impl Literal {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Intrinsic {
     MakeList(Repr),
     NotBool,
@@ -112,21 +112,24 @@ pub enum Intrinsic {
     Union(u64), // Type ID
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Pat {
     Wildcard,
     Literal(Literal), // Expression is evaluated and then compared
     Single(MirNode<Binding>),
-    Add(MirNode<Binding>, u64),
+    Add(MirNode<Binding>, u64), // Only refutable for naturals: integers wrap around
+    Mul(MirNode<Binding>, u64), // Matches multiples of the factor, using euclidean division for integers
+    Range(Literal, Literal), // Inclusive of both bounds
     Tuple(Vec<MirNode<Binding>>),
     ListExact(Vec<MirNode<Binding>>),
     ListFront(Vec<MirNode<Binding>>, Option<MirNode<Binding>>),
     Variant(usize, MirNode<Binding>),
     UnionVariant(u64, MirNode<Binding>),
+    Or(Vec<MirNode<Binding>>), // Every alternative binds the same locals
 }
 
 // Uniquely refer to locals *without* shadowing
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Local(pub usize);
 
 impl Local {
@@ -137,7 +140,7 @@ impl Local {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Binding {
     pub pat: Pat,
     pub name: Option<Local>,
@@ -159,7 +162,9 @@ impl Binding {
                 _ => true,
             },
             Pat::Single(inner) => inner.is_refutable(),
-            Pat::Add(lhs, rhs) => *rhs > 0 || lhs.is_refutable(),
+            Pat::Add(lhs, rhs) => (*rhs > 0 && *lhs.meta() == Repr::Prim(Prim::Nat)) || lhs.is_refutable(),
+            Pat::Mul(lhs, rhs) => *rhs != 1 || lhs.is_refutable(),
+            Pat::Range(_, _) => true,
             Pat::Tuple(fields) => fields
                 .iter()
                 .any(|field| field.is_refutable()),
@@ -167,6 +172,9 @@ impl Binding {
             Pat::ListFront(items, tail) => items.len() > 0 || tail.as_ref().map_or(false, |tail| tail.is_refutable()),
             Pat::Variant(_, _) => true, // TODO: Check number of variants
             Pat::UnionVariant(_, _) => true, // TODO: Check number of variants
+            Pat::Or(alts) => alts
+                .iter()
+                .all(|alt| alt.is_refutable()),
         }
     }
 
@@ -175,8 +183,10 @@ impl Binding {
         match &self.pat {
             Pat::Wildcard => {},
             Pat::Literal(_) => {},
+            Pat::Range(_, _) => {},
             Pat::Single(inner) => inner.visit_bindings(bind),
             Pat::Add(lhs, _) => lhs.visit_bindings(bind),
+            Pat::Mul(lhs, _) => lhs.visit_bindings(bind),
             Pat::Tuple(fields) => fields
                 .iter()
                 .for_each(|field| field.visit_bindings(bind)),
@@ -191,6 +201,8 @@ impl Binding {
             },
             Pat::Variant(_, inner) => inner.visit_bindings(bind),
             Pat::UnionVariant(_, inner) => inner.visit_bindings(bind),
+            // Alternatives all bind the same locals, so the first decides their order
+            Pat::Or(alts) => alts[0].visit_bindings(bind),
         }
     }
 
@@ -212,7 +224,7 @@ impl Binding {
         binds
     }
 
-    fn refresh_locals_inner(&mut self, stack: &mut Vec<(Local, Local)>) {
+    pub(crate) fn refresh_locals_inner(&mut self, stack: &mut Vec<(Local, Local)>) {
         if let Some(name) = self.name {
             let new_name = stack.iter().rev().find(|(old, _)| *old == name).expect("No such local").1;
             self.name = Some(new_name);
@@ -221,7 +233,7 @@ impl Binding {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GlobalFlags {
     /// Determines whether a global reference may be inlined. By default this is `true`, but inlining is not permitted
     /// for recursive definitions.
@@ -236,14 +248,16 @@ impl Default for GlobalFlags {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
     Literal(Literal),
     Local(Local),
     Global(ProcId, Cell<GlobalFlags>),
 
     Intrinsic(Intrinsic, Vec<MirNode<Self>>),
-    Match(MirNode<Self>, Vec<(MirNode<Binding>, MirNode<Self>)>),
+    // (pred, [(binding, guard, body)]), where the last arm never has a guard. Exhaustivity has already been checked,
+    // so backends may assume that the last arm matches without testing its binding.
+    Match(MirNode<Self>, Vec<(MirNode<Binding>, Option<MirNode<Self>>, MirNode<Self>)>),
 
     // (captures, arg, body)
     Func(Local, MirNode<Self>),
@@ -260,6 +274,24 @@ pub enum Expr {
 }
 
 impl Expr {
+    /// If the expression applies a procedure to one or more arguments, returns the procedure and the arguments in the
+    /// order that they are applied.
+    pub fn as_call(self: &MirNode<Self>) -> Option<(ProcId, Vec<&MirNode<Self>>)> {
+        let mut args = Vec::new();
+        let mut f = self;
+        while let Expr::Apply(g, arg) = &**f {
+            args.push(arg);
+            f = g;
+        }
+        match &**f {
+            Expr::Global(proc, _) if !args.is_empty() => {
+                args.reverse();
+                Some((*proc, args))
+            },
+            _ => None,
+        }
+    }
+
     pub fn required_globals(&self) -> HashSet<ProcId> {
         let mut globals = HashSet::new();
         self.required_globals_inner(&mut globals);
@@ -280,6 +312,17 @@ impl Expr {
         self.refresh_locals_inner(&mut Vec::new());
     }
 
+    // Like `refresh_locals`, but the expression may capture locals from its surroundings: only the locals that it binds
+    // itself are refreshed. Every copy of a duplicated expression must be refreshed like this.
+    pub fn refresh_bound_locals(&mut self) {
+        let mut stack = self
+            .required_locals(None)
+            .into_iter()
+            .map(|local| (local, local))
+            .collect();
+        self.refresh_locals_inner(&mut stack);
+    }
+
     fn refresh_locals_inner(&mut self, stack: &mut Vec<(Local, Local)>) {
         match self {
             Expr::Local(local) => {
@@ -289,11 +332,14 @@ impl Expr {
             },
             Expr::Match(pred, arms) => {
                 pred.refresh_locals_inner(stack);
-                for (binding, arm) in arms {
+                for (binding, guard, arm) in arms {
                     let old_stack = stack.len();
                     binding.visit_bindings(&mut |name, _| stack.push((name, Local::new())));
 
                     binding.refresh_locals_inner(stack);
+                    if let Some(guard) = guard {
+                        guard.refresh_locals_inner(stack);
+                    }
                     arm.refresh_locals_inner(stack);
                     stack.truncate(old_stack);
                 }
@@ -323,10 +369,13 @@ impl Expr {
                 .for_each(|arg| arg.required_locals_inner(stack, required)),
             Expr::Match(pred, arms) => {
                 pred.required_locals_inner(stack, required);
-                for (arm, body) in arms {
+                for (arm, guard, body) in arms {
                     let old_stack = stack.len();
                     stack.append(&mut arm.binding_names());
 
+                    if let Some(guard) = guard {
+                        guard.required_locals_inner(stack, required);
+                    }
                     body.required_locals_inner(stack, required);
 
                     stack.truncate(old_stack);
@@ -360,89 +409,255 @@ impl Expr {
         required
     }
 
+    /// The repr of the expression, if it can be determined from the expression and the reprs of its children alone.
+    /// The reprs of locals, globals and functions depend on their surroundings, so are never determined.
+    pub fn infer_repr(&self) -> Option<Repr> {
+        use Intrinsic::*;
+        match self {
+            Expr::Literal(c) => c.infer_repr(),
+            Expr::Local(_) | Expr::Global(_, _) | Expr::Func(_, _) => None,
+            Expr::Intrinsic(intrinsic, _) => match intrinsic {
+                MakeList(item) | Join(item) => Some(Repr::List(Box::new(item.clone()))),
+                NotBool
+                | EqNat | EqInt | EqChar
+                | NotEqNat | NotEqInt | NotEqChar
+                | LessNat | LessInt | MoreNat | MoreInt
+                | LessEqNat | LessEqInt | MoreEqNat | MoreEqInt => Some(Repr::Prim(Prim::Bool)),
+                AddNat | MulNat => Some(Repr::Prim(Prim::Nat)),
+                NegNat | NegInt | AddInt | SubNat | SubInt | MulInt => Some(Repr::Prim(Prim::Int)),
+                NegReal => Some(Repr::Prim(Prim::Real)),
+                Union(_) => None,
+            },
+            Expr::Match(_, arms) => arms.first().map(|(_, _, body)| body.meta().clone()),
+            Expr::Apply(f, _) => match f.meta() {
+                Repr::Func(_, o) => Some((**o).clone()),
+                _ => None,
+            },
+            Expr::Tuple(fields) => Some(Repr::Tuple(fields.iter().map(|field| field.meta().clone()).collect())),
+            Expr::Access(tuple, field) => match tuple.meta() {
+                Repr::Tuple(fields) => fields.get(*field).cloned(),
+                _ => None,
+            },
+            Expr::List(items) => items.first().map(|item| Repr::List(Box::new(item.meta().clone()))),
+            Expr::Variant(_, _) => None,
+            Expr::AccessVariant(inner, variant) => match inner.meta() {
+                Repr::Sum(variants) => variants.get(*variant).cloned(),
+                _ => None,
+            },
+            Expr::Debug(inner) => Some(inner.meta().clone()),
+        }
+    }
+
+    /// Render the expression as textual MIR (see [`crate::parse`]). The repr of the expression itself is not known, so
+    /// parameters of a function at the top of the expression are not annotated.
     pub fn print(&self) -> impl fmt::Display + '_ {
-        struct DisplayBinding<'a>(&'a Binding, usize);
-
-        impl<'a> fmt::Display for DisplayBinding<'a> {
-            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                if let Some(name) = self.0.name {
-                    write!(f, "${}", name.0)?;
-                    if let Pat::Wildcard = &self.0.pat {
-                        return Ok(());
-                    } else {
-                        write!(f, " ~ ")?;
-                    }
-                }
-                match &self.0.pat {
-                    Pat::Wildcard => write!(f, "_"),
-                    Pat::Literal(c) => write!(f, "const {:?}", c),
-                    Pat::Single(inner) => write!(f, "{}", DisplayBinding(inner, self.1)),
-                    Pat::Variant(variant, inner) => write!(f, "#{} {}", variant, DisplayBinding(inner, self.1)),
-                    Pat::UnionVariant(id, inner) => write!(f, "#{} {}", id, DisplayBinding(inner, self.1)),
-                    Pat::ListExact(items) => write!(f, "[{}]", items.iter().map(|i| format!("{},", DisplayBinding(i, self.1 + 1))).collect::<Vec<_>>().join(" ")),
-                    Pat::ListFront(items, tail) => write!(
-                        f,
-                        "[{} .. {}]",
-                        items.iter().map(|i| format!("{},", DisplayBinding(i, self.1 + 1))).collect::<Vec<_>>().join(" "),
-                        tail.as_ref().map(|tail| format!("{}", DisplayBinding(tail, self.1))).unwrap_or_default(),
-                    ),
-                    Pat::Tuple(fields) => write!(f, "({})", fields.iter().map(|f| format!("{},", DisplayBinding(f, self.1 + 1))).collect::<Vec<_>>().join(" ")),
-                    // _ => write!(f, "<PAT>"),
-                    pat => todo!("{:?}", pat),
-                }
+        DisplayExpr(self, None, 0, true, Nesting::Top)
+    }
+
+    /// Like [`Expr::print`], but with the repr of the expression known.
+    pub fn print_with_repr<'a>(&'a self, repr: &'a Repr, indent: usize) -> impl fmt::Display + 'a {
+        DisplayExpr(self, Some(repr), indent, true, Nesting::Top)
+    }
+}
+
+impl Literal {
+    /// The repr of the literal, if it can be determined from the literal alone.
+    pub fn infer_repr(&self) -> Option<Repr> {
+        match self {
+            Self::Unknown(x) => *x,
+            Self::Nat(_) => Some(Repr::Prim(Prim::Nat)),
+            Self::Int(_) => Some(Repr::Prim(Prim::Int)),
+            Self::Real(_) => Some(Repr::Prim(Prim::Real)),
+            Self::Char(_) => Some(Repr::Prim(Prim::Char)),
+            Self::Bool(_) => Some(Repr::Prim(Prim::Bool)),
+            Self::Tuple(fields) => Some(Repr::Tuple(fields
+                .iter()
+                .map(|field| field.infer_repr())
+                .collect::<Option<_>>()?)),
+            Self::List(items) => Some(Repr::List(Box::new(items.first()?.infer_repr()?))),
+            Self::Sum(_, _) | Self::Union(_, _) => None,
+        }
+    }
+}
+
+struct DisplayLiteral<'a>(&'a Literal);
+
+impl<'a> fmt::Display for DisplayLiteral<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Const::Unknown(x) => *x,
+            Const::Nat(x) => write!(f, "{}", x),
+            Const::Int(x) => write!(f, "{}i", x),
+            Const::Real(x) => write!(f, "{:?}", x),
+            Const::Char(c) => write!(f, "{:?}", c),
+            Const::Bool(x) => write!(f, "{}", x),
+            Const::Tuple(fields) => write!(f, "({})", fields.iter().map(|field| format!("{},", DisplayLiteral(field))).collect::<Vec<_>>().join(" ")),
+            // Strings are common enough to deserve their own syntax
+            Const::List(items) if !items.is_empty() && items.iter().all(|item| matches!(item, Const::Char(_))) => {
+                write!(f, "{:?}", items.iter().map(|item| if let Const::Char(c) = item { *c } else { unreachable!() }).collect::<String>())
+            },
+            Const::List(items) => write!(f, "[{}]", items.iter().map(|item| format!("{}", DisplayLiteral(item))).collect::<Vec<_>>().join(", ")),
+            Const::Sum(variant, inner) => write!(f, "#{} {}", variant, DisplayLiteral(inner)),
+            Const::Union(id, inner) => write!(f, "union#{} {}", id, DisplayLiteral(inner)),
+        }
+    }
+}
+
+struct DisplayBinding<'a>(&'a Binding, usize);
+
+impl<'a> fmt::Display for DisplayBinding<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = self.0.name {
+            write!(f, "${}", name.0)?;
+            if let Pat::Wildcard = &self.0.pat {
+                return Ok(());
+            } else {
+                write!(f, " ~ ")?;
             }
         }
+        match &self.0.pat {
+            Pat::Wildcard => write!(f, "_"),
+            Pat::Literal(c) => write!(f, "const {}", DisplayLiteral(c)),
+            Pat::Single(inner) => write!(f, "({})", DisplayBinding(inner, self.1)),
+            Pat::Add(lhs, rhs) => write!(f, "({} + {})", DisplayBinding(lhs, self.1), rhs),
+            Pat::Mul(lhs, rhs) => write!(f, "({} * {})", DisplayBinding(lhs, self.1), rhs),
+            Pat::Range(lo, hi) => write!(f, "range {} .. {}", DisplayLiteral(lo), DisplayLiteral(hi)),
+            Pat::Variant(variant, inner) => write!(f, "#{} {}", variant, DisplayBinding(inner, self.1)),
+            // The variants of a union are not ordered, so the inner repr can't be derived from that of the union
+            Pat::UnionVariant(id, inner) => write!(f, "union#{} ({} : {})", id, DisplayBinding(inner, self.1), inner.meta()),
+            Pat::ListExact(items) => write!(f, "[{}]", items.iter().map(|i| format!("{},", DisplayBinding(i, self.1 + 1))).collect::<Vec<_>>().join(" ")),
+            Pat::ListFront(items, tail) => write!(
+                f,
+                "[{} .. {}]",
+                items.iter().map(|i| format!("{},", DisplayBinding(i, self.1 + 1))).collect::<Vec<_>>().join(" "),
+                tail.as_ref().map(|tail| format!("{}", DisplayBinding(tail, self.1))).unwrap_or_default(),
+            ),
+            Pat::Tuple(fields) => write!(f, "({})", fields.iter().map(|f| format!("{},", DisplayBinding(f, self.1 + 1))).collect::<Vec<_>>().join(" ")),
+            Pat::Or(alts) => write!(f, "or ({})", alts.iter().map(|alt| format!("{}", DisplayBinding(alt, self.1))).collect::<Vec<_>>().join(" | ")),
+        }
+    }
+}
 
-        struct DisplayExpr<'a>(&'a Expr, usize, bool);
+// Where an expression is printed, which decides whether it needs parentheses (or a repr annotation) to be read back
+#[derive(Copy, Clone, PartialEq)]
+enum Nesting {
+    // The whole of a procedure body, the repr of which is known by the reader
+    Top,
+    // Anywhere that the end of the expression is obvious, such as before `in`, `,` or `)`
+    Full,
+    // The body of a match arm, where a trailing match would swallow the arms that follow
+    Arm,
+    // The operand of a prefix or infix operator
+    Operand,
+}
+
+// Whether the expression ends with the arms of a match
+fn ends_with_arms(expr: &Expr) -> bool {
+    match expr {
+        Expr::Match(_, arms) if arms.len() == 1 => ends_with_arms(&arms[0].2),
+        Expr::Match(_, arms) => arms.len() > 1,
+        Expr::Func(_, body) => ends_with_arms(body),
+        _ => false,
+    }
+}
 
-        impl<'a> fmt::Display for DisplayExpr<'a> {
-            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                use Intrinsic::*;
-                if self.2 {
-                    write!(f, "{}", "    ".repeat(self.1))?;
+struct DisplayExpr<'a>(&'a Expr, Option<&'a Repr>, usize, bool, Nesting);
+
+impl<'a> DisplayExpr<'a> {
+    fn child(expr: &'a MirNode<Expr>, indent: usize, nesting: Nesting) -> Self {
+        DisplayExpr(expr, Some(expr.meta()), indent, false, nesting)
+    }
+
+    fn fmt_inner(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Intrinsic::*;
+        let child = |expr, nesting| Self::child(expr, self.2, nesting);
+        let binary = |f: &mut fmt::Formatter, op: &str, args: &'a Vec<MirNode<Expr>>| {
+            write!(f, "({} {} {})", child(&args[0], Nesting::Operand), op, child(&args[1], Nesting::Operand))
+        };
+        match self.0 {
+            Expr::Local(local) => write!(f, "${}", local.0),
+            Expr::Global(ProcId::Named(name), _) => write!(f, "global {}", name),
+            Expr::Global(global, _) => write!(f, "global {:?}", global),
+            Expr::Literal(c) => write!(f, "const {}", DisplayLiteral(c)),
+            Expr::Func(arg, body) => {
+                write!(f, "fn ${}", arg.0)?;
+                if let Some(Repr::Func(i, _)) = self.1 {
+                    write!(f, ": {}", i)?;
                 }
-                match self.0 {
-                    Expr::Local(local) => write!(f, "${}", local.0),
-                    Expr::Global(global, _) => write!(f, "global {:?}", global),
-                    Expr::Literal(c) => write!(f, "const {:?}", c),
-                    Expr::Func(arg, body) => write!(f, "fn ${} =>\n{}", arg.0, DisplayExpr(body, self.1 + 1, true)),
-                    Expr::Apply(func, arg) => write!(f, "({})({})", DisplayExpr(func, self.1, false), DisplayExpr(arg, self.1, false)),
-                    Expr::Variant(variant, inner) => write!(f, "#{} {}", variant, DisplayExpr(inner, self.1, false)),
-                    Expr::Tuple(fields) => write!(f, "({})", fields.iter().map(|f| format!("{},", DisplayExpr(f, self.1 + 1, false))).collect::<Vec<_>>().join(" ")),
-                    Expr::List(items) => write!(f, "[{}]", items.iter().map(|i| format!("{}", DisplayExpr(i, self.1 + 1, false))).collect::<Vec<_>>().join(", ")),
-                    Expr::Intrinsic(NotBool, args) => write!(f, "!{}", DisplayExpr(&args[0], self.1, false)),
-                    Expr::Intrinsic(NegNat | NegInt | NegReal, args) => write!(f, "-{}", DisplayExpr(&args[0], self.1, false)),
-                    Expr::Intrinsic(EqChar | EqNat | EqInt, args) => write!(f, "{} = {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(AddNat | AddInt, args) => write!(f, "{} + {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(SubNat | SubInt, args) => write!(f, "{} - {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(MulNat | MulInt, args) => write!(f, "{} * {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(LessNat, args) => write!(f, "{} < {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(MoreNat, args) => write!(f, "{} > {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(MoreEqNat, args) => write!(f, "{} >= {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(LessEqNat, args) => write!(f, "{} <= {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(Join(_), args) => write!(f, "{} ++ {}", DisplayExpr(&args[0], self.1, false), DisplayExpr(&args[1], self.1, false)),
-                    Expr::Intrinsic(Union(_), args) => write!(f, "?{}", DisplayExpr(&args[0], self.1, false)),
-                    Expr::Match(pred, arms) if arms.len() == 1 => {
-                        let (arm, body) = &arms[0];
-                        write!(f, "let {} = {} in\n{}", DisplayBinding(arm, self.1 + 1), DisplayExpr(pred, self.1, false), DisplayExpr(body, self.1 + 1, true))
-                    },
-                    Expr::Match(pred, arms) => {
-                        write!(f, "match {} in", DisplayExpr(pred, self.1, false))?;
-                        for (arm, body) in arms {
-                            write!(f, "\n{}| {} => {}", "    ".repeat(self.1 + 1), DisplayBinding(arm, self.1 + 1), DisplayExpr(body, self.1 + 1, false))?;
-                        }
-                        if arms.len() == 0 {
-                            write!(f, " (no arms)")?;
-                        }
-                        Ok(())
-                    },
-                    Expr::Debug(inner) => write!(f, "?{}", DisplayExpr(inner, self.1, false)),
-                    // _ => write!(f, "<TODO>"),
-                    expr => todo!("{:?}", expr),
+                write!(f, " =>\n{}", DisplayExpr(body, Some(body.meta()), self.2 + 1, true, Nesting::Full))
+            },
+            Expr::Apply(func, arg) => write!(f, "({})({})", child(func, Nesting::Full), child(arg, Nesting::Full)),
+            Expr::Variant(variant, inner) => write!(f, "#{} {}", variant, child(inner, Nesting::Operand)),
+            Expr::AccessVariant(inner, variant) => write!(f, "({}).#{}", child(inner, Nesting::Full), variant),
+            Expr::Tuple(fields) => write!(f, "({})", fields.iter().map(|f| format!("{},", Self::child(f, self.2 + 1, Nesting::Full))).collect::<Vec<_>>().join(" ")),
+            Expr::Access(tuple, field) => write!(f, "({}).{}", child(tuple, Nesting::Full), field),
+            Expr::List(items) => write!(f, "[{}]", items.iter().map(|i| format!("{}", Self::child(i, self.2 + 1, Nesting::Full))).collect::<Vec<_>>().join(", ")),
+            Expr::Intrinsic(MakeList(item), args) => write!(f, "make_list<{}>({})", item, args.iter().map(|a| format!("{}", child(a, Nesting::Full))).collect::<Vec<_>>().join(", ")),
+            Expr::Intrinsic(NotBool, args) => write!(f, "!{}", child(&args[0], Nesting::Operand)),
+            Expr::Intrinsic(NegNat | NegInt | NegReal, args) => write!(f, "-{}", child(&args[0], Nesting::Operand)),
+            Expr::Intrinsic(EqChar | EqNat | EqInt, args) => binary(f, "=", args),
+            Expr::Intrinsic(NotEqChar | NotEqNat | NotEqInt, args) => binary(f, "!=", args),
+            Expr::Intrinsic(AddNat | AddInt, args) => binary(f, "+", args),
+            Expr::Intrinsic(SubNat | SubInt, args) => binary(f, "-", args),
+            Expr::Intrinsic(MulNat | MulInt, args) => binary(f, "*", args),
+            Expr::Intrinsic(LessNat | LessInt, args) => binary(f, "<", args),
+            Expr::Intrinsic(MoreNat | MoreInt, args) => binary(f, ">", args),
+            Expr::Intrinsic(MoreEqNat | MoreEqInt, args) => binary(f, ">=", args),
+            Expr::Intrinsic(LessEqNat | LessEqInt, args) => binary(f, "<=", args),
+            Expr::Intrinsic(Join(_), args) => binary(f, "++", args),
+            Expr::Intrinsic(Union(id), args) => write!(f, "union#{} {}", id, child(&args[0], Nesting::Operand)),
+            Expr::Match(pred, arms) if arms.len() == 1 => {
+                let (arm, _, body) = &arms[0];
+                write!(
+                    f,
+                    "let {} = {} in\n{}",
+                    DisplayBinding(arm, self.2 + 1),
+                    child(pred, Nesting::Full),
+                    DisplayExpr(body, Some(body.meta()), self.2 + 1, true, Nesting::Full),
+                )
+            },
+            Expr::Match(pred, arms) => {
+                write!(f, "match {} in", child(pred, Nesting::Full))?;
+                for (arm, guard, body) in arms {
+                    write!(f, "\n{}| {}", "    ".repeat(self.2 + 1), DisplayBinding(arm, self.2 + 1))?;
+                    if let Some(guard) = guard {
+                        write!(f, " if {}", Self::child(guard, self.2 + 1, Nesting::Operand))?;
+                    }
+                    write!(f, " => {}", Self::child(body, self.2 + 1, Nesting::Arm))?;
                 }
-            }
+                if arms.len() == 0 {
+                    write!(f, " (no arms)")?;
+                }
+                Ok(())
+            },
+            Expr::Debug(inner) => write!(f, "?{}", child(inner, Nesting::Operand)),
         }
+    }
+}
 
-        DisplayExpr(self, 0, true)
+impl<'a> fmt::Display for DisplayExpr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.3 {
+            write!(f, "{}", "    ".repeat(self.2))?;
+        }
+        let annotation = self.1.filter(|repr| self.4 != Nesting::Top
+            && !matches!(self.0, Expr::Local(_) | Expr::Global(_, _) | Expr::Func(_, _))
+            && self.0.infer_repr().as_ref() != Some(*repr));
+        let wrap = annotation.is_some() || match self.4 {
+            Nesting::Top | Nesting::Full => false,
+            Nesting::Arm => ends_with_arms(self.0),
+            Nesting::Operand => matches!(self.0, Expr::Match(_, _) | Expr::Func(_, _)),
+        };
+        if wrap {
+            write!(f, "(")?;
+        }
+        self.fmt_inner(f)?;
+        if let Some(repr) = annotation {
+            write!(f, " : {}", repr)?;
+        }
+        if wrap {
+            write!(f, ")")?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,82 @@
+use super::*;
+
+// Whether the value of an expression is known to be constructed directly, such that a match on it can be reduced
+fn is_constructor(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) | Expr::Variant(_, _) | Expr::Tuple(_) => true,
+        // Look through `let`s
+        Expr::Match(_, arms) if arms.len() == 1 => is_constructor(&arms[0].2),
+        _ => false,
+    }
+}
+
+/// Move matches on the result of another match into the arms of that match (i.e: `match (match x in | A => B | C =>
+/// D) in ...` becomes `match x in | A => (match B in ...) | C => (match D in ...)`).
+///
+/// This duplicates the arms of the outer match, but when the arms of the inner match produce known constructors (as
+/// is common when combinators return a `Maybe` or `Result` that is immediately matched upon) the duplicated matches
+/// may be reduced by [`KnownConstructors`], removing the intermediate value entirely.
+#[derive(Default)]
+pub struct CaseOfCase;
+
+impl CaseOfCase {
+    // The maximum amount of code that may be produced by duplicating the outer match when the inner match produces
+    // known constructors, and when it does not.
+    const MAX_DUPLICATED_SIZE: usize = 64;
+    const MAX_SPECULATIVE_SIZE: usize = 16;
+
+    fn transform(&self, expr: &mut MirNode<Expr>) {
+        let (pred, outer_arms) = if let Expr::Match(pred, arms) = &**expr { (pred, arms) } else { return };
+        let (inner_pred, inner_arms) = if let Expr::Match(pred, arms) = &**pred { (pred, arms) } else { return };
+
+        let outer_size = outer_arms
+            .iter()
+            .map(|(_, guard, body)| guard.as_ref().map_or(0, |guard| size(guard)) + size(body))
+            .sum::<usize>();
+        let copies = inner_arms.len().saturating_sub(1);
+        let max_size = if inner_arms.iter().all(|(_, _, body)| is_constructor(body)) {
+            Self::MAX_DUPLICATED_SIZE
+        } else {
+            Self::MAX_SPECULATIVE_SIZE
+        };
+        if copies * outer_size > max_size {
+            return;
+        }
+
+        let arms = inner_arms
+            .iter()
+            .map(|(binding, guard, body)| {
+                let mut body = MirNode::new(Expr::Match(body.clone(), outer_arms.clone()), expr.meta().clone());
+                // Each copy of the outer arms must bind its own locals
+                body.refresh_bound_locals();
+                (binding.clone(), guard.clone(), body)
+            })
+            .collect();
+        let transformed = Expr::Match(inner_pred.clone(), arms);
+        **expr = transformed;
+
+        // The new matches may themselves be matches on the result of a match
+        if let Expr::Match(_, arms) = &mut **expr {
+            arms
+                .iter_mut()
+                .for_each(|(_, _, body)| self.transform(body));
+        }
+    }
+
+    fn visit(&self, expr: &mut MirNode<Expr>) {
+        expr.for_children_mut(|expr| self.visit(expr));
+        self.transform(expr);
+    }
+}
+
+impl Pass for CaseOfCase {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let mut changed = false;
+        for proc in ctx.reachable_procs() {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit(&mut body);
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
@@ -0,0 +1,207 @@
+use super::*;
+
+// The repr of the parameter of a function with the given repr
+fn param_repr(ctx: &Context, repr: &Repr) -> Repr {
+    match repr {
+        Repr::Func(i, _) => (**i).clone(),
+        Repr::Data(data) => param_repr(ctx, ctx.reprs.get(*data)),
+        repr => panic!("Function has non-function repr {:?}", repr),
+    }
+}
+
+// Whether every use of the local is as a function that is immediately applied to an argument
+fn only_called(expr: &Expr, local: Local) -> bool {
+    match expr {
+        Expr::Local(l) => *l != local,
+        Expr::Apply(f, arg) if matches!(&**f, Expr::Local(l) if *l == local) => only_called(arg, local),
+        _ => {
+            let mut only_called_children = true;
+            expr.for_children(|expr| only_called_children &= only_called(expr, local));
+            only_called_children
+        },
+    }
+}
+
+fn replace_local(expr: &mut MirNode<Expr>, local: Local, with: &MirNode<Expr>) {
+    if matches!(&**expr, Expr::Local(l) if *l == local) {
+        *expr = with.clone();
+    } else {
+        expr.for_children_mut(|expr| replace_local(expr, local, with));
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Lift {
+    // Captures become extra parameters of the procedure
+    Params,
+    // Captures are passed to the procedure in a tuple
+    Env,
+}
+
+/// Make closures explicit by lifting every function that is not a parameter of a procedure out into a procedure of
+/// its own.
+///
+/// Functions bound by a `let` that are only ever called directly are lambda-lifted: the locals that they capture
+/// become extra parameters of the new procedure, which is given them at each call site. Other functions are
+/// closure-converted: the new procedure takes a tuple of the captured locals (its environment) as its first parameter,
+/// and the function is replaced with an application of the procedure to that tuple.
+///
+/// Afterwards, the only functions that capture locals are the parameters of procedures and every closure is a
+/// procedure applied to its environment, so backends need not analyse captures themselves. Lifted procedures may be
+/// inlined like any other, so running the pass again after optimisation lifts whatever functions inlining restored.
+#[derive(Default)]
+pub struct ClosureConversion;
+
+impl ClosureConversion {
+    // Lift a function into a new procedure, returning an expression that refers to the function from where it was
+    fn lift(
+        &self,
+        ctx: &mut Context,
+        proc: ProcId,
+        func: &MirNode<Expr>,
+        scope: &[(Local, Repr)],
+        lift: Lift,
+    ) -> MirNode<Expr> {
+        let mut captures = Vec::new();
+        for local in func.required_locals(None) {
+            if !captures.iter().any(|(l, _)| *l == local) {
+                let repr = scope
+                    .iter()
+                    .rev()
+                    .find(|(l, _)| *l == local)
+                    .unwrap_or_else(|| panic!("Captured local ${} is not in scope", local.0))
+                    .1
+                    .clone();
+                captures.push((local, repr));
+            }
+        }
+
+        let (body, callee_repr) = if captures.is_empty() {
+            (func.clone(), func.meta().clone())
+        } else {
+            match lift {
+                // fn c0 => fn c1 => ... => func
+                Lift::Params => captures
+                    .iter()
+                    .rev()
+                    .fold((func.clone(), func.meta().clone()), |(body, repr), (capture, capture_repr)| {
+                        let repr = Repr::Func(Box::new(capture_repr.clone()), Box::new(repr));
+                        (MirNode::new(Expr::Func(*capture, body), repr.clone()), repr)
+                    }),
+                // fn env => fn arg => let (c0, c1, ...) = env in body
+                Lift::Env => {
+                    let (arg, func_body) = if let Expr::Func(arg, body) = &**func { (*arg, body) } else { unreachable!() };
+                    let env = Local::new();
+                    let env_repr = Repr::Tuple(captures
+                        .iter()
+                        .map(|(_, repr)| repr.clone())
+                        .collect());
+                    let unpack = MirNode::new(Binding {
+                        pat: Pat::Tuple(captures
+                            .iter()
+                            .map(|(capture, repr)| MirNode::new(Binding::wildcard(*capture), repr.clone()))
+                            .collect()),
+                        name: None,
+                    }, env_repr.clone());
+                    let inner = MirNode::new(Expr::Func(arg, MirNode::new(Expr::Match(
+                        MirNode::new(Expr::Local(env), env_repr.clone()),
+                        vec![(unpack, None, func_body.clone())],
+                    ), func_body.meta().clone())), func.meta().clone());
+                    let repr = Repr::Func(Box::new(env_repr), Box::new(func.meta().clone()));
+                    (MirNode::new(Expr::Func(env, inner), repr.clone()), repr)
+                },
+            }
+        };
+
+        let mut body = body;
+        self.visit_params(ctx, proc, &mut body, &mut Vec::new());
+        let lifted = ctx.procs.lift(proc, Proc {
+            body,
+            // Left to the cost model, so that a function applied directly may still be inlined and beta-reduced
+            inline: InlineHint::Auto,
+            fusion: None,
+        });
+
+        let callee = MirNode::new(Expr::Global(lifted, Default::default()), callee_repr);
+        match lift {
+            _ if captures.is_empty() => callee,
+            Lift::Params => captures
+                .into_iter()
+                .fold(callee, |f, (capture, repr)| {
+                    let f_repr = f.meta().clone();
+                    let arg = MirNode::new(Expr::Local(capture), repr);
+                    let out_repr = if let Repr::Func(_, o) = f_repr { *o } else { unreachable!() };
+                    MirNode::new(Expr::Apply(f, arg), out_repr)
+                }),
+            Lift::Env => {
+                let env_repr = param_repr(ctx, callee.meta());
+                let env = MirNode::new(Expr::Tuple(captures
+                    .into_iter()
+                    .map(|(capture, repr)| MirNode::new(Expr::Local(capture), repr))
+                    .collect()), env_repr);
+                MirNode::new(Expr::Apply(callee, env), func.meta().clone())
+            },
+        }
+    }
+
+    // Visit the body of a procedure, skipping over the functions that form its parameters
+    fn visit_params(&self, ctx: &mut Context, proc: ProcId, expr: &mut MirNode<Expr>, scope: &mut Vec<(Local, Repr)>) {
+        let repr = expr.meta().clone();
+        if let Expr::Func(arg, body) = &mut **expr {
+            scope.push((*arg, param_repr(ctx, &repr)));
+            self.visit_params(ctx, proc, body, scope);
+            scope.pop();
+        } else {
+            self.visit(ctx, proc, expr, scope);
+        }
+    }
+
+    fn visit(&self, ctx: &mut Context, proc: ProcId, expr: &mut MirNode<Expr>, scope: &mut Vec<(Local, Repr)>) {
+        // `let f = fn x => ... in ... f(y) ...`
+        if let Some(name) = expr.let_name() {
+            if let Expr::Match(pred, arms) = &**expr {
+                if matches!(&**pred, Expr::Func(_, _)) && only_called(&arms[0].2, name) {
+                    let callee = self.lift(ctx, proc, pred, scope, Lift::Params);
+                    let mut body = arms[0].2.clone();
+                    replace_local(&mut body, name, &callee);
+                    *expr = body;
+                    self.visit(ctx, proc, expr, scope);
+                    return;
+                }
+            }
+        }
+
+        if matches!(&**expr, Expr::Func(_, _)) {
+            *expr = self.lift(ctx, proc, expr, scope, Lift::Env);
+            return;
+        }
+
+        match &mut **expr {
+            Expr::Match(pred, arms) => {
+                self.visit(ctx, proc, pred, scope);
+                for (binding, guard, body) in arms {
+                    let old_scope = scope.len();
+                    scope.extend(binding.bindings());
+                    if let Some(guard) = guard {
+                        self.visit(ctx, proc, guard, scope);
+                    }
+                    self.visit(ctx, proc, body, scope);
+                    scope.truncate(old_scope);
+                }
+            },
+            expr => expr.for_children_mut(|expr| self.visit(ctx, proc, expr, scope)),
+        }
+    }
+}
+
+impl Pass for ClosureConversion {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let mut changed = false;
+        for proc in ctx.reachable_procs() {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit_params(ctx, proc, &mut body, &mut Vec::new());
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
@@ -0,0 +1,149 @@
+use super::*;
+
+// Whether two expressions are structurally identical. Only expressions that may be shared are considered: those that
+// bind locals, construct functions, or perform debugging are never identical.
+fn same(a: &Expr, b: &Expr) -> bool {
+    fn all_same(xs: &[MirNode<Expr>], ys: &[MirNode<Expr>]) -> bool {
+        xs.len() == ys.len() && xs
+            .iter()
+            .zip(ys)
+            .all(|(x, y)| same(x, y))
+    }
+
+    match (a, b) {
+        (Expr::Literal(x), Expr::Literal(y)) => x == y,
+        (Expr::Local(x), Expr::Local(y)) => x == y,
+        (Expr::Global(x, _), Expr::Global(y, _)) => x == y,
+        (Expr::Intrinsic(x, xs), Expr::Intrinsic(y, ys)) => x == y && all_same(xs, ys),
+        (Expr::Apply(f, x), Expr::Apply(g, y)) => same(f, g) && same(x, y),
+        (Expr::Tuple(xs), Expr::Tuple(ys))
+        | (Expr::List(xs), Expr::List(ys)) => all_same(xs, ys),
+        (Expr::Access(x, i), Expr::Access(y, j))
+        | (Expr::AccessVariant(x, i), Expr::AccessVariant(y, j))
+        | (Expr::Variant(i, x), Expr::Variant(j, y)) => i == j && same(x, y),
+        _ => false,
+    }
+}
+
+// Whether an expression is worth sharing, i.e: it performs work and may be compared with `same`
+fn is_candidate(expr: &Expr) -> bool {
+    fn is_pure(expr: &Expr) -> bool {
+        let mut pure = !matches!(expr, Expr::Match(_, _) | Expr::Func(_, _) | Expr::Debug(_));
+        expr.for_children(|expr| pure &= is_pure(expr));
+        pure
+    }
+
+    matches!(expr, Expr::Intrinsic(_, _) | Expr::Apply(_, _) | Expr::Access(_, _) | Expr::AccessVariant(_, _))
+        && is_pure(expr)
+}
+
+// The children of an expression that are always evaluated when it is. The arms of matches and the bodies of functions
+// are not, and are treated as separate regions.
+fn strict_children(expr: &Expr) -> Vec<&MirNode<Expr>> {
+    match expr {
+        Expr::Literal(_) | Expr::Local(_) | Expr::Global(_, _) | Expr::Func(_, _) => Vec::new(),
+        Expr::Match(pred, _) => vec![pred],
+        Expr::Intrinsic(_, xs) | Expr::Tuple(xs) | Expr::List(xs) => xs.iter().collect(),
+        Expr::Apply(f, x) => vec![f, x],
+        Expr::Access(x, _) | Expr::AccessVariant(x, _) | Expr::Variant(_, x) | Expr::Debug(x) => vec![x],
+    }
+}
+
+fn for_strict_children_mut(expr: &mut Expr, mut f: impl FnMut(&mut MirNode<Expr>)) {
+    match expr {
+        Expr::Match(pred, _) => f(pred),
+        Expr::Func(_, _) => {},
+        _ => expr.for_children_mut(f),
+    }
+}
+
+/// Share identical pure subexpressions (such as repeated chains of field accesses, or repeated calls to a procedure
+/// with the same arguments) by evaluating them once and binding the result to a fresh local.
+///
+/// Subexpressions are only shared within a region of the program in which they are certain to be evaluated (i.e: not
+/// across the arms of a match, nor into or out of a function) so that the transformation never introduces work that
+/// the original program would not have done.
+#[derive(Default)]
+pub struct CommonSubexprs;
+
+impl CommonSubexprs {
+    // Find the largest candidate subexpression in the region that occurs more than once
+    fn find_shared<'a>(&self, region: &'a Expr) -> Option<&'a MirNode<Expr>> {
+        fn collect<'a>(expr: &'a MirNode<Expr>, candidates: &mut Vec<&'a MirNode<Expr>>) {
+            if is_candidate(expr) {
+                candidates.push(expr);
+            }
+            for child in strict_children(expr) {
+                collect(child, candidates);
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for child in strict_children(region) {
+            collect(child, &mut candidates);
+        }
+
+        candidates.sort_by_key(|expr| std::cmp::Reverse(size(expr)));
+        candidates
+            .iter()
+            .enumerate()
+            .find(|(i, x)| candidates[i + 1..].iter().any(|y| same(x, y)))
+            .map(|(_, x)| *x)
+    }
+
+    fn replace(&self, expr: &mut MirNode<Expr>, shared: &MirNode<Expr>, local: Local) {
+        if same(expr, shared) {
+            **expr = Expr::Local(local);
+        } else {
+            for_strict_children_mut(expr, |expr| self.replace(expr, shared, local));
+        }
+    }
+
+    fn visit(&self, region: &mut MirNode<Expr>) {
+        let mut lets = Vec::new();
+        while let Some(shared) = self.find_shared(region) {
+            let shared = shared.clone();
+            let local = Local::new();
+            for_strict_children_mut(region, |expr| self.replace(expr, &shared, local));
+            lets.push((local, shared));
+        }
+
+        // Visit the regions nested within this one
+        fn visit_nested(this: &CommonSubexprs, expr: &mut MirNode<Expr>) {
+            match &mut **expr {
+                Expr::Match(pred, arms) => {
+                    visit_nested(this, pred);
+                    for (_, guard, body) in arms {
+                        if let Some(guard) = guard {
+                            this.visit(guard);
+                        }
+                        this.visit(body);
+                    }
+                },
+                Expr::Func(_, body) => this.visit(body),
+                expr => for_strict_children_mut(expr, |expr| visit_nested(this, expr)),
+            }
+        }
+        visit_nested(self, region);
+
+        // Bind the shared subexpressions, in the order in which they were found, around the region
+        let repr = region.meta().clone();
+        for (local, shared) in lets.into_iter().rev() {
+            let body = std::mem::replace(region, MirNode::new(Expr::Tuple(Vec::new()), repr.clone()));
+            let binding = MirNode::new(Binding::wildcard(local), shared.meta().clone());
+            *region = MirNode::new(Expr::Match(shared, vec![(binding, None, body)]), repr.clone());
+        }
+    }
+}
+
+impl Pass for CommonSubexprs {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let mut changed = false;
+        for proc in ctx.reachable_procs() {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit(&mut body);
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
@@ -1,12 +1,9 @@
 use super::*;
 
 /// Fold constants into one-another, eagerly evaluating expressions at compile-time where possible. Additionally,
-/// globals and locals will get const-folded if possible.
+/// locals will get const-folded if possible. Inlining of globals is left to [`Inline`].
 #[derive(Default)]
-pub struct ConstFold {
-    // Is inlining permitted?
-    pub inline: bool,
-}
+pub struct ConstFold;
 
 impl ConstFold {
     // Returns `true` if the branch *could* still match the partial value. If `false` is returned, there's no
@@ -23,6 +20,30 @@ impl ConstFold {
         } else {
             match (&mut binding.pat, partial) {
                 (Pat::Wildcard, _) => true,
+                (Pat::Single(inner), partial) => self.extract(ctx, inner, partial, locals),
+                (Pat::Literal(litr), partial) => partial.to_literal().map_or(true, |partial| partial == *litr),
+                (Pat::Add(lhs, rhs), Partial::Nat(x)) => if *x >= *rhs {
+                    self.extract(ctx, lhs, &Partial::Nat(*x - *rhs), locals)
+                } else {
+                    false
+                },
+                (Pat::Add(lhs, rhs), Partial::Int(x)) => self.extract(ctx, lhs, &Partial::Int(x.wrapping_sub(*rhs as i64)), locals),
+                (Pat::Mul(lhs, rhs), Partial::Nat(x)) => if *x % *rhs == 0 {
+                    self.extract(ctx, lhs, &Partial::Nat(*x / *rhs), locals)
+                } else {
+                    false
+                },
+                (Pat::Mul(lhs, rhs), Partial::Int(x)) => if x.rem_euclid(*rhs as i64) == 0 {
+                    self.extract(ctx, lhs, &Partial::Int(x.div_euclid(*rhs as i64)), locals)
+                } else {
+                    false
+                },
+                (Pat::Range(lo, hi), partial) => match (lo, hi, partial) {
+                    (Literal::Nat(lo), Literal::Nat(hi), Partial::Nat(x)) => (*lo..=*hi).contains(x),
+                    (Literal::Int(lo), Literal::Int(hi), Partial::Int(x)) => (*lo..=*hi).contains(x),
+                    (Literal::Char(lo), Literal::Char(hi), Partial::Char(c)) => (*lo..=*hi).contains(c),
+                    _ => true,
+                },
                 (Pat::Variant(variant, x), Partial::Sum(tag, y)) => if variant == tag {
                     self.extract(ctx, x, y, locals)
                 } else {
@@ -56,6 +77,16 @@ impl ConstFold {
                         .zip(ys.iter())
                         .all(|(x, y)| self.extract(ctx, x, y, locals))
                 },
+                // We can't always know which alternative will match, so the locals they bind are left unknown
+                (Pat::Or(alts), partial) => if alts.iter_mut().any(|alt| self.extract(ctx, alt, partial, &mut Vec::new())) {
+                    locals.extend(alts[0]
+                        .binding_names()
+                        .into_iter()
+                        .map(|name| (name, Partial::Unknown(None))));
+                    true
+                } else {
+                    false
+                },
                 p => todo!("{:?}", p),
             }
         }
@@ -79,22 +110,17 @@ impl ConstFold {
                 },
                 partial => partial,
             },
-            Expr::Global(proc_id, flags) => if flags.get().can_inline && self.inline {
-                *expr = ctx.procs.get(*proc_id).unwrap().body.inner().clone();
-                expr.refresh_locals();
-                // Return directly, since we apply to itself
-                return self.eval(ctx, expr, &mut Vec::new())
-            } else {
-                Partial::Unknown(None)
-            },
+            Expr::Global(_, _) => Partial::Unknown(None),
             Expr::Match(pred, arms) => {
                 let pred = self.eval(ctx, pred, stack);
                 arms
-                    .drain_filter(|(binding, arm)| {
+                    .drain_filter(|(binding, guard, arm)| {
                         let old_stack = stack.len();
                         let cull = if self.extract(ctx, binding, &pred, stack) {
+                            // Arms with guards that never hold can never be taken
+                            let guard = guard.as_mut().map(|guard| self.eval(ctx, guard, stack));
                             self.eval(ctx, arm, stack);
-                            false
+                            guard == Some(Partial::Bool(false))
                         } else {
                             true
                         };
@@ -103,6 +129,7 @@ impl ConstFold {
                         cull
                     })
                     .for_each(|_| {});
+                drop_last_guard(arms);
                 Partial::Unknown(None) // TODO: unify arms
             },
             Expr::Func(arg, body) => {
@@ -124,17 +151,7 @@ impl ConstFold {
             Expr::Apply(f, arg) => {
                 self.eval(ctx, f, stack);
                 self.eval(ctx, arg, stack);
-
-                // Lower `(fn x => y)(z)` into `let x = z in y`
-                if let Expr::Func(param, body) = &mut **f {
-                    *expr = Expr::Match(
-                        arg.clone(),
-                        vec![(MirNode::new(Binding::wildcard(*param), arg.meta().clone()), body.clone())],
-                    );
-                    return self.eval(ctx, expr, stack)
-                } else {
-                    Partial::Unknown(None)
-                }
+                Partial::Unknown(None)
             },
             Expr::Intrinsic(intrinsic, args) => {
                 let args = args
@@ -188,16 +205,21 @@ impl Intrinsic {
             };
         }
 
+        // Integer arithmetic wraps, like the VM
         match self {
-            NegNat => op!(Nat(x) => Int(-(*x as i64))),
-            AddNat => op!(Nat(x), Nat(y) => Nat(x + y)),
-            MulNat => op!(Nat(x), Nat(y) => Nat(x * y)),
+            NegNat => op!(Nat(x) => Int((*x as i64).wrapping_neg())),
+            AddNat => op!(Nat(x), Nat(y) => Nat(x.wrapping_add(*y))),
+            SubNat => match args {
+                [Nat(x), Nat(y)] if x >= y => Nat(x - y),
+                _ => Unknown(None),
+            },
+            MulNat => op!(Nat(x), Nat(y) => Nat(x.wrapping_mul(*y))),
             LessNat => op!(Nat(x), Nat(y) => Bool(x < y)),
             MoreNat => op!(Nat(x), Nat(y) => Bool(x > y)),
             MoreEqNat => op!(Nat(x), Nat(y) => Bool(x >= y)),
-            AddInt => op!(Int(x), Int(y) => Int(x + y)),
-            SubInt => op!(Int(x), Int(y) => Int(x - y)),
-            MulInt => op!(Int(x), Int(y) => Int(x * y)),
+            AddInt => op!(Int(x), Int(y) => Int(x.wrapping_add(*y))),
+            SubInt => op!(Int(x), Int(y) => Int(x.wrapping_sub(*y))),
+            MulInt => op!(Int(x), Int(y) => Int(x.wrapping_mul(*y))),
             EqChar => op!(Char(x), Char(y) => Bool(x == y)),
             Join(_) => op!(List(xs), List(ys) => List(xs.iter().chain(ys).cloned().collect())),
             i => todo!("{:?}", i),
@@ -207,18 +229,20 @@ impl Intrinsic {
 }
 
 impl Pass for ConstFold {
-    fn apply(&mut self, ctx: &mut Context) {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
         let proc_bodies = ctx.procs
             .iter()
             .map(|(id, proc)| (id, proc.body.clone()))
             .collect::<Vec<_>>();
 
+        let mut changed = false;
         for (id, mut body) in proc_bodies {
             // visit(&ctx, &mut body, &mut Vec::new());
             self.eval(&ctx, &mut body, &mut Vec::new());
             let requires = body.required_locals(None);
             debug_assert_eq!(requires.len(), 0, "Procedure requires locals {:?}\n\nOld = {}\n\n\nNew = {}\n", requires, ctx.procs.get_mut(id).unwrap().body.print(), body.print());
-            ctx.procs.get_mut(id).unwrap().body = body;
+            changed |= ctx.set_body(id, body);
         }
+        changed
     }
 }
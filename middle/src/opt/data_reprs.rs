@@ -0,0 +1,367 @@
+use super::*;
+use std::cell::Cell;
+
+// How the values of a sum are represented once rewritten
+#[derive(Copy, Clone, PartialEq)]
+enum Layout {
+    // A sum with a single variant is represented by the inner value of that variant
+    Unwrapped,
+    // A sum in which every variant has a unit inner value is represented by the index of the variant
+    Tag,
+    // A sum with a unit variant and a variant holding an enumeration is represented by `0` for the unit variant and by
+    // the tag of the enumeration plus one for the other variant. Enumerations cannot have `u64::MAX` variants, so the
+    // tag always has a value to spare.
+    Niche { none: usize },
+}
+
+// Chooses the layout of a sum, given its variants
+type Select = fn(&Rewriter<'_>, &[Repr]) -> Option<Layout>;
+
+struct Rewriter<'a> {
+    reprs: &'a Reprs,
+    // Reprs that must not change because the values they describe are observable (i.e: printed by the program)
+    frozen: Vec<Repr>,
+    select: Select,
+    // Whether any repr has been given a layout, and so rewritten
+    changed: Cell<bool>,
+}
+
+impl<'a> Rewriter<'a> {
+    fn new(ctx: &'a Context, select: Select) -> Self {
+        fn freeze(reprs: &Reprs, repr: &Repr, frozen: &mut Vec<Repr>) {
+            if frozen.contains(repr) {
+                return;
+            }
+            match repr {
+                Repr::Prim(_) | Repr::Func(_, _) => {},
+                Repr::Data(data) => {
+                    frozen.push(repr.clone());
+                    freeze(reprs, reprs.get(*data), frozen);
+                },
+                Repr::Sum(variants) => {
+                    frozen.push(repr.clone());
+                    variants
+                        .iter()
+                        .for_each(|variant| freeze(reprs, variant, frozen));
+                },
+                Repr::List(item) => freeze(reprs, item, frozen),
+                Repr::Tuple(fields) => fields
+                    .iter()
+                    .for_each(|field| freeze(reprs, field, frozen)),
+                Repr::Union(inhabitants) => inhabitants
+                    .iter()
+                    .for_each(|inhabitant| freeze(reprs, inhabitant, frozen)),
+            }
+        }
+
+        fn freeze_debugged(reprs: &Reprs, expr: &Expr, frozen: &mut Vec<Repr>) {
+            if let Expr::Debug(inner) = expr {
+                freeze(reprs, inner.meta(), frozen);
+            }
+            expr.for_children(|expr| freeze_debugged(reprs, expr, frozen));
+        }
+
+        let mut frozen = Vec::new();
+        if let Some(entry) = ctx.entry {
+            freeze(&ctx.reprs, ctx.procs.get(entry).unwrap().body.meta(), &mut frozen);
+        }
+        for (_, proc) in ctx.procs.iter() {
+            freeze_debugged(&ctx.reprs, &proc.body, &mut frozen);
+        }
+
+        Self { reprs: &ctx.reprs, frozen, select, changed: Cell::new(false) }
+    }
+
+    fn resolve<'b>(&'b self, repr: &'b Repr) -> &'b Repr {
+        match repr {
+            Repr::Data(data) => self.reprs.get(*data),
+            repr => repr,
+        }
+    }
+
+    fn is_unit(&self, repr: &Repr) -> bool {
+        matches!(self.resolve(repr), Repr::Tuple(fields) if fields.is_empty())
+    }
+
+    fn is_frozen(&self, repr: &Repr) -> bool {
+        self.frozen.contains(repr) || self.frozen.contains(self.resolve(repr))
+    }
+
+    fn layout(&self, repr: &Repr) -> Option<Layout> {
+        let resolved = self.resolve(repr);
+        if self.is_frozen(repr) {
+            return None;
+        }
+        let layout = match resolved {
+            Repr::Sum(variants) => (self.select)(self, variants),
+            _ => None,
+        };
+        if layout.is_some() {
+            self.changed.set(true);
+        }
+        layout
+    }
+
+    fn repr(&self, repr: &Repr) -> Repr {
+        match repr {
+            Repr::Prim(_) => repr.clone(),
+            Repr::Data(_) => match self.layout(repr) {
+                Some(Layout::Tag | Layout::Niche { .. }) => Repr::Prim(Prim::Nat),
+                // Data types are only ever unwrapped to non-data reprs, so they're still needed to break cycles
+                _ => repr.clone(),
+            },
+            Repr::Sum(variants) => match self.layout(repr) {
+                Some(Layout::Unwrapped) => self.repr(&variants[0]),
+                Some(Layout::Tag | Layout::Niche { .. }) => Repr::Prim(Prim::Nat),
+                None => Repr::Sum(variants
+                    .iter()
+                    .map(|variant| self.repr(variant))
+                    .collect()),
+            },
+            Repr::List(item) => Repr::List(Box::new(self.repr(item))),
+            Repr::Tuple(fields) => Repr::Tuple(fields
+                .iter()
+                .map(|field| self.repr(field))
+                .collect()),
+            Repr::Func(i, o) => Repr::Func(Box::new(self.repr(i)), Box::new(self.repr(o))),
+            Repr::Union(inhabitants) => Repr::Union(inhabitants
+                .iter()
+                .map(|inhabitant| self.repr(inhabitant))
+                .collect()),
+        }
+    }
+
+    fn literal(&self, litr: &Literal, repr: &Repr) -> Literal {
+        match (litr, self.layout(repr), self.resolve(repr)) {
+            (Literal::Sum(_, inner), Some(Layout::Unwrapped), Repr::Sum(variants)) => self.literal(inner, &variants[0]),
+            (Literal::Sum(variant, _), Some(Layout::Tag), _) => Literal::Nat(*variant as u64),
+            (Literal::Sum(variant, _), Some(Layout::Niche { none }), _) if *variant == none => Literal::Nat(0),
+            (Literal::Sum(variant, inner), Some(Layout::Niche { .. }), Repr::Sum(variants)) => {
+                Literal::Nat(self.literal(inner, &variants[*variant]).nat() + 1)
+            },
+            (Literal::Sum(variant, inner), None, Repr::Sum(variants)) => {
+                Literal::Sum(*variant, Box::new(self.literal(inner, &variants[*variant])))
+            },
+            (Literal::Tuple(fields), _, Repr::Tuple(reprs)) => Literal::Tuple(fields
+                .iter()
+                .zip(reprs.iter())
+                .map(|(field, repr)| self.literal(field, repr))
+                .collect()),
+            (Literal::List(items), _, Repr::List(item_repr)) => Literal::List(items
+                .iter()
+                .map(|item| self.literal(item, item_repr))
+                .collect()),
+            (litr, _, _) => litr.clone(),
+        }
+    }
+
+    // The names bound by patterns that no longer have a value to bind are collected into `units`, to be bound to unit
+    // by the arm instead.
+    fn binding(&self, binding: &mut MirNode<Binding>, units: &mut Vec<(Local, Repr)>) {
+        let layout = self.layout(binding.meta());
+        let old_repr = binding.meta().clone();
+        *binding.meta_mut() = self.repr(&old_repr);
+
+        let new_pat = if let (Pat::Variant(variant, inner), Some(layout)) = (&mut binding.pat, layout) {
+            self.binding(inner, units);
+            let mut discard = |litr| {
+                units.append(&mut inner.bindings());
+                Pat::Literal(litr)
+            };
+            Some(match layout {
+                Layout::Unwrapped => Pat::Single(inner.clone()),
+                Layout::Tag => discard(Literal::Nat(*variant as u64)),
+                Layout::Niche { none } if *variant == none => discard(Literal::Nat(0)),
+                Layout::Niche { .. } => match &inner.pat {
+                    Pat::Literal(Literal::Nat(x)) if inner.name.is_none() => Pat::Literal(Literal::Nat(x + 1)),
+                    _ => Pat::Add(inner.clone(), 1),
+                },
+            })
+        } else if let Pat::Or(alts) = &mut binding.pat {
+            // Alternatives bind the same locals, which must only be bound to unit once
+            for alt in alts {
+                let mut alt_units = Vec::new();
+                self.binding(alt, &mut alt_units);
+                for (name, repr) in alt_units {
+                    if !units.iter().any(|(unit, _)| *unit == name) {
+                        units.push((name, repr));
+                    }
+                }
+            }
+            None
+        } else {
+            if let Pat::Literal(litr) = &mut binding.pat {
+                *litr = self.literal(litr, &old_repr);
+            }
+            binding.for_children_mut(|binding| self.binding(binding, units));
+            None
+        };
+
+        if let Some(pat) = new_pat {
+            binding.pat = pat;
+        }
+    }
+
+    // Replace a value with a literal, keeping the value around if evaluating it might be significant
+    fn discard(&self, value: &MirNode<Expr>, litr: Literal, repr: &Repr) -> Expr {
+        let is_trivial = matches!(&**value, Expr::Literal(_) | Expr::Local(_))
+            || matches!(&**value, Expr::Tuple(fields) if fields.is_empty());
+        if is_trivial {
+            Expr::Literal(litr)
+        } else {
+            let wildcard = MirNode::new(Binding { pat: Pat::Wildcard, name: None }, value.meta().clone());
+            Expr::Match(value.clone(), vec![(wildcard, None, MirNode::new(Expr::Literal(litr), repr.clone()))])
+        }
+    }
+
+    fn expr(&self, expr: &mut MirNode<Expr>) {
+        // Decisions are made according to the old reprs, so must happen before anything is rewritten
+        let layout = match &**expr {
+            Expr::Variant(_, _) => self.layout(expr.meta()),
+            Expr::AccessVariant(inner, _) => self.layout(inner.meta()),
+            _ => None,
+        };
+        let old_repr = expr.meta().clone();
+        *expr.meta_mut() = self.repr(&old_repr);
+
+        match &mut **expr {
+            Expr::Literal(litr) => *litr = self.literal(litr, &old_repr),
+            Expr::Intrinsic(intrinsic, args) => {
+                if let Intrinsic::MakeList(repr) | Intrinsic::Join(repr) = intrinsic {
+                    *repr = self.repr(repr);
+                }
+                args
+                    .iter_mut()
+                    .for_each(|arg| self.expr(arg));
+            },
+            Expr::Match(pred, arms) => {
+                self.expr(pred);
+                for (binding, guard, body) in arms {
+                    let mut units = Vec::new();
+                    self.binding(binding, &mut units);
+                    if let Some(guard) = guard.as_mut() {
+                        self.expr(guard);
+                    }
+                    self.expr(body);
+                    for (name, repr) in units.into_iter().rev() {
+                        let unit = MirNode::new(Expr::Literal(Literal::Tuple(Vec::new())), repr.clone());
+                        let binding = MirNode::new(Binding::wildcard(name), repr);
+                        for expr in guard.iter_mut().chain(Some(&mut *body)) {
+                            *expr = MirNode::new(Expr::Match(unit.clone(), vec![(binding.clone(), None, expr.clone())]), expr.meta().clone());
+                        }
+                    }
+                }
+            },
+            expr => expr.for_children_mut(|expr| self.expr(expr)),
+        }
+
+        let nat = |x| MirNode::new(Expr::Literal(Literal::Nat(x)), Repr::Prim(Prim::Nat));
+        let rewritten = match (&**expr, layout) {
+            (_, None) => None,
+            (Expr::Variant(_, inner), Some(Layout::Unwrapped))
+            | (Expr::AccessVariant(inner, _), Some(Layout::Unwrapped)) => Some(inner.inner().clone()),
+            (Expr::Variant(variant, inner), Some(Layout::Tag)) => {
+                Some(self.discard(inner, Literal::Nat(*variant as u64), expr.meta()))
+            },
+            (Expr::Variant(variant, inner), Some(Layout::Niche { none })) if *variant == none => {
+                Some(self.discard(inner, Literal::Nat(0), expr.meta()))
+            },
+            (Expr::Variant(_, inner), Some(Layout::Niche { .. })) => {
+                Some(Expr::Intrinsic(Intrinsic::AddNat, vec![inner.clone(), nat(1)]))
+            },
+            (Expr::AccessVariant(inner, variant), Some(Layout::Niche { none })) if *variant != none => {
+                Some(Expr::Intrinsic(Intrinsic::SubNat, vec![inner.clone(), nat(1)]))
+            },
+            (Expr::AccessVariant(inner, _), Some(Layout::Tag | Layout::Niche { .. })) => {
+                Some(self.discard(inner, Literal::Tuple(Vec::new()), expr.meta()))
+            },
+            _ => None,
+        };
+        if let Some(rewritten) = rewritten {
+            **expr = rewritten;
+        }
+    }
+}
+
+// Rewrite the representation of every sum that `select` gives a layout to, returning whether any were rewritten
+fn rewrite(ctx: &mut Context, select: Select) -> bool {
+    let (procs, datas, changed) = {
+        let this = Rewriter::new(ctx, select);
+        let procs = ctx.procs
+            .iter()
+            .map(|(id, proc)| {
+                let mut body = proc.body.clone();
+                this.expr(&mut body);
+                (id, body)
+            })
+            .collect::<Vec<_>>();
+        let datas = ctx.reprs.datas
+            .iter()
+            .filter_map(|(data, repr)| Some((*data, this.repr(repr.as_ref()?))))
+            .collect::<Vec<_>>();
+        (procs, datas, this.changed.get())
+    };
+
+    for (id, body) in procs {
+        ctx.procs.get_mut(id).unwrap().body = body;
+    }
+    for (data, repr) in datas {
+        ctx.reprs.datas.insert(data, Some(repr));
+    }
+    changed
+}
+
+/// Represent data types with only a single variant (such as records declared with `data`) by the inner value of that
+/// variant. This generalises [`FlattenSingleField`] to sums.
+#[derive(Default)]
+pub struct FlattenSingleVariant;
+
+impl Pass for FlattenSingleVariant {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        rewrite(ctx, |_, variants| match variants {
+            // Unwrapping to another data type could produce a data type that refers only to itself
+            [Repr::Data(_)] => None,
+            [_] => Some(Layout::Unwrapped),
+            _ => None,
+        })
+    }
+}
+
+/// Represent sums in which no variant carries a value (i.e: enumerations like `data Dir = | North | South`) as the
+/// natural number index of the variant, avoiding an allocation for each value.
+#[derive(Default)]
+pub struct TagUnitSums;
+
+impl Pass for TagUnitSums {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        rewrite(ctx, |this, variants| tag(this, variants))
+    }
+}
+
+fn tag(this: &Rewriter<'_>, variants: &[Repr]) -> Option<Layout> {
+    if variants.len() > 1 && variants.iter().all(|v| this.is_unit(v)) {
+        Some(Layout::Tag)
+    } else {
+        None
+    }
+}
+
+/// Represent `Maybe`-like sums of an enumeration as a natural number, using `0` for the empty variant and offsetting
+/// the tag of the enumeration by one. Enumerations are tagged as by [`TagUnitSums`] in the same pass, since once tagged
+/// they can no longer be told apart from natural numbers, which have no value to spare.
+#[derive(Default)]
+pub struct NicheSums;
+
+impl Pass for NicheSums {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        rewrite(ctx, |this, variants| {
+            let is_tagged = |repr| !this.is_frozen(repr)
+                && matches!(this.resolve(repr), Repr::Sum(variants) if tag(this, variants).is_some());
+            match variants {
+                [a, b] if this.is_unit(a) && is_tagged(b) => Some(Layout::Niche { none: 0 }),
+                [a, b] if this.is_unit(b) && is_tagged(a) => Some(Layout::Niche { none: 1 }),
+                _ => tag(this, variants),
+            }
+        })
+    }
+}
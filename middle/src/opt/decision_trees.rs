@@ -0,0 +1,367 @@
+use super::*;
+
+// The constructor that a pattern tests for at its top level
+#[derive(Clone, PartialEq)]
+enum Head {
+    Variant(usize),
+    Union(u64),
+    Literal(Literal),
+    ListLen(usize),
+}
+
+// The items of a list of known length are matched as a tuple once the length has been tested
+fn head_of(binding: &MirNode<Binding>) -> Option<(Head, Option<MirNode<Binding>>)> {
+    match &binding.pat {
+        Pat::Variant(variant, inner) => Some((Head::Variant(*variant), Some(inner.clone()))),
+        Pat::UnionVariant(id, inner) => Some((Head::Union(*id), Some(inner.clone()))),
+        Pat::Literal(literal @ (Literal::Nat(_) | Literal::Int(_) | Literal::Char(_) | Literal::Bool(_))) => {
+            Some((Head::Literal(literal.clone()), None))
+        },
+        Pat::ListExact(items) => {
+            let repr = Repr::Tuple(items.iter().map(|item| item.meta().clone()).collect());
+            let inner = Binding { pat: Pat::Tuple(items.clone()), name: None };
+            Some((Head::ListLen(items.len()), Some(MirNode::new(inner, repr))))
+        },
+        _ => None,
+    }
+}
+
+// The pattern that a tree arm uses to test for a constructor, along with an expression referring to the inner value
+// that it binds
+fn head_pat(head: &Head, inner_repr: Option<&Repr>) -> (Pat, Option<MirNode<Expr>>) {
+    let bind_fresh = |repr: &Repr| {
+        let local = Local::new();
+        (MirNode::new(Binding::wildcard(local), repr.clone()), MirNode::new(Expr::Local(local), repr.clone()))
+    };
+    match head {
+        Head::Literal(literal) => (Pat::Literal(literal.clone()), None),
+        Head::Variant(variant) => {
+            let (binding, inner) = bind_fresh(inner_repr.unwrap());
+            (Pat::Variant(*variant, binding), Some(inner))
+        },
+        Head::Union(id) => {
+            let (binding, inner) = bind_fresh(inner_repr.unwrap());
+            (Pat::UnionVariant(*id, binding), Some(inner))
+        },
+        Head::ListLen(_) => {
+            let item_reprs = if let Some(Repr::Tuple(items)) = inner_repr { items } else { unreachable!() };
+            let (bindings, items) = item_reprs.iter().map(bind_fresh).unzip();
+            (Pat::ListExact(bindings), Some(MirNode::new(Expr::Tuple(items), Repr::Tuple(item_reprs.clone()))))
+        },
+    }
+}
+
+// An arm of the original match, with its top-level constructor test split off
+struct Arm<'a> {
+    names: Vec<Local>,
+    inner: Option<MirNode<Binding>>,
+    body: &'a MirNode<Expr>,
+}
+
+// The tested sub-pattern of a column of a tuple pattern, if it tests anything, with any names given to it split off
+fn test_of<'a>(column: Option<&'a MirNode<Binding>>) -> Option<(Vec<Local>, &'a MirNode<Binding>)> {
+    let column = column.filter(|column| column.is_refutable())?;
+    let mut names = Vec::new();
+    let tested = column.strip_names(&mut names);
+    Some((names, tested))
+}
+
+/// Compile matches into decision trees.
+///
+/// Arms that test the same constructor (a sum variant, a union variant, a literal, or the length of a list) are grouped
+/// together so that the constructor of the value is only tested once, after which the inner value is matched against
+/// the remaining sub-patterns of the group. The result is a match in which each arm tests a distinct constructor with
+/// an irrefutable inner pattern, optionally followed by a single default arm: backends are able to compile this form to
+/// a jump table.
+///
+/// If a group might fall through (i.e: its inner patterns are not exhaustive), the default arm is duplicated into it.
+/// Matches for which this would duplicate too much code are left alone.
+///
+/// Matches on tuples, such as those produced by the arms of definitions with several parameters, have no constructor of
+/// their own. Instead, the first column that tests at least two distinct constructors is chosen and the match is split
+/// into one group per constructor of that column. Arms that do not test the column are duplicated into every group.
+#[derive(Default)]
+pub struct DecisionTrees;
+
+impl DecisionTrees {
+    // The maximum amount of code that may be produced by duplicating the default arm
+    const MAX_DUPLICATED_SIZE: usize = 64;
+
+    // Bind the predicate to a local so that it may be referred to by the inner matches
+    fn scrutinee_of(pred: &MirNode<Expr>) -> (Local, bool) {
+        match &**pred {
+            Expr::Local(local) => (*local, false),
+            _ => (Local::new(), true),
+        }
+    }
+
+    fn finish(pred: &MirNode<Expr>, scrutinee: Local, needs_binding: bool, tree: Expr, out_repr: &Repr) -> Expr {
+        if needs_binding {
+            Expr::Match(pred.clone(), vec![(
+                MirNode::new(Binding::wildcard(scrutinee), pred.meta().clone()),
+                None,
+                MirNode::new(tree, out_repr.clone()),
+            )])
+        } else {
+            tree
+        }
+    }
+
+    fn build(&self, expr: &MirNode<Expr>) -> Option<Expr> {
+        self.build_heads(expr).or_else(|| self.build_columns(expr))
+    }
+
+    fn build_heads(&self, expr: &MirNode<Expr>) -> Option<Expr> {
+        let (pred, arms) = if let Expr::Match(pred, arms) = &**expr { (pred, arms) } else { return None };
+
+        let mut groups: Vec<(Head, Vec<Arm>)> = Vec::new();
+        let mut default = None;
+        for (binding, guard, body) in arms {
+            // A guard is a test that cannot be shared, so guarded matches are left alone
+            if guard.is_some() {
+                return None;
+            }
+
+            if !binding.is_refutable() {
+                // Any arms that follow are unreachable and will be removed by other passes
+                default = Some((binding, body));
+                break;
+            }
+
+            let mut names = Vec::new();
+            let (head, inner) = head_of(binding.strip_names(&mut names))?;
+            let arm = Arm { names, inner, body };
+            match groups.iter_mut().find(|(h, _)| *h == head) {
+                Some((_, group)) => group.push(arm),
+                None => groups.push((head, vec![arm])),
+            }
+        }
+
+        let falls_through = |group: &[Arm]| group
+            .last()
+            .and_then(|arm| arm.inner.as_ref())
+            .map_or(false, |inner| inner.is_refutable());
+
+        // Matches that are already in canonical form have nothing to gain, nor do matches that test only one
+        // constructor and so would gain nothing but a duplicated default arm
+        let is_canonical = groups
+            .iter()
+            .all(|(_, group)| group.len() == 1 && !falls_through(group));
+        let shares_tests = groups
+            .iter()
+            .any(|(_, group)| group.len() > 1);
+        if is_canonical || (groups.len() < 2 && !shares_tests) {
+            return None;
+        }
+
+        if let Some((_, default_body)) = default {
+            let copies = groups
+                .iter()
+                .filter(|(_, group)| falls_through(group))
+                .count();
+            if copies * size(default_body) > Self::MAX_DUPLICATED_SIZE {
+                return None;
+            }
+        }
+
+        let (scrutinee, needs_binding) = Self::scrutinee_of(pred);
+        let pred_repr = pred.meta().clone();
+        let out_repr = expr.meta().clone();
+        let local = |local: Local, repr: &Repr| MirNode::new(Expr::Local(local), repr.clone());
+        let bind = |pred: MirNode<Expr>, binding: MirNode<Binding>, body: MirNode<Expr>| {
+            MirNode::new(Expr::Match(pred, vec![(binding, None, body)]), out_repr.clone())
+        };
+        // Rebind any names that were given to the whole value by the original arm
+        let rebind = |names: &[Local], body: &MirNode<Expr>| names
+            .iter()
+            .rev()
+            .fold(body.clone(), |body, name| bind(
+                local(scrutinee, &pred_repr),
+                MirNode::new(Binding::wildcard(*name), pred_repr.clone()),
+                body,
+            ));
+        let default = default.map(|(binding, body)| bind(local(scrutinee, &pred_repr), binding.clone(), body.clone()));
+
+        let mut tree_arms = Vec::new();
+        for (head, group) in groups {
+            let inner_repr = group[0].inner.as_ref().map(|inner| inner.meta().clone());
+            let (pat, inner) = head_pat(&head, inner_repr.as_ref());
+            let body = match inner {
+                Some(inner) => {
+                    let mut inner_arms = group
+                        .iter()
+                        .map(|arm| (arm.inner.clone().unwrap(), None, rebind(&arm.names, arm.body)))
+                        .collect::<Vec<_>>();
+                    if let Some(default) = default.as_ref().filter(|_| falls_through(&group)) {
+                        // Each copy of the default arm must bind its own locals
+                        let mut default = default.clone();
+                        default.refresh_bound_locals();
+                        let binding = Binding { pat: Pat::Wildcard, name: None };
+                        inner_arms.push((MirNode::new(binding, inner.meta().clone()), None, default));
+                    }
+                    MirNode::new(Expr::Match(inner, inner_arms), out_repr.clone())
+                },
+                // Literals have no inner value, so only the first arm of the group can ever be reached
+                None => rebind(&group[0].names, group[0].body),
+            };
+            tree_arms.push((MirNode::new(Binding { pat, name: None }, pred_repr.clone()), None, body));
+        }
+        if let Some(default) = default {
+            tree_arms.push((MirNode::new(Binding { pat: Pat::Wildcard, name: None }, pred_repr.clone()), None, default));
+        }
+
+        let tree = Expr::Match(local(scrutinee, &pred_repr), tree_arms);
+        Some(Self::finish(pred, scrutinee, needs_binding, tree, &out_repr))
+    }
+
+    fn build_columns(&self, expr: &MirNode<Expr>) -> Option<Expr> {
+        let (pred, arms) = if let Expr::Match(pred, arms) = &**expr { (pred, arms) } else { return None };
+        let field_reprs = if let Repr::Tuple(fields) = pred.meta() { fields.clone() } else { return None };
+        if arms.iter().any(|(_, guard, _)| guard.is_some()) {
+            return None;
+        }
+
+        // The sub-pattern that each arm applies to each column (`None` if the arm does not destructure the tuple),
+        // along with any names given to the whole tuple
+        let mut rows = Vec::new();
+        for arm @ (binding, _, _) in arms {
+            let mut names = Vec::new();
+            let columns = match &binding.strip_names(&mut names).pat {
+                Pat::Tuple(fields) => fields.iter().map(Some).collect::<Vec<_>>(),
+                Pat::Wildcard => vec![None; field_reprs.len()],
+                _ => return None,
+            };
+            rows.push((names, columns, arm));
+            // Any arms that follow are unreachable and will be removed by other passes
+            if !binding.is_refutable() {
+                break;
+            }
+        }
+
+        // Every arm must either test a constructor in the column or leave it untested
+        let heads_of_column = |column: usize| rows
+            .iter()
+            .filter_map(|(_, columns, _)| test_of(columns[column]))
+            .map(|(_, tested)| head_of(tested).map(|(head, _)| head))
+            .collect::<Option<Vec<_>>>()
+            .map(|heads| heads.into_iter().fold(Vec::new(), |mut distinct, head| {
+                if !distinct.contains(&head) {
+                    distinct.push(head);
+                }
+                distinct
+            }));
+        let (column, heads) = (0..field_reprs.len())
+            .filter_map(|column| Some((column, heads_of_column(column)?)))
+            .find(|(_, heads)| heads.len() >= 2)?;
+
+        let is_untested = |columns: &[Option<&MirNode<Binding>>]| test_of(columns[column]).is_none();
+        let untested_size = rows
+            .iter()
+            .filter(|(_, columns, _)| is_untested(columns))
+            .map(|(_, _, (_, _, body))| size(body))
+            .sum::<usize>();
+        if untested_size * heads.len() > Self::MAX_DUPLICATED_SIZE {
+            return None;
+        }
+
+        let (scrutinee, needs_binding) = Self::scrutinee_of(pred);
+        let pred_repr = pred.meta().clone();
+        let out_repr = expr.meta().clone();
+        let whole = MirNode::new(Expr::Local(scrutinee), pred_repr.clone());
+        let access = |i: usize| MirNode::new(Expr::Access(whole.clone(), i), field_reprs[i].clone());
+        let wildcard = |repr: &Repr| MirNode::new(Binding { pat: Pat::Wildcard, name: None }, repr.clone());
+        let rebind = |names: &[Local], value: MirNode<Expr>, body: MirNode<Expr>| names
+            .iter()
+            .rev()
+            .fold(body, |body, name| {
+                let binding = MirNode::new(Binding::wildcard(*name), value.meta().clone());
+                MirNode::new(Expr::Match(value.clone(), vec![(binding, None, body)]), out_repr.clone())
+            });
+
+        let mut tree_arms = Vec::new();
+        for head in &heads {
+            let inner_repr = rows
+                .iter()
+                .filter_map(|(_, columns, _)| head_of(test_of(columns[column])?.1))
+                .find(|(h, _)| h == head)
+                .and_then(|(_, inner)| inner.map(|inner| inner.meta().clone()));
+
+            // The inner match is on every column, followed by the inner value bound by the constructor (if any). The
+            // chosen column is still required by arms that do not test it.
+            let mut inner_arms = Vec::new();
+            for (names, columns, (_, _, body)) in &rows {
+                let mut fields = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| field.cloned().unwrap_or_else(|| wildcard(&field_reprs[i])))
+                    .collect::<Vec<_>>();
+                let mut body = rebind(names, whole.clone(), body.clone());
+                match test_of(columns[column]) {
+                    Some((column_names, tested)) => {
+                        let (row_head, inner) = head_of(tested).unwrap();
+                        if row_head != *head {
+                            continue;
+                        }
+                        fields[column] = wildcard(&field_reprs[column]);
+                        fields.extend(inner);
+                        body = rebind(&column_names, access(column), body);
+                    },
+                    None => fields.extend(inner_repr.as_ref().map(wildcard)),
+                }
+                inner_arms.push((fields, body));
+            }
+
+            let (pat, inner) = head_pat(head, inner_repr.as_ref());
+            let mut items = (0..field_reprs.len()).map(access).collect::<Vec<_>>();
+            items.extend(inner);
+            let items_repr = Repr::Tuple(items.iter().map(|item| item.meta().clone()).collect());
+            let inner_arms = inner_arms
+                .into_iter()
+                .map(|(fields, body)| {
+                    let binding = Binding { pat: Pat::Tuple(fields), name: None };
+                    (MirNode::new(binding, items_repr.clone()), None, body)
+                })
+                .collect();
+            let inner_pred = MirNode::new(Expr::Tuple(items), items_repr);
+            let mut body = MirNode::new(Expr::Match(inner_pred, inner_arms), out_repr.clone());
+            // Arms that do not test the column appear in several groups, so each copy must bind its own locals
+            body.refresh_bound_locals();
+            tree_arms.push((MirNode::new(Binding { pat, name: None }, field_reprs[column].clone()), None, body));
+        }
+
+        // Values with constructors that no arm tests for can only be handled by the arms that do not test the column
+        let untested_arms = rows
+            .iter()
+            .filter(|(_, columns, _)| is_untested(columns))
+            .map(|(_, _, arm)| (*arm).clone())
+            .collect::<Vec<_>>();
+        if !untested_arms.is_empty() {
+            let mut body = MirNode::new(Expr::Match(whole.clone(), untested_arms), out_repr.clone());
+            body.refresh_bound_locals();
+            tree_arms.push((wildcard(&field_reprs[column]), None, body));
+        }
+
+        let tree = Expr::Match(access(column), tree_arms);
+        Some(Self::finish(pred, scrutinee, needs_binding, tree, &out_repr))
+    }
+
+    fn visit(&self, expr: &mut MirNode<Expr>) {
+        if let Some(tree) = self.build(expr) {
+            **expr = tree;
+        }
+        // Inner matches produced by the transformation are visited too, since they may themselves be compiled further
+        expr.for_children_mut(|expr| self.visit(expr));
+    }
+}
+
+impl Pass for DecisionTrees {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let mut changed = false;
+        for proc in ctx.reachable_procs() {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit(&mut body);
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
@@ -1,33 +1,46 @@
 use super::*;
+use std::cell::Cell;
 
 /// Turns tuples with single fields into their inner value.
 #[derive(Default)]
 pub struct FlattenSingleField;
 
 impl Pass for FlattenSingleField {
-    fn apply(&mut self, ctx: &mut Context) {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let changed = Cell::new(false);
         ctx.visit(
             VisitOrder::First,
             |repr| {
                 if let Repr::Tuple(fields) = repr {
                     if fields.len() == 1 {
                         *repr = fields.remove(0);
+                        changed.set(true);
                     }
                 }
             },
             |binding| {
                 if let Pat::Tuple(fields) = &mut binding.pat {
                     if fields.len() == 1 {
-                        *binding = fields.remove(0).into_inner();
+                        changed.set(true);
+                        let field = fields.remove(0);
+                        // Keep the name given to the whole tuple, if any
+                        *binding = match binding.name {
+                            Some(_) => Binding { pat: Pat::Single(field), name: binding.name },
+                            None => field.into_inner(),
+                        };
                     }
                 }
             },
             |expr| {
                 match expr {
-                    Expr::Tuple(fields) if fields.len() == 1 => *expr = fields.remove(0).into_inner(),
+                    Expr::Tuple(fields) if fields.len() == 1 => {
+                        *expr = fields.remove(0).into_inner();
+                        changed.set(true);
+                    },
                     Expr::Access(tuple, field) => if let Repr::Tuple(fields) = tuple.meta() {
                         if fields.len() == 1 {
                             *expr = tuple.inner().clone();
+                            changed.set(true);
                         }
                     } else {
                         unreachable!()
@@ -36,5 +49,6 @@ impl Pass for FlattenSingleField {
                 }
             },
         );
+        changed.get()
     }
 }
@@ -0,0 +1,149 @@
+use super::*;
+
+fn has_debug(expr: &Expr) -> bool {
+    let mut debug = matches!(expr, Expr::Debug(_));
+    expr.for_children(|expr| debug |= has_debug(expr));
+    debug
+}
+
+// Whether evaluating an expression earlier than it otherwise would be is certain to terminate and have no effects
+fn can_hoist(expr: &Expr) -> bool {
+    match expr {
+        // Creating a function never evaluates its body
+        Expr::Func(_, _) => true,
+        Expr::Apply(_, _) | Expr::Global(_, _) | Expr::Debug(_) => false,
+        _ => {
+            let mut can_hoist_children = true;
+            expr.for_children(|expr| can_hoist_children &= can_hoist(expr));
+            can_hoist_children
+        },
+    }
+}
+
+fn captures(func: &Expr) -> usize {
+    if let Expr::Func(arg, body) = func {
+        body
+            .required_locals(Some(*arg))
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .len()
+    } else {
+        0
+    }
+}
+
+/// Move `let`s to places where they are cheaper to evaluate.
+///
+/// `let`s at the start of a function body that do not depend on the function's parameter are floated out of the
+/// function, provided that doing so does not increase the number of locals that the function captures (i.e:
+/// `fn x => let y = a + b in x * y` becomes `let y = a + b in fn x => x * y`, capturing only `y` instead of both `a`
+/// and `b`). Conversely, `let`s that are only used by a single arm of the match that follows them are floated into
+/// that arm so that they are only evaluated when needed.
+#[derive(Default)]
+pub struct FloatLets;
+
+impl FloatLets {
+    fn float_out(&self, expr: &mut MirNode<Expr>) {
+        let (arg, body) = if let Expr::Func(arg, body) = &**expr { (*arg, body) } else { return };
+        if body.let_name().is_none() {
+            return;
+        }
+        let (value, binding, inner) = if let Expr::Match(value, arms) = &**body {
+            (value, &arms[0].0, &arms[0].2)
+        } else {
+            unreachable!()
+        };
+
+        if !can_hoist(value) || value.required_locals(None).contains(&arg) {
+            return;
+        }
+
+        let func = MirNode::new(Expr::Func(arg, inner.clone()), expr.meta().clone());
+        if captures(&func) > captures(expr) {
+            return;
+        }
+
+        let floated = Expr::Match(value.clone(), vec![(binding.clone(), None, func)]);
+        **expr = floated;
+
+        // The function might begin with more `let`s that can be floated out
+        if let Expr::Match(_, arms) = &mut **expr {
+            self.float_out(&mut arms[0].2);
+        }
+    }
+
+    fn float_in(&self, expr: &mut MirNode<Expr>) {
+        let name = if let Some(name) = expr.let_name() { name } else { return };
+        let (value, binding, body) = if let Expr::Match(value, arms) = &**expr {
+            (value, &arms[0].0, &arms[0].2)
+        } else {
+            unreachable!()
+        };
+        let (pred, arms) = match &**body {
+            // Floating a `let` into another `let` gains nothing
+            Expr::Match(pred, arms) if arms.len() > 1 => (pred, arms),
+            _ => return,
+        };
+
+        // Guards are evaluated before it is known which arm will be taken
+        let guard_uses = arms
+            .iter()
+            .filter_map(|(_, guard, _)| guard.as_ref())
+            .any(|guard| guard.required_locals(None).contains(&name));
+        if has_debug(value) || guard_uses || pred.required_locals(None).contains(&name) {
+            return;
+        }
+
+        let mut users = arms
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, arm))| arm.required_locals(None).contains(&name))
+            .map(|(i, _)| i);
+        // Unused `let`s are left for `RemoveUnusedBindings` to remove
+        let user = match (users.next(), users.next()) {
+            (Some(user), None) => user,
+            _ => return,
+        };
+
+        // Don't float the `let` into an arm that binds a local that the value requires
+        let required = value.required_locals(None);
+        if arms[user].0
+            .binding_names()
+            .iter()
+            .any(|name| required.contains(name))
+        {
+            return;
+        }
+
+        let mut arms = arms.clone();
+        let arm = &mut arms[user].2;
+        *arm = MirNode::new(Expr::Match(value.clone(), vec![(binding.clone(), None, arm.clone())]), arm.meta().clone());
+        // The arm might itself be a match that the `let` can be floated further into
+        self.float_in(arm);
+
+        let floated = Expr::Match(pred.clone(), arms);
+        **expr = floated;
+    }
+
+    fn visit(&self, expr: &mut MirNode<Expr>) {
+        expr.for_children_mut(|expr| self.visit(expr));
+
+        if matches!(&**expr, Expr::Func(_, _)) {
+            self.float_out(expr);
+        } else {
+            self.float_in(expr);
+        }
+    }
+}
+
+impl Pass for FloatLets {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let mut changed = false;
+        for proc in ctx.reachable_procs() {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit(&mut body);
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
@@ -0,0 +1,238 @@
+use super::*;
+
+// The repr of the result of a function with the given repr
+fn output_repr(ctx: &Context, repr: &Repr) -> Repr {
+    match repr {
+        Repr::Func(_, o) => (**o).clone(),
+        Repr::Data(data) => output_repr(ctx, ctx.reprs.get(*data)),
+        repr => panic!("Function has non-function repr {:?}", repr),
+    }
+}
+
+// The repr of the items of a list with the given repr
+fn item_repr(ctx: &Context, repr: &Repr) -> Repr {
+    match repr {
+        Repr::List(item) => (**item).clone(),
+        Repr::Data(data) => item_repr(ctx, ctx.reprs.get(*data)),
+        repr => panic!("List has non-list repr {:?}", repr),
+    }
+}
+
+fn apply(ctx: &Context, f: MirNode<Expr>, arg: MirNode<Expr>) -> MirNode<Expr> {
+    let repr = output_repr(ctx, f.meta());
+    MirNode::new(Expr::Apply(f, arg), repr)
+}
+
+fn local(local: Local, repr: &Repr) -> MirNode<Expr> {
+    MirNode::new(Expr::Local(local), repr.clone())
+}
+
+/// Fuse chains of list combinators (those declared with `$[fuse(...)]`, see [`Fusion`]) into a single traversal of
+/// the list at the start of the chain, such that `xs:filter(p):map(f):fold(init, g)` no longer builds intermediate
+/// lists.
+///
+/// Each chain becomes a new recursive procedure that takes the functions given to the combinators as parameters and
+/// applies each of them in turn to every item of the list. Chains that end in a `fold` accumulate their result as
+/// they go, while chains that end in a `map` or `filter` build only the list that they produce.
+#[derive(Default)]
+pub struct FuseLists;
+
+// If the expression is a call that gives a combinator all of its arguments, returns the combinator and arguments
+fn combinator<'a>(ctx: &Context, expr: &'a MirNode<Expr>) -> Option<(Fusion, Vec<&'a MirNode<Expr>>)> {
+    let (proc, args) = expr.as_call()?;
+    let fusion = ctx.procs.get(proc)?.fusion?;
+    if args.len() == fusion.arity() {
+        Some((fusion, args))
+    } else {
+        None
+    }
+}
+
+// Whether the expression is a call to a combinator that would be fused with its neighbours in a chain. `consumed` is
+// whether the result of the expression is the list given to another combinator.
+pub(super) fn is_chained(ctx: &Context, expr: &MirNode<Expr>, consumed: bool) -> bool {
+    let is_stage = |expr| matches!(combinator(ctx, expr), Some((Fusion::Map | Fusion::Filter, _)));
+    match combinator(ctx, expr) {
+        Some((_, args)) => (consumed && is_stage(expr)) || is_stage(args.last().unwrap()),
+        None => false,
+    }
+}
+
+impl FuseLists {
+
+    // If the expression is the last combinator of a chain, creates a procedure that performs the chain. Returns the
+    // procedure, its body and the expression that should replace the chain.
+    fn fuse(
+        &self,
+        ctx: &mut Context,
+        proc: ProcId,
+        expr: &MirNode<Expr>,
+    ) -> Option<(ProcId, MirNode<Expr>, MirNode<Expr>)> {
+        let (consumer, args) = combinator(ctx, expr)?;
+
+        // The stages of the chain, along with the function given to each
+        let mut stages = Vec::new();
+        let mut source = *args.last().unwrap();
+        while let Some((stage @ (Fusion::Map | Fusion::Filter), stage_args)) = combinator(ctx, source) {
+            stages.push((stage, stage_args[0].clone()));
+            source = stage_args[1];
+        }
+        if stages.is_empty() {
+            return None;
+        }
+        stages.reverse();
+
+        // `(init, f)` if the chain ends in a fold, otherwise the last combinator is just another stage
+        let fold = match consumer {
+            Fusion::Fold => Some((args[0].clone(), args[1].clone())),
+            Fusion::Map | Fusion::Filter => {
+                stages.push((consumer, args[0].clone()));
+                None
+            },
+        };
+
+        let list_repr = source.meta().clone();
+        let out_repr = expr.meta().clone();
+
+        // Parameters of the new procedure: the function of each stage, then the fold function and accumulator (if
+        // any), then the list
+        let stage_params = stages
+            .iter()
+            .map(|(_, f)| (Local::new(), f.meta().clone()))
+            .collect::<Vec<_>>();
+        let fold_params = fold
+            .as_ref()
+            .map(|(init, f)| ((Local::new(), f.meta().clone()), (Local::new(), init.meta().clone())));
+        let list = Local::new();
+        let params = stage_params
+            .iter()
+            .cloned()
+            .chain(fold_params.iter().flat_map(|(f, acc)| [f.clone(), acc.clone()]))
+            .chain(std::iter::once((list, list_repr.clone())))
+            .collect::<Vec<_>>();
+        let go_repr = params
+            .iter()
+            .rev()
+            .fold(out_repr.clone(), |o, (_, i)| Repr::Func(Box::new(i.clone()), Box::new(o)));
+
+        // The body is filled in once the procedure can be referred to
+        let go = ctx.procs.lift(proc, Proc {
+            body: MirNode::new(Expr::Tuple(Vec::new()), Repr::Tuple(Vec::new())),
+            inline: InlineHint::Never,
+            fusion: None,
+        });
+        let ctx = &*ctx;
+
+        let tail = Local::new();
+        let recurse = |acc: Option<MirNode<Expr>>| {
+            let mut call = MirNode::new(Expr::Global(go, Default::default()), go_repr.clone());
+            for (f, f_repr) in &stage_params {
+                call = apply(ctx, call, local(*f, f_repr));
+            }
+            if let (Some(((f, f_repr), _)), Some(acc)) = (&fold_params, acc) {
+                call = apply(ctx, call, local(*f, f_repr));
+                call = apply(ctx, call, acc);
+            }
+            apply(ctx, call, local(tail, &list_repr))
+        };
+        let acc = fold_params.as_ref().map(|(_, (acc, acc_repr))| local(*acc, acc_repr));
+        // Move on to the next item without producing anything for this one
+        let skip = recurse(acc.clone());
+
+        // The local holding the item at each stage of the chain
+        let mut items = vec![(Local::new(), item_repr(ctx, &list_repr))];
+        for (stage, f) in &stages {
+            let item = match stage {
+                Fusion::Map => (Local::new(), output_repr(ctx, f.meta())),
+                _ => items.last().unwrap().clone(),
+            };
+            items.push(item);
+        }
+
+        let (last, last_repr) = items.last().unwrap();
+        let mut body = match (&fold_params, acc) {
+            (Some(((f, f_repr), _)), Some(acc)) => {
+                let f = apply(ctx, local(*f, f_repr), acc);
+                recurse(Some(apply(ctx, f, local(*last, last_repr))))
+            },
+            _ => MirNode::new(Expr::Intrinsic(Intrinsic::Join(last_repr.clone()), vec![
+                MirNode::new(Expr::List(vec![local(*last, last_repr)]), out_repr.clone()),
+                recurse(None),
+            ]), out_repr.clone()),
+        };
+        for (i, (stage, _)) in stages.iter().enumerate().rev() {
+            let (f, f_repr) = &stage_params[i];
+            let (input, input_repr) = &items[i];
+            let applied = apply(ctx, local(*f, f_repr), local(*input, input_repr));
+            body = match stage {
+                Fusion::Map => {
+                    let (output, output_repr) = &items[i + 1];
+                    let binding = MirNode::new(Binding::wildcard(*output), output_repr.clone());
+                    MirNode::new(Expr::Match(applied, vec![(binding, None, body)]), out_repr.clone())
+                },
+                Fusion::Filter => {
+                    let bool_repr = Repr::Prim(Prim::Bool);
+                    let keep = MirNode::new(Binding { pat: Pat::Literal(Literal::Bool(true)), name: None }, bool_repr.clone());
+                    let discard = MirNode::new(Binding { pat: Pat::Wildcard, name: None }, bool_repr);
+                    MirNode::new(Expr::Match(applied, vec![(keep, None, body), (discard, None, skip.clone())]), out_repr.clone())
+                },
+                Fusion::Fold => unreachable!(),
+            };
+        }
+
+        let empty = match &fold_params {
+            Some((_, (acc, acc_repr))) => local(*acc, acc_repr),
+            None => MirNode::new(Expr::Literal(Literal::List(Vec::new())), out_repr.clone()),
+        };
+        let (first, first_repr) = &items[0];
+        let front = MirNode::new(Binding {
+            pat: Pat::ListFront(
+                vec![MirNode::new(Binding::wildcard(*first), first_repr.clone())],
+                Some(MirNode::new(Binding::wildcard(tail), list_repr.clone())),
+            ),
+            name: None,
+        }, list_repr.clone());
+        let rest = MirNode::new(Binding { pat: Pat::Wildcard, name: None }, list_repr.clone());
+        let traverse = MirNode::new(Expr::Match(local(list, &list_repr), vec![(front, None, body), (rest, None, empty)]), out_repr);
+        let go_body = params
+            .iter()
+            .rev()
+            .fold(traverse, |body, (param, param_repr)| {
+                let repr = Repr::Func(Box::new(param_repr.clone()), Box::new(body.meta().clone()));
+                MirNode::new(Expr::Func(*param, body), repr)
+            });
+
+        // Call the new procedure with the arguments that were given to the chain
+        let mut call = MirNode::new(Expr::Global(go, Default::default()), go_repr);
+        for (_, f) in stages {
+            call = apply(ctx, call, f);
+        }
+        if let Some((init, f)) = fold {
+            call = apply(ctx, call, f);
+            call = apply(ctx, call, init);
+        }
+        let call = apply(ctx, call, source.clone());
+
+        Some((go, go_body, call))
+    }
+
+    fn visit(&self, ctx: &mut Context, proc: ProcId, expr: &mut MirNode<Expr>) {
+        if let Some((go, go_body, call)) = self.fuse(ctx, proc, expr) {
+            ctx.procs.get_mut(go).unwrap().body = go_body;
+            *expr = call;
+        }
+        expr.for_children_mut(|expr| self.visit(ctx, proc, expr));
+    }
+}
+
+impl Pass for FuseLists {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let mut changed = false;
+        for proc in ctx.reachable_procs() {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit(ctx, proc, &mut body);
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
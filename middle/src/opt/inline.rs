@@ -0,0 +1,122 @@
+use super::*;
+
+/// Inline procedures into the places that refer to them and beta-reduce functions that are applied directly (i.e:
+/// `(fn x => y)(z)` becomes `let x = z in y`).
+///
+/// Whether a procedure is inlined is determined by a cost model: procedures referred to only once are always inlined,
+/// since doing so does not duplicate code. Otherwise, the size of the procedure is compared against a threshold that
+/// grows with the number of arguments it is given at the reference site, since each of those is an opportunity for
+/// beta-reduction. `$[inline]` and `$[no_inline]` override the cost model, although recursive references are never
+/// inlined, and neither are combinators that [`FuseLists`] may yet fuse into a chain.
+pub struct Inline {
+    /// Procedures no larger than this may be inlined regardless of how often they are referred to.
+    pub threshold: usize,
+    /// The increase in the threshold for every argument a procedure is applied to.
+    pub apply_bonus: usize,
+}
+
+impl Default for Inline {
+    fn default() -> Self { Self::for_mode(OptMode::Fast) }
+}
+
+impl Inline {
+    pub fn for_mode(opt_mode: OptMode) -> Self {
+        match opt_mode {
+            // Only inline procedures that are about as small as the reference to them
+            OptMode::Size => Self {
+                threshold: 3,
+                apply_bonus: 0,
+            },
+            OptMode::None | OptMode::Fast => Self {
+                threshold: 32,
+                apply_bonus: 8,
+            },
+        }
+    }
+
+    fn should_inline(&self, proc: &Proc, can_inline: bool, call_sites: usize, applied: usize) -> bool {
+        match proc.inline {
+            _ if !can_inline => false,
+            // A combinator that has not been given its list may yet join a chain once its caller has been inlined
+            _ if proc.fusion.map_or(false, |fusion| applied < fusion.arity()) => false,
+            InlineHint::Never => false,
+            InlineHint::Always => true,
+            InlineHint::Auto => call_sites <= 1 || size(&proc.body) <= self.threshold + applied * self.apply_bonus,
+        }
+    }
+
+    // `consumed` is whether the expression is the list given to a combinator (see `fuse_lists::is_chained`)
+    fn visit(&self, ctx: &Context, call_sites: &HashMap<ProcId, usize>, expr: &mut MirNode<Expr>, applied: usize, consumed: bool) {
+        // Combinators in a chain must remain recognisable until the chain has been fused, so only their arguments are
+        // visited
+        if fuse_lists::is_chained(ctx, expr, consumed) {
+            self.visit_chained(ctx, call_sites, expr);
+            return;
+        }
+
+        match &mut **expr {
+            Expr::Global(proc_id, flags) => {
+                let proc = ctx.procs.get(*proc_id).unwrap();
+                let call_sites = call_sites.get(proc_id).copied().unwrap_or(0);
+                if self.should_inline(proc, flags.get().can_inline, call_sites, applied) {
+                    **expr = proc.body.inner().clone();
+                    expr.refresh_locals();
+                }
+            },
+            Expr::Apply(f, _) => {
+                self.visit(ctx, call_sites, f, applied + 1, false);
+
+                // Inlining the function may have revealed a chain of combinators that consumes the argument
+                let chained = fuse_lists::is_chained(ctx, expr, consumed);
+                if let Expr::Apply(f, arg) = &mut **expr {
+                    self.visit(ctx, call_sites, arg, 0, chained);
+
+                    if let Expr::Func(param, body) = &**f {
+                        let binding = MirNode::new(Binding::wildcard(*param), arg.meta().clone());
+                        let body = body.clone();
+                        let arg = arg.clone();
+                        **expr = Expr::Match(arg, vec![(binding, None, body)]);
+                    }
+                }
+            },
+            _ => expr.for_children_mut(|expr| self.visit(ctx, call_sites, expr, 0, false)),
+        }
+    }
+
+    // Visit the arguments of a combinator in a chain, the last of which is the list that it consumes
+    fn visit_chained(&self, ctx: &Context, call_sites: &HashMap<ProcId, usize>, expr: &mut MirNode<Expr>) {
+        if let Expr::Apply(f, list) = &mut **expr {
+            self.visit(ctx, call_sites, list, 0, true);
+            let mut f = f;
+            while let Expr::Apply(g, arg) = &mut **f {
+                self.visit(ctx, call_sites, arg, 0, false);
+                f = g;
+            }
+        }
+    }
+}
+
+impl Pass for Inline {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let reachable = ctx.reachable_procs();
+
+        let mut call_sites = HashMap::new();
+        fn count(expr: &Expr, call_sites: &mut HashMap<ProcId, usize>) {
+            if let Expr::Global(proc, _) = expr {
+                *call_sites.entry(*proc).or_insert(0) += 1;
+            }
+            expr.for_children(|expr| count(expr, call_sites));
+        }
+        for proc in reachable.iter() {
+            count(&ctx.procs.get(*proc).unwrap().body, &mut call_sites);
+        }
+
+        let mut changed = false;
+        for proc in reachable {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit(ctx, &call_sites, &mut body, 0, false);
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
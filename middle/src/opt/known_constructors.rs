@@ -0,0 +1,156 @@
+use super::*;
+
+// A local that is known to hold a specific variant of a sum, along with the local (if any) that holds its inner value
+struct Known {
+    local: Local,
+    variant: usize,
+    inner: Option<(Local, Repr)>,
+}
+
+/// Propagate knowledge about which variant of a sum a value is.
+///
+/// A match on a value that is constructed directly (i.e: `match Just x in | Just y => a | None => b`) is reduced to a
+/// match on the inner value (`match x in y => a`). Within the arm of a match on a local, the local is known to be the
+/// variant that the arm matched, so any further matches on it have their unreachable arms removed and any variant
+/// accesses are replaced with the inner value bound by the arm. In combination with [`CaseOfCase`], this removes many
+/// intermediate sum values that exist only to be immediately matched upon.
+#[derive(Default)]
+pub struct KnownConstructors;
+
+impl KnownConstructors {
+    // Reduce a match on a value that is known to be the given variant to a match on its inner value. `whole` is an
+    // expression that refers to the whole value, which is required if any arms give the whole value a name.
+    fn select_variant(
+        &self,
+        whole: Option<&MirNode<Expr>>,
+        variant: usize,
+        inner: MirNode<Expr>,
+        arms: &[(MirNode<Binding>, Option<MirNode<Expr>>, MirNode<Expr>)],
+        repr: &Repr,
+    ) -> Option<Expr> {
+        let mut selected = Vec::new();
+        for (binding, guard, body) in arms {
+            let mut names = Vec::new();
+            let inner_binding = match &binding.strip_names(&mut names).pat {
+                Pat::Variant(v, _) if *v != variant => continue,
+                Pat::Variant(_, inner_binding) => inner_binding.clone(),
+                Pat::Wildcard => MirNode::new(Binding { pat: Pat::Wildcard, name: None }, inner.meta().clone()),
+                _ => return None,
+            };
+
+            let mut guard = guard.clone();
+            let mut body = body.clone();
+            for name in names.into_iter().rev() {
+                let binding = MirNode::new(Binding::wildcard(name), binding.meta().clone());
+                guard = match guard {
+                    Some(guard) => {
+                        let guard_repr = guard.meta().clone();
+                        Some(MirNode::new(Expr::Match(whole?.clone(), vec![(binding.clone(), None, guard)]), guard_repr))
+                    },
+                    None => None,
+                };
+                body = MirNode::new(Expr::Match(whole?.clone(), vec![(binding, None, body)]), repr.clone());
+            }
+
+            let is_refutable = inner_binding.is_refutable() || guard.is_some();
+            selected.push((inner_binding, guard, body));
+            // Any arms that follow are unreachable
+            if !is_refutable {
+                break;
+            }
+        }
+
+        if selected.is_empty() {
+            None
+        } else {
+            drop_last_guard(&mut selected);
+            Some(Expr::Match(inner, selected))
+        }
+    }
+
+    fn reduce(&self, expr: &MirNode<Expr>, known: &[Known]) -> Option<Expr> {
+        let find = |local: Local| known.iter().rev().find(|k| k.local == local);
+
+        match &**expr {
+            Expr::Match(pred, arms) => match &**pred {
+                Expr::Variant(variant, inner) => self.select_variant(None, *variant, inner.clone(), arms, expr.meta()),
+                Expr::Local(local) => {
+                    let known = find(*local)?;
+                    if let Some((inner, inner_repr)) = &known.inner {
+                        let inner = MirNode::new(Expr::Local(*inner), inner_repr.clone());
+                        self.select_variant(Some(pred), known.variant, inner, arms, expr.meta())
+                    } else {
+                        // Without access to the inner value, the best we can do is remove arms that cannot match
+                        let mut reachable = arms
+                            .iter()
+                            .filter(|(binding, _, _)| match &binding.strip_names(&mut Vec::new()).pat {
+                                Pat::Variant(v, _) => *v == known.variant,
+                                _ => true,
+                            })
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        if reachable.len() < arms.len() {
+                            drop_last_guard(&mut reachable);
+                            Some(Expr::Match(pred.clone(), reachable))
+                        } else {
+                            None
+                        }
+                    }
+                },
+                _ => None,
+            },
+            Expr::AccessVariant(inner, variant) => match &**inner {
+                Expr::Variant(v, inner) if v == variant => Some(inner.inner().clone()),
+                Expr::Local(local) => match find(*local)? {
+                    Known { variant: v, inner: Some((inner, _)), .. } if v == variant => Some(Expr::Local(*inner)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn visit(&self, expr: &mut MirNode<Expr>, known: &mut Vec<Known>) {
+        while let Some(reduced) = self.reduce(expr, known) {
+            **expr = reduced;
+        }
+
+        if let Expr::Match(pred, arms) = &mut **expr {
+            self.visit(pred, known);
+
+            let scrutinee = if let Expr::Local(local) = &**pred { Some(*local) } else { None };
+            for (binding, guard, body) in arms {
+                let old_known = known.len();
+
+                let mut names = Vec::new();
+                if let Pat::Variant(variant, inner) = &binding.strip_names(&mut names).pat {
+                    let inner = inner.name.map(|name| (name, inner.meta().clone()));
+                    for local in scrutinee.into_iter().chain(names) {
+                        known.push(Known { local, variant: *variant, inner: inner.clone() });
+                    }
+                }
+
+                if let Some(guard) = guard {
+                    self.visit(guard, known);
+                }
+                self.visit(body, known);
+                known.truncate(old_known);
+            }
+        } else {
+            expr.for_children_mut(|expr| self.visit(expr, known));
+        }
+    }
+}
+
+impl Pass for KnownConstructors {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let mut changed = false;
+        for proc in ctx.reachable_procs() {
+            let mut body = ctx.procs.get(proc).unwrap().body.clone();
+            self.visit(&mut body, &mut Vec::new());
+            changed |= ctx.set_body(proc, body);
+        }
+        changed
+    }
+}
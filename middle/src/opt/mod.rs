@@ -1,32 +1,122 @@
 use super::*;
 use std::any::{Any, type_name};
 
+mod case_of_case;
+mod closure_conversion;
+mod common_subexprs;
 mod const_fold;
+mod data_reprs;
+mod decision_trees;
 mod flatten_single_field;
+mod float_lets;
+mod fuse_lists;
+mod inline;
+mod known_constructors;
+mod remove_dead_procs;
 mod remove_unused_bindings;
 
 pub use {
+    case_of_case::CaseOfCase,
+    closure_conversion::ClosureConversion,
+    common_subexprs::CommonSubexprs,
     const_fold::ConstFold,
+    data_reprs::{FlattenSingleVariant, TagUnitSums, NicheSums},
+    decision_trees::DecisionTrees,
     flatten_single_field::FlattenSingleField,
+    float_lets::FloatLets,
+    fuse_lists::FuseLists,
+    inline::Inline,
+    known_constructors::KnownConstructors,
+    remove_dead_procs::RemoveDeadProcs,
     remove_unused_bindings::RemoveUnusedBindings,
 };
 
 pub trait Pass: Any {
-    fn apply(&mut self, ctx: &mut Context);
+    /// Apply the pass to the MIR, returning whether it changed anything.
+    fn apply(&mut self, ctx: &mut Context) -> bool;
 
-    fn run(&mut self, ctx: &mut Context, debug: bool) {
-        self.apply(ctx);
-
-        if debug {
-            println!("\nMIR after {}:\n\n", type_name::<Self>());
-            for (id, proc) in ctx.procs.iter() {
-                println!("PROCEDURE {:?}\n\n{}\n", id, proc.body.print());
+    /// The name of the pass, as accepted by `--passes`. By default, this is derived from the name of the type, such
+    /// that `ConstFold` becomes `const_fold`.
+    fn name(&self) -> String {
+        let ty_name = type_name::<Self>().rsplit("::").next().unwrap();
+        let mut name = String::new();
+        for (i, c) in ty_name.chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                name.push('_');
             }
+            name.extend(c.to_lowercase());
+        }
+        name
+    }
+
+    fn run(&mut self, ctx: &mut Context) -> bool {
+        let changed = self.apply(ctx);
+
+        if cfg!(debug_assertions) {
             opt::check(ctx);
         }
+
+        changed
     }
 }
 
+/// The names of all passes that may be created with [`pass_from_name`].
+pub const PASS_NAMES: &[&str] = &[
+    "flatten_single_field",
+    "flatten_single_variant",
+    "tag_unit_sums",
+    "niche_sums",
+    "inline",
+    "const_fold",
+    "fuse_lists",
+    "case_of_case",
+    "known_constructors",
+    "common_subexprs",
+    "float_lets",
+    "decision_trees",
+    "remove_unused_bindings",
+    "remove_dead_procs",
+    "closure_conversion",
+];
+
+/// Create a pass from its name, configured appropriately for the given optimisation mode.
+pub fn pass_from_name(name: &str, opt_mode: OptMode) -> Option<Box<dyn Pass>> {
+    match name {
+        "flatten_single_field" => Some(Box::new(FlattenSingleField::default())),
+        "flatten_single_variant" => Some(Box::new(FlattenSingleVariant::default())),
+        "tag_unit_sums" => Some(Box::new(TagUnitSums::default())),
+        "niche_sums" => Some(Box::new(NicheSums::default())),
+        "inline" => Some(Box::new(Inline::for_mode(opt_mode))),
+        "const_fold" => Some(Box::new(ConstFold::default())),
+        "fuse_lists" => Some(Box::new(FuseLists::default())),
+        "case_of_case" => Some(Box::new(CaseOfCase::default())),
+        "known_constructors" => Some(Box::new(KnownConstructors::default())),
+        "common_subexprs" => Some(Box::new(CommonSubexprs::default())),
+        "float_lets" => Some(Box::new(FloatLets::default())),
+        "decision_trees" => Some(Box::new(DecisionTrees::default())),
+        "remove_unused_bindings" => Some(Box::new(RemoveUnusedBindings::default())),
+        "remove_dead_procs" => Some(Box::new(RemoveDeadProcs::default())),
+        "closure_conversion" => Some(Box::new(ClosureConversion::default())),
+        _ => None,
+    }
+}
+
+// Remove the guard of the last arm of a match from which arms that cannot match have been removed. Guarded arms do not
+// count towards exhaustivity, so if the last remaining arm is guarded then the unguarded arms before it already cover
+// every value that can reach the match and the arm is unreachable.
+fn drop_last_guard(arms: &mut [(MirNode<Binding>, Option<MirNode<Expr>>, MirNode<Expr>)]) {
+    if let Some((_, guard, _)) = arms.last_mut() {
+        *guard = None;
+    }
+}
+
+// An approximation of the amount of code an expression produces
+fn size(expr: &Expr) -> usize {
+    let mut n = 1;
+    expr.for_children(|expr| n += size(expr));
+    n
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum VisitOrder {
     First,
@@ -45,13 +135,10 @@ impl Context {
             .iter_mut()
             .for_each(|(_, proc)| proc.body.visit_inner(order, repr, binding, expr));
 
+        // `Reprs::iter_mut` yields every defined data type, so each is visited (and rewritten) exactly once
         self.reprs
             .iter_mut()
             .for_each(|r| r.visit_inner(order, repr, binding, expr));
-
-        self.reprs.datas
-            .values_mut()
-            .for_each(|r| r.as_mut().unwrap().visit_inner(order, repr, binding, expr));
     }
     pub fn visit(
         &mut self,
@@ -109,11 +196,22 @@ impl Repr {
 }
 
 impl Binding {
+    // Strip `Single` wrappers from the top of a binding, collecting the names that were given to the whole value
+    fn strip_names<'a>(self: &'a MirNode<Self>, names: &mut Vec<Local>) -> &'a MirNode<Self> {
+        if let Some(name) = self.name {
+            names.push(name);
+        }
+        match &self.pat {
+            mir::Pat::Single(inner) => inner.strip_names(names),
+            _ => self,
+        }
+    }
+
     pub fn for_children(&self, mut f: impl FnMut(&MirNode<Self>)) {
         match &self.pat {
-            mir::Pat::Wildcard | mir::Pat::Literal(_) => {},
+            mir::Pat::Wildcard | mir::Pat::Literal(_) | mir::Pat::Range(_, _) => {},
             mir::Pat::Single(inner) => f(inner),
-            mir::Pat::Add(lhs, _) => f(lhs),
+            mir::Pat::Add(lhs, _) | mir::Pat::Mul(lhs, _) => f(lhs),
             mir::Pat::Tuple(fields) => fields
                 .iter()
                 .for_each(|field| f(field)),
@@ -128,14 +226,17 @@ impl Binding {
             },
             mir::Pat::Variant(_, inner) => f(inner),
             mir::Pat::UnionVariant(_, inner) => f(inner),
+            mir::Pat::Or(alts) => alts
+                .iter()
+                .for_each(|alt| f(alt)),
         }
     }
 
     pub fn for_children_mut(&mut self, mut f: impl FnMut(&mut MirNode<Self>)) {
         match &mut self.pat {
-            mir::Pat::Wildcard | mir::Pat::Literal(_) => {},
+            mir::Pat::Wildcard | mir::Pat::Literal(_) | mir::Pat::Range(_, _) => {},
             mir::Pat::Single(inner) => f(inner),
-            mir::Pat::Add(lhs, _) => f(lhs),
+            mir::Pat::Add(lhs, _) | mir::Pat::Mul(lhs, _) => f(lhs),
             mir::Pat::Tuple(fields) => fields
                 .iter_mut()
                 .for_each(|field| f(field)),
@@ -150,6 +251,9 @@ impl Binding {
             },
             mir::Pat::Variant(_, inner) => f(inner),
             mir::Pat::UnionVariant(_, inner) => f(inner),
+            mir::Pat::Or(alts) => alts
+                .iter_mut()
+                .for_each(|alt| f(alt)),
         }
     }
 
@@ -167,9 +271,9 @@ impl Binding {
         self.meta_mut().visit_inner(order, repr, binding, expr);
 
         match &mut self.pat {
-            mir::Pat::Wildcard | mir::Pat::Literal(_) => {},
+            mir::Pat::Wildcard | mir::Pat::Literal(_) | mir::Pat::Range(_, _) => {},
             mir::Pat::Single(inner) => inner.visit_inner(order, repr, binding, expr),
-            mir::Pat::Add(lhs, _) => lhs.visit_inner(order, repr, binding, expr),
+            mir::Pat::Add(lhs, _) | mir::Pat::Mul(lhs, _) => lhs.visit_inner(order, repr, binding, expr),
             mir::Pat::Tuple(fields) => fields
                 .iter_mut()
                 .for_each(|field| field.visit_inner(order, repr, binding, expr)),
@@ -184,6 +288,9 @@ impl Binding {
             },
             mir::Pat::Variant(_, inner) => inner.visit_inner(order, repr, binding, expr),
             mir::Pat::UnionVariant(_, inner) => inner.visit_inner(order, repr, binding, expr),
+            mir::Pat::Or(alts) => alts
+                .iter_mut()
+                .for_each(|alt| alt.visit_inner(order, repr, binding, expr)),
         }
 
         if order == VisitOrder::Last {
@@ -193,6 +300,18 @@ impl Binding {
 }
 
 impl Expr {
+    // If the expression is a `let` (i.e: a match with a single arm that binds the whole value to a name), returns the
+    // name
+    fn let_name(&self) -> Option<Local> {
+        match self {
+            Expr::Match(_, arms) if arms.len() == 1 => match &*arms[0].0 {
+                Binding { pat: mir::Pat::Wildcard, name: Some(name) } => Some(*name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn for_children(&self, mut f: impl FnMut(&MirNode<Self>)) {
         match self {
             Expr::Literal(_) | Expr::Local(_) | Expr::Global(_, _) => {},
@@ -207,7 +326,10 @@ impl Expr {
                 .for_each(|item| f(item)),
             Expr::Match(pred, arms) => {
                 f(pred);
-                for (_, body) in arms {
+                for (_, guard, body) in arms {
+                    if let Some(guard) = guard {
+                        f(guard);
+                    }
                     f(body);
                 }
             },
@@ -239,7 +361,10 @@ impl Expr {
                 .for_each(|item| f(item)),
             Expr::Match(pred, arms) => {
                 f(pred);
-                for (_, body) in arms {
+                for (_, guard, body) in arms {
+                    if let Some(guard) = guard {
+                        f(guard);
+                    }
                     f(body);
                 }
             },
@@ -259,11 +384,18 @@ impl Expr {
 
     pub fn inline_local(&mut self, name: Local, local_expr: &Self) {
         match self {
-            Expr::Local(local) if *local == name => *self = local_expr.clone(),
+            Expr::Local(local) if *local == name => {
+                *self = local_expr.clone();
+                // The expression may be inlined in several places, each of which must bind its own locals
+                self.refresh_bound_locals();
+            },
             Expr::Match(pred, arms) => {
                 pred.inline_local(name, local_expr);
-                for (arm, body) in arms {
+                for (arm, guard, body) in arms {
                     if !arm.binding_names().contains(&name) {
+                        if let Some(guard) = guard {
+                            guard.inline_local(name, local_expr);
+                        }
                         body.inline_local(name, local_expr);
                     }
                 }
@@ -303,8 +435,11 @@ impl Expr {
                 .for_each(|item| item.visit_inner(order, repr, binding, expr)),
             Expr::Match(pred, arms) => {
                 pred.visit_inner(order, repr, binding, expr);
-                for (b, body) in arms {
+                for (b, guard, body) in arms {
                     b.visit_inner(order, repr, binding, expr);
+                    if let Some(guard) = guard {
+                        guard.visit_inner(order, repr, binding, expr);
+                    }
                     body.visit_inner(order, repr, binding, expr);
                 }
             },
@@ -351,85 +486,655 @@ pub fn prepare(ctx: &mut Context) {
     }
 }
 
-/// Check the self-consistency of the MIR
+/// Check the self-consistency of the MIR, panicking if an inconsistency is found.
+///
+/// This is not a type checker: representations are only compared where they are known to be comparable, so data types
+/// are treated as compatible with anything.
 pub fn check(ctx: &Context) {
-    fn check_binding(ctx: &Context, binding: &Binding, repr: &Repr, stack: &mut Vec<(Local, Repr)>) {
-        match (&binding.pat, repr) {
-            (Pat::Literal(Literal::Bool(_)), Repr::Prim(Prim::Bool)) => {},
-            (Pat::Wildcard, _) => {},
-            (Pat::Tuple(a), Repr::Tuple(b)) if a.len() == b.len() => {},
-            (Pat::Variant(_, _), Repr::Data(_)) => {},
-            (Pat::Single(inner), _) => check_binding(ctx, inner, repr, stack),
-            (Pat::UnionVariant(_, _), Repr::Union(_)) => {},
-            (Pat::ListExact(_), Repr::List(_)) => {},
-            (Pat::ListFront(_, _), Repr::List(_)) => {},
-            (_, repr) => panic!("Inconsistency between binding\n\n {:?}\n\nand repr {:?}", binding, repr),
+    fn check_binding(binding: &MirNode<Binding>, repr: &Repr) {
+        let is_consistent = match (&binding.pat, repr) {
+            (_, Repr::Data(_)) => true,
+            (Pat::Wildcard | Pat::Literal(_) | Pat::Single(_) | Pat::Or(_), _) => true,
+            (Pat::Add(_, _) | Pat::Mul(_, _), Repr::Prim(Prim::Nat | Prim::Int)) => true,
+            (Pat::Range(_, _), Repr::Prim(Prim::Nat | Prim::Int | Prim::Char)) => true,
+            (Pat::Tuple(a), Repr::Tuple(b)) => a.len() == b.len(),
+            (Pat::ListExact(_) | Pat::ListFront(_, _), Repr::List(_)) => true,
+            (Pat::Variant(idx, _), Repr::Sum(variants)) => *idx < variants.len(),
+            (Pat::UnionVariant(_, _), Repr::Union(_)) => true,
+            _ => false,
+        };
+        assert!(is_consistent, "Inconsistency between binding\n\n {:?}\n\nand repr {:?}", binding.inner(), repr);
+
+        if let Pat::Single(inner) = &binding.pat {
+            check_binding(inner, repr);
+        } else if let Pat::Or(alts) = &binding.pat {
+            let mut names = alts[0].binding_names();
+            names.sort_by_key(|name| name.0);
+            for alt in alts {
+                let mut alt_names = alt.binding_names();
+                alt_names.sort_by_key(|name| name.0);
+                assert_eq!(names, alt_names, "Alternatives of or-pattern bind different locals");
+                check_binding(alt, repr);
+            }
+        } else {
+            binding.for_children(|binding| check_binding(binding, binding.meta()));
         }
+    }
 
-        binding.for_children(|binding| check_binding(ctx, binding, binding.meta(), stack));
+    // Data types are opaque until their representation is chosen, so they are compatible with anything
+    fn compatible(a: &Repr, b: &Repr) -> bool {
+        match (a, b) {
+            (Repr::Data(_), _) | (_, Repr::Data(_)) => true,
+            (Repr::Prim(a), Repr::Prim(b)) => a == b,
+            (Repr::List(a), Repr::List(b)) => compatible(a, b),
+            (Repr::Tuple(a), Repr::Tuple(b))
+            | (Repr::Sum(a), Repr::Sum(b))
+            | (Repr::Union(a), Repr::Union(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| compatible(a, b)),
+            (Repr::Func(a_i, a_o), Repr::Func(b_i, b_o)) => compatible(a_i, b_i) && compatible(a_o, b_o),
+            _ => false,
+        }
     }
 
-    fn check_expr(ctx: &Context, expr: &Expr, repr: &Repr, stack: &mut Vec<(Local, Repr)>) {
-        match (expr, repr) {
-            // TODO: Check literals elsewhere
-            (Expr::Literal(Literal::Bool(_)), Repr::Prim(Prim::Bool)) => {},
-            (Expr::Literal(Literal::Nat(_)), Repr::Prim(Prim::Nat)) => {},
-            (Expr::Literal(Literal::List(_)), Repr::List(_)) => {},
-            (Expr::Literal(Literal::Sum(_, _)), _) => {},
-            (Expr::Global(_, _), _) => {}, // TODO
-            (Expr::Local(local), repr) if &stack
-                .iter()
-                .rev()
-                .find(|(name, _)| name == local)
-                .unwrap_or_else(|| panic!("Failed to find local ${} in scope", local.0)).1 == repr => {},
-            (Expr::Func(i, body), Repr::Func(i_repr, _)) => {
-                stack.push((*i, (**i_repr).clone()));
-                visit_expr(ctx, body, stack);
+    fn check_literal(literal: &Literal, repr: &Repr) {
+        let is_consistent = match (literal, repr) {
+            (_, Repr::Data(_)) => true,
+            (Literal::Nat(_), Repr::Prim(Prim::Nat))
+            | (Literal::Int(_), Repr::Prim(Prim::Int))
+            | (Literal::Real(_), Repr::Prim(Prim::Real))
+            | (Literal::Char(_), Repr::Prim(Prim::Char))
+            | (Literal::Bool(_), Repr::Prim(Prim::Bool))
+            | (Literal::Union(_, _), Repr::Union(_)) => true,
+            (Literal::Tuple(fields), Repr::Tuple(reprs)) => {
+                assert_eq!(fields.len(), reprs.len(), "Tuple literal has the wrong number of fields for its repr");
+                fields.iter().zip(reprs).for_each(|(field, repr)| check_literal(field, repr));
+                true
+            },
+            (Literal::List(items), Repr::List(item_repr)) => {
+                items.iter().for_each(|item| check_literal(item, item_repr));
+                true
+            },
+            (Literal::Sum(idx, inner), Repr::Sum(variants)) if *idx < variants.len() => {
+                check_literal(inner, &variants[*idx]);
+                true
+            },
+            _ => false,
+        };
+        assert!(is_consistent, "Inconsistency between literal {:?} and repr {:?}", literal, repr);
+    }
+
+    // Every local is bound exactly once, so `bound` contains every local that has been bound so far, in any scope
+    fn bind(stack: &mut Vec<(Local, Repr)>, bound: &mut HashSet<Local>, local: Local, repr: Repr) {
+        assert!(bound.insert(local), "Local ${} is bound more than once", local.0);
+        stack.push((local, repr));
+    }
+
+    fn check_expr(expr: &MirNode<Expr>, stack: &mut Vec<(Local, Repr)>, bound: &mut HashSet<Local>) {
+        match &**expr {
+            Expr::Local(local) => {
+                let (_, repr) = stack
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| name == local)
+                    .unwrap_or_else(|| panic!("Local ${} is used outside of its scope", local.0));
+                assert!(
+                    compatible(repr, expr.meta()),
+                    "Local ${} is bound with repr {:?} but used with repr {:?}",
+                    local.0,
+                    repr,
+                    expr.meta(),
+                );
+            },
+            Expr::Literal(literal) => check_literal(literal, expr.meta()),
+            Expr::Func(arg, body) => {
+                let arg_repr = match expr.meta() {
+                    Repr::Func(i, _) => (**i).clone(),
+                    Repr::Data(data) => Repr::Data(*data),
+                    repr => panic!("Function has non-function repr {:?}", repr),
+                };
+                bind(stack, bound, *arg, arg_repr);
+                check_expr(body, stack, bound);
                 stack.pop();
             },
-            (Expr::Apply(f, arg), _) => {
-                assert!(matches!(f.meta(), Repr::Func(_, _)));
+            Expr::Apply(f, arg) => {
+                assert!(matches!(f.meta(), Repr::Func(_, _) | Repr::Data(_)), "Applied value has non-function repr {:?}", f.meta());
+                check_expr(f, stack, bound);
+                check_expr(arg, stack, bound);
             },
-            (Expr::Tuple(a), Repr::Tuple(b)) if a.len() == b.len() => {
-                expr.for_children(|expr| visit_expr(ctx, expr, stack));
+            Expr::Tuple(fields) => {
+                if let Repr::Tuple(reprs) = expr.meta() {
+                    assert_eq!(fields.len(), reprs.len(), "Tuple has the wrong number of fields for its repr");
+                }
+                expr.for_children(|expr| check_expr(expr, stack, bound));
             },
-            (Expr::List(items), Repr::List(b)) => {
-                for item in items {
-                    check_expr(ctx, item, b, stack);
+            Expr::Variant(idx, inner) => {
+                if let Repr::Sum(variants) = expr.meta() {
+                    assert!(*idx < variants.len(), "Variant #{} does not exist in repr {:?}", idx, expr.meta());
+                    assert!(
+                        compatible(inner.meta(), &variants[*idx]),
+                        "Variant #{} has inner repr {:?} but its sum expects {:?}",
+                        idx,
+                        inner.meta(),
+                        variants[*idx],
+                    );
                 }
+                check_expr(inner, stack, bound);
             },
-            (Expr::Match(pred, arms), repr) => {
-                for (arm, body) in arms {
-                    // TODO: visit binding
-                    check_binding(ctx, arm.inner(), pred.meta(), stack);
+            Expr::Match(pred, arms) => {
+                check_expr(pred, stack, bound);
+                for (i, (arm, guard, body)) in arms.iter().enumerate() {
+                    check_binding(arm, pred.meta());
+                    assert!(guard.is_none() || i + 1 < arms.len(), "The last arm of a match has a guard");
                     let old_stack = stack.len();
-                    stack.append(&mut arm.bindings());
-                    check_expr(ctx, body, body.meta(), stack);
+                    for (name, repr) in arm.bindings() {
+                        bind(stack, bound, name, repr);
+                    }
+                    if let Some(guard) = guard {
+                        assert!(matches!(guard.meta(), Repr::Prim(Prim::Bool)), "Guard has non-boolean repr {:?}", guard.meta());
+                        check_expr(guard, stack, bound);
+                    }
+                    check_expr(body, stack, bound);
                     stack.truncate(old_stack);
                 }
             },
-            (Expr::Variant(idx, inner), Repr::Sum(variants)) if *idx < variants.len() => {
-                check_expr(ctx, inner, &variants[*idx], stack);
-                expr.for_children(|expr| visit_expr(ctx, expr, stack));
-            },
-            (expr, Repr::Data(data)) => {
-                check_expr(ctx, expr, ctx.reprs.get(*data), stack);
-            },
-            (Expr::Variant(idx, inner), Repr::Data(_)) => {}, // TODO
-            (Expr::Intrinsic(_, _), _) => {}, // TODO
-            // (Expr::Data(_, _, _), Repr::Func(_, _)) => {},
-            (expr, repr) => panic!("Inconsistency between expression\n\n {:?}\n\nand repr {:?}", expr, repr),
+            _ => expr.for_children(|expr| check_expr(expr, stack, bound)),
         }
     }
 
-    fn visit_expr(ctx: &Context, expr: &MirNode<Expr>, stack: &mut Vec<(Local, Repr)>) {
-        check_expr(ctx, expr.inner(), expr.meta(), stack);
+    for (id, proc) in ctx.procs.iter() {
+        assert_eq!(proc.body.required_locals(None).len(), 0, "Procedure {:?} requires locals", id);
+        check_expr(&proc.body, &mut Vec::new(), &mut HashSet::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::tests::normalize;
+
+    // Run the passes over the program until they stop changing it, then compare the result with the expected program
+    fn check(passes: &[&str], before: &str, after: &str) {
+        check_with(passes, before, after, |_| {});
     }
 
-    for (id, proc) in ctx.procs.iter() {
-        println!("Checking {:?}", id);
-        println!("{}", proc.body.print());
-        assert_eq!(proc.body.required_locals(None).len(), 0, "Procedure requires locals");
-        visit_expr(ctx, &proc.body, &mut Vec::new());
+    // Like `check`, but `setup` may first adjust what textual MIR cannot express (such as the fusion of a procedure)
+    fn check_with(passes: &[&str], before: &str, after: &str, setup: impl FnOnce(&mut Context)) {
+        let mut ctx = Context::parse(before).unwrap_or_else(|e| panic!("{}", e));
+        setup(&mut ctx);
+        let passes = passes
+            .iter()
+            .map(|name| pass_from_name(name, OptMode::Fast).unwrap())
+            .collect();
+        ctx.run_passes(passes, |_, _| {});
+        let expected = Context::parse(after).unwrap_or_else(|e| panic!("{}", e));
+        // Locals are only unique within a procedure, so each procedure is normalised on its own
+        let procs = |src: &str| src
+            .split("\n\nproc ")
+            .map(normalize)
+            .collect::<Vec<_>>();
+        assert_eq!(procs(&ctx.print()), procs(&expected.print()));
+    }
+
+    #[test]
+    fn const_fold_arithmetic() {
+        check(&["const_fold"], "
+            proc main : Nat =
+                ((const 2 + const 3) * const 4)
+        ", "
+            proc main : Nat =
+                const 20
+        ");
+    }
+
+    #[test]
+    fn inline_small_procs() {
+        check(&["inline", "const_fold", "remove_dead_procs"], "
+            proc main : Nat =
+                (global double)(const 4)
+
+            proc double : (Nat -> Nat) =
+                fn $0 =>
+                    ($0 + $0)
+        ", "
+            proc main : Nat =
+                let $0 = const 4 in
+                const 8
+        ");
+    }
+
+    #[test]
+    fn const_fold_guards() {
+        check(&["const_fold"], "
+            proc main : Nat =
+                match const 4 in
+                    | $0 if ($0 > const 10) => const 1
+                    | _ => const 2
+        ", "
+            proc main : Nat =
+                let _ = const 4 in
+                const 2
+        ");
+    }
+
+    #[test]
+    fn decision_trees_group_variants() {
+        check(&["decision_trees"], "
+            proc main : (#(Nat | Nat) -> Nat) =
+                fn $0 =>
+                    match $0 in
+                        | #0 const 0 => const 1
+                        | #1 $1 => $1
+                        | #0 $2 => $2
+        ", "
+            proc main : (#(Nat | Nat) -> Nat) =
+                fn $0 =>
+                    match $0 in
+                        | #0 $1 => (match $1 in
+                            | const 0 => const 1
+                            | $2 => $2)
+                        | #1 $3 => let $4 = $3 in $4
+        ");
+    }
+
+    #[test]
+    fn decision_trees_switch_on_column() {
+        check(&["decision_trees"], "
+            proc main : ((#(Nat | ()), Nat,) -> Nat) =
+                fn $0 =>
+                    match $0 in
+                        | (#0 $1, const 0,) => $1
+                        | (#1 _, $2,) => $2
+                        | (_, $3,) => $3
+        ", "
+            proc main : ((#(Nat | ()), Nat,) -> Nat) =
+                fn $0 =>
+                    match ($0).0 in
+                        | #0 $1 => (match (($0).0, ($0).1, $1,) in
+                            | (_, const 0, $2,) => $2
+                            | (_, $3, _,) => $3)
+                        | #1 $4 => (match (($0).0, ($0).1, $4,) in
+                            | (_, $5, _,) => $5
+                            | (_, $6, _,) => $6)
+                        | _ => let (_, $7,) = $0 in $7
+        ");
+    }
+
+    #[test]
+    fn case_of_case_duplicates_outer_arms() {
+        check(&["case_of_case"], "
+            proc main : (Bool -> Nat) =
+                fn $0 =>
+                    match (match $0 in
+                        | const true => (#0 const 1 : #(Nat | ()))
+                        | _ => (#1 () : #(Nat | ()))) in
+                        | #0 $1 => $1
+                        | _ => const 0
+        ", "
+            proc main : (Bool -> Nat) =
+                fn $0 =>
+                    match $0 in
+                        | const true => (match (#0 const 1 : #(Nat | ())) in
+                            | #0 $1 => $1
+                            | _ => const 0)
+                        | _ => (match (#1 () : #(Nat | ())) in
+                            | #0 $2 => $2
+                            | _ => const 0)
+        ");
+    }
+
+    #[test]
+    fn known_constructors_within_arm() {
+        check(&["known_constructors"], "
+            proc main : (#(Nat | ()) -> Nat) =
+                fn $0 =>
+                    match $0 in
+                        | #0 $1 => (match $0 in
+                            | #0 $2 => $2
+                            | _ => const 0)
+                        | _ => const 1
+        ", "
+            proc main : (#(Nat | ()) -> Nat) =
+                fn $0 =>
+                    match $0 in
+                        | #0 $1 => let $2 = $1 in $2
+                        | _ => const 1
+        ");
+    }
+
+    #[test]
+    fn common_subexprs_share_calls() {
+        check(&["common_subexprs"], "
+            proc main : (Nat -> Nat) =
+                fn $0 =>
+                    ((global double)($0) + (global double)($0))
+
+            proc double : (Nat -> Nat) =
+                fn $0 =>
+                    ($0 + $0)
+        ", "
+            proc main : (Nat -> Nat) =
+                fn $0 =>
+                    let $1 = (global double)($0) in
+                    ($1 + $1)
+
+            proc double : (Nat -> Nat) =
+                fn $0 =>
+                    ($0 + $0)
+        ");
+    }
+
+    #[test]
+    fn common_subexprs_survive_remove_unused_bindings() {
+        check(&["common_subexprs", "remove_unused_bindings"], "
+            proc main : (Nat -> Nat) =
+                fn $0 =>
+                    let $1 = ($0 + $0) in
+                    ((global double)($1) + (global double)($1))
+
+            proc double : (Nat -> Nat) =
+                fn $0 =>
+                    ($0 + $0)
+        ", "
+            proc main : (Nat -> Nat) =
+                fn $0 =>
+                    let $2 = (global double)(($0 + $0)) in
+                    ($2 + $2)
+
+            proc double : (Nat -> Nat) =
+                fn $0 =>
+                    ($0 + $0)
+        ");
+    }
+
+    #[test]
+    fn float_lets_out_of_functions() {
+        check(&["float_lets"], "
+            proc main : (Nat -> (Nat -> Nat)) =
+                fn $0 =>
+                    fn $1 =>
+                        let $2 = ($0 + const 1) in
+                        ($1 * $2)
+        ", "
+            proc main : (Nat -> (Nat -> Nat)) =
+                fn $0 =>
+                    let $2 = ($0 + const 1) in
+                    fn $1 =>
+                        ($1 * $2)
+        ");
+    }
+
+    #[test]
+    fn float_lets_into_arms() {
+        check(&["float_lets"], "
+            proc main : (Bool -> Nat) =
+                fn $0 =>
+                    let $1 = (global double)(const 4) in
+                    match $0 in
+                        | const true => $1
+                        | _ => const 0
+
+            proc double : (Nat -> Nat) =
+                fn $0 =>
+                    ($0 + $0)
+        ", "
+            proc main : (Bool -> Nat) =
+                fn $0 =>
+                    match $0 in
+                        | const true => let $1 = (global double)(const 4) in $1
+                        | _ => const 0
+
+            proc double : (Nat -> Nat) =
+                fn $0 =>
+                    ($0 + $0)
+        ");
+    }
+
+    #[test]
+    fn fuse_lists_chain_of_maps() {
+        check_with(&["fuse_lists"], "
+            proc main : ([Nat] -> [Nat]) =
+                fn $0 =>
+                    (global map)(fn $1: Nat => ($1 + const 1))((global map)(fn $2: Nat => ($2 * const 2))($0))
+
+            proc map : ((Nat -> Nat) -> ([Nat] -> [Nat])) =
+                fn $0 =>
+                    fn $1 =>
+                        match $1 in
+                            | [$2, .. $3] => ([$0($2)] ++ (global map)($0)($3))
+                            | _ => []
+        ", "
+            proc main : ([Nat] -> [Nat]) =
+                fn $0 =>
+                    (global main_lifted1)(fn $1: Nat => ($1 * const 2))(fn $2: Nat => ($2 + const 1))($0)
+
+            proc main_lifted1 : ((Nat -> Nat) -> ((Nat -> Nat) -> ([Nat] -> [Nat]))) =
+                fn $0 =>
+                    fn $1 =>
+                        fn $2 =>
+                            match $2 in
+                                | [$3, .. $4] =>
+                                    let $5 = $0($3) in
+                                    let $6 = $1($5) in
+                                    ([$6] ++ (global main_lifted1)($0)($1)($4))
+                                | _ => const []
+
+            proc map : ((Nat -> Nat) -> ([Nat] -> [Nat])) =
+                fn $0 =>
+                    fn $1 =>
+                        match $1 in
+                            | [$2, .. $3] => ([$0($2)] ++ (global map)($0)($3))
+                            | _ => []
+        ", |ctx| {
+            let map = ProcId::Named(Intern::new("map".to_string()));
+            ctx.procs.get_mut(map).unwrap().fusion = Some(Fusion::Map);
+        });
+    }
+
+    #[test]
+    fn inline_leaves_chained_combinators() {
+        check_with(&["inline"], "
+            proc main : ([Nat] -> [Nat]) =
+                fn $0 =>
+                    ((global map)(fn $1: Nat => ($1 + const 1))((global map)(fn $2: Nat => ($2 * const 2))($0)) ++ (global map)(fn $3: Nat => $3)($0))
+
+            proc map : ((Nat -> Nat) -> ([Nat] -> [Nat])) =
+                fn $0 =>
+                    fn $1 =>
+                        match $1 in
+                            | [$2, .. $3] => ([$0($2)] ++ (global map)($0)($3))
+                            | _ => []
+        ", "
+            proc main : ([Nat] -> [Nat]) =
+                fn $0 =>
+                    ((global map)(fn $1: Nat => ($1 + const 1))((global map)(fn $2: Nat => ($2 * const 2))($0)) ++ (let $8 = fn $3: Nat => $3 in
+                        fn $9: [Nat] =>
+                            match $9 in
+                                | [$10, .. $11] => ([$8($10)] ++ (global map)($8)($11))
+                                | _ => ([] : [Nat]))($0))
+
+            proc map : ((Nat -> Nat) -> ([Nat] -> [Nat])) =
+                fn $0 =>
+                    fn $1 =>
+                        match $1 in
+                            | [$2, .. $3] => ([$0($2)] ++ (global map)($0)($3))
+                            | _ => []
+        ", |ctx| {
+            let map = ProcId::Named(Intern::new("map".to_string()));
+            ctx.procs.get_mut(map).unwrap().fusion = Some(Fusion::Map);
+        });
+    }
+
+    #[test]
+    fn flatten_single_field_tuples() {
+        check(&["flatten_single_field"], "
+            proc main : ((Nat,) -> Nat) =
+                fn $0 =>
+                    let ($1,) = $0 in
+                    ($1 + ($0).0)
+        ", "
+            proc main : (Nat -> Nat) =
+                fn $0 =>
+                    let $1 = $0 in
+                    ($1 + $0)
+        ");
+    }
+
+    #[test]
+    fn flatten_single_variant_sums() {
+        check(&["flatten_single_variant"], "
+            proc main : Nat =
+                match (#0 const 3 : #(Nat)) in
+                    | #0 $0 => $0
+        ", "
+            proc main : Nat =
+                let ($0) = const 3 in $0
+        ");
+    }
+
+    #[test]
+    fn tag_unit_sums_as_nats() {
+        check(&["tag_unit_sums"], "
+            proc main : Nat =
+                match (#1 () : #(() | ())) in
+                    | #0 _ => const 5
+                    | _ => const 6
+        ", "
+            proc main : Nat =
+                match const 1 in
+                    | const 0 => const 5
+                    | _ => const 6
+        ");
+    }
+
+    #[test]
+    fn niche_sums_offset_tags() {
+        check(&["niche_sums"], "
+            proc main : Nat =
+                match (#1 (#1 () : #(() | ())) : #(() | #(() | ()))) in
+                    | #1 #0 _ => const 5
+                    | #0 _ => const 6
+                    | _ => const 7
+        ", "
+            proc main : Nat =
+                match (const 1 + const 1) in
+                    | const 1 => const 5
+                    | const 0 => const 6
+                    | _ => const 7
+        ");
+    }
+
+    // Every natural number is a valid value, so there is no niche in which to put the empty variant
+    #[test]
+    fn niche_sums_leave_nats() {
+        let src = "
+            proc main : Nat =
+                match (#1 const 4 : #(() | Nat)) in
+                    | #1 $0 => $0
+                    | _ => const 0
+        ";
+        check(&["niche_sums"], src, src);
+    }
+
+    #[test]
+    fn closure_conversion_lifts_closures() {
+        check(&["closure_conversion"], "
+            proc main : (Nat -> Nat) =
+                fn $0 =>
+                    let $1 = fn $2: Nat => ($2 + $0) in
+                    ($1(const 1) + (global apply)($1))
+
+            proc apply : ((Nat -> Nat) -> Nat) =
+                fn $0 =>
+                    $0(const 2)
+        ", "
+            proc main : (Nat -> Nat) =
+                fn $0 =>
+                    let $1 = (global main_lifted1)(($0,)) in
+                    ($1(const 1) + (global apply)($1))
+
+            proc apply : ((Nat -> Nat) -> Nat) =
+                fn $2 =>
+                    $2(const 2)
+
+            proc main_lifted1 : ((Nat,) -> (Nat -> Nat)) =
+                fn $3 =>
+                    fn $4 =>
+                        let ($5,) = $3 in
+                        ($4 + $5)
+        ");
+    }
+
+    #[test]
+    fn remove_dead_procs_unreachable() {
+        check(&["remove_dead_procs"], "
+            proc main : Nat =
+                (global used)(const 1)
+
+            proc used : (Nat -> Nat) =
+                fn $0 => $0
+
+            proc unused : (Nat -> Nat) =
+                fn $0 => $0
+        ", "
+            proc main : Nat =
+                (global used)(const 1)
+
+            proc used : (Nat -> Nat) =
+                fn $0 => $0
+        ");
+    }
+
+    #[test]
+    fn remove_dead_procs_records_reasons() {
+        let mut ctx = Context::parse("
+            proc main : Nat =
+                const 1
+
+            proc unused : (Nat -> Nat) =
+                fn $0 => (global helper)($0)
+
+            proc helper : (Nat -> Nat) =
+                fn $0 => $0
+        ").unwrap_or_else(|e| panic!("{}", e));
+        ctx.run_passes(vec![Box::new(RemoveDeadProcs::default())], |_, _| {});
+
+        let proc = |name: &str| ProcId::Named(Intern::new(name.to_string()));
+        assert_eq!(ctx.eliminated.len(), 2);
+        assert!(ctx.eliminated.contains(&(proc("unused"), Elimination::Unreferenced)));
+        assert!(ctx.eliminated.contains(&(proc("helper"), Elimination::ReferencedBy(vec![proc("unused")]))));
+        assert_eq!(ctx.procs.iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![proc("main")]);
+    }
+
+    // Closures are converted before optimisation, but the procedures they become may still be inlined and beta-reduced
+    #[test]
+    fn lifted_lambdas_fold() {
+        let mut ctx = Context::parse("
+            proc main : Nat =
+                (fn $0: Nat => ($0 + const 1))(const 2)
+        ").unwrap_or_else(|e| panic!("{}", e));
+        ctx.optimize(OptMode::Fast);
+        let expected = Context::parse("
+            proc main : Nat =
+                const 3
+        ").unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(normalize(&ctx.print()), normalize(&expected.print()));
+    }
+
+    // Passes that never settle are stopped after a fixed number of iterations rather than aborting compilation
+    #[test]
+    fn run_passes_stops_oscillating() {
+        struct Restless;
+
+        impl Pass for Restless {
+            fn apply(&mut self, _: &mut Context) -> bool { true }
+        }
+
+        let mut ctx = Context::parse("
+            proc main : Nat =
+                const 1
+        ").unwrap_or_else(|e| panic!("{}", e));
+        let mut runs = 0;
+        ctx.run_passes(vec![Box::new(Restless)], |_, _| runs += 1);
+        assert!(runs > 1);
+        assert_eq!(ctx.print(), Context::parse("proc main : Nat = const 1").unwrap().print());
     }
 }
@@ -0,0 +1,83 @@
+use super::*;
+
+fn datas_in(repr: &Repr, datas: &mut Vec<ConDataId>) {
+    match repr {
+        Repr::Prim(_) => {},
+        Repr::List(item) => datas_in(item, datas),
+        Repr::Tuple(xs) | Repr::Sum(xs) | Repr::Union(xs) => xs
+            .iter()
+            .for_each(|x| datas_in(x, datas)),
+        Repr::Data(data) => datas.push(*data),
+        Repr::Func(i, o) => {
+            datas_in(i, datas);
+            datas_in(o, datas);
+        },
+    }
+}
+
+/// Remove procedures that can no longer be reached from the entry point (usually because every reference to them was
+/// inlined or folded away), along with the representations of data types that are no longer used. Eliminated
+/// procedures are recorded in [`Context::eliminated`].
+#[derive(Default)]
+pub struct RemoveDeadProcs;
+
+impl Pass for RemoveDeadProcs {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        let reachable = ctx.reachable_procs();
+
+        let dead = ctx.procs
+            .iter()
+            .filter(|(id, _)| !reachable.contains(id))
+            .map(|(id, proc)| (id, proc.body.required_globals()))
+            .collect::<Vec<_>>();
+
+        for (id, _) in dead.iter() {
+            let mut referenced_by = dead
+                .iter()
+                .filter(|(_, globals)| globals.contains(id))
+                .map(|(referrer, _)| *referrer)
+                .collect::<Vec<_>>();
+            referenced_by.sort();
+
+            let reason = if referenced_by.is_empty() {
+                Elimination::Unreferenced
+            } else {
+                Elimination::ReferencedBy(referenced_by)
+            };
+
+            ctx.procs.remove(*id);
+            ctx.eliminated.push((*id, reason));
+        }
+
+        // Find the data types that are still in use, both directly and via one-another
+        let mut datas = Vec::new();
+        let mut intrinsic_reprs = Vec::new();
+        for (_, proc) in ctx.procs.iter_mut() {
+            proc.body.visit_inner(
+                VisitOrder::First,
+                &mut |repr| datas_in(repr, &mut datas),
+                &mut |_| {},
+                &mut |expr| if let Expr::Intrinsic(Intrinsic::MakeList(repr) | Intrinsic::Join(repr), _) = expr {
+                    intrinsic_reprs.push(repr.clone());
+                },
+            );
+        }
+        intrinsic_reprs
+            .iter()
+            .for_each(|repr| datas_in(repr, &mut datas));
+
+        let mut live = HashSet::new();
+        while let Some(data) = datas.pop() {
+            if live.insert(data) {
+                if let Some(Some(repr)) = ctx.reprs.datas.get(&data) {
+                    datas_in(repr, &mut datas);
+                }
+            }
+        }
+
+        let data_count = ctx.reprs.datas.len();
+        ctx.reprs.datas.retain(|data, _| live.contains(data));
+
+        !dead.is_empty() || ctx.reprs.datas.len() != data_count
+    }
+}
@@ -1,12 +1,12 @@
 use super::*;
 
 /// Remove bindings that are never used. Also, matches with a single arm that do not bind are flattened and arms that
-/// follow irrefutable arms are removed.
+/// follow irrefutable arms without guards are removed.
 #[derive(Default)]
 pub struct RemoveUnusedBindings;
 
 impl Pass for RemoveUnusedBindings {
-    fn apply(&mut self, ctx: &mut Context) {
+    fn apply(&mut self, ctx: &mut Context) -> bool {
         fn visit(
             mir: &Context,
             expr: &mut Expr,
@@ -25,20 +25,29 @@ impl Pass for RemoveUnusedBindings {
 
                     // Remove any arms that follow an irrefutable arm
                     for i in 0..arms.len() {
-                        if !arms[i].0.is_refutable() {
+                        if !arms[i].0.is_refutable() && arms[i].1.is_none() {
                             arms.truncate(i + 1);
                             break;
                         }
                     }
 
+                    let mut first_uses = 0;
                     arms
                         .iter_mut()
-                        .for_each(|(arm, body)| {
+                        .enumerate()
+                        .for_each(|(i, (arm, guard, body))| {
                             let old_stack = stack.len();
 
                             stack.extend(arm.binding_names().into_iter().map(|name| (name, 0)));
+                            if let Some(guard) = guard {
+                                visit(mir, guard, stack, proc_stack);
+                            }
                             visit(mir, body, stack, proc_stack);
 
+                            if i == 0 {
+                                first_uses = arm.name.map_or(0, |name| stack.iter().rev().find(|(n, _)| *n == name).unwrap().1);
+                            }
+
                             fn remove_unused(binding: &mut Binding, stack: &mut Vec<(Local, u64)>) {
                                 if let Some(name) = binding.name {
                                     if stack.iter_mut().rev().find(|(n, _)| *n == name).unwrap().1 == 0 {
@@ -49,8 +58,10 @@ impl Pass for RemoveUnusedBindings {
                                 match &mut binding.pat {
                                     Pat::Wildcard => {},
                                     Pat::Literal(_) => {},
+                                    Pat::Range(_, _) => {},
                                     Pat::Single(inner) => remove_unused(inner, stack),
                                     Pat::Add(lhs, _) => remove_unused(lhs, stack),
+                                    Pat::Mul(lhs, _) => remove_unused(lhs, stack),
                                     Pat::Tuple(fields) => fields
                                         .iter_mut()
                                         .for_each(|field| remove_unused(field, stack)),
@@ -67,6 +78,10 @@ impl Pass for RemoveUnusedBindings {
                                     },
                                     Pat::Variant(_, inner) => remove_unused(inner, stack),
                                     Pat::UnionVariant(_, inner) => remove_unused(inner, stack),
+                                    // Alternatives share their locals, so a local is removed from all of them or none
+                                    Pat::Or(alts) => alts
+                                        .iter_mut()
+                                        .for_each(|alt| remove_unused(alt, stack)),
                                 }
                             }
 
@@ -77,16 +92,16 @@ impl Pass for RemoveUnusedBindings {
 
                     // Flatten matches with a single arm where the arm does not bind
                     if arms.len() == 1 && !arms.first().unwrap().0.binds() {
-                        *expr = arms.remove(0).1.into_inner();
-                    } else if arms.get(0).map_or(false, |(b, _)| matches!(&b.pat, Pat::Wildcard)) {
-                        let (arm, mut body) = arms.remove(0);
+                        *expr = arms.remove(0).2.into_inner();
+                    } else if arms.get(0).map_or(false, |(b, guard, _)| matches!(&b.pat, Pat::Wildcard) && guard.is_none())
+                        // Bindings used several times are kept so that their value is only computed once
+                        && (first_uses <= 1 || matches!(&**pred, Expr::Local(_) | Expr::Literal(_) | Expr::Global(_, _)))
+                    {
+                        let (arm, _, mut body) = arms.remove(0);
                         if let Some(name) = arm.name {
                             body.inline_local(name, pred);
                         }
                         *expr = body.into_inner();
-                    } else {
-                        // Visit predicate last to avoid visiting it again if the match was removed
-                        visit(mir, pred, stack, proc_stack);
                     }
                 },
                 Expr::Func(arg, body) => {
@@ -103,12 +118,14 @@ impl Pass for RemoveUnusedBindings {
             .map(|(id, proc)| (id, proc.body.clone()))
             .collect::<Vec<_>>();
 
+        let mut changed = false;
         for (id, mut body) in proc_bodies {
             let mut proc_stack = vec![id];
             visit(&ctx, &mut body, &mut Vec::new(), &mut proc_stack);
             let requires = body.required_locals(None);
             debug_assert_eq!(requires.len(), 0, "Procedure requires locals {:?}\n\nOld = {}\n\n\nNew = {}\n", requires, ctx.procs.get_mut(id).unwrap().body.print(), body.print());
-            ctx.procs.get_mut(id).unwrap().body = body;
+            changed |= ctx.set_body(id, body);
         }
+        changed
     }
 }
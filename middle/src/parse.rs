@@ -0,0 +1,933 @@
+//! A parser for textual MIR, the syntax produced by [`Expr::print`] and [`Context::print`].
+//!
+//! Textual MIR makes it possible to write tests for optimisation passes in terms of the MIR that they accept and
+//! produce. It looks like this:
+//!
+//! ```text
+//! proc main : Nat =
+//!     let $0 = (global double)(const 4) in
+//!     match ($0 < const 10) in
+//!         | const true => $0
+//!         | _ => const 0
+//!
+//! proc double : (Nat -> Nat) =
+//!     fn $0: Nat =>
+//!         ($0 + $0)
+//! ```
+//!
+//! The reprs of most expressions follow from the reprs of their parts, but those that don't (such as variants) must be
+//! annotated like `(#1 const 5 : #(() | Nat))`. Locals get fresh names when parsed, so the numbers used in the source
+//! only need to be consistent with one-another. Data types have no textual form.
+
+use super::*;
+use std::fmt;
+
+/// An error encountered while parsing textual MIR.
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    /// The byte offset into the source at which the error was found.
+    pub at: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.msg, self.at)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Local(usize),
+    Ident(String),
+    Nat(u64),
+    Int(u64),
+    Real(f64),
+    Char(char),
+    Str(String),
+    Punct(&'static str),
+}
+
+// Longer punctuation comes first so that it takes priority
+const PUNCTS: &[&str] = &[
+    "=>", "->", "++", "!=", "<=", ">=", "..",
+    "(", ")", "[", "]", ",", ":", "=", "|", "~", "#", "+", "-", "*", "!", "<", ">", ".", "?",
+];
+
+struct Lexer<'a> {
+    src: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self) -> Option<char> { self.peek_nth(0) }
+
+    fn peek_nth(&self, n: usize) -> Option<char> { self.chars.get(self.pos + n).map(|(_, c)| *c) }
+
+    fn offset(&self) -> usize { self.chars.get(self.pos).map_or(self.src.len(), |(at, _)| *at) }
+
+    fn error<T>(&self, msg: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError { at: self.offset(), msg: msg.into() })
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.offset();
+        while self.peek().map_or(false, &f) {
+            self.pos += 1;
+        }
+        let src = self.src;
+        &src[start..self.offset()]
+    }
+
+    // A character of a char or string literal, with escapes resolved
+    fn escaped(&mut self) -> Result<char, ParseError> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return self.error("Unexpected end of input in literal"),
+        };
+        self.pos += 1;
+        if c != '\\' {
+            return Ok(c);
+        }
+        let c = self.peek();
+        self.pos += 1;
+        match c {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('0') => Ok('\0'),
+            Some(c @ ('\\' | '\'' | '"')) => Ok(c),
+            Some('u') if self.peek() == Some('{') => {
+                self.pos += 1;
+                let hex = self.take_while(|c| c.is_ascii_hexdigit());
+                let c = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+                match (c, self.peek()) {
+                    (Some(c), Some('}')) => {
+                        self.pos += 1;
+                        Ok(c)
+                    },
+                    _ => self.error("Invalid unicode escape"),
+                }
+            },
+            _ => self.error("Invalid escape"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Token, ParseError> {
+        let start = self.offset();
+        self.take_while(|c| c.is_ascii_digit());
+        let mut is_real = false;
+        // A `.` is only part of the number when a digit follows, since `..` and `).0` mean something else
+        if self.peek() == Some('.') && self.peek_nth(1).map_or(false, |c| c.is_ascii_digit()) {
+            is_real = true;
+            self.pos += 1;
+            self.take_while(|c| c.is_ascii_digit());
+        }
+        if self.peek() == Some('e') {
+            is_real = true;
+            self.pos += 1;
+            if let Some('-' | '+') = self.peek() {
+                self.pos += 1;
+            }
+            self.take_while(|c| c.is_ascii_digit());
+        }
+        let text = &self.src[start..self.offset()];
+        let token = if is_real {
+            text.parse().ok().map(Token::Real)
+        } else if self.peek() == Some('i') {
+            self.pos += 1;
+            text.parse().ok().map(Token::Int)
+        } else {
+            text.parse().ok().map(Token::Nat)
+        };
+        match token {
+            Some(token) => Ok(token),
+            None => self.error(format!("Invalid number `{}`", text)),
+        }
+    }
+
+    fn lex(mut self) -> Result<Vec<(Token, usize)>, ParseError> {
+        let mut tokens = Vec::new();
+        while let Some(c) = self.peek() {
+            let at = self.offset();
+            let token = if c.is_whitespace() {
+                self.pos += 1;
+                continue;
+            } else if c == '$' {
+                self.pos += 1;
+                match self.take_while(|c| c.is_ascii_digit()).parse() {
+                    Ok(n) => Token::Local(n),
+                    Err(_) => return self.error("Expected the number of a local after `$`"),
+                }
+            } else if c.is_ascii_digit() {
+                self.number()?
+            } else if c.is_alphabetic() || c == '_' {
+                Token::Ident(self.take_while(|c| c.is_alphanumeric() || c == '_').to_string())
+            } else if c == '\'' {
+                self.pos += 1;
+                let c = self.escaped()?;
+                if self.peek() != Some('\'') {
+                    return self.error("Expected `'` at the end of char literal");
+                }
+                self.pos += 1;
+                Token::Char(c)
+            } else if c == '"' {
+                self.pos += 1;
+                let mut s = String::new();
+                while self.peek() != Some('"') {
+                    s.push(self.escaped()?);
+                }
+                self.pos += 1;
+                Token::Str(s)
+            } else if let Some(punct) = PUNCTS.iter().find(|p| self.src[at..].starts_with(**p)) {
+                self.pos += punct.chars().count();
+                Token::Punct(*punct)
+            } else {
+                return self.error(format!("Unexpected character `{}`", c));
+            };
+            tokens.push((token, at));
+        }
+        Ok(tokens)
+    }
+}
+
+fn lex(src: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    Lexer { src, chars: src.char_indices().collect(), pos: 0 }.lex()
+}
+
+// An expression that has been parsed, but that might still need a repr annotation
+type Pending = (Expr, Option<Repr>);
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    src_len: usize,
+    globals: &'a dyn Fn(&str) -> Option<(ProcId, Repr)>,
+    // Locals in scope, with the number they were given in the source
+    scope: Vec<(usize, Local, Repr)>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos).map(|(t, _)| t) }
+
+    fn offset(&self) -> usize { self.tokens.get(self.pos).map_or(self.src_len, |(_, at)| *at) }
+
+    fn error<T>(&self, msg: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError { at: self.offset(), msg: msg.into() })
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_ident(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(i)) if i == word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ParseError> {
+        if self.eat(punct) { Ok(()) } else { self.error(format!("Expected `{}`", punct)) }
+    }
+
+    fn expect_ident(&mut self, word: &str) -> Result<(), ParseError> {
+        if self.eat_ident(word) { Ok(()) } else { self.error(format!("Expected `{}`", word)) }
+    }
+
+    fn expect_end(&self) -> Result<(), ParseError> {
+        if self.peek().is_none() { Ok(()) } else { self.error("Expected end of input") }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.peek().cloned() {
+            Some(Token::Ident(ident)) => {
+                self.pos += 1;
+                Ok(ident)
+            },
+            _ => self.error("Expected identifier"),
+        }
+    }
+
+    fn nat(&mut self) -> Result<u64, ParseError> {
+        match self.peek() {
+            Some(Token::Nat(x)) => {
+                let x = *x;
+                self.pos += 1;
+                Ok(x)
+            },
+            _ => self.error("Expected natural number"),
+        }
+    }
+
+    // Find the position of the next occurrence of the punctuation that is not nested within brackets. Some syntax (such
+    // as `let`) can only be understood once a later part of it has been parsed, so this is used to look ahead.
+    fn find(&self, punct: &str) -> Result<usize, ParseError> {
+        let mut depth = 0usize;
+        for (i, (token, _)) in self.tokens.iter().enumerate().skip(self.pos) {
+            match token {
+                Token::Punct(p) if *p == punct && depth == 0 => return Ok(i),
+                Token::Punct("(" | "[") => depth += 1,
+                Token::Punct(")" | "]") if depth == 0 => break,
+                Token::Punct(")" | "]") => depth -= 1,
+                _ => {},
+            }
+        }
+        self.error(format!("Expected `{}`", punct))
+    }
+
+    fn repr(&mut self) -> Result<Repr, ParseError> {
+        fn variants(this: &mut Parser) -> Result<Vec<Repr>, ParseError> {
+            this.expect("(")?;
+            let mut variants = Vec::new();
+            if !this.eat(")") {
+                loop {
+                    variants.push(this.repr()?);
+                    if this.eat(")") {
+                        break;
+                    }
+                    this.expect("|")?;
+                }
+            }
+            Ok(variants)
+        }
+
+        match self.next() {
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "Nat" => Ok(Repr::Prim(Prim::Nat)),
+                "Int" => Ok(Repr::Prim(Prim::Int)),
+                "Real" => Ok(Repr::Prim(Prim::Real)),
+                "Char" => Ok(Repr::Prim(Prim::Char)),
+                "Bool" => Ok(Repr::Prim(Prim::Bool)),
+                "union" => Ok(Repr::Union(variants(self)?)),
+                "data" => { self.pos -= 1; self.error("Data types cannot be written as textual MIR") },
+                _ => { self.pos -= 1; self.error("Expected repr") },
+            },
+            Some(Token::Punct("[")) => {
+                let item = self.repr()?;
+                self.expect("]")?;
+                Ok(Repr::List(Box::new(item)))
+            },
+            Some(Token::Punct("(")) => {
+                if self.eat(")") {
+                    return Ok(Repr::Tuple(Vec::new()));
+                }
+                let first = self.repr()?;
+                if self.eat("->") {
+                    let output = self.repr()?;
+                    self.expect(")")?;
+                    Ok(Repr::Func(Box::new(first), Box::new(output)))
+                } else {
+                    let mut fields = vec![first];
+                    self.expect(",")?;
+                    while !self.eat(")") {
+                        fields.push(self.repr()?);
+                        self.expect(",")?;
+                    }
+                    Ok(Repr::Tuple(fields))
+                }
+            },
+            Some(Token::Punct("#")) => Ok(Repr::Sum(variants(self)?)),
+            _ => { self.pos -= 1; self.error("Expected repr") },
+        }
+    }
+
+    fn literal(&mut self) -> Result<Literal, ParseError> {
+        match self.next() {
+            Some(Token::Nat(x)) => Ok(Literal::Nat(x)),
+            Some(Token::Int(x)) => Ok(Literal::Int(x as i64)),
+            Some(Token::Real(x)) => Ok(Literal::Real(x)),
+            Some(Token::Char(c)) => Ok(Literal::Char(c)),
+            Some(Token::Str(s)) => Ok(Literal::List(s.chars().map(Literal::Char).collect())),
+            Some(Token::Ident(ident)) if ident == "true" => Ok(Literal::Bool(true)),
+            Some(Token::Ident(ident)) if ident == "false" => Ok(Literal::Bool(false)),
+            Some(Token::Punct("-")) => match self.next() {
+                Some(Token::Int(x)) => Ok(Literal::Int((x as i64).wrapping_neg())),
+                Some(Token::Real(x)) => Ok(Literal::Real(-x)),
+                _ => { self.pos -= 1; self.error("Expected integer or real after `-`") },
+            },
+            Some(Token::Punct("(")) => {
+                let mut fields = Vec::new();
+                while !self.eat(")") {
+                    fields.push(self.literal()?);
+                    self.expect(",")?;
+                }
+                Ok(Literal::Tuple(fields))
+            },
+            Some(Token::Punct("[")) => {
+                let mut items = Vec::new();
+                if !self.eat("]") {
+                    loop {
+                        items.push(self.literal()?);
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Literal::List(items))
+            },
+            Some(Token::Punct("#")) => {
+                let variant = self.nat()? as usize;
+                Ok(Literal::Sum(variant, Box::new(self.literal()?)))
+            },
+            Some(Token::Ident(ident)) if ident == "union" => {
+                self.expect("#")?;
+                let id = self.nat()?;
+                Ok(Literal::Union(id, Box::new(self.literal()?)))
+            },
+            _ => { self.pos -= 1; self.error("Expected literal") },
+        }
+    }
+
+    // Bindings are given the repr of the value that they bind, and the locals they bind are brought into scope
+    fn binding(&mut self, repr: &Repr) -> Result<MirNode<Binding>, ParseError> {
+        let name = if let Some(Token::Local(n)) = self.peek() {
+            let (n, local) = (*n, Local::new());
+            self.pos += 1;
+            self.scope.push((n, local, repr.clone()));
+            Some(local)
+        } else {
+            None
+        };
+        let pat = if name.is_some() && !self.eat("~") {
+            Pat::Wildcard
+        } else {
+            self.pat(repr)?
+        };
+        Ok(MirNode::new(Binding { pat, name }, repr.clone()))
+    }
+
+    fn pat(&mut self, repr: &Repr) -> Result<Pat, ParseError> {
+        if self.eat_ident("_") {
+            Ok(Pat::Wildcard)
+        } else if self.eat_ident("const") {
+            Ok(Pat::Literal(self.literal()?))
+        } else if self.eat_ident("range") {
+            let lo = self.literal()?;
+            self.expect("..")?;
+            Ok(Pat::Range(lo, self.literal()?))
+        } else if self.eat_ident("or") {
+            self.expect("(")?;
+            let old_scope = self.scope.len();
+            let mut alts = vec![self.binding(repr)?];
+            let names = self.scope[old_scope..].to_vec();
+            while self.eat("|") {
+                let mut alt = self.binding(repr)?;
+                // Later alternatives bind the same locals as the first
+                let mut renames = Vec::new();
+                for (n, local, _) in self.scope.split_off(old_scope + names.len()) {
+                    match names.iter().find(|(m, _, _)| *m == n) {
+                        Some((_, first, _)) => renames.push((local, *first)),
+                        None => return self.error(format!("Local ${} is not bound by every alternative", n)),
+                    }
+                }
+                alt.refresh_locals_inner(&mut renames);
+                alts.push(alt);
+            }
+            self.expect(")")?;
+            Ok(Pat::Or(alts))
+        } else if self.eat("(") {
+            if self.eat(")") {
+                return Ok(Pat::Tuple(Vec::new()));
+            }
+            // A tuple can only be told apart from a parenthesised pattern by looking ahead for a comma
+            if self.find(",").is_ok() {
+                let reprs = match repr {
+                    Repr::Tuple(fields) => fields.clone(),
+                    _ => return self.error(format!("Tuple pattern cannot match {}", repr)),
+                };
+                let mut fields = Vec::new();
+                for repr in &reprs {
+                    fields.push(self.binding(repr)?);
+                    self.expect(",")?;
+                }
+                self.expect(")")?;
+                Ok(Pat::Tuple(fields))
+            } else {
+                let inner = self.binding(repr)?;
+                if self.eat("+") {
+                    let rhs = self.nat()?;
+                    self.expect(")")?;
+                    Ok(Pat::Add(inner, rhs))
+                } else if self.eat("*") {
+                    let rhs = self.nat()?;
+                    self.expect(")")?;
+                    Ok(Pat::Mul(inner, rhs))
+                } else {
+                    self.expect(")")?;
+                    Ok(Pat::Single(inner))
+                }
+            }
+        } else if self.eat("[") {
+            let item = match repr {
+                Repr::List(item) => (**item).clone(),
+                _ => return self.error(format!("List pattern cannot match {}", repr)),
+            };
+            let mut items = Vec::new();
+            loop {
+                if self.eat("..") {
+                    let tail = if self.eat("]") {
+                        None
+                    } else {
+                        let tail = self.binding(repr)?;
+                        self.expect("]")?;
+                        Some(tail)
+                    };
+                    break Ok(Pat::ListFront(items, tail));
+                } else if self.eat("]") {
+                    break Ok(Pat::ListExact(items));
+                }
+                items.push(self.binding(&item)?);
+                self.expect(",")?;
+            }
+        } else if self.eat("#") {
+            let variant = self.nat()? as usize;
+            let inner = match repr {
+                Repr::Sum(variants) if variant < variants.len() => variants[variant].clone(),
+                _ => return self.error(format!("Variant pattern cannot match {}", repr)),
+            };
+            Ok(Pat::Variant(variant, self.binding(&inner)?))
+        } else if self.eat_ident("union") {
+            self.expect("#")?;
+            let id = self.nat()?;
+            self.expect("(")?;
+            // The inner binding is annotated with its repr, which comes after it
+            let start = self.pos;
+            self.pos = self.find(":")? + 1;
+            let inner = self.repr()?;
+            self.expect(")")?;
+            let end = self.pos;
+            self.pos = start;
+            let inner = self.binding(&inner)?;
+            self.expect(":")?;
+            self.pos = end;
+            Ok(Pat::UnionVariant(id, inner))
+        } else {
+            self.error("Expected pattern")
+        }
+    }
+
+    // Give the expression a repr, preferring the one that is expected of it
+    fn finish(&self, at: usize, (expr, repr): Pending, expected: Option<&Repr>) -> Result<MirNode<Expr>, ParseError> {
+        match expected.cloned().or(repr) {
+            Some(repr) => Ok(MirNode::new(expr, repr)),
+            None => Err(ParseError {
+                at,
+                msg: "Cannot determine the repr of this expression, consider annotating it like `(... : repr)`".to_string(),
+            }),
+        }
+    }
+
+    fn node(&mut self, expected: Option<&Repr>) -> Result<MirNode<Expr>, ParseError> {
+        let at = self.offset();
+        let expr = self.expr(expected)?;
+        self.finish(at, expr, expected)
+    }
+
+    fn operand(&mut self) -> Result<MirNode<Expr>, ParseError> {
+        let at = self.offset();
+        let expr = self.prefix()?;
+        self.finish(at, expr, None)
+    }
+
+    fn expr(&mut self, expected: Option<&Repr>) -> Result<Pending, ParseError> {
+        if self.eat_ident("let") {
+            // The repr of the binding depends on the value, which comes after it
+            let start = self.pos;
+            self.pos = self.find("=")? + 1;
+            let pred = self.node(None)?;
+            self.expect_ident("in")?;
+            let body_start = self.pos;
+            self.pos = start;
+
+            let old_scope = self.scope.len();
+            let binding = self.binding(pred.meta())?;
+            self.expect("=")?;
+            self.pos = body_start;
+            let body = self.node(expected)?;
+            self.scope.truncate(old_scope);
+
+            let repr = body.meta().clone();
+            Ok((Expr::Match(pred, vec![(binding, None, body)]), Some(repr)))
+        } else if self.eat_ident("match") {
+            let pred = self.node(None)?;
+            self.expect_ident("in")?;
+            let mut arms = Vec::new();
+            if self.eat("(") {
+                self.expect_ident("no")?;
+                self.expect_ident("arms")?;
+                self.expect(")")?;
+            } else {
+                while self.eat("|") {
+                    let old_scope = self.scope.len();
+                    let binding = self.binding(pred.meta())?;
+                    let guard = if self.eat_ident("if") {
+                        Some(self.node(Some(&Repr::Prim(Prim::Bool)))?)
+                    } else {
+                        None
+                    };
+                    self.expect("=>")?;
+                    let body = self.node(expected)?;
+                    self.scope.truncate(old_scope);
+                    arms.push((binding, guard, body));
+                }
+                if arms.is_empty() {
+                    return self.error("Expected `|`");
+                }
+            }
+            let expr = Expr::Match(pred, arms);
+            let repr = expr.infer_repr();
+            Ok((expr, repr))
+        } else if self.eat_ident("fn") {
+            let n = match self.next() {
+                Some(Token::Local(n)) => n,
+                _ => { self.pos -= 1; return self.error("Expected parameter") },
+            };
+            let (param_repr, output) = match expected {
+                Some(Repr::Func(i, o)) => (Some((**i).clone()), Some(&**o)),
+                _ => (None, None),
+            };
+            let param_repr = if self.eat(":") {
+                self.repr()?
+            } else if let Some(param_repr) = param_repr {
+                param_repr
+            } else {
+                return self.error("Expected `:` followed by the repr of the parameter");
+            };
+            self.expect("=>")?;
+
+            let param = Local::new();
+            self.scope.push((n, param, param_repr.clone()));
+            let body = self.node(output)?;
+            self.scope.pop();
+
+            let repr = Repr::Func(Box::new(param_repr), Box::new(body.meta().clone()));
+            Ok((Expr::Func(param, body), Some(repr)))
+        } else {
+            self.prefix()
+        }
+    }
+
+    fn prefix(&mut self) -> Result<Pending, ParseError> {
+        let expr = if self.eat("-") {
+            let arg = self.operand()?;
+            let intrinsic = match arg.meta() {
+                Repr::Prim(Prim::Nat) => Intrinsic::NegNat,
+                Repr::Prim(Prim::Int) => Intrinsic::NegInt,
+                Repr::Prim(Prim::Real) => Intrinsic::NegReal,
+                repr => return self.error(format!("Cannot negate {}", repr)),
+            };
+            Expr::Intrinsic(intrinsic, vec![arg])
+        } else if self.eat("!") {
+            Expr::Intrinsic(Intrinsic::NotBool, vec![self.operand()?])
+        } else if self.eat("?") {
+            Expr::Debug(self.operand()?)
+        } else if self.eat("#") {
+            let variant = self.nat()? as usize;
+            Expr::Variant(variant, self.operand()?)
+        } else if self.eat_ident("union") {
+            self.expect("#")?;
+            let id = self.nat()?;
+            Expr::Intrinsic(Intrinsic::Union(id), vec![self.operand()?])
+        } else {
+            return self.postfix();
+        };
+        let repr = expr.infer_repr();
+        Ok((expr, repr))
+    }
+
+    fn postfix(&mut self) -> Result<Pending, ParseError> {
+        let at = self.offset();
+        let mut expr = self.primary()?;
+        loop {
+            let outer = if self.eat("(") {
+                let f = self.finish(at, expr, None)?;
+                let arg = self.node(None)?;
+                self.expect(")")?;
+                Expr::Apply(f, arg)
+            } else if self.eat(".") {
+                let inner = self.finish(at, expr, None)?;
+                if self.eat("#") {
+                    Expr::AccessVariant(inner, self.nat()? as usize)
+                } else {
+                    Expr::Access(inner, self.nat()? as usize)
+                }
+            } else {
+                break Ok(expr);
+            };
+            let repr = outer.infer_repr();
+            expr = (outer, repr);
+        }
+    }
+
+    fn primary(&mut self) -> Result<Pending, ParseError> {
+        let at = self.offset();
+        let expr = match self.next() {
+            Some(Token::Local(n)) => match self.scope.iter().rev().find(|(m, _, _)| *m == n) {
+                Some((_, local, repr)) => return Ok((Expr::Local(*local), Some(repr.clone()))),
+                None => { self.pos -= 1; return self.error(format!("No such local `${}`", n)) },
+            },
+            Some(Token::Ident(ident)) if ident == "global" => {
+                let name = self.ident()?;
+                match (self.globals)(&name) {
+                    Some((proc, repr)) => return Ok((Expr::Global(proc, Default::default()), Some(repr))),
+                    None => { self.pos -= 1; return self.error(format!("No such global `{}`", name)) },
+                }
+            },
+            Some(Token::Ident(ident)) if ident == "const" => Expr::Literal(self.literal()?),
+            Some(Token::Ident(ident)) if ident == "make_list" => {
+                self.expect("<")?;
+                let item = self.repr()?;
+                self.expect(">")?;
+                self.expect("(")?;
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.node(None)?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Expr::Intrinsic(Intrinsic::MakeList(item), args)
+            },
+            Some(Token::Punct("[")) => {
+                let mut items = Vec::new();
+                if !self.eat("]") {
+                    loop {
+                        items.push(self.node(None)?);
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Expr::List(items)
+            },
+            Some(Token::Punct("(")) => {
+                if self.eat(")") {
+                    Expr::Tuple(Vec::new())
+                } else {
+                    let first = self.expr(None)?;
+                    if self.eat(")") {
+                        return Ok(first);
+                    } else if self.eat(":") {
+                        let repr = self.repr()?;
+                        self.expect(")")?;
+                        return Ok((first.0, Some(repr)));
+                    } else if self.eat(",") {
+                        let mut fields = vec![self.finish(at, first, None)?];
+                        while !self.eat(")") {
+                            fields.push(self.node(None)?);
+                            self.expect(",")?;
+                        }
+                        Expr::Tuple(fields)
+                    } else {
+                        let lhs = self.finish(at, first, None)?;
+                        let op = match self.next() {
+                            Some(Token::Punct(op)) => op,
+                            _ => { self.pos -= 1; return self.error("Expected `)`, `,`, `:` or an operator") },
+                        };
+                        let rhs = self.operand()?;
+                        self.expect(")")?;
+                        let intrinsic = match (op, lhs.meta()) {
+                            ("+", Repr::Prim(Prim::Nat)) => Intrinsic::AddNat,
+                            ("+", Repr::Prim(Prim::Int)) => Intrinsic::AddInt,
+                            ("-", Repr::Prim(Prim::Nat)) => Intrinsic::SubNat,
+                            ("-", Repr::Prim(Prim::Int)) => Intrinsic::SubInt,
+                            ("*", Repr::Prim(Prim::Nat)) => Intrinsic::MulNat,
+                            ("*", Repr::Prim(Prim::Int)) => Intrinsic::MulInt,
+                            ("=", Repr::Prim(Prim::Nat)) => Intrinsic::EqNat,
+                            ("=", Repr::Prim(Prim::Int)) => Intrinsic::EqInt,
+                            ("=", Repr::Prim(Prim::Char)) => Intrinsic::EqChar,
+                            ("!=", Repr::Prim(Prim::Nat)) => Intrinsic::NotEqNat,
+                            ("!=", Repr::Prim(Prim::Int)) => Intrinsic::NotEqInt,
+                            ("!=", Repr::Prim(Prim::Char)) => Intrinsic::NotEqChar,
+                            ("<", Repr::Prim(Prim::Nat)) => Intrinsic::LessNat,
+                            ("<", Repr::Prim(Prim::Int)) => Intrinsic::LessInt,
+                            (">", Repr::Prim(Prim::Nat)) => Intrinsic::MoreNat,
+                            (">", Repr::Prim(Prim::Int)) => Intrinsic::MoreInt,
+                            ("<=", Repr::Prim(Prim::Nat)) => Intrinsic::LessEqNat,
+                            ("<=", Repr::Prim(Prim::Int)) => Intrinsic::LessEqInt,
+                            (">=", Repr::Prim(Prim::Nat)) => Intrinsic::MoreEqNat,
+                            (">=", Repr::Prim(Prim::Int)) => Intrinsic::MoreEqInt,
+                            ("++", Repr::List(item)) => Intrinsic::Join((**item).clone()),
+                            (op, repr) => return Err(ParseError {
+                                at,
+                                msg: format!("Operator `{}` cannot be applied to {}", op, repr),
+                            }),
+                        };
+                        Expr::Intrinsic(intrinsic, vec![lhs, rhs])
+                    }
+                }
+            },
+            _ => { self.pos -= 1; return self.error("Expected expression") },
+        };
+        let repr = expr.infer_repr();
+        Ok((expr, repr))
+    }
+}
+
+/// Parse a single expression written as textual MIR. Globals are referred to by name: `globals` gives the procedure
+/// that a name refers to, along with its repr. Since nothing is expected of the expression, the parameter of a
+/// function at the top of it must be annotated with its repr.
+pub fn parse_expr(
+    src: &str,
+    globals: impl Fn(&str) -> Option<(ProcId, Repr)>,
+) -> Result<MirNode<Expr>, ParseError> {
+    let mut parser = Parser {
+        tokens: lex(src)?,
+        pos: 0,
+        src_len: src.len(),
+        globals: &globals,
+        scope: Vec::new(),
+    };
+    let expr = parser.node(None)?;
+    parser.expect_end()?;
+    Ok(expr)
+}
+
+impl Context {
+    /// Parse a program written as textual MIR, such as that produced by [`Context::print`]. Each procedure is written as
+    /// `proc <name> : <repr> = <body>` and the first is the entry point of the program. Procedures are given
+    /// [`ProcId::Named`] identities.
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let tokens = lex(src)?;
+        let no_globals = |_: &str| -> Option<(ProcId, Repr)> { None };
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            src_len: src.len(),
+            globals: &no_globals,
+            scope: Vec::new(),
+        };
+
+        // Procedures may refer to those that come after them, so the headers of all procedures are read first
+        let starts = (0..parser.tokens.len())
+            .filter(|&i| {
+                let is_word = |i: usize, word: &str| matches!(&parser.tokens[i].0, Token::Ident(ident) if ident == word);
+                is_word(i, "proc") && (i == 0 || !is_word(i - 1, "global"))
+            })
+            .collect::<Vec<_>>();
+        if starts.first() != Some(&0) {
+            return parser.error("Expected `proc`");
+        }
+        let mut headers = Vec::new();
+        let mut globals = HashMap::new();
+        for (i, start) in starts.iter().enumerate() {
+            parser.pos = start + 1;
+            let name = parser.ident()?;
+            parser.expect(":")?;
+            let repr = parser.repr()?;
+            parser.expect("=")?;
+            let id = ProcId::Named(Intern::new(name.clone()));
+            if globals.insert(name.clone(), (id, repr.clone())).is_some() {
+                return parser.error(format!("Procedure `{}` is defined more than once", name));
+            }
+            let end = starts.get(i + 1).copied().unwrap_or(parser.tokens.len());
+            headers.push((id, repr, parser.pos, end));
+        }
+
+        let globals = |name: &str| globals.get(name).cloned();
+        parser.globals = &globals;
+        let mut ctx = Self {
+            reprs: Reprs::default(),
+            procs: Procs::default(),
+            entry: headers.first().map(|(id, _, _, _)| *id),
+            eliminated: Vec::new(),
+        };
+        for (id, repr, start, end) in headers {
+            parser.pos = start;
+            let body = parser.node(Some(&repr))?;
+            if parser.pos != end {
+                return parser.error("Expected `proc` or end of input");
+            }
+            ctx.procs.declare(id);
+            ctx.procs.define(id, Proc {
+                body,
+                inline: InlineHint::default(),
+                fusion: None,
+            });
+        }
+
+        Ok(ctx)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Renumber the locals of textual MIR in the order that they first appear, so that MIR can be compared without
+    /// regard to the numbers that its locals were given.
+    pub(crate) fn normalize(src: &str) -> String {
+        let mut names = Vec::new();
+        let mut out = String::new();
+        let mut chars = src.chars().peekable();
+        while let Some(c) = chars.next() {
+            out.push(c);
+            if c == '$' {
+                let mut name = String::new();
+                while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    name.push(*d);
+                    chars.next();
+                }
+                let idx = names.iter().position(|n| *n == name).unwrap_or_else(|| {
+                    names.push(name);
+                    names.len() - 1
+                });
+                out += &idx.to_string();
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = r#"
+            proc main : Nat =
+                let $0 = (global pair)(const 3) in
+                let ($1, $2 ~ (_ + 1),) = $0 in
+                match (#1 ($1, const 'a',) : #(() | (Nat, Char,))) in
+                    | #0 _ => ($2 * const 2)
+                    | or (#1 ($9, const 'x',) | #1 ($9, const 'y',)) => $9
+                    | #1 ($3, const '\n',) if ($3 > $1) => (match [$3, const 4] in
+                        | [$4, .. $5] =>
+                            let $6 = ($5 ++ make_list<Nat>(const 1)) in
+                            ($4 + ?$3)
+                        | _ => let $7 = const "hi" in const 0)
+                    | _ => let $8 = -(const 7 - $1) in $1
+
+            proc pair : (Nat -> (Nat, Nat,)) =
+                fn $0 =>
+                    ($0, ($0 + const 1),)
+        "#;
+
+        let first = Context::parse(src).unwrap_or_else(|e| panic!("{}", e)).print();
+        let second = Context::parse(&first).unwrap_or_else(|e| panic!("{}\n\n{}", e, first)).print();
+        assert_eq!(normalize(&first), normalize(&second));
+    }
+
+    #[test]
+    fn needs_annotation() {
+        let err = parse_expr("#1 const 5", |_| None).unwrap_err();
+        assert_eq!(err.at, 0);
+        assert!(parse_expr("(#1 const 5 : #(() | Nat))", |_| None).is_ok());
+    }
+}
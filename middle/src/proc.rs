@@ -1,19 +1,83 @@
 use super::*;
 
-pub type ProcId = ConDefId;
+/// The identity of a procedure.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ProcId {
+    /// A procedure lowered from a monomorphised definition.
+    Def(ConDefId),
+    /// A procedure created during optimisation (such as a lifted lambda) from the body of the given definition.
+    Lifted(ConDefId, usize),
+    /// A procedure written by hand in textual MIR (see [`crate::parse`]), such as in tests of optimisation passes.
+    Named(Intern<String>),
+}
+
+/// A hint from the source (`$[inline]` or `$[no_inline]`) about whether a procedure should be inlined.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InlineHint {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Default for InlineHint {
+    fn default() -> Self { InlineHint::Auto }
+}
+
+/// The list combinator that a procedure implements, as declared by `$[fuse(map)]`, `$[fuse(filter)]` or
+/// `$[fuse(fold)]`. Chains of combinators are fused into a single traversal by [`opt::FuseLists`], so the declaration
+/// is a promise that the procedure behaves exactly like the combinator it names.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fusion {
+    /// `(A -> B) -> [A] -> [B]`
+    Map,
+    /// `(A -> Bool) -> [A] -> [A]`
+    Filter,
+    /// `A -> (A -> B -> A) -> [B] -> A`, applying the function from the front of the list
+    Fold,
+}
+
+impl Fusion {
+    /// The number of arguments, including the list, that the combinator takes.
+    pub fn arity(&self) -> usize {
+        match self {
+            Fusion::Map | Fusion::Filter => 2,
+            Fusion::Fold => 3,
+        }
+    }
+}
 
 pub struct Proc {
     pub body: mir::MirNode<mir::Expr>,
+    pub inline: InlineHint,
+    pub fusion: Option<Fusion>,
+}
+
+impl Proc {
+    /// The parameters that the procedure takes directly (i.e: the functions that its body begins with), along with the
+    /// expression that they are given to. Calls that provide all of them at once need not go through the curried form
+    /// of the procedure.
+    pub fn params(&self) -> (Vec<mir::Local>, &mir::MirNode<mir::Expr>) {
+        let mut params = Vec::new();
+        let mut body = &self.body;
+        while let mir::Expr::Func(param, inner) = &**body {
+            params.push(*param);
+            body = inner;
+        }
+        (params, body)
+    }
+
+    pub fn arity(&self) -> usize { self.params().0.len() }
 }
 
 #[derive(Default)]
 pub struct Procs {
     procs: BTreeMap<ProcId, Option<Proc>>,
+    lifted: usize,
 }
 
 impl Procs {
     pub fn id_of_con(&self, def: ConDefId) -> ProcId {
-        def
+        ProcId::Def(def)
     }
 
     pub fn is_declared(&self, id: ProcId) -> bool {
@@ -40,6 +104,21 @@ impl Procs {
         assert!(self.procs.insert(id, None).is_none(), "Proc declared twice");
     }
 
+    pub fn remove(&mut self, id: ProcId) -> Option<Proc> {
+        self.procs.remove(&id).flatten()
+    }
+
+    /// Define a new procedure created from the body of an existing one.
+    pub fn lift(&mut self, from: ProcId, proc: Proc) -> ProcId {
+        self.lifted += 1;
+        let id = match from {
+            ProcId::Def(def) | ProcId::Lifted(def, _) => ProcId::Lifted(def, self.lifted),
+            ProcId::Named(name) => ProcId::Named(Intern::new(format!("{}_lifted{}", name, self.lifted))),
+        };
+        self.procs.insert(id, Some(proc));
+        id
+    }
+
     pub fn define(&mut self, id: ProcId, proc: Proc) {
         assert!(self.procs.insert(id, Some(proc)).unwrap().is_none(), "Proc defined without declaration");
     }
@@ -1,5 +1,6 @@
 use super::*;
 use hashbrown::hash_map::Entry;
+use std::fmt;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Prim {
@@ -21,6 +22,35 @@ pub enum Repr {
     Union(Vec<Repr>),
 }
 
+impl fmt::Display for Prim {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Prim::Nat => write!(f, "Nat"),
+            Prim::Int => write!(f, "Int"),
+            Prim::Real => write!(f, "Real"),
+            Prim::Char => write!(f, "Char"),
+            Prim::Bool => write!(f, "Bool"),
+        }
+    }
+}
+
+// Written in the syntax of textual MIR (see [`crate::parse`]). Data types have no textual form, so they are only shown
+// for debugging.
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |reprs: &[Repr], sep| reprs.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(sep);
+        match self {
+            Repr::Prim(prim) => write!(f, "{}", prim),
+            Repr::List(item) => write!(f, "[{}]", item),
+            Repr::Tuple(fields) => write!(f, "({})", fields.iter().map(|r| format!("{},", r)).collect::<Vec<_>>().join(" ")),
+            Repr::Sum(variants) => write!(f, "#({})", list(variants, " | ")),
+            Repr::Data(data) => write!(f, "data {:?}", data),
+            Repr::Func(i, o) => write!(f, "({} -> {})", i, o),
+            Repr::Union(variants) => write!(f, "union({})", list(variants, " | ")),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Reprs {
     pub datas: HashMap<ConDataId, Option<Repr>>,
@@ -129,6 +129,8 @@ pub enum Type {
     // TODO: Replace name with `Item` when ready
     Data(SrcNode<Ident>, Vec<SrcNode<Self>>),
     Assoc(SrcNode<Self>, SrcNode<Ident>),
+    // A placeholder that turns the type around it into a type constructor (i.e: `Result _ E`)
+    Hole,
 }
 
 impl Type {
@@ -151,6 +153,7 @@ impl Type {
                 .iter()
                 .all(|arg| arg.is_fully_specified()),
             Self::Assoc(inner, _) => inner.is_fully_specified(),
+            Self::Hole => true,
         }
     }
 }
@@ -159,6 +162,8 @@ impl Type {
 pub struct ClassInst {
     pub name: SrcNode<Ident>,
     pub params: Vec<SrcNode<Type>>,
+    // Equality constraints on associated types (i.e: `Item = Nat` in `S < Stream with Item = Nat`)
+    pub assoc: Vec<(SrcNode<Ident>, SrcNode<Type>)>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -169,13 +174,15 @@ pub enum Pat {
     Literal(Literal),
     Single(SrcNode<Binding>),
     Union(SrcNode<Binding>),
-    Binary(SrcNode<BinaryOp>, SrcNode<Binding>, SrcNode<Literal>), // x + N, only for nats
+    Binary(SrcNode<BinaryOp>, SrcNode<Binding>, SrcNode<Literal>), // x + N or x * N, only for nats and ints
+    Range(SrcNode<Literal>, SrcNode<Literal>), // N..M, inclusive of both bounds
     Tuple(Vec<SrcNode<Binding>>),
-    Record(Vec<(SrcNode<Ident>, SrcNode<Binding>)>),
+    Record(Vec<(SrcNode<Ident>, SrcNode<Binding>)>, bool), // { a, b ~ x, .. }, the flag is set if other fields may be present
     ListExact(Vec<SrcNode<Binding>>),
     ListFront(Vec<SrcNode<Binding>>, Option<SrcNode<Binding>>),
     // TODO: Replace name with `Item` when ready
     Deconstruct(SrcNode<Ident>, SrcNode<Binding>),
+    Or(Vec<SrcNode<Binding>>), // A | B, every alternative must bind the same names
 }
 
 #[derive(Debug, PartialEq)]
@@ -198,17 +205,22 @@ pub enum Expr {
     Literal(Literal),
     // TODO: replace with `Item` when scoping is added
     Local(Ident),
+    // A typed hole (`?` or `_`), reported along with its expected type
+    Hole,
     Tuple(Vec<SrcNode<Self>>),
     List(Vec<SrcNode<Self>>),
     ListFront(Vec<SrcNode<Self>>, SrcNode<Self>),
     Record(Vec<(SrcNode<Ident>, SrcNode<Self>)>),
+    // Functional record update (`{ r with x: 1 }`), all other fields are taken from the record
+    Update(SrcNode<Self>, Vec<(SrcNode<Ident>, SrcNode<Self>)>),
     Access(SrcNode<Self>, SrcNode<Ident>),
     Unary(SrcNode<UnaryOp>, SrcNode<Self>),
     Binary(SrcNode<BinaryOp>, SrcNode<Self>, SrcNode<Self>),
     Let(Vec<(SrcNode<Binding>, SrcNode<Self>)>, SrcNode<Self>),
-    Match(SrcNode<Vec<SrcNode<Self>>>, Vec<(SrcNode<Vec<SrcNode<Binding>>>, SrcNode<Self>)>),
+    // Each arm has an optional guard (`| pat if cond => body`)
+    Match(SrcNode<Vec<SrcNode<Self>>>, Vec<(SrcNode<Vec<SrcNode<Binding>>>, Option<SrcNode<Self>>, SrcNode<Self>)>),
     If(SrcNode<Self>, SrcNode<Self>, SrcNode<Self>),
-    Func(Vec<(SrcNode<Vec<SrcNode<Binding>>>, SrcNode<Self>)>),
+    Func(Vec<(SrcNode<Vec<SrcNode<Binding>>>, Option<SrcNode<Self>>, SrcNode<Self>)>),
     Apply(SrcNode<Self>, SrcNode<Self>),
     Cons(SrcNode<Ident>, SrcNode<Self>),
     ClassAccess(SrcNode<Type>, SrcNode<Ident>),
@@ -256,6 +268,7 @@ pub struct Def {
 pub enum ClassItem {
     Value {
         name: SrcNode<Ident>,
+        generics: SrcNode<Generics>,
         ty: SrcNode<Type>,
     },
     Type {
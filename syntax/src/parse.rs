@@ -101,6 +101,9 @@ pub fn type_parser() -> impl Parser<ast::Type> {
         let unknown = just(Token::Question)
             .map(|_| ast::Type::Unknown);
 
+        let hole = just(Token::Wildcard)
+            .map(|_| ast::Type::Hole);
+
         let paren_ty = nested_parser(
             ty.clone().map(Some),
             Delimiter::Paren,
@@ -115,6 +118,7 @@ pub fn type_parser() -> impl Parser<ast::Type> {
             .or(union)
             .or(record)
             .or(unknown)
+            .or(hole)
             .or(select! { Token::Error(_) => () }.map(|_| ast::Type::Error))
             .map_with_span(SrcNode::new)
             .boxed();
@@ -150,14 +154,25 @@ pub fn type_parser() -> impl Parser<ast::Type> {
 }
 
 pub fn class_inst_parser() -> impl Parser<ast::ClassInst> {
+    let assoc = type_ident_parser()
+        .map_with_span(SrcNode::new)
+        .then_ignore(just(Token::Op(Op::Eq)))
+        .then(type_parser()
+            .map_with_span(SrcNode::new));
+
     type_ident_parser()
         .map_with_span(SrcNode::new)
         .then(type_parser()
             .map_with_span(SrcNode::new)
             .repeated())
-        .map(|(name, params)| ast::ClassInst {
+        // Commas already separate generic types, so each equality gets its own `with`
+        .then(just(Token::With)
+            .ignore_then(assoc)
+            .repeated())
+        .map(|((name, params), assoc)| ast::ClassInst {
             name,
             params,
+            assoc,
         })
 }
 
@@ -193,16 +208,36 @@ pub fn binding_parser() -> impl Parser<ast::Binding> {
         let wildcard = just(Token::Wildcard)
             .map_with_span(|_, span| SrcNode::new(ast::Pat::Wildcard, span));
 
-        let litr = literal_parser()
+        // Negative integers are permitted in patterns, since there's no other way to match them
+        let pat_litr = just(Token::Op(Op::Sub))
+            .ignore_then(select! { Token::Int(x) => ast::Literal::Int(-x) })
+            .or(literal_parser());
+
+        let litr = pat_litr.clone()
             .map_with_span(|litr, span| SrcNode::new(ast::Pat::Literal(litr), span));
 
+        let range = pat_litr.clone()
+            .map_with_span(SrcNode::new)
+            .then_ignore(just(Token::Op(Op::Ellipsis)))
+            .then(pat_litr.map_with_span(SrcNode::new))
+            .map_with_span(|(lo, hi), span| SrcNode::new(ast::Pat::Range(lo, hi), span));
+
+        // Alternatives may also be nested within parentheses (`(A x | B x)`)
         let paren_expr = nested_parser(
             binding.clone()
-                .map(Some),
+                .then(just(Token::Pipe)
+                    .ignore_then(binding.clone())
+                    .repeated())
+                .map(Some)
+                .boxed(),
             Delimiter::Paren,
             |_| None,
         )
-            .map(|x| x.map(ast::Pat::Single).unwrap_or(ast::Pat::Error))
+            .map(|x| match x {
+                Some((binding, alts)) if alts.is_empty() => ast::Pat::Single(binding),
+                Some((binding, alts)) => ast::Pat::Or(std::iter::once(binding).chain(alts).collect()),
+                None => ast::Pat::Error,
+            })
             .map_with_span(SrcNode::new);
 
         let tuple = nested_parser(
@@ -235,12 +270,16 @@ pub fn binding_parser() -> impl Parser<ast::Binding> {
                 .separated_by(just(Token::Comma))
                 .allow_trailing()
                 .then_ignore(just(Token::Comma).or_not())
+                // Other fields may be ignored with `..`
+                .then(just(Token::Op(Op::Ellipsis)).or_not())
                 .map(Some)
                 .boxed(),
             Delimiter::Brace,
             |_| None,
         )
-            .map(|x| x.map(ast::Pat::Record).unwrap_or(ast::Pat::Error))
+            .map(|x| x
+                .map(|(fields, rest)| ast::Pat::Record(fields, rest.is_some()))
+                .unwrap_or(ast::Pat::Error))
             .map_with_span(SrcNode::new);
 
         let list = nested_parser(
@@ -277,6 +316,7 @@ pub fn binding_parser() -> impl Parser<ast::Binding> {
             });
 
         let atom = wildcard
+            .or(range)
             .or(litr)
             .or(paren_expr)
             .or(tuple)
@@ -291,8 +331,8 @@ pub fn binding_parser() -> impl Parser<ast::Binding> {
             .boxed();
 
         let sum = atom
-            .then(just(Token::Op(Op::Add))
-                .to(ast::BinaryOp::Add)
+            .then(just(Token::Op(Op::Add)).to(ast::BinaryOp::Add)
+                .or(just(Token::Op(Op::Mul)).to(ast::BinaryOp::Mul))
                 .map_with_span(SrcNode::new)
                 .then(literal_parser().map_with_span(SrcNode::new))
                 .repeated())
@@ -358,7 +398,7 @@ pub fn binding_parser() -> impl Parser<ast::Binding> {
         });
 
     // Union pattern
-    just(Token::Question).or_not()
+    let binding = just(Token::Question).or_not()
         .then(binding.map_with_span(SrcNode::new))
         .map(|(union, binding)| if union.is_some() {
             let binding_span = binding.span();
@@ -369,6 +409,22 @@ pub fn binding_parser() -> impl Parser<ast::Binding> {
             }
         } else {
             binding.into_inner()
+        });
+
+    // Or pattern
+    binding.clone()
+        .map_with_span(SrcNode::new)
+        .then(just(Token::Pipe)
+            .ignore_then(binding.map_with_span(SrcNode::new))
+            .repeated())
+        .map_with_span(|(binding, alts), span| if alts.is_empty() {
+            binding.into_inner()
+        } else {
+            ast::Binding {
+                pat: SrcNode::new(ast::Pat::Or(std::iter::once(binding).chain(alts).collect()), span),
+                name: None,
+                ty: None,
+            }
         })
 }
 
@@ -376,6 +432,7 @@ pub fn expr_parser() -> impl Parser<ast::Expr> {
     recursive(|expr| {
         let litr = literal_parser().map(ast::Expr::Literal);
         let ident = term_ident_parser().map(ast::Expr::Local);
+        let hole = just(Token::Wildcard).map(|_| ast::Expr::Hole);
 
         let paren_exp_list = nested_parser(
             expr
@@ -393,22 +450,30 @@ pub fn expr_parser() -> impl Parser<ast::Expr> {
             .map(|x| x.map(ast::Expr::Tuple).unwrap_or(ast::Expr::Error))
             .labelled("tuple");
 
+        let record_fields = term_ident_parser()
+            .map_with_span(SrcNode::new)
+            .then(just(Token::Colon)
+                .ignore_then(expr.clone().map_with_span(SrcNode::new))
+                .or_not())
+            .map(|(field, val)| match val {
+                Some(val) => (field, val),
+                None => {
+                    let val = SrcNode::new(ast::Expr::Local(*field), field.span());
+                    (field, val)
+                },
+            })
+            .separated_by(just(Token::Comma))
+            .allow_trailing()
+            .boxed();
+
         let record = nested_parser(
-            term_ident_parser()
+            // Record update (`{ r with x: 1 }`)
+            expr.clone()
                 .map_with_span(SrcNode::new)
-                .then(just(Token::Colon)
-                    .ignore_then(expr.clone().map_with_span(SrcNode::new))
-                    .or_not())
-                .map(|(field, val)| match val {
-                    Some(val) => (field, val),
-                    None => {
-                        let val = SrcNode::new(ast::Expr::Local(*field), field.span());
-                        (field, val)
-                    },
-                })
-                .separated_by(just(Token::Comma))
-                .allow_trailing()
-                .map(ast::Expr::Record)
+                .then_ignore(just(Token::With))
+                .then(record_fields.clone())
+                .map(|(record, fields)| ast::Expr::Update(record, fields))
+                .or(record_fields.map(ast::Expr::Record))
                 .boxed(),
             Delimiter::Brace,
             |_| ast::Expr::Error,
@@ -440,10 +505,14 @@ pub fn expr_parser() -> impl Parser<ast::Expr> {
             .separated_by(just(Token::Comma))
             .allow_trailing()
             .map_with_span(SrcNode::new)
+            .then(just(Token::If)
+                .ignore_then(expr.clone().map_with_span(SrcNode::new))
+                .or_not())
             .then_ignore(just(Token::Op(Op::RFlow)))
             .then(expr
                 .clone()
                 .map_with_span(SrcNode::new))
+            .map(|((bindings, guard), body)| (bindings, guard, body))
             .boxed();
 
         let branches = branches(branch);
@@ -533,6 +602,7 @@ pub fn expr_parser() -> impl Parser<ast::Expr> {
 
         let atom = litr
             .or(ident)
+            .or(hole)
             .or(nested_parser(expr, Delimiter::Paren, |_| ast::Expr::Error))
             .or(tuple)
             .or(record)
@@ -626,6 +696,8 @@ pub fn expr_parser() -> impl Parser<ast::Expr> {
                 let span = op.span().union(expr.span());
                 SrcNode::new(ast::Expr::Unary(op, expr), span)
             })
+            // A `?` without an operand is a typed hole
+            .or(just(Token::Question).map_with_span(|_, span| SrcNode::new(ast::Expr::Hole, span)))
             .boxed();
 
         // Product
@@ -784,8 +856,12 @@ pub fn def_parser() -> impl Parser<ast::Def> {
         .separated_by(just(Token::Comma))
         .allow_trailing()
         .map_with_span(SrcNode::new)
+        .then(just(Token::If)
+            .ignore_then(expr_parser().map_with_span(SrcNode::new))
+            .or_not())
         .then_ignore(just(Token::Op(Op::RFlow)))
-        .then(expr_parser().map_with_span(SrcNode::new)))
+        .then(expr_parser().map_with_span(SrcNode::new))
+        .map(|((bindings, guard), body)| (bindings, guard, body)))
         .map_with_span(|branches, span| SrcNode::new(ast::Expr::Func(branches), span));
 
     just(Token::Def)
@@ -807,9 +883,11 @@ pub fn def_parser() -> impl Parser<ast::Def> {
 pub fn class_parser() -> impl Parser<ast::Class> {
     let value = term_ident_parser()
         .map_with_span(SrcNode::new)
+        .then(generics_parser().map_with_span(SrcNode::new))
         .then(ty_hint_parser())
-        .map(|(name, ty)| ast::ClassItem::Value {
+        .map(|((name, generics), ty)| ast::ClassItem::Value {
             ty: ty.unwrap_or_else(|| SrcNode::new(ast::Type::Unknown, name.span())),
+            generics,
             name,
         });
 
@@ -97,6 +97,7 @@ pub enum Token {
     In,
     Of,
     Do,
+    With,
     Tilde,
     Dollar,
     Semicolon,
@@ -145,6 +146,7 @@ impl fmt::Display for Token {
             Token::In => write!(f, "in"),
             Token::Of => write!(f, "of"),
             Token::Do => write!(f, "do"),
+            Token::With => write!(f, "with"),
             Token::Tilde => write!(f, "~"),
             Token::Dollar => write!(f, "$"),
             Token::Semicolon => write!(f, ";"),
@@ -259,6 +261,7 @@ pub fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Error> {
         "in" => Token::In,
         "of" => Token::Of,
         "do" => Token::Do,
+        "with" => Token::With,
         "and" => Token::Op(Op::And),
         "or" => Token::Op(Op::Or),
         "xor" => Token::Op(Op::Xor),
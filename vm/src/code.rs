@@ -8,9 +8,12 @@ pub enum Instr {
     Break,
 
     Call(isize),
+    CallWith(isize, usize), // Call the relative offset after moving the last N items on the stack to the locals stack
     Ret,
     MakeFunc(isize, usize), // Make a function using the relative offset and by capturing the last N items on the stack
     ApplyFunc,
+    #[cfg(feature = "jit")]
+    CallNative(usize), // Call the Nth native procedure with the arguments on the stack
 
     MakeList(usize), // T * N => [T]
     IndexList(usize), // Nth field of list/tuple
@@ -24,6 +27,7 @@ pub enum Instr {
 
     Jump(isize),
     IfNot,
+    Switch(usize), // Int => [], jumping to the target selected by the value in the Nth switch table
 
     Imm(Value),
     Pop(usize),
@@ -40,6 +44,8 @@ pub enum Instr {
     AddInt, // Int -> Int -> Int
     SubInt, // Int -> Int -> Int
     MulInt,
+    DivInt, // Euclidean division
+    RemInt, // Euclidean remainder, never negative
 
     EqInt, // Int -> Int -> Bool
     EqBool, // Bool -> Bool -> Bool
@@ -48,6 +54,8 @@ pub enum Instr {
     MoreInt,
     LessEqInt,
     MoreEqInt,
+    LessEqChar, // Char -> Char -> Bool
+    MoreEqChar, // Char -> Char -> Bool
 
     AndBool, // Bool -> Bool -> Bool
 }
@@ -67,11 +75,22 @@ impl Addr {
     pub fn jump_to(self, other: Self) -> isize { other.0 as isize - self.0 as isize }
 }
 
+/// A jump table. Offsets are relative to the address of the `Switch` instruction that uses the table.
+#[derive(Clone, Debug, Default)]
+pub struct SwitchTable {
+    pub targets: Vec<isize>,
+    /// Taken when the value is not a valid index into `targets`.
+    pub default: isize,
+}
+
 #[derive(Default, Debug)]
 pub struct Program {
     instrs: Vec<Instr>,
+    switches: Vec<SwitchTable>,
     pub entry: Addr,
     debug: Vec<(Addr, String)>,
+    #[cfg(feature = "jit")]
+    pub jit: Option<crate::jit::Jit>,
 }
 
 impl Program {
@@ -98,6 +117,28 @@ impl Program {
         self.instrs[addr.0] = make_instr(addr.jump_to(tgt));
     }
 
+    pub fn switch_table(&self, n: usize) -> &SwitchTable { &self.switches[n] }
+
+    // Push a `Switch` instruction with an empty table that must later be filled in with `fixup_switch`
+    pub fn push_switch(&mut self) -> Addr {
+        self.switches.push(SwitchTable::default());
+        self.push(Instr::Switch(self.switches.len() - 1))
+    }
+
+    pub fn fixup_switch(&mut self, addr: Addr, targets: impl IntoIterator<Item = Addr>, default: Addr) {
+        if let Instr::Switch(n) = self.instrs[addr.0] {
+            self.switches[n] = SwitchTable {
+                targets: targets
+                    .into_iter()
+                    .map(|tgt| addr.jump_to(tgt))
+                    .collect(),
+                default: addr.jump_to(default),
+            };
+        } else {
+            panic!("Attempted to fix up non-switch instruction at 0x{:03X}", addr.0);
+        }
+    }
+
     pub fn write(&self, mut writer: impl Write) {
         let mut debug = self.debug.iter().peekable();
         for addr in (0..self.instrs.len()).map(Addr) {
@@ -113,9 +154,12 @@ impl Program {
                 Instr::Pop(n) => -(n as isize),
                 Instr::Replace => -1,
                 Instr::Call(_) => 0,
+                Instr::CallWith(_, n) => -(n as isize) + 1,
                 Instr::Ret => 0,
                 Instr::MakeFunc(_, n) => -(n as isize),
                 Instr::ApplyFunc => 0, // Turns input stack item into output stack item
+                #[cfg(feature = "jit")]
+                Instr::CallNative(n) => 1 - self.jit.as_ref().map_or(0, |jit| jit.native(n).params.len() as isize),
                 Instr::MakeList(n) => -(n as isize) + 1,
                 Instr::IndexList(_) => 0,
                 Instr::SkipList(_) => 0,
@@ -127,6 +171,7 @@ impl Program {
                 Instr::Dup => 1,
                 Instr::Jump(_) => 0,
                 Instr::IfNot => -1,
+                Instr::Switch(_) => -1,
                 Instr::PushLocal => -1,
                 Instr::PopLocal(_) => 0,
                 Instr::GetLocal(_) => 1,
@@ -136,6 +181,8 @@ impl Program {
                 Instr::AddInt
                 | Instr::SubInt
                 | Instr::MulInt
+                | Instr::DivInt
+                | Instr::RemInt
                 | Instr::EqInt
                 | Instr::EqBool
                 | Instr::EqChar
@@ -143,6 +190,8 @@ impl Program {
                 | Instr::MoreInt
                 | Instr::LessEqInt
                 | Instr::MoreEqInt
+                | Instr::LessEqChar
+                | Instr::MoreEqChar
                 | Instr::AndBool => -1,
             };
 
@@ -154,9 +203,12 @@ impl Program {
                 Instr::Pop(n) => format!("pop {}", n),
                 Instr::Replace => format!("replace"),
                 Instr::Call(x) => format!("call {:+} (0x{:03X})", x, addr.jump(x).0),
+                Instr::CallWith(x, n) => format!("call.with {:+} (0x{:03X}) {}", x, addr.jump(x).0, n),
                 Instr::Ret => format!("ret"),
                 Instr::MakeFunc(i, n) => format!("func.make {:+} (0x{:03X}) {}", i, addr.jump(i).0, n),
                 Instr::ApplyFunc => format!("func.apply"),
+                #[cfg(feature = "jit")]
+                Instr::CallNative(n) => format!("native.call {}", n),
                 Instr::MakeList(n) => format!("list.make {}", n),
                 Instr::IndexList(i) => format!("list.index #{}", i),
                 Instr::SkipList(i) => format!("list.skip #{}", i),
@@ -168,6 +220,19 @@ impl Program {
                 Instr::Dup => format!("dup"),
                 Instr::Jump(x) => format!("jump {:+} (0x{:03X})", x, addr.jump(x).0),
                 Instr::IfNot => format!("if_not"),
+                Instr::Switch(n) => {
+                    let table = self.switch_table(n);
+                    format!(
+                        "switch [{}] default {:+} (0x{:03X})",
+                        table.targets
+                            .iter()
+                            .map(|x| format!("{:+}", x))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        table.default,
+                        addr.jump(table.default).0,
+                    )
+                },
                 Instr::PushLocal => format!("local.push"),
                 Instr::PopLocal(n) => format!("local.pop {}", n),
                 Instr::GetLocal(x) => format!("local.get +{}", x),
@@ -177,6 +242,8 @@ impl Program {
                 Instr::AddInt => format!("int.add"),
                 Instr::SubInt => format!("int.sub"),
                 Instr::MulInt => format!("int.mul"),
+                Instr::DivInt => format!("int.div"),
+                Instr::RemInt => format!("int.rem"),
                 Instr::EqInt => format!("int.eq"),
                 Instr::EqBool => format!("bool.eq"),
                 Instr::EqChar => format!("char.eq"),
@@ -184,6 +251,8 @@ impl Program {
                 Instr::MoreInt => format!("int.more"),
                 Instr::LessEqInt => format!("int.less_eq"),
                 Instr::MoreEqInt => format!("int.more_eq"),
+                Instr::LessEqChar => format!("char.less_eq"),
+                Instr::MoreEqChar => format!("char.more_eq"),
                 Instr::AndBool => format!("bool.and"),
             };
 
@@ -85,6 +85,13 @@ pub fn exec(prog: &Program) -> Option<Value> {
                 funcs.push(next_addr);
                 next_addr = addr.jump(n);
             },
+            Instr::CallWith(n, arity) => {
+                let mut args = stack.split_off(stack.len() - arity);
+                locals.append(&mut args);
+
+                funcs.push(next_addr);
+                next_addr = addr.jump(n);
+            },
             Instr::Ret => if let Some(addr) = funcs.pop() {
                 next_addr = addr;
             } else {
@@ -106,6 +113,15 @@ pub fn exec(prog: &Program) -> Option<Value> {
 
                 locals.append(&mut captures);
             },
+            #[cfg(feature = "jit")]
+            Instr::CallNative(n) => {
+                let native = prog.jit
+                    .as_ref()
+                    .expect("Native call in program without natives")
+                    .native(n);
+                let args = stack.split_off(stack.len() - native.params.len());
+                stack.push(native.call(args));
+            },
             Instr::MakeList(n) => {
                 let val = Value::List(stack.split_off(stack.len().saturating_sub(n)));
                 stack.push(val);
@@ -154,6 +170,16 @@ pub fn exec(prog: &Program) -> Option<Value> {
                     next_addr = next_addr.jump(1);
                 }
             },
+            Instr::Switch(n) => {
+                let table = prog.switch_table(n);
+                let x = stack.pop().unwrap().int();
+                let offset = usize::try_from(x)
+                    .ok()
+                    .and_then(|x| table.targets.get(x))
+                    .copied()
+                    .unwrap_or(table.default);
+                next_addr = addr.jump(offset);
+            },
             Instr::PushLocal => locals.push(stack.pop().unwrap()),
             Instr::PopLocal(n) => locals.truncate(locals.len() - n),
             Instr::GetLocal(x) => stack.push(locals[locals.len() - 1 - x].clone()),
@@ -163,7 +189,7 @@ pub fn exec(prog: &Program) -> Option<Value> {
             },
             Instr::NegInt => {
                 let x = stack.pop().unwrap().int();
-                stack.push(Value::Int(-x))
+                stack.push(Value::Int(x.wrapping_neg()))
             },
             Instr::NegReal => {
                 let x = stack.pop().unwrap().real();
@@ -172,17 +198,27 @@ pub fn exec(prog: &Program) -> Option<Value> {
             Instr::AddInt => {
                 let y = stack.pop().unwrap().int();
                 let x = stack.pop().unwrap().int();
-                stack.push(Value::Int(x + y))
+                stack.push(Value::Int(x.wrapping_add(y)))
             },
             Instr::SubInt => {
                 let y = stack.pop().unwrap().int();
                 let x = stack.pop().unwrap().int();
-                stack.push(Value::Int(x - y))
+                stack.push(Value::Int(x.wrapping_sub(y)))
             },
             Instr::MulInt => {
                 let y = stack.pop().unwrap().int();
                 let x = stack.pop().unwrap().int();
-                stack.push(Value::Int(x * y))
+                stack.push(Value::Int(x.wrapping_mul(y)))
+            },
+            Instr::DivInt => {
+                let y = stack.pop().unwrap().int();
+                let x = stack.pop().unwrap().int();
+                stack.push(Value::Int(x.div_euclid(y)))
+            },
+            Instr::RemInt => {
+                let y = stack.pop().unwrap().int();
+                let x = stack.pop().unwrap().int();
+                stack.push(Value::Int(x.rem_euclid(y)))
             },
             Instr::EqInt => {
                 let y = stack.pop().unwrap().int();
@@ -219,6 +255,16 @@ pub fn exec(prog: &Program) -> Option<Value> {
                 let x = stack.pop().unwrap().int();
                 stack.push(Value::Bool(x >= y))
             },
+            Instr::LessEqChar => {
+                let y = stack.pop().unwrap().char();
+                let x = stack.pop().unwrap().char();
+                stack.push(Value::Bool(x <= y))
+            },
+            Instr::MoreEqChar => {
+                let y = stack.pop().unwrap().char();
+                let x = stack.pop().unwrap().char();
+                stack.push(Value::Bool(x >= y))
+            },
             Instr::AndBool => {
                 let y = stack.pop().unwrap().bool();
                 let x = stack.pop().unwrap().bool();
@@ -0,0 +1,636 @@
+//! Native compilation of numeric procedures with Cranelift.
+//!
+//! Only procedures that are curried functions of primitive parameters producing a primitive, and whose bodies use only
+//! arithmetic, primitive patterns and saturated calls to other such procedures, are compiled. Everything else is left to
+//! the interpreter.
+
+use super::*;
+use cranelift_codegen::{
+    ir::{types, AbiParam, InstBuilder, MemFlags, TrapCode, Type, Value as ClifValue, condcodes::{IntCC, FloatCC}},
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{Module, Linkage, FuncId, default_libcall_names};
+use std::fmt;
+
+fn prim_of(repr: &repr::Repr) -> Option<repr::Prim> {
+    if let repr::Repr::Prim(prim) = repr { Some(*prim) } else { None }
+}
+
+fn clif_ty(prim: repr::Prim) -> Type {
+    match prim {
+        repr::Prim::Real => types::F64,
+        repr::Prim::Nat | repr::Prim::Int | repr::Prim::Char | repr::Prim::Bool => types::I64,
+    }
+}
+
+/// The signature of a procedure that might be compiled natively.
+struct Signature<'a> {
+    params: Vec<(mir::Local, repr::Prim)>,
+    ret: repr::Prim,
+    body: &'a MirNode<mir::Expr>,
+}
+
+impl<'a> Signature<'a> {
+    fn of(proc: &'a MirNode<mir::Expr>) -> Option<Self> {
+        let mut params = Vec::new();
+        let mut body = proc;
+        while let mir::Expr::Func(arg, inner) = &**body {
+            match body.meta() {
+                repr::Repr::Func(i, _) => params.push((*arg, prim_of(i)?)),
+                _ => return None,
+            }
+            body = inner;
+        }
+
+        if params.is_empty() {
+            None
+        } else {
+            Some(Self { params, ret: prim_of(body.meta())?, body })
+        }
+    }
+}
+
+fn is_supported_binding(binding: &MirNode<mir::Binding>) -> bool {
+    match &binding.pat {
+        mir::Pat::Wildcard => true,
+        mir::Pat::Literal(litr) => matches!(
+            litr,
+            mir::Literal::Nat(_) | mir::Literal::Int(_) | mir::Literal::Real(_) | mir::Literal::Char(_) | mir::Literal::Bool(_),
+        ),
+        mir::Pat::Range(_, _) => true,
+        mir::Pat::Single(inner) => is_supported_binding(inner),
+        mir::Pat::Add(lhs, _) => is_supported_binding(lhs),
+        mir::Pat::Tuple(fields) => fields
+            .iter()
+            .all(is_supported_binding),
+        _ => false,
+    }
+}
+
+// `arities` contains the procedures that are still candidates for native compilation
+fn is_supported(expr: &MirNode<mir::Expr>, arities: &HashMap<ProcId, usize>) -> bool {
+    use mir::Intrinsic::*;
+    match &**expr {
+        mir::Expr::Literal(litr) => matches!(
+            litr,
+            mir::Literal::Nat(_) | mir::Literal::Int(_) | mir::Literal::Real(_) | mir::Literal::Char(_) | mir::Literal::Bool(_),
+        ),
+        // Locals are always bound to values produced by supported expressions
+        mir::Expr::Local(_) => true,
+        mir::Expr::Intrinsic(MakeList(_) | Join(_) | Union(_), _) => false,
+        mir::Expr::Intrinsic(_, args) => args
+            .iter()
+            .all(|arg| is_supported(arg, arities)),
+        mir::Expr::Match(pred, arms) => prim_of(expr.meta()).is_some()
+            && is_supported(pred, arities)
+            && arms
+                .iter()
+                .all(|(binding, guard, body)| guard.is_none() && is_supported_binding(binding) && is_supported(body, arities)),
+        mir::Expr::Apply(_, _) => match expr.as_call() {
+            Some((proc, args)) => arities.get(&proc) == Some(&args.len()) && args
+                .into_iter()
+                .all(|arg| is_supported(arg, arities)),
+            None => false,
+        },
+        mir::Expr::Tuple(fields) => fields
+            .iter()
+            .all(|field| is_supported(field, arities)),
+        mir::Expr::Access(tuple, _) => is_supported(tuple, arities),
+        _ => false,
+    }
+}
+
+#[derive(Clone)]
+enum JitVal {
+    Prim(ClifValue),
+    Tuple(Vec<JitVal>),
+}
+
+impl JitVal {
+    fn prim(&self) -> ClifValue {
+        match self {
+            JitVal::Prim(val) => *val,
+            JitVal::Tuple(_) => panic!("Expected primitive, found tuple"),
+        }
+    }
+}
+
+struct FuncCompiler<'a, 'b> {
+    builder: FunctionBuilder<'b>,
+    module: &'a mut JITModule,
+    funcs: &'a HashMap<ProcId, FuncId>,
+    locals: Vec<(mir::Local, JitVal)>,
+}
+
+impl<'a, 'b> FuncCompiler<'a, 'b> {
+    // Comparisons produce an 8-bit integer, but booleans are 64 bits wide like every other primitive
+    fn bool_to_int(&mut self, cond: ClifValue) -> ClifValue {
+        self.builder.ins().uextend(types::I64, cond)
+    }
+
+    fn and(&mut self, a: Option<ClifValue>, b: Option<ClifValue>) -> Option<ClifValue> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(self.builder.ins().band(a, b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // Produces an integer that is non-zero if the value matches the binding, or `None` if it always matches
+    fn compile_test(&mut self, binding: &MirNode<mir::Binding>, val: &JitVal) -> Option<ClifValue> {
+        match &binding.pat {
+            mir::Pat::Wildcard => None,
+            mir::Pat::Literal(litr) => {
+                let cond = match litr {
+                    mir::Literal::Nat(x) => self.builder.ins().icmp_imm(IntCC::Equal, val.prim(), *x as i64),
+                    mir::Literal::Int(x) => self.builder.ins().icmp_imm(IntCC::Equal, val.prim(), *x),
+                    mir::Literal::Char(c) => self.builder.ins().icmp_imm(IntCC::Equal, val.prim(), *c as i64),
+                    mir::Literal::Bool(x) => self.builder.ins().icmp_imm(IntCC::Equal, val.prim(), *x as i64),
+                    mir::Literal::Real(x) => {
+                        let x = self.builder.ins().f64const(*x);
+                        self.builder.ins().fcmp(FloatCC::Equal, val.prim(), x)
+                    },
+                    litr => unreachable!("{:?}", litr),
+                };
+                Some(self.bool_to_int(cond))
+            },
+            mir::Pat::Range(lo, hi) => {
+                let (lo, hi) = match (lo, hi) {
+                    (mir::Literal::Nat(lo), mir::Literal::Nat(hi)) => (*lo as i64, *hi as i64),
+                    (mir::Literal::Int(lo), mir::Literal::Int(hi)) => (*lo, *hi),
+                    (mir::Literal::Char(lo), mir::Literal::Char(hi)) => (*lo as i64, *hi as i64),
+                    range => unreachable!("{:?}", range),
+                };
+                let above = self.builder.ins().icmp_imm(IntCC::SignedGreaterThanOrEqual, val.prim(), lo);
+                let above = self.bool_to_int(above);
+                let below = self.builder.ins().icmp_imm(IntCC::SignedLessThanOrEqual, val.prim(), hi);
+                let below = self.bool_to_int(below);
+                self.and(Some(above), Some(below))
+            },
+            mir::Pat::Single(inner) => self.compile_test(inner, val),
+            mir::Pat::Add(lhs, rhs) => {
+                // Only naturals have a lower bound to check (see `mir::Pat::Add`)
+                let cond = if *lhs.meta() == repr::Repr::Prim(repr::Prim::Int) {
+                    None
+                } else {
+                    let cond = self.builder.ins().icmp_imm(IntCC::SignedGreaterThanOrEqual, val.prim(), *rhs as i64);
+                    Some(self.bool_to_int(cond))
+                };
+                let rest = self.builder.ins().iadd_imm(val.prim(), -(*rhs as i64));
+                let inner = self.compile_test(lhs, &JitVal::Prim(rest));
+                self.and(cond, inner)
+            },
+            mir::Pat::Tuple(fields) => match val {
+                JitVal::Tuple(vals) => fields
+                    .iter()
+                    .zip(vals.iter())
+                    .fold(None, |cond, (field, val)| {
+                        let field = self.compile_test(field, val);
+                        self.and(cond, field)
+                    }),
+                JitVal::Prim(_) => panic!("Tuple pattern applied to primitive"),
+            },
+            pat => unreachable!("{:?}", pat),
+        }
+    }
+
+    fn compile_extractor(&mut self, binding: &MirNode<mir::Binding>, val: JitVal) {
+        if let Some(name) = binding.name {
+            self.locals.push((name, val.clone()));
+        }
+
+        match &binding.pat {
+            mir::Pat::Wildcard | mir::Pat::Literal(_) | mir::Pat::Range(_, _) => {},
+            mir::Pat::Single(inner) => self.compile_extractor(inner, val),
+            mir::Pat::Add(lhs, rhs) => {
+                let rest = self.builder.ins().iadd_imm(val.prim(), -(*rhs as i64));
+                self.compile_extractor(lhs, JitVal::Prim(rest));
+            },
+            mir::Pat::Tuple(fields) => match val {
+                JitVal::Tuple(vals) => fields
+                    .iter()
+                    .zip(vals.into_iter())
+                    .for_each(|(field, val)| self.compile_extractor(field, val)),
+                JitVal::Prim(_) => panic!("Tuple pattern applied to primitive"),
+            },
+            pat => unreachable!("{:?}", pat),
+        }
+    }
+
+    fn compile_expr(&mut self, expr: &MirNode<mir::Expr>) -> JitVal {
+        match &**expr {
+            mir::Expr::Literal(litr) => JitVal::Prim(match litr {
+                mir::Literal::Nat(x) => self.builder.ins().iconst(types::I64, *x as i64),
+                mir::Literal::Int(x) => self.builder.ins().iconst(types::I64, *x),
+                mir::Literal::Char(c) => self.builder.ins().iconst(types::I64, *c as i64),
+                mir::Literal::Bool(x) => self.builder.ins().iconst(types::I64, *x as i64),
+                mir::Literal::Real(x) => self.builder.ins().f64const(*x),
+                litr => unreachable!("{:?}", litr),
+            }),
+            mir::Expr::Local(local) => self.locals
+                .iter()
+                .rev()
+                .find(|(name, _)| name == local)
+                .unwrap_or_else(|| panic!("Tried to find local ${}, but it was not found", local.0))
+                .1
+                .clone(),
+            mir::Expr::Intrinsic(intrinsic, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.compile_expr(arg).prim())
+                    .collect::<Vec<_>>();
+                use mir::Intrinsic::*;
+                let (cc, a, b) = match intrinsic {
+                    NotBool => return JitVal::Prim(self.builder.ins().bxor_imm(args[0], 1)),
+                    NegNat | NegInt => return JitVal::Prim(self.builder.ins().ineg(args[0])),
+                    NegReal => return JitVal::Prim(self.builder.ins().fneg(args[0])),
+                    AddNat | AddInt => return JitVal::Prim(self.builder.ins().iadd(args[0], args[1])),
+                    SubNat | SubInt => return JitVal::Prim(self.builder.ins().isub(args[0], args[1])),
+                    MulNat | MulInt => return JitVal::Prim(self.builder.ins().imul(args[0], args[1])),
+                    EqNat | EqInt | EqChar => (IntCC::Equal, args[0], args[1]),
+                    NotEqNat | NotEqInt | NotEqChar => (IntCC::NotEqual, args[0], args[1]),
+                    LessNat | LessInt => (IntCC::SignedLessThan, args[0], args[1]),
+                    MoreNat | MoreInt => (IntCC::SignedGreaterThan, args[0], args[1]),
+                    LessEqNat | LessEqInt => (IntCC::SignedLessThanOrEqual, args[0], args[1]),
+                    MoreEqNat | MoreEqInt => (IntCC::SignedGreaterThanOrEqual, args[0], args[1]),
+                    intrinsic => unreachable!("{:?}", intrinsic),
+                };
+                let cond = self.builder.ins().icmp(cc, a, b);
+                JitVal::Prim(self.bool_to_int(cond))
+            },
+            mir::Expr::Match(pred, arms) => {
+                let pred = self.compile_expr(pred);
+                let ret = prim_of(expr.meta()).unwrap();
+
+                let merge = self.builder.create_block();
+                self.builder.append_block_param(merge, clif_ty(ret));
+
+                for (i, (binding, _, body)) in arms.iter().enumerate() {
+                    let is_last = i + 1 == arms.len();
+                    // The last arm is assumed to match (see `mir::Expr::Match`)
+                    let cond = if is_last { None } else { self.compile_test(binding, &pred) };
+
+                    let next = cond.map(|cond| {
+                        let arm_block = self.builder.create_block();
+                        let next = self.builder.create_block();
+                        self.builder.ins().brif(cond, arm_block, &[], next, &[]);
+                        self.builder.seal_block(arm_block);
+                        self.builder.seal_block(next);
+                        self.builder.switch_to_block(arm_block);
+                        next
+                    });
+
+                    let old_locals = self.locals.len();
+                    self.compile_extractor(binding, pred.clone());
+                    let output = self.compile_expr(body).prim();
+                    self.locals.truncate(old_locals);
+                    self.builder.ins().jump(merge, &[output]);
+
+                    match next {
+                        Some(next) => self.builder.switch_to_block(next),
+                        // Later arms can never be reached
+                        None => break,
+                    }
+                }
+
+                if arms.is_empty() {
+                    // Matches without arms are never evaluated
+                    self.builder.ins().trap(TrapCode::unwrap_user(1));
+                }
+
+                self.builder.seal_block(merge);
+                self.builder.switch_to_block(merge);
+                JitVal::Prim(self.builder.block_params(merge)[0])
+            },
+            mir::Expr::Apply(_, _) => {
+                let (proc, args) = expr.as_call().unwrap();
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.compile_expr(arg).prim())
+                    .collect::<Vec<_>>();
+                let func = self.module.declare_func_in_func(self.funcs[&proc], &mut self.builder.func);
+                let call = self.builder.ins().call(func, &args);
+                JitVal::Prim(self.builder.inst_results(call)[0])
+            },
+            mir::Expr::Tuple(fields) => JitVal::Tuple(fields
+                .iter()
+                .map(|field| self.compile_expr(field))
+                .collect()),
+            mir::Expr::Access(tuple, field) => match self.compile_expr(tuple) {
+                JitVal::Tuple(mut fields) => fields.remove(*field),
+                JitVal::Prim(_) => panic!("Tried to access field of primitive"),
+            },
+            expr => unreachable!("{:?}", expr),
+        }
+    }
+}
+
+/// A natively compiled procedure.
+pub struct Native {
+    pub params: Vec<repr::Prim>,
+    pub ret: repr::Prim,
+    // Takes a pointer to the arguments, encoded as bits, and returns the result as bits
+    trampoline: extern "C" fn(*const u64) -> u64,
+}
+
+impl Native {
+    pub fn call(&self, args: Vec<Value>) -> Value {
+        let args = args
+            .into_iter()
+            .map(|arg| match arg {
+                Value::Int(x) => x as u64,
+                Value::Real(x) => x.to_bits(),
+                Value::Char(c) => c as u64,
+                Value::Bool(x) => x as u64,
+                arg => panic!("Non-primitive argument {} passed to native procedure", arg),
+            })
+            .collect::<Vec<_>>();
+        debug_assert_eq!(args.len(), self.params.len());
+
+        let ret = (self.trampoline)(args.as_ptr());
+
+        match self.ret {
+            repr::Prim::Nat | repr::Prim::Int => Value::Int(ret as i64),
+            repr::Prim::Real => Value::Real(f64::from_bits(ret)),
+            repr::Prim::Char => Value::Char(char::from_u32(ret as u32).expect("Native procedure produced an invalid char")),
+            repr::Prim::Bool => Value::Bool(ret != 0),
+        }
+    }
+}
+
+/// A set of natively compiled procedures.
+pub struct Jit {
+    // Owns the memory that the compiled code lives in
+    _module: JITModule,
+    natives: Vec<Native>,
+    procs: HashMap<ProcId, usize>,
+}
+
+impl fmt::Debug for Jit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Jit({} native procedures)", self.natives.len())
+    }
+}
+
+impl Jit {
+    pub fn native_of(&self, proc: ProcId) -> Option<usize> {
+        self.procs.get(&proc).copied()
+    }
+
+    pub fn native(&self, idx: usize) -> &Native {
+        &self.natives[idx]
+    }
+
+    pub fn compile(mir: &MirContext, procs: impl IntoIterator<Item = ProcId>) -> Self {
+        let sigs = procs
+            .into_iter()
+            .filter_map(|proc| Some((proc, Signature::of(&mir.procs.get(proc)?.body)?)))
+            .collect::<HashMap<_, _>>();
+
+        // Procedures may only call procedures that are themselves native, so iterate until no more are removed
+        let mut arities = sigs
+            .iter()
+            .map(|(proc, sig)| (*proc, sig.params.len()))
+            .collect::<HashMap<_, _>>();
+        loop {
+            let unsupported = arities
+                .keys()
+                .copied()
+                .filter(|proc| !is_supported(sigs[proc].body, &arities))
+                .collect::<Vec<_>>();
+            if unsupported.is_empty() {
+                break;
+            }
+            for proc in unsupported {
+                arities.remove(&proc);
+            }
+        }
+
+        let mut flags = settings::builder();
+        flags.set("use_colocated_libcalls", "false").unwrap();
+        flags.set("is_pic", "false").unwrap();
+        let isa = cranelift_native::builder()
+            .expect("Host machine is not supported by Cranelift")
+            .finish(settings::Flags::new(flags))
+            .expect("Failed to configure Cranelift for the host machine");
+        let mut module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        let ptr_ty = module.target_config().pointer_type();
+
+        // Declare everything first so that procedures may call one-another
+        let mut funcs = HashMap::new();
+        let mut trampolines = HashMap::new();
+        for (i, proc) in arities.keys().enumerate() {
+            let sig = &sigs[proc];
+
+            let mut func_sig = module.make_signature();
+            func_sig.params.extend(sig.params.iter().map(|(_, prim)| AbiParam::new(clif_ty(*prim))));
+            func_sig.returns.push(AbiParam::new(clif_ty(sig.ret)));
+            funcs.insert(*proc, module
+                .declare_function(&format!("proc_{}", i), Linkage::Local, &func_sig)
+                .unwrap());
+
+            let mut tramp_sig = module.make_signature();
+            tramp_sig.params.push(AbiParam::new(ptr_ty));
+            tramp_sig.returns.push(AbiParam::new(types::I64));
+            trampolines.insert(*proc, (module
+                .declare_function(&format!("trampoline_{}", i), Linkage::Local, &tramp_sig)
+                .unwrap(), tramp_sig));
+        }
+
+        let mut ctx = module.make_context();
+        let mut func_ctx = FunctionBuilderContext::new();
+        for proc in arities.keys() {
+            let sig = &sigs[proc];
+
+            // The procedure itself
+            ctx.func.signature = module.declarations().get_function_decl(funcs[proc]).signature.clone();
+            {
+                let mut builder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+                let entry = builder.create_block();
+                builder.append_block_params_for_function_params(entry);
+                builder.switch_to_block(entry);
+                builder.seal_block(entry);
+
+                let locals = sig.params
+                    .iter()
+                    .zip(builder.block_params(entry).to_vec())
+                    .map(|((name, _), val)| (*name, JitVal::Prim(val)))
+                    .collect();
+                let mut compiler = FuncCompiler {
+                    builder,
+                    module: &mut module,
+                    funcs: &funcs,
+                    locals,
+                };
+                let output = compiler.compile_expr(sig.body).prim();
+                compiler.builder.ins().return_(&[output]);
+                compiler.builder.finalize();
+            }
+            module
+                .define_function(funcs[proc], &mut ctx)
+                .unwrap();
+            module.clear_context(&mut ctx);
+
+            // A trampoline with a uniform signature so that the interpreter can call it
+            let (tramp, tramp_sig) = &trampolines[proc];
+            ctx.func.signature = tramp_sig.clone();
+            {
+                let mut builder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+                let entry = builder.create_block();
+                builder.append_block_params_for_function_params(entry);
+                builder.switch_to_block(entry);
+                builder.seal_block(entry);
+
+                let args_ptr = builder.block_params(entry)[0];
+                let args = sig.params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, prim))| {
+                        let bits = builder.ins().load(types::I64, MemFlags::trusted(), args_ptr, (i * 8) as i32);
+                        match prim {
+                            repr::Prim::Real => builder.ins().bitcast(types::F64, MemFlags::new(), bits),
+                            _ => bits,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let func = module.declare_func_in_func(funcs[proc], &mut builder.func);
+                let call = builder.ins().call(func, &args);
+                let output = builder.inst_results(call)[0];
+                let output = match sig.ret {
+                    repr::Prim::Real => builder.ins().bitcast(types::I64, MemFlags::new(), output),
+                    _ => output,
+                };
+                builder.ins().return_(&[output]);
+                builder.finalize();
+            }
+            module
+                .define_function(*tramp, &mut ctx)
+                .unwrap();
+            module.clear_context(&mut ctx);
+        }
+
+        module
+            .finalize_definitions()
+            .expect("Failed to finalise native procedures");
+
+        let mut natives = Vec::new();
+        let mut procs = HashMap::new();
+        for proc in arities.keys() {
+            let sig = &sigs[proc];
+            let ptr = module.get_finalized_function(trampolines[proc].0);
+            procs.insert(*proc, natives.len());
+            natives.push(Native {
+                params: sig.params
+                    .iter()
+                    .map(|(_, prim)| *prim)
+                    .collect(),
+                ret: sig.ret,
+                // Safety: the trampoline was declared with exactly this signature
+                trampoline: unsafe { std::mem::transmute::<*const u8, extern "C" fn(*const u64) -> u64>(ptr) },
+            });
+        }
+
+        Self {
+            _module: module,
+            natives,
+            procs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tao_syntax::{parse_module, SrcId};
+    use tao_analysis::Context as HirContext;
+    use tao_middle::OptMode;
+
+    // Test programs run without the standard library, but every program needs the lang items
+    const PRELUDE: &str = include_str!("../../compiler/tests/prelude.tao");
+
+    // Compile a program to optimised MIR, as `tao` would
+    fn compile(src: &str) -> MirContext {
+        let src = format!("{}\n{}", PRELUDE, src);
+        let (ast, errors) = parse_module(&src, SrcId::from_path("jit.tao"));
+        assert!(errors.is_empty(), "Program has {} syntax errors", errors.len());
+        let (hir, errors) = HirContext::from_module(&ast.unwrap());
+        assert!(errors.is_empty(), "Program has {} type errors", errors.len());
+        let (con, errors) = hir.concretize();
+        assert!(errors.is_empty(), "Program has {} errors after concretization", errors.len());
+
+        let mut mir = MirContext::from_concrete(&hir, &con);
+        mir.optimize(OptMode::Fast);
+        mir
+    }
+
+    // Run a program with and without native compilation, both of which must produce the expected value
+    fn check(src: &str, expected: &str) {
+        let mir = compile(src);
+
+        let interpreted = Program::from_mir_with(&mir, false);
+        let native = Program::from_mir_with(&mir, true);
+        assert!(
+            native.jit.as_ref().map_or(false, |jit| !jit.procs.is_empty()),
+            "No procedures were compiled to native code",
+        );
+
+        let interpreted = exec(&interpreted).expect("Program produced no value").to_string();
+        let native = exec(&native).expect("Program produced no value").to_string();
+        assert_eq!(interpreted, expected);
+        assert_eq!(native, expected);
+    }
+
+    #[test]
+    fn factorial() {
+        check("
+            def factorial : Nat -> Nat =
+                | 0 => 1
+                \\ n ~ m + 1 => n * factorial(m)
+
+            $[main]
+            def main = factorial(20)
+        ", "2432902008176640000i");
+    }
+
+    #[test]
+    fn fibonacci() {
+        check("
+            def fib : Nat -> Nat =
+                | 0 => 0
+                | 1 => 1
+                \\ n + 2 => fib(n) + fib(n + 1)
+
+            $[main]
+            def main = fib(25)
+        ", "75025i");
+    }
+
+    #[test]
+    fn accumulate_ints() {
+        check("
+            def sum_to : Int -> Int -> Int =
+                | acc, 0i => acc
+                \\ acc, n => sum_to(acc + n, n - 1i)
+
+            $[main]
+            def main = sum_to(0i, 1000i)
+        ", "500500i");
+    }
+
+    // Procedures over lists are interpreted, but may call native procedures for each item
+    #[test]
+    fn sum_of_squares() {
+        check("
+            $[no_inline]
+            def square : Nat -> Nat = fn x => x * x
+
+            def sum_squares : [Nat] -> Nat =
+                | [] => 0
+                \\ [x .. xs] => square(x) + sum_squares(xs)
+
+            $[main]
+            def main = sum_squares([1, 2, 3, 4, 5])
+        ", "55i");
+    }
+}
@@ -1,6 +1,8 @@
 pub mod code;
 pub mod exec;
 pub mod lower;
+#[cfg(feature = "jit")]
+pub mod jit;
 
 pub use crate::{
     code::{Instr, Program, Addr},
@@ -24,6 +24,60 @@ fn litr_to_value(literal: &mir::Literal) -> Value {
     }
 }
 
+// The largest jump table that will be generated for a match
+const MAX_SWITCH_LEN: usize = 256;
+
+// The value that a jump table selects an arm with
+#[derive(Copy, Clone, PartialEq)]
+enum SwitchOn {
+    Variant,
+    Int,
+    ListLen,
+}
+
+// The entry of a jump table that selects the arm with the given binding, if the arm can be selected by one
+fn switch_key(binding: &MirNode<mir::Binding>) -> Option<(SwitchOn, usize)> {
+    match &binding.pat {
+        mir::Pat::Variant(variant, inner) if !inner.is_refutable() => Some((SwitchOn::Variant, *variant)),
+        mir::Pat::UnionVariant(id, inner) if !inner.is_refutable() => Some((SwitchOn::Variant, *id as usize)),
+        mir::Pat::Literal(mir::Literal::Nat(x)) => Some((SwitchOn::Int, *x as usize)),
+        mir::Pat::Literal(mir::Literal::Int(x)) if *x >= 0 => Some((SwitchOn::Int, *x as usize)),
+        mir::Pat::ListExact(items) if items.iter().all(|item| !item.is_refutable()) => Some((SwitchOn::ListLen, items.len())),
+        _ => None,
+    }
+}
+
+// If each arm of a match tests for a distinct constructor without a guard (optionally followed by a single irrefutable
+// arm), as produced by decision tree compilation, then the arm can be selected with a jump table. Returns what the
+// jump table selects arms with and the key of each arm.
+fn switch_keys(arms: &[(MirNode<mir::Binding>, Option<MirNode<mir::Expr>>, MirNode<mir::Expr>)]) -> Option<(SwitchOn, Vec<Option<usize>>)> {
+    let (last, init) = arms.split_last()?;
+    let mut keys = init
+        .iter()
+        .map(|(binding, guard, _)| guard.is_none().then(|| switch_key(binding)).flatten().map(Some))
+        .collect::<Option<Vec<_>>>()?;
+    if last.0.is_refutable() {
+        keys.push(Some(switch_key(&last.0)?));
+    } else {
+        keys.push(None);
+    }
+
+    let mut switch_on = None;
+    let mut seen = Vec::new();
+    for (on, key) in keys.iter().flatten() {
+        if *switch_on.get_or_insert(*on) != *on || *key >= MAX_SWITCH_LEN || seen.contains(key) {
+            return None;
+        }
+        seen.push(*key);
+    }
+
+    if seen.len() < 2 {
+        None
+    } else {
+        Some((switch_on?, keys.into_iter().map(|key| key.map(|(_, key)| key)).collect()))
+    }
+}
+
 impl Program {
     // [.., T] => [..]
     pub fn compile_extractor(&mut self, mir: &MirContext, binding: &MirNode<mir::Binding>) {
@@ -35,6 +89,7 @@ impl Program {
         match &binding.pat {
             mir::Pat::Wildcard => {},
             mir::Pat::Literal(_) => {},
+            mir::Pat::Range(_, _) => {},
             mir::Pat::Single(inner) => {
                 self.push(Instr::Dup);
                 self.compile_extractor(mir, inner);
@@ -45,6 +100,12 @@ impl Program {
                 self.push(Instr::SubInt);
                 self.compile_extractor(mir, lhs);
             },
+            mir::Pat::Mul(lhs, rhs) => {
+                self.push(Instr::Dup);
+                self.push(Instr::Imm(Value::Int(*rhs as i64)));
+                self.push(Instr::DivInt);
+                self.compile_extractor(mir, lhs);
+            },
             mir::Pat::Tuple(items) | mir::Pat::ListExact(items) => {
                 for (i, item) in items.iter().enumerate() {
                     if item.binds() {
@@ -85,11 +146,54 @@ impl Program {
                 self.push(Instr::IndexSum(*id as usize));
                 self.compile_extractor(mir, inner);
             },
+            mir::Pat::Or(alts) => {
+                // Extract from the first alternative that matches. Alternatives may bind their locals in a different
+                // order, so the locals are then reordered to match the first alternative.
+                let names = alts[0].binding_names();
+                let mut done = Vec::new();
+                for (i, alt) in alts.iter().enumerate() {
+                    let next = if i + 1 < alts.len() {
+                        self.push(Instr::Dup);
+                        self.compile_matcher(alt);
+                        self.push(Instr::IfNot);
+                        Some(self.push(Instr::Jump(0))) // Fixed by #12
+                    } else {
+                        None
+                    };
+
+                    self.push(Instr::Dup);
+                    self.compile_extractor(mir, alt);
+                    self.reorder_locals(&alt.binding_names(), &names);
+
+                    if let Some(next) = next {
+                        done.push(self.push(Instr::Jump(0))); // Fixed by #13
+                        self.fixup(next, self.next_addr(), Instr::Jump); // Fixes #12
+                    }
+                }
+
+                for done in done {
+                    self.fixup(done, self.next_addr(), Instr::Jump); // Fixes #13
+                }
+            },
         }
 
         self.push(Instr::Pop(1));
     }
 
+    // Reorder the most recently pushed locals from the order given by `from` to the order given by `to`
+    fn reorder_locals(&mut self, from: &[mir::Local], to: &[mir::Local]) {
+        if from != to {
+            for name in to.iter().rev() {
+                let idx = from.iter().position(|n| n == name).expect("Locals cannot be reordered");
+                self.push(Instr::GetLocal(from.len() - 1 - idx));
+            }
+            self.push(Instr::PopLocal(from.len()));
+            for _ in to {
+                self.push(Instr::PushLocal);
+            }
+        }
+    }
+
     // [.., [T]] -> [.., Bool]
     pub fn compile_item_matcher<'a>(&mut self, items: impl IntoIterator<Item = &'a MirNode<mir::Binding>>, is_list: bool, fail_fixup: impl IntoIterator<Item = Addr>) {
         let mut fixups = fail_fixup.into_iter().collect::<Vec<_>>();
@@ -137,17 +241,60 @@ impl Program {
                         repr::Repr::Prim(repr::Prim::Bool) => Instr::EqBool,
                         repr::Repr::Prim(repr::Prim::Nat) => Instr::EqInt,
                         repr::Repr::Prim(repr::Prim::Int) => Instr::EqInt,
+                        repr::Repr::Prim(repr::Prim::Char) => Instr::EqChar,
                         r => todo!("{:?}", r),
                     });
                 },
             },
+            mir::Pat::Range(lo, hi) => {
+                let (more_eq, less_eq) = match binding.meta() {
+                    repr::Repr::Prim(repr::Prim::Char) => (Instr::MoreEqChar, Instr::LessEqChar),
+                    _ => (Instr::MoreEqInt, Instr::LessEqInt),
+                };
+                self.push(Instr::Dup);
+                self.push(Instr::Imm(litr_to_value(lo)));
+                self.push(more_eq);
+                self.push(Instr::IfNot);
+                let fail_fixup = self.push(Instr::Jump(0)); // Fixed by #2
+                self.push(Instr::Imm(litr_to_value(hi)));
+                self.push(less_eq);
+                let success = self.push(Instr::Jump(0)); // Fixed by #3
+
+                self.fixup(fail_fixup, self.next_addr(), Instr::Jump); // Fixes #2
+                self.push(Instr::Pop(1));
+                self.push(Instr::bool(false));
+
+                self.fixup(success, self.next_addr(), Instr::Jump); // Fixes #3
+            },
             mir::Pat::Single(inner) => self.compile_matcher(inner),
-            mir::Pat::Add(lhs, rhs) => {
+            mir::Pat::Add(lhs, rhs) => if *lhs.meta() == repr::Repr::Prim(repr::Prim::Int) {
+                // Integers wrap around, so every integer has an offset from which it was produced. `SubInt` wraps
+                // too, so `i64::MIN` matches `x + 1` with `x = i64::MAX` just as the evaluator does.
+                self.push(Instr::Imm(Value::Int(*rhs as i64)));
+                self.push(Instr::SubInt);
+                self.compile_matcher(lhs);
+            } else {
                 self.push(Instr::Dup);
                 self.push(Instr::Imm(Value::Int(*rhs as i64)));
                 self.push(Instr::MoreEqInt);
                 self.push(Instr::IfNot);
                 let fail_fixup = self.push(Instr::Jump(0)); // Fixed by #2
+                // The inner pattern matches the value with the offset removed
+                self.push(Instr::Imm(Value::Int(*rhs as i64)));
+                self.push(Instr::SubInt);
+                self.compile_item_matcher(Some(lhs), false, Some(fail_fixup));
+            },
+            mir::Pat::Mul(lhs, rhs) => {
+                self.push(Instr::Dup);
+                self.push(Instr::Imm(Value::Int(*rhs as i64)));
+                self.push(Instr::RemInt);
+                self.push(Instr::Imm(Value::Int(0)));
+                self.push(Instr::EqInt);
+                self.push(Instr::IfNot);
+                let fail_fixup = self.push(Instr::Jump(0)); // Fixed by #2
+                // The inner pattern matches the value with the factor removed
+                self.push(Instr::Imm(Value::Int(*rhs as i64)));
+                self.push(Instr::DivInt);
                 self.compile_item_matcher(Some(lhs), false, Some(fail_fixup));
             },
             mir::Pat::Tuple(items) => {
@@ -206,18 +353,60 @@ impl Program {
                 self.push(Instr::IndexSum(*id as usize));
                 self.compile_item_matcher(Some(inner), false, Some(fail_fixup));
             },
+            mir::Pat::Or(alts) => {
+                let (last, init) = alts.split_last().unwrap();
+                let mut successes = Vec::new();
+                for alt in init {
+                    self.push(Instr::Dup);
+                    self.compile_matcher(alt);
+                    self.push(Instr::IfNot);
+                    let next = self.push(Instr::Jump(0)); // Fixed by #10
+                    self.push(Instr::Pop(1));
+                    self.push(Instr::bool(true));
+                    successes.push(self.push(Instr::Jump(0))); // Fixed by #11
+                    self.fixup(next, self.next_addr(), Instr::Jump); // Fixes #10
+                }
+                self.compile_matcher(last);
+
+                for success in successes {
+                    self.fixup(success, self.next_addr(), Instr::Jump); // Fixes #11
+                }
+            },
+        }
+    }
+
+    // [.., T] -> [.., U]
+    fn compile_arm(
+        &mut self,
+        mir: &MirContext,
+        binding: &MirNode<mir::Binding>,
+        body: &MirNode<mir::Expr>,
+        stack: &mut Vec<mir::Local>,
+        proc_fixups: &mut Vec<(ProcId, Addr)>,
+    ) {
+        self.compile_extractor(mir, binding);
+
+        let old_stack = stack.len();
+        let names = binding.binding_names();
+        stack.extend(names.iter().copied()); // Start scope
+
+        self.compile_expr(mir, body, stack, proc_fixups);
+
+        if names.len() > 0 {
+            self.push(Instr::PopLocal(names.len()));
         }
+        stack.truncate(old_stack); // End scope
     }
 
     // [..] -> [.., T]
     pub fn compile_expr(
         &mut self,
         mir: &MirContext,
-        expr: &mir::Expr,
+        expr: &MirNode<mir::Expr>,
         stack: &mut Vec<mir::Local>,
         proc_fixups: &mut Vec<(ProcId, Addr)>,
     ) {
-        match &*expr {
+        match &**expr {
             mir::Expr::Literal(literal) => { self.push(Instr::Imm(litr_to_value(literal))); },
             mir::Expr::Local(local) => {
                 let idx = stack
@@ -276,12 +465,50 @@ impl Program {
                 }
                 self.push(Instr::MakeList(items.len()));
             },
-            mir::Expr::Match(pred, arms) => {
+            mir::Expr::Match(pred, arms) => if let Some((switch_on, keys)) = switch_keys(arms) {
                 self.compile_expr(mir, pred, stack, proc_fixups);
 
+                self.push(Instr::Dup);
+                match switch_on {
+                    SwitchOn::Variant => { self.push(Instr::VariantSum); },
+                    SwitchOn::Int => {},
+                    SwitchOn::ListLen => { self.push(Instr::LenList); },
+                }
+                let switch = self.push_switch(); // Fixed by #7
+
                 let mut end_matches = Vec::new();
+                let mut arm_addrs = Vec::new();
+                for (i, (binding, _, body)) in arms.iter().enumerate() {
+                    arm_addrs.push(self.next_addr());
+                    self.compile_arm(mir, binding, body, stack, proc_fixups);
+
+                    if i + 1 != arms.len() {
+                        end_matches.push(self.push(Instr::Jump(0))); // Fixed by #1
+                    }
+                }
 
-                for (i, (binding, body)) in arms.iter().enumerate() {
+                let end_match = self.next_addr();
+                for end_arm in end_matches {
+                    self.fixup(end_arm, end_match, Instr::Jump); // Fixes #1
+                }
+
+                // Values that no arm tests for can only be handled by the irrefutable arm, if there is one. Otherwise,
+                // the match is exhaustive and such values cannot occur.
+                let default = *arm_addrs.last().unwrap();
+                let len = keys.iter().flatten().max().unwrap() + 1;
+                let targets = (0..len)
+                    .map(|key| keys
+                        .iter()
+                        .position(|k| *k == Some(key))
+                        .map_or(default, |i| arm_addrs[i]))
+                    .collect::<Vec<_>>();
+                self.fixup_switch(switch, targets, default); // Fixes #7
+            } else {
+                self.compile_expr(mir, pred, stack, proc_fixups);
+
+                let mut end_matches = Vec::new();
+
+                for (i, (binding, guard, body)) in arms.iter().enumerate() {
                     let is_last = i + 1 == arms.len();
 
                     let mut fail_jumps = Vec::new();
@@ -295,21 +522,38 @@ impl Program {
                         fail_jumps.push(self.push(Instr::Jump(0)));
                     }
 
-                    self.compile_extractor(mir, binding);
-
-                    let old_stack = stack.len();
-                    let names = binding.binding_names();
-                    stack.extend(names.iter().copied()); // Start scope
+                    // The last arm never has a guard
+                    if let Some(guard) = guard.as_ref().filter(|_| !is_last) {
+                        // The predicate must be kept around in case the guard does not hold
+                        self.push(Instr::Dup);
+                        self.compile_extractor(mir, binding);
 
-                    self.compile_expr(mir, body, stack, proc_fixups);
+                        let old_stack = stack.len();
+                        let names = binding.binding_names();
+                        stack.extend(names.iter().copied()); // Start scope
 
-                    if names.len() > 0 {
-                        self.push(Instr::PopLocal(names.len()));
-                    }
-                    stack.truncate(old_stack); // End scope
+                        self.compile_expr(mir, guard, stack, proc_fixups);
+                        self.push(Instr::IfNot);
+                        let guard_fail = self.push(Instr::Jump(0)); // Fixed by #9
 
-                    if !is_last {
+                        self.push(Instr::Pop(1)); // The guard holds, so the predicate is no longer needed
+                        self.compile_expr(mir, body, stack, proc_fixups);
+                        if names.len() > 0 {
+                            self.push(Instr::PopLocal(names.len()));
+                        }
                         end_matches.push(self.push(Instr::Jump(0))); // Fixed by #1
+
+                        self.fixup(guard_fail, self.next_addr(), Instr::Jump); // Fixes #9
+                        if names.len() > 0 {
+                            self.push(Instr::PopLocal(names.len()));
+                        }
+                        stack.truncate(old_stack); // End scope
+                    } else {
+                        self.compile_arm(mir, binding, body, stack, proc_fixups);
+
+                        if !is_last {
+                            end_matches.push(self.push(Instr::Jump(0))); // Fixed by #1
+                        }
                     }
 
                     for fail_jump in fail_jumps {
@@ -357,11 +601,29 @@ impl Program {
 
                 stack.truncate(old_stack);
             },
-            mir::Expr::Apply(f, arg) => {
-                self.compile_expr(mir, f, stack, proc_fixups);
-                self.compile_expr(mir, arg, stack, proc_fixups);
-                self.push(Instr::PushLocal);
-                self.push(Instr::ApplyFunc);
+            mir::Expr::Apply(f, arg) => match expr
+                .as_call()
+                .map(|(proc, args)| (mir.procs.get(proc).unwrap().arity(), proc, args))
+                .filter(|(arity, _, args)| (1..=args.len()).contains(arity))
+            {
+                // Calls that provide all of the parameters of a procedure can use its direct entry point
+                Some((arity, proc, args)) => {
+                    for arg in &args[..arity] {
+                        self.compile_expr(mir, arg, stack, proc_fixups);
+                    }
+                    proc_fixups.push((proc, self.push(Instr::CallWith(0, arity)))); // Fixed by #4
+                    for arg in &args[arity..] {
+                        self.compile_expr(mir, arg, stack, proc_fixups);
+                        self.push(Instr::PushLocal);
+                        self.push(Instr::ApplyFunc);
+                    }
+                },
+                None => {
+                    self.compile_expr(mir, f, stack, proc_fixups);
+                    self.compile_expr(mir, arg, stack, proc_fixups);
+                    self.push(Instr::PushLocal);
+                    self.push(Instr::ApplyFunc);
+                },
             },
             mir::Expr::Variant(variant, inner) => {
                 self.compile_expr(mir, inner, stack, proc_fixups);
@@ -382,29 +644,125 @@ impl Program {
         }
     }
 
-    pub fn compile_proc(&mut self, mir: &MirContext, proc: ProcId, proc_fixups: &mut Vec<(ProcId, Addr)>) -> Addr {
+    // Returns the address of the procedure and, if it has parameters, the address of its direct entry point. The entry
+    // point expects `CallWith` to have moved the parameters into locals.
+    pub fn compile_proc(&mut self, mir: &MirContext, proc: ProcId, proc_fixups: &mut Vec<(ProcId, Addr)>) -> (Addr, Option<Addr>) {
+        let (params, body) = mir.procs.get(proc).unwrap().params();
+
         self.debug(format!("Proc {:?}", proc));
+        if params.is_empty() {
+            let addr = self.next_addr();
+            self.compile_expr(mir, body, &mut Vec::new(), proc_fixups);
+            self.push(Instr::Ret);
+            (addr, None)
+        } else {
+            let (addr, call) = self.compile_curried(params.len());
+
+            self.debug(format!("Entry {:?}", proc));
+            let entry = self.next_addr();
+            self.compile_expr(mir, body, &mut params.clone(), proc_fixups);
+            self.push(Instr::PopLocal(params.len()));
+            self.push(Instr::Ret);
+
+            self.fixup(call, entry, |i| Instr::CallWith(i, params.len())); // Fixes #8
+            (addr, Some(entry))
+        }
+    }
+
+    // Produce a curried function that collects `arity` arguments before calling a direct entry point with them. Returns
+    // the address of the function and of the call, which must be fixed up.
+    fn compile_curried(&mut self, arity: usize) -> (Addr, Addr) {
         let addr = self.next_addr();
-        self.compile_expr(mir, &mir.procs.get(proc).unwrap().body, &mut Vec::new(), proc_fixups);
+        let mut call = None;
+
+        let mut make_func = self.push(Instr::MakeFunc(0, 0)); // Fixed by #6
+        self.push(Instr::Ret);
+
+        // Each level of the function receives its argument and the arguments of the previous levels as captures
+        for level in 1..=arity {
+            self.fixup(make_func, self.next_addr(), |i| Instr::MakeFunc(i, level - 1)); // Fixes #6
+
+            // Locals are [arg_level, arg_1, .., arg_(level - 1)]
+            for i in 1..level {
+                self.push(Instr::GetLocal(level - 1 - i));
+            }
+            self.push(Instr::GetLocal(level - 1));
+
+            if level == arity {
+                call = Some(self.push(Instr::CallWith(0, arity))); // Fixed by #8
+            } else {
+                make_func = self.push(Instr::MakeFunc(0, 0)); // Fixed by #6
+            }
+
+            self.push(Instr::PopLocal(level));
+            self.push(Instr::Ret);
+        }
+
+        (addr, call.expect("Curried function must have at least one parameter"))
+    }
+
+    #[cfg(feature = "jit")]
+    pub fn compile_native_proc(&mut self, proc: ProcId, native: usize, arity: usize) -> (Addr, Option<Addr>) {
+        self.debug(format!("Native proc {:?}", proc));
+        let (addr, call) = self.compile_curried(arity);
+
+        // Locals are [arg_1, .., arg_arity]
+        let entry = self.next_addr();
+        for i in 0..arity {
+            self.push(Instr::GetLocal(arity - 1 - i));
+        }
+        self.push(Instr::CallNative(native));
+        self.push(Instr::PopLocal(arity));
         self.push(Instr::Ret);
-        addr
+
+        self.fixup(call, entry, |i| Instr::CallWith(i, arity)); // Fixes #8
+        (addr, Some(entry))
     }
 
     pub fn from_mir(mir: &MirContext) -> Self {
+        Self::from_mir_with(mir, true)
+    }
+
+    // Like `Program::from_mir`, but compiling procedures to native code may be disabled so that tests can compare the
+    // two. Without the `jit` feature, procedures are never compiled to native code.
+    #[cfg_attr(not(feature = "jit"), allow(unused_variables))]
+    pub(crate) fn from_mir_with(mir: &MirContext, native: bool) -> Self {
         let mut this = Self::default();
 
         let mut procs = HashMap::new();
         let mut proc_fixups = Vec::new();
 
+        #[cfg(feature = "jit")]
+        let jit = crate::jit::Jit::compile(mir, mir.reachable_procs().into_iter().filter(|_| native));
+
         for proc_id in mir.reachable_procs() {
+            #[cfg(feature = "jit")]
+            if let Some(native) = jit.native_of(proc_id) {
+                let arity = jit.native(native).params.len();
+                procs.insert(proc_id, this.compile_native_proc(proc_id, native, arity));
+                continue;
+            }
+
             procs.insert(proc_id, this.compile_proc(mir, proc_id, &mut proc_fixups));
         }
 
+        #[cfg(feature = "jit")]
+        {
+            this.jit = Some(jit);
+        }
+
         for (proc_id, addr) in proc_fixups {
-            this.fixup(addr, procs[&proc_id], Instr::Call); // Fixes #4
+            let (proc_addr, entry) = procs[&proc_id];
+            match this.instr(addr) {
+                Instr::CallWith(_, arity) => {
+                    let entry = entry.expect("Direct call to procedure without an entry point");
+                    this.fixup(addr, entry, |i| Instr::CallWith(i, arity)); // Fixes #4
+                },
+                _ => this.fixup(addr, proc_addr, Instr::Call), // Fixes #4
+            }
         }
 
-        this.entry = procs[&mir.entry.expect("No entry point")];
+        this.entry = procs[&mir.entry.expect("No entry point")].0;
 
         this
     }
@@ -0,0 +1,19 @@
+pub mod lower;
+
+pub use crate::lower::Module;
+
+use tao_middle::{
+    mir,
+    repr,
+    Context as MirContext,
+    MirNode,
+    ProcId,
+};
+use hashbrown::HashMap;
+use std::io::{self, Write};
+
+/// The WebAssembly runtime that every generated module is prefixed with.
+///
+/// Generated modules import `write_char`, `write_int` and `write_real` from the `env` namespace and export a `main`
+/// function that prints the value of the entry point, followed by a newline.
+pub const RUNTIME: &str = include_str!("runtime.wat");
@@ -0,0 +1,462 @@
+use super::*;
+
+fn local_name(local: mir::Local) -> String {
+    format!("$l{}", local.0)
+}
+
+fn int_litr(x: i64) -> String {
+    format!("(i64.const {})", x)
+}
+
+fn make_list(items: Vec<String>) -> String {
+    let len = items.len();
+    items
+        .into_iter()
+        .enumerate()
+        .fold(
+            format!("(call $tao_make_list (i32.const {}))", len),
+            |list, (i, item)| format!("(call $tao_list_init {} (i32.const {}) {})", list, i, item),
+        )
+}
+
+fn make_int(x: String) -> String { format!("(call $tao_make_int {})", x) }
+fn make_bool(x: String) -> String { format!("(call $tao_make_bool {})", x) }
+fn get_int(x: &str) -> String { format!("(call $tao_int {})", x) }
+
+fn compile_litr(literal: &mir::Literal) -> String {
+    match literal {
+        mir::Literal::Unknown(x) => *x,
+        mir::Literal::Nat(x) => make_int(int_litr(*x as i64)),
+        mir::Literal::Int(x) => make_int(int_litr(*x)),
+        // Reals are emitted by bit pattern so that infinities, NaNs and rounding survive the trip through text
+        mir::Literal::Real(x) => format!("(call $tao_make_real (f64.reinterpret_i64 {}))", int_litr(x.to_bits() as i64)),
+        mir::Literal::Char(c) => format!("(call $tao_make_char (i32.const {}))", *c as u32),
+        mir::Literal::Bool(x) => make_bool(format!("(i32.const {})", *x as u32)),
+        mir::Literal::Tuple(fields) => make_list(fields
+            .iter()
+            .map(compile_litr)
+            .collect()),
+        mir::Literal::List(items) => make_list(items
+            .iter()
+            .map(compile_litr)
+            .collect()),
+        mir::Literal::Sum(variant, inner) => format!("(call $tao_make_sum (i32.const {}) {})", variant, compile_litr(inner)),
+        mir::Literal::Union(id, inner) => format!("(call $tao_make_sum (i32.const {}) {})", id, compile_litr(inner)),
+    }
+}
+
+// Conditions short-circuit, since later conditions may only be valid to evaluate if earlier ones hold
+fn all(conds: Vec<String>) -> String {
+    conds
+        .into_iter()
+        .rev()
+        .reduce(|rest, cond| format!("(if (result i32) {} (then {}) (else (i32.const 0)))", cond, rest))
+        .unwrap_or_else(|| format!("(i32.const 1)"))
+}
+
+fn any(conds: Vec<String>) -> String {
+    conds
+        .into_iter()
+        .rev()
+        .reduce(|rest, cond| format!("(if (result i32) {} (then (i32.const 1)) (else {}))", cond, rest))
+        .unwrap_or_else(|| format!("(i32.const 0)"))
+}
+
+// Produces an `i32` condition that is non-zero if the value matches the literal
+fn compile_litr_matcher(literal: &mir::Literal, val: &str) -> String {
+    match literal {
+        mir::Literal::Unknown(x) => *x,
+        mir::Literal::Nat(x) => format!("(i64.eq {} {})", get_int(val), int_litr(*x as i64)),
+        mir::Literal::Int(x) => format!("(i64.eq {} {})", get_int(val), int_litr(*x)),
+        mir::Literal::Real(x) => format!("(f64.eq (call $tao_real {}) (f64.reinterpret_i64 {}))", val, int_litr(x.to_bits() as i64)),
+        mir::Literal::Char(c) => format!("(i32.eq (call $tao_char {}) (i32.const {}))", val, *c as u32),
+        mir::Literal::Bool(true) => format!("(call $tao_bool {})", val),
+        mir::Literal::Bool(false) => format!("(i32.eqz (call $tao_bool {}))", val),
+        mir::Literal::Tuple(fields) => all(fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| compile_litr_matcher(field, &format!("(call $tao_index {} (i32.const {}))", val, i)))
+            .collect()),
+        mir::Literal::List(items) => all(std::iter::once(format!("(i32.eq (call $tao_len {}) (i32.const {}))", val, items.len()))
+            .chain(items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| compile_litr_matcher(item, &format!("(call $tao_index {} (i32.const {}))", val, i))))
+            .collect()),
+        mir::Literal::Sum(variant, inner) => all(vec![
+            format!("(i32.eq (call $tao_variant {}) (i32.const {}))", val, variant),
+            compile_litr_matcher(inner, &format!("(call $tao_inner {})", val)),
+        ]),
+        mir::Literal::Union(id, inner) => all(vec![
+            format!("(i32.eq (call $tao_variant {}) (i32.const {}))", val, id),
+            compile_litr_matcher(inner, &format!("(call $tao_inner {})", val)),
+        ]),
+    }
+}
+
+// Produces an `i32` condition that is non-zero if the value matches the binding
+fn compile_matcher(binding: &MirNode<mir::Binding>, val: &str) -> String {
+    match &binding.pat {
+        mir::Pat::Wildcard => format!("(i32.const 1)"),
+        mir::Pat::Literal(literal) => compile_litr_matcher(literal, val),
+        mir::Pat::Range(lo, hi) => match (lo, hi) {
+            (mir::Literal::Nat(lo), mir::Literal::Nat(hi)) => all(vec![
+                format!("(i64.ge_s {} {})", get_int(val), int_litr(*lo as i64)),
+                format!("(i64.le_s {} {})", get_int(val), int_litr(*hi as i64)),
+            ]),
+            (mir::Literal::Int(lo), mir::Literal::Int(hi)) => all(vec![
+                format!("(i64.ge_s {} {})", get_int(val), int_litr(*lo)),
+                format!("(i64.le_s {} {})", get_int(val), int_litr(*hi)),
+            ]),
+            (mir::Literal::Char(lo), mir::Literal::Char(hi)) => all(vec![
+                format!("(i32.ge_u (call $tao_char {}) (i32.const {}))", val, *lo as u32),
+                format!("(i32.le_u (call $tao_char {}) (i32.const {}))", val, *hi as u32),
+            ]),
+            range => unreachable!("{:?}", range),
+        },
+        mir::Pat::Single(inner) => compile_matcher(inner, val),
+        // Only naturals have a lower bound to check (see `mir::Pat::Add`)
+        mir::Pat::Add(lhs, rhs) if *lhs.meta() == repr::Repr::Prim(repr::Prim::Int) => {
+            compile_matcher(lhs, &make_int(format!("(i64.sub {} {})", get_int(val), int_litr(*rhs as i64))))
+        },
+        mir::Pat::Add(lhs, rhs) => all(vec![
+            format!("(i64.ge_s {} {})", get_int(val), int_litr(*rhs as i64)),
+            compile_matcher(lhs, &make_int(format!("(i64.sub {} {})", get_int(val), int_litr(*rhs as i64)))),
+        ]),
+        mir::Pat::Mul(lhs, rhs) => all(vec![
+            format!("(i64.eqz (call $tao_rem_int {} {}))", get_int(val), int_litr(*rhs as i64)),
+            compile_matcher(lhs, &make_int(format!("(call $tao_div_int {} {})", get_int(val), int_litr(*rhs as i64)))),
+        ]),
+        mir::Pat::Tuple(fields) => all(fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| compile_matcher(field, &format!("(call $tao_index {} (i32.const {}))", val, i)))
+            .collect()),
+        mir::Pat::ListExact(items) => all(std::iter::once(format!("(i32.eq (call $tao_len {}) (i32.const {}))", val, items.len()))
+            .chain(items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| compile_matcher(item, &format!("(call $tao_index {} (i32.const {}))", val, i))))
+            .collect()),
+        mir::Pat::ListFront(items, tail) => all(std::iter::once(format!("(i32.ge_u (call $tao_len {}) (i32.const {}))", val, items.len()))
+            .chain(items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| compile_matcher(item, &format!("(call $tao_index {} (i32.const {}))", val, i))))
+            .chain(tail
+                .as_ref()
+                .map(|tail| compile_matcher(tail, &format!("(call $tao_skip {} (i32.const {}))", val, items.len()))))
+            .collect()),
+        mir::Pat::Variant(variant, inner) => all(vec![
+            format!("(i32.eq (call $tao_variant {}) (i32.const {}))", val, variant),
+            compile_matcher(inner, &format!("(call $tao_inner {})", val)),
+        ]),
+        mir::Pat::UnionVariant(id, inner) => all(vec![
+            format!("(i32.eq (call $tao_variant {}) (i32.const {}))", val, id),
+            compile_matcher(inner, &format!("(call $tao_inner {})", val)),
+        ]),
+        mir::Pat::Or(alts) => any(alts
+            .iter()
+            .map(|alt| compile_matcher(alt, val))
+            .collect()),
+    }
+}
+
+/// A wasm function under construction. Wasm locals are declared up-front, so we keep track of those that get used.
+#[derive(Default)]
+struct Func {
+    locals: Vec<String>,
+}
+
+impl Func {
+    fn local(&mut self, name: String) -> String {
+        if !self.locals.contains(&name) {
+            self.locals.push(name.clone());
+        }
+        name
+    }
+
+    fn finish(self, header: String, prelude: String, body: String) -> String {
+        let locals = self.locals
+            .iter()
+            .map(|local| format!(" (local {} i32)", local))
+            .collect::<String>();
+        format!("  (func {}{}\n{}{})\n", header, locals, prelude, body)
+    }
+}
+
+// Produces instructions that set the locals bound by the binding, extracting them from the value
+fn compile_extractor(binding: &MirNode<mir::Binding>, val: &str, func: &mut Func) -> String {
+    let mut code = String::new();
+
+    if let Some(name) = binding.name {
+        code += &format!("(local.set {} {})\n", func.local(local_name(name)), val);
+    }
+
+    match &binding.pat {
+        mir::Pat::Wildcard => {},
+        mir::Pat::Literal(_) => {},
+        mir::Pat::Range(_, _) => {},
+        mir::Pat::Single(inner) => code += &compile_extractor(inner, val, func),
+        mir::Pat::Add(lhs, rhs) => code += &compile_extractor(lhs, &make_int(format!("(i64.sub {} {})", get_int(val), int_litr(*rhs as i64))), func),
+        mir::Pat::Mul(lhs, rhs) => code += &compile_extractor(lhs, &make_int(format!("(call $tao_div_int {} {})", get_int(val), int_litr(*rhs as i64))), func),
+        mir::Pat::Tuple(items) | mir::Pat::ListExact(items) => for (i, item) in items.iter().enumerate() {
+            if item.binds() {
+                code += &compile_extractor(item, &format!("(call $tao_index {} (i32.const {}))", val, i), func);
+            }
+        },
+        mir::Pat::ListFront(items, tail) => {
+            for (i, item) in items.iter().enumerate() {
+                if item.binds() {
+                    code += &compile_extractor(item, &format!("(call $tao_index {} (i32.const {}))", val, i), func);
+                }
+            }
+            if let Some(tail) = tail.as_ref() {
+                code += &compile_extractor(tail, &format!("(call $tao_skip {} (i32.const {}))", val, items.len()), func);
+            }
+        },
+        mir::Pat::Variant(_, inner) | mir::Pat::UnionVariant(_, inner) => code += &compile_extractor(inner, &format!("(call $tao_inner {})", val), func),
+        // Extract from the first alternative that matches
+        mir::Pat::Or(alts) => {
+            let (last, init) = alts.split_last().unwrap();
+            let last = compile_extractor(last, val, func);
+            code += &init
+                .iter()
+                .rev()
+                .fold(last, |rest, alt| format!(
+                    "(if {} (then\n{}) (else\n{}))\n",
+                    compile_matcher(alt, val),
+                    compile_extractor(alt, val, func),
+                    rest,
+                ));
+        },
+    }
+
+    code
+}
+
+/// A WebAssembly text module produced from MIR.
+#[derive(Default)]
+pub struct Module {
+    procs: HashMap<ProcId, usize>,
+    proc_defs: Vec<String>,
+    lambdas: Vec<String>,
+    entry: Option<usize>,
+    tmps: usize,
+}
+
+impl Module {
+    fn tmp(&mut self, prefix: &str) -> String {
+        self.tmps += 1;
+        format!("${}{}", prefix, self.tmps)
+    }
+
+    // Compile an expression to a folded instruction that leaves its value on the stack
+    fn compile_expr(&mut self, expr: &MirNode<mir::Expr>, func: &mut Func) -> String {
+        match &**expr {
+            mir::Expr::Literal(literal) => compile_litr(literal),
+            mir::Expr::Local(local) => format!("(local.get {})", local_name(*local)),
+            mir::Expr::Global(global, _) => format!("(call $proc_{})", self.procs[global]),
+            mir::Expr::Intrinsic(intrinsic, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.compile_expr(arg, func))
+                    .collect::<Vec<_>>();
+                let int_op = |op: &str| format!("({} {} {})", op, get_int(&args[0]), get_int(&args[1]));
+                let char_op = |op: &str| format!("({} (call $tao_char {}) (call $tao_char {}))", op, args[0], args[1]);
+                use mir::Intrinsic::*;
+                match intrinsic {
+                    MakeList(_) => make_list(args),
+                    NotBool => make_bool(format!("(i32.eqz (call $tao_bool {}))", args[0])),
+                    // Wasm integer arithmetic wraps on overflow, as the VM's `wrapping_*` instructions do
+                    NegNat | NegInt => make_int(format!("(i64.sub (i64.const 0) {})", get_int(&args[0]))),
+                    NegReal => format!("(call $tao_make_real (f64.neg (call $tao_real {})))", args[0]),
+                    AddNat | AddInt => make_int(int_op("i64.add")),
+                    SubNat | SubInt => make_int(int_op("i64.sub")),
+                    MulNat | MulInt => make_int(int_op("i64.mul")),
+                    EqNat | EqInt => make_bool(int_op("i64.eq")),
+                    EqChar => make_bool(char_op("i32.eq")),
+                    NotEqNat | NotEqInt => make_bool(int_op("i64.ne")),
+                    NotEqChar => make_bool(char_op("i32.ne")),
+                    LessNat | LessInt => make_bool(int_op("i64.lt_s")),
+                    MoreNat | MoreInt => make_bool(int_op("i64.gt_s")),
+                    LessEqNat | LessEqInt => make_bool(int_op("i64.le_s")),
+                    MoreEqNat | MoreEqInt => make_bool(int_op("i64.ge_s")),
+                    Join(_) => format!("(call $tao_join {} {})", args[0], args[1]),
+                    Union(id) => format!("(call $tao_make_sum (i32.const {}) {})", id, args[0]),
+                }
+            },
+            mir::Expr::Match(pred, arms) => {
+                let pred = self.compile_expr(pred, func);
+                let scrutinee = func.local(self.tmp("t"));
+                let scrutinee_val = format!("(local.get {})", scrutinee);
+                let output = self.tmp("m");
+
+                let mut code = format!("(block {} (result i32)\n(local.set {} {})\n", output, scrutinee, pred);
+                let mut exhausted = false;
+                for (i, (binding, guard, arm)) in arms.iter().enumerate() {
+                    let is_last = i + 1 == arms.len();
+                    // The last arm is assumed to match (see `mir::Expr::Match`)
+                    let is_tested = binding.is_refutable() && !is_last;
+
+                    let extractor = compile_extractor(binding, &scrutinee_val, func);
+                    // The last arm never has a guard
+                    let guard = guard
+                        .as_ref()
+                        .filter(|_| !is_last)
+                        .map(|guard| self.compile_expr(guard, func));
+                    let arm = self.compile_expr(arm, func);
+
+                    if is_tested || guard.is_some() {
+                        let next = self.tmp("a");
+                        let matcher = if is_tested {
+                            format!("(br_if {} (i32.eqz {}))\n", next, compile_matcher(binding, &scrutinee_val))
+                        } else {
+                            String::new()
+                        };
+                        // The guard refers to the locals bound by the extractor, so is tested after it
+                        let guard = match guard {
+                            Some(guard) => format!("(br_if {} (i32.eqz (call $tao_bool {})))\n", next, guard),
+                            None => String::new(),
+                        };
+                        code += &format!(
+                            "(block {}\n{}{}{}(br {} {}))\n",
+                            next,
+                            matcher,
+                            extractor,
+                            guard,
+                            output,
+                            arm,
+                        );
+                    } else {
+                        code += &format!("{}{}", extractor, arm);
+                        exhausted = true;
+                        // Later arms can never be reached
+                        break;
+                    }
+                }
+
+                if !exhausted {
+                    code += "unreachable";
+                }
+
+                format!("{})", code)
+            },
+            mir::Expr::Func(arg, func_body) => {
+                let mut captures = Vec::new();
+                for capture in func_body.required_locals(Some(*arg)) {
+                    if !captures.contains(&capture) {
+                        captures.push(capture);
+                    }
+                }
+
+                let idx = self.lambdas.len();
+                self.lambdas.push(String::new()); // Reserve the index, nested lambdas are pushed after
+
+                let mut inner = Func::default();
+                let mut prelude = format!("(local.set {} (local.get $arg))\n", inner.local(local_name(*arg)));
+                for (i, capture) in captures.iter().enumerate() {
+                    prelude += &format!(
+                        "(local.set {} (call $tao_capture (local.get $env) (i32.const {})))\n",
+                        inner.local(local_name(*capture)),
+                        i,
+                    );
+                }
+                let body = self.compile_expr(func_body, &mut inner);
+
+                self.lambdas[idx] = inner.finish(
+                    format!("$lambda_{} (type $lambda) (param $env i32) (param $arg i32) (result i32)", idx),
+                    prelude,
+                    body,
+                );
+
+                captures
+                    .iter()
+                    .enumerate()
+                    .fold(
+                        format!("(call $tao_make_func (i32.const {}) (i32.const {}))", idx, captures.len()),
+                        |f, (i, capture)| format!("(call $tao_func_init {} (i32.const {}) (local.get {}))", f, i, local_name(*capture)),
+                    )
+            },
+            mir::Expr::Apply(f, arg) => {
+                let f = self.compile_expr(f, func);
+                let arg = self.compile_expr(arg, func);
+                format!("(call $tao_apply {} {})", f, arg)
+            },
+            mir::Expr::Tuple(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| self.compile_expr(field, func))
+                    .collect();
+                make_list(fields)
+            },
+            mir::Expr::List(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.compile_expr(item, func))
+                    .collect();
+                make_list(items)
+            },
+            mir::Expr::Access(record, field) => format!("(call $tao_index {} (i32.const {}))", self.compile_expr(record, func), field),
+            mir::Expr::Variant(variant, inner) => format!("(call $tao_make_sum (i32.const {}) {})", variant, self.compile_expr(inner, func)),
+            mir::Expr::AccessVariant(inner, _) => format!("(call $tao_inner {})", self.compile_expr(inner, func)),
+            // The host has no debugging facilities, so this is a no-op
+            mir::Expr::Debug(inner) => self.compile_expr(inner, func),
+        }
+    }
+
+    pub fn compile_proc(&mut self, mir: &MirContext, proc: ProcId) {
+        let mut func = Func::default();
+        let body = self.compile_expr(&mir.procs.get(proc).unwrap().body, &mut func);
+
+        let def = func.finish(format!("$proc_{} (result i32)", self.procs[&proc]), String::new(), body);
+        self.proc_defs.push(format!("  ;; Proc {:?}\n{}", proc, def));
+    }
+
+    pub fn from_mir(mir: &MirContext) -> Self {
+        let mut this = Self::default();
+
+        let reachable = mir.reachable_procs();
+
+        // Assign names up-front so that procs can refer to one-another regardless of order
+        for proc_id in reachable.iter() {
+            let idx = this.procs.len();
+            this.procs.insert(*proc_id, idx);
+        }
+
+        for proc_id in reachable {
+            this.compile_proc(mir, proc_id);
+        }
+
+        this.entry = Some(this.procs[&mir.entry.expect("No entry point")]);
+
+        this
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "(module")?;
+        writeln!(writer, "{}", RUNTIME)?;
+
+        writeln!(writer, "  ;; Lambdas\n")?;
+        writeln!(writer, "  (table {} funcref)", self.lambdas.len())?;
+        if !self.lambdas.is_empty() {
+            writeln!(writer, "  (elem (i32.const 0) {})", (0..self.lambdas.len())
+                .map(|idx| format!("$lambda_{}", idx))
+                .collect::<Vec<_>>()
+                .join(" "))?;
+        }
+        for lambda in &self.lambdas {
+            writeln!(writer, "{}", lambda)?;
+        }
+
+        writeln!(writer, "  ;; Procedures\n")?;
+        for proc in &self.proc_defs {
+            writeln!(writer, "{}", proc)?;
+        }
+
+        writeln!(writer, "  (func (export \"main\")")?;
+        writeln!(writer, "    (call $tao_print (call $proc_{}))", self.entry.expect("No entry point"))?;
+        writeln!(writer, "    (call $write_char (i32.const 10)))")?;
+        writeln!(writer, ")")
+    }
+}
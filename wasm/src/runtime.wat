@@ -0,0 +1,227 @@
+  ;; Tao WebAssembly runtime
+  ;;
+  ;; Every value is an `i32` pointer to a cell in linear memory. Cells are bump-allocated and never freed: programs are
+  ;; expected to be short-lived. The layout of a cell is a kind tag at offset 0 followed by its payload at offset 8:
+  ;;
+  ;;   0 Int   i64
+  ;;   1 Real  f64
+  ;;   2 Char  i32
+  ;;   3 Bool  i32
+  ;;   4 List  i32 length, i32 pointer to items (tuples share this representation)
+  ;;   5 Func  i32 table index, i32 capture count, i32 captures...
+  ;;   6 Sum   i32 variant, i32 inner
+
+  ;; The host is responsible for encoding characters and formatting numbers
+  (import "env" "write_char" (func $write_char (param i32)))
+  (import "env" "write_int" (func $write_int (param i64)))
+  (import "env" "write_real" (func $write_real (param f64)))
+
+  (memory (export "memory") 1)
+  (data (i32.const 0) "TrueFalseFunction(captures = ")
+  (global $heap (mut i32) (i32.const 32))
+
+  (type $lambda (func (param $env i32) (param $arg i32) (result i32)))
+
+  (func $tao_alloc (param $size i32) (result i32)
+    (local $ptr i32)
+    (local $limit i32)
+    (local.set $ptr (global.get $heap))
+    (global.set $heap (i32.and (i32.add (i32.add (local.get $ptr) (local.get $size)) (i32.const 7)) (i32.const -8)))
+    (local.set $limit (i32.mul (memory.size) (i32.const 65536)))
+    (if (i32.gt_u (global.get $heap) (local.get $limit))
+      (then
+        (if (i32.eq
+              (memory.grow (i32.add (i32.shr_u (i32.sub (global.get $heap) (local.get $limit)) (i32.const 16)) (i32.const 1)))
+              (i32.const -1))
+          (then unreachable))))
+    (local.get $ptr))
+
+  (func $tao_make_int (param $x i64) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.const 16)))
+    (i32.store (local.get $ptr) (i32.const 0))
+    (i64.store offset=8 (local.get $ptr) (local.get $x))
+    (local.get $ptr))
+
+  (func $tao_make_real (param $x f64) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.const 16)))
+    (i32.store (local.get $ptr) (i32.const 1))
+    (f64.store offset=8 (local.get $ptr) (local.get $x))
+    (local.get $ptr))
+
+  (func $tao_make_char (param $x i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.const 16)))
+    (i32.store (local.get $ptr) (i32.const 2))
+    (i32.store offset=8 (local.get $ptr) (local.get $x))
+    (local.get $ptr))
+
+  (func $tao_make_bool (param $x i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.const 16)))
+    (i32.store (local.get $ptr) (i32.const 3))
+    (i32.store offset=8 (local.get $ptr) (local.get $x))
+    (local.get $ptr))
+
+  (func $tao_int (param $x i32) (result i64) (i64.load offset=8 (local.get $x)))
+  (func $tao_real (param $x i32) (result f64) (f64.load offset=8 (local.get $x)))
+  (func $tao_char (param $x i32) (result i32) (i32.load offset=8 (local.get $x)))
+  (func $tao_bool (param $x i32) (result i32) (i32.load offset=8 (local.get $x)))
+
+  ;; Euclidean division, like the VM, so that the remainder is never negative. The divisor is always positive.
+  (func $tao_rem_int (param $x i64) (param $y i64) (result i64)
+    (local $r i64)
+    (local.set $r (i64.rem_s (local.get $x) (local.get $y)))
+    (if (result i64) (i64.lt_s (local.get $r) (i64.const 0))
+      (then (i64.add (local.get $r) (local.get $y)))
+      (else (local.get $r))))
+  (func $tao_div_int (param $x i64) (param $y i64) (result i64)
+    (i64.div_s (i64.sub (local.get $x) (call $tao_rem_int (local.get $x) (local.get $y))) (local.get $y)))
+
+  (func $tao_make_list (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.const 16)))
+    (i32.store (local.get $ptr) (i32.const 4))
+    (i32.store offset=8 (local.get $ptr) (local.get $len))
+    (i32.store offset=12 (local.get $ptr) (call $tao_alloc (i32.mul (local.get $len) (i32.const 4))))
+    (local.get $ptr))
+
+  ;; Initialise an item of a freshly made list, returning the list
+  (func $tao_list_init (param $list i32) (param $i i32) (param $x i32) (result i32)
+    (i32.store
+      (i32.add (i32.load offset=12 (local.get $list)) (i32.mul (local.get $i) (i32.const 4)))
+      (local.get $x))
+    (local.get $list))
+
+  (func $tao_len (param $list i32) (result i32) (i32.load offset=8 (local.get $list)))
+
+  (func $tao_index (param $list i32) (param $i i32) (result i32)
+    (i32.load (i32.add (i32.load offset=12 (local.get $list)) (i32.mul (local.get $i) (i32.const 4)))))
+
+  ;; Skipping items shares the underlying storage
+  (func $tao_skip (param $list i32) (param $n i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.const 16)))
+    (i32.store (local.get $ptr) (i32.const 4))
+    (i32.store offset=8 (local.get $ptr) (i32.sub (call $tao_len (local.get $list)) (local.get $n)))
+    (i32.store offset=12 (local.get $ptr)
+      (i32.add (i32.load offset=12 (local.get $list)) (i32.mul (local.get $n) (i32.const 4))))
+    (local.get $ptr))
+
+  (func $tao_join (param $x i32) (param $y i32) (result i32)
+    (local $ptr i32)
+    (local $x_len i32)
+    (local $i i32)
+    (local.set $x_len (call $tao_len (local.get $x)))
+    (local.set $ptr (call $tao_make_list (i32.add (local.get $x_len) (call $tao_len (local.get $y)))))
+    (block $x_done
+      (loop $x_loop
+        (br_if $x_done (i32.ge_u (local.get $i) (local.get $x_len)))
+        (drop (call $tao_list_init (local.get $ptr) (local.get $i) (call $tao_index (local.get $x) (local.get $i))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $x_loop)))
+    (local.set $i (i32.const 0))
+    (block $y_done
+      (loop $y_loop
+        (br_if $y_done (i32.ge_u (local.get $i) (call $tao_len (local.get $y))))
+        (drop (call $tao_list_init
+          (local.get $ptr)
+          (i32.add (local.get $x_len) (local.get $i))
+          (call $tao_index (local.get $y) (local.get $i))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $y_loop)))
+    (local.get $ptr))
+
+  (func $tao_make_sum (param $variant i32) (param $inner i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.const 16)))
+    (i32.store (local.get $ptr) (i32.const 6))
+    (i32.store offset=8 (local.get $ptr) (local.get $variant))
+    (i32.store offset=12 (local.get $ptr) (local.get $inner))
+    (local.get $ptr))
+
+  (func $tao_variant (param $x i32) (result i32) (i32.load offset=8 (local.get $x)))
+  (func $tao_inner (param $x i32) (result i32) (i32.load offset=12 (local.get $x)))
+
+  (func $tao_make_func (param $idx i32) (param $captures i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $tao_alloc (i32.add (i32.const 16) (i32.mul (local.get $captures) (i32.const 4)))))
+    (i32.store (local.get $ptr) (i32.const 5))
+    (i32.store offset=8 (local.get $ptr) (local.get $idx))
+    (i32.store offset=12 (local.get $ptr) (local.get $captures))
+    (local.get $ptr))
+
+  ;; Initialise a capture of a freshly made function, returning the function
+  (func $tao_func_init (param $f i32) (param $i i32) (param $x i32) (result i32)
+    (i32.store offset=16 (i32.add (local.get $f) (i32.mul (local.get $i) (i32.const 4))) (local.get $x))
+    (local.get $f))
+
+  (func $tao_capture (param $f i32) (param $i i32) (result i32)
+    (i32.load offset=16 (i32.add (local.get $f) (i32.mul (local.get $i) (i32.const 4)))))
+
+  (func $tao_apply (param $f i32) (param $arg i32) (result i32)
+    (call_indirect (type $lambda) (local.get $f) (local.get $arg) (i32.load offset=8 (local.get $f))))
+
+  (func $tao_write_str (param $ptr i32) (param $len i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (call $write_char (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next))))
+
+  ;; Mirrors the `Display` implementation of `tao_vm::Value` so that outputs can be compared directly
+  (func $tao_print (param $x i32)
+    (local $kind i32)
+    (local $i i32)
+    (local.set $kind (i32.load (local.get $x)))
+    (if (i32.eq (local.get $kind) (i32.const 0)) (then (call $write_int (call $tao_int (local.get $x))) (call $write_char (i32.const 105)) (return)))
+    (if (i32.eq (local.get $kind) (i32.const 1)) (then (call $write_real (call $tao_real (local.get $x))) (call $write_char (i32.const 102)) (return)))
+    (if (i32.eq (local.get $kind) (i32.const 2)) (then (call $write_char (call $tao_char (local.get $x))) (return)))
+    (if (i32.eq (local.get $kind) (i32.const 3))
+      (then
+        (if (call $tao_bool (local.get $x))
+          (then (call $tao_write_str (i32.const 0) (i32.const 4)))
+          (else (call $tao_write_str (i32.const 4) (i32.const 5))))
+        (return)))
+    (if (i32.eq (local.get $kind) (i32.const 4))
+      (then
+        (if (i32.and
+              (i32.gt_u (call $tao_len (local.get $x)) (i32.const 0))
+              (i32.eq (i32.load (call $tao_index (local.get $x) (i32.const 0))) (i32.const 2)))
+          (then
+            (block $done
+              (loop $next
+                (br_if $done (i32.ge_u (local.get $i) (call $tao_len (local.get $x))))
+                (call $tao_print (call $tao_index (local.get $x) (local.get $i)))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $next))))
+          (else
+            (call $write_char (i32.const 91)) ;; [
+            (block $done
+              (loop $next
+                (br_if $done (i32.ge_u (local.get $i) (call $tao_len (local.get $x))))
+                (if (i32.gt_u (local.get $i) (i32.const 0))
+                  (then (call $write_char (i32.const 44)) (call $write_char (i32.const 32)))) ;; ", "
+                (call $tao_print (call $tao_index (local.get $x) (local.get $i)))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $next)))
+            (call $write_char (i32.const 93)))) ;; ]
+        (return)))
+    (if (i32.eq (local.get $kind) (i32.const 5))
+      (then
+        (call $tao_write_str (i32.const 9) (i32.const 20)) ;; "Function(captures = "
+        (call $write_int (i64.extend_i32_u (i32.load offset=12 (local.get $x))))
+        (call $write_char (i32.const 41)) ;; )
+        (return)))
+    (if (i32.eq (local.get $kind) (i32.const 6))
+      (then
+        (call $write_char (i32.const 35)) ;; #
+        (call $write_int (i64.extend_i32_u (call $tao_variant (local.get $x))))
+        (call $write_char (i32.const 32))
+        (call $tao_print (call $tao_inner (local.get $x)))
+        (return)))
+    unreachable)
+